@@ -6,6 +6,7 @@ use clap::{Args, Parser, Subcommand};
 use logline_core::identity::LogLineKeyPair;
 
 mod onboarding;
+mod rules;
 
 use onboarding::{
     print_assignment, print_identity_created, print_purpose, print_shell_execution,
@@ -41,6 +42,9 @@ enum Commands {
     /// Execute computable actions through the gateway
     #[command(subcommand)]
     Run(RunCommands),
+    /// Work with rule sets locally
+    #[command(subcommand)]
+    Rules(RulesCommands),
     /// Generate a standalone LogLine ID locally
     GenerateId {
         /// Node name for the identity
@@ -139,6 +143,21 @@ struct RunShellArgs {
     command: Option<String>,
 }
 
+#[derive(Subcommand)]
+enum RulesCommands {
+    /// Run rule test fixtures against a rules file or directory
+    Test(RulesTestArgs),
+}
+
+#[derive(Args)]
+struct RulesTestArgs {
+    /// Rules file or directory, as scanned by the rules engine
+    path: std::path::PathBuf,
+    /// Fixture file or directory (defaults to the fixtures next to the rules)
+    #[arg(long)]
+    fixtures: Option<std::path::PathBuf>,
+}
+
 #[tokio::main]
 async fn main() -> Result<(), OnboardingCliError> {
     let cli = Cli::parse();
@@ -159,6 +178,9 @@ async fn main() -> Result<(), OnboardingCliError> {
             );
             Ok(())
         }
+        Commands::Rules(RulesCommands::Test(args)) => {
+            rules::run_rule_tests(&args.path, args.fixtures.as_deref())
+        }
         Commands::Version => {
             println!("LogLine Universe v{}", env!("CARGO_PKG_VERSION"));
             println!("Microservices architecture with WebSocket mesh");
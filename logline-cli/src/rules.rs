@@ -0,0 +1,66 @@
+use std::path::Path;
+
+use colored::*;
+use logline_rules::{load_fixtures, run_fixtures, FixtureReport, RuleEngine};
+
+use crate::onboarding::OnboardingCliError;
+
+/// Runs the rule fixtures found next to (or at) `fixtures` against the rules
+/// loaded from `rules_path`, printing a report. Fails when any fixture fails.
+pub fn run_rule_tests(
+    rules_path: &Path,
+    fixtures: Option<&Path>,
+) -> Result<(), OnboardingCliError> {
+    let engine = RuleEngine::from_path(rules_path)
+        .map_err(|err| OnboardingCliError::Validation(err.to_string()))?;
+
+    let fixtures_path = match fixtures {
+        Some(path) => path.to_path_buf(),
+        None if rules_path.is_dir() => rules_path.to_path_buf(),
+        None => rules_path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."))
+            .to_path_buf(),
+    };
+    let fixtures = load_fixtures(&fixtures_path)
+        .map_err(|err| OnboardingCliError::Validation(err.to_string()))?;
+
+    let report = run_fixtures(&engine, &fixtures);
+    print_report(&report);
+
+    if report.is_success() {
+        Ok(())
+    } else {
+        Err(OnboardingCliError::Validation(format!(
+            "{} teste(s) de regras falharam",
+            report.failed
+        )))
+    }
+}
+
+fn print_report(report: &FixtureReport) {
+    for result in &report.results {
+        if result.passed {
+            println!("{} {}", "✔".green().bold(), result.name);
+            continue;
+        }
+
+        println!("{} {}", "✘".red().bold(), result.name.bold());
+        if let Some(error) = &result.error {
+            println!("    erro: {}", error);
+        }
+        for mismatch in &result.mismatches {
+            println!("    {}:", mismatch.field);
+            println!("      {} {}", "- esperado:".green(), mismatch.expected);
+            println!("      {} {}", "+ obtido:  ".red(), mismatch.actual);
+        }
+    }
+
+    println!(
+        "{} {} aprovado(s), {} falharam",
+        "Testes de regras:".bold(),
+        report.passed,
+        report.failed
+    );
+}
@@ -132,6 +132,16 @@ pub enum ServiceMessage {
         #[serde(default)]
         output: serde_json::Value,
     },
+    /// A task of the engine moved to another lifecycle status.
+    TaskStatusChanged {
+        task_id: String,
+        tenant_id: String,
+        status: String,
+        #[serde(default)]
+        previous_status: Option<String>,
+        #[serde(default)]
+        task: serde_json::Value,
+    },
     ConnectionLost {
         peer: String,
     },
@@ -146,6 +156,7 @@ impl ServiceMessage {
             ServiceMessage::SpanCreated { .. } => "span_created",
             ServiceMessage::RuleEvaluationRequest { .. } => "rule_evaluation_request",
             ServiceMessage::RuleExecutionResult { .. } => "rule_execution_result",
+            ServiceMessage::TaskStatusChanged { .. } => "task_status_changed",
             ServiceMessage::ConnectionLost { .. } => "connection_lost",
         }
     }
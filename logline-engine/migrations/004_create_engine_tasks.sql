@@ -0,0 +1,39 @@
+-- Migration 004: Durable task queue for logline-engine
+
+CREATE TABLE IF NOT EXISTS engine_tasks (
+    id UUID PRIMARY KEY,
+    tenant_id TEXT NOT NULL,
+    payload JSONB NOT NULL DEFAULT 'null',
+    priority INTEGER NOT NULL,
+    scheduled_for TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    metadata JSONB,
+    status TEXT NOT NULL DEFAULT 'queued'
+        CHECK (status IN ('queued', 'running', 'completed', 'failed', 'cancelled')),
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ,
+    last_error TEXT,
+    result JSONB,
+    lease_owner TEXT,
+    lease_expires_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Claim path: due queued tasks ordered by priority and schedule.
+CREATE INDEX IF NOT EXISTS idx_engine_tasks_claim
+    ON engine_tasks (priority, scheduled_for, created_at)
+    WHERE status = 'queued';
+
+-- Lease recovery: running tasks by lease deadline.
+CREATE INDEX IF NOT EXISTS idx_engine_tasks_lease
+    ON engine_tasks (lease_expires_at)
+    WHERE status = 'running';
+
+CREATE INDEX IF NOT EXISTS idx_engine_tasks_tenant
+    ON engine_tasks (tenant_id, created_at DESC);
+
+-- Round-robin cursor: the tenant served longest ago is served next.
+CREATE TABLE IF NOT EXISTS engine_task_tenants (
+    tenant_id TEXT PRIMARY KEY,
+    last_claimed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,11 @@
+-- Migration 005: Retry bookkeeping for engine tasks
+-- Attempt counter, failure history and the per-task retry policy.
+
+ALTER TABLE engine_tasks ADD COLUMN IF NOT EXISTS attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE engine_tasks ADD COLUMN IF NOT EXISTS error_history JSONB NOT NULL DEFAULT '[]';
+ALTER TABLE engine_tasks ADD COLUMN IF NOT EXISTS retry_policy JSONB;
+
+-- Dead letters: failed tasks per tenant.
+CREATE INDEX IF NOT EXISTS idx_engine_tasks_dead_letters
+    ON engine_tasks (tenant_id, finished_at DESC)
+    WHERE status = 'failed';
@@ -0,0 +1,4 @@
+-- Migration 006: Cancellation requests and per-task execution timeouts
+
+ALTER TABLE engine_tasks ADD COLUMN IF NOT EXISTS cancel_requested BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE engine_tasks ADD COLUMN IF NOT EXISTS timeout_ms BIGINT;
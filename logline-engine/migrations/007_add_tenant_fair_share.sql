@@ -0,0 +1,8 @@
+-- Migration 007: Weighted fair share between tenants
+
+-- Claims advance a tenant's virtual time by 1 / weight; the tenant with the
+-- lowest virtual time is served next.
+ALTER TABLE engine_task_tenants ADD COLUMN IF NOT EXISTS virtual_time DOUBLE PRECISION NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_engine_tasks_tenant_status
+    ON engine_tasks (tenant_id, status);
@@ -0,0 +1,3 @@
+-- Migration 008: Task kind used to pick the registered handler
+
+ALTER TABLE engine_tasks ADD COLUMN IF NOT EXISTS kind TEXT;
@@ -0,0 +1,9 @@
+-- Migration 009: Idempotency keys for task submission
+
+ALTER TABLE engine_tasks ADD COLUMN IF NOT EXISTS idempotency_key TEXT;
+
+-- Keys are cleared from tasks once they are older than the idempotency TTL,
+-- so a live key belongs to a single task per tenant.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_engine_tasks_idempotency_key
+    ON engine_tasks (tenant_id, idempotency_key)
+    WHERE idempotency_key IS NOT NULL;
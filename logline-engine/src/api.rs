@@ -1,22 +1,43 @@
+use std::convert::Infallible;
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::response::IntoResponse;
-use axum::routing::get;
+use axum::routing::{delete, get, post};
 use axum::{Json, Router};
 use chrono::{DateTime, Utc};
-use futures::{SinkExt, StreamExt};
+use futures::{SinkExt, Stream, StreamExt};
 use serde::{Deserialize, Serialize};
-use tokio::sync::oneshot;
+use tokio::sync::{broadcast, oneshot};
 use tracing::{debug, info, warn};
 use uuid::Uuid;
 
 use crate::error::EngineError;
-use crate::runtime::{EngineHandle, ExecutionRuntime, TaskHandler};
+use crate::events::TaskEvent;
+use crate::postgres::PostgresTaskQueue;
+use crate::quota::{
+    QuotaConfig, SubscriptionTier, TenantLoad, TenantQuota, TenantQuotas, WaitTimeStats,
+};
+use crate::recurring::{MissedRunPolicy, RecurrenceSchedule, RecurringTask};
+use crate::registry::{PayloadSchema, TaskKind, TaskRegistry};
+use crate::retry::{RetryPolicy, TaskFailure};
+use crate::runtime::{
+    EngineHandle, ExecutionRuntime, TaskHandler, DEFAULT_DRAIN_DEADLINE, DEFAULT_IDEMPOTENCY_TTL,
+    DEFAULT_TASK_LEASE,
+};
 use crate::task::{ExecutionTask, TaskPriority, TaskRecord, TaskStatus};
+use crate::timeline_client::{SpanRecorder, TimelineClient};
+#[cfg(feature = "wasm")]
+use crate::wasm::{WasmExecutor, WasmLimits, WasmModuleStore};
+#[cfg(feature = "wasm")]
+use crate::wasm_executor::WasmtimeExecutor;
+use crate::workflow::{FailurePolicy, Workflow, WorkflowState, WorkflowStep};
 use crate::ws_client;
+use logline_core::identity::{LogLineID, LogLineKeyPair};
 use logline_core::websocket::{ServiceMessage, WebSocketEnvelope};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +50,51 @@ pub struct EngineServiceConfig {
     pub timeline_ws_url: Option<String>,
     #[serde(default)]
     pub rules_service_url: Option<String>,
+    /// Base URL of the timeline REST API that engine spans are recorded on.
+    #[serde(default)]
+    pub timeline_url: Option<String>,
+    /// Postgres URL of the durable task queue; tasks are kept in memory when
+    /// unset.
+    #[serde(default)]
+    pub database_url: Option<String>,
+    /// Seconds a claimed task stays leased to its worker between renewals.
+    #[serde(default = "default_task_lease_secs")]
+    pub task_lease_secs: u64,
+    /// How often idle workers poll the durable queue for new tasks.
+    #[serde(default = "default_queue_poll_interval_ms")]
+    pub queue_poll_interval_ms: u64,
+    /// Seconds an idempotency key keeps resolving to the task first
+    /// submitted with it.
+    #[serde(default = "default_idempotency_ttl_secs")]
+    pub idempotency_ttl_secs: u64,
+    /// Per-tier weights and limits; tiers are also loaded from
+    /// `organizations` when the queue is backed by Postgres.
+    #[serde(default)]
+    pub quotas: QuotaConfig,
+    /// Limits applied to tenant WebAssembly modules.
+    #[cfg(feature = "wasm")]
+    #[serde(default)]
+    pub wasm: WasmLimits,
+    /// Directory uploaded modules are persisted in, e.g.
+    /// `data/wasm-modules`; they are only kept in memory when unset.
+    #[cfg(feature = "wasm")]
+    #[serde(default)]
+    pub module_dir: Option<String>,
+    /// Records every task outcome on the timeline as a span signed with the
+    /// engine's identity.
+    #[serde(default)]
+    pub record_executions: bool,
+    /// Alias of the identity in `~/.logline` that execution spans are signed
+    /// with; an ephemeral one is generated when unset.
+    #[serde(default)]
+    pub identity_alias: Option<String>,
+    /// Seconds a shutdown waits for running tasks before interrupting them.
+    #[serde(default = "default_drain_deadline_secs")]
+    pub drain_deadline_secs: u64,
+    /// Bearer token required by admin routes such as quota updates; they
+    /// are refused when unset. Falls back to `ENGINE_ADMIN_TOKEN`.
+    #[serde(default)]
+    pub admin_token: Option<String>,
 }
 
 fn default_bind_address() -> String {
@@ -39,6 +105,22 @@ fn default_worker_count() -> usize {
     2
 }
 
+fn default_task_lease_secs() -> u64 {
+    DEFAULT_TASK_LEASE.as_secs()
+}
+
+fn default_queue_poll_interval_ms() -> u64 {
+    1_000
+}
+
+fn default_idempotency_ttl_secs() -> u64 {
+    DEFAULT_IDEMPOTENCY_TTL.as_secs()
+}
+
+fn default_drain_deadline_secs() -> u64 {
+    DEFAULT_DRAIN_DEADLINE.as_secs()
+}
+
 impl Default for EngineServiceConfig {
     fn default() -> Self {
         Self {
@@ -46,6 +128,20 @@ impl Default for EngineServiceConfig {
             workers: default_worker_count(),
             timeline_ws_url: None,
             rules_service_url: None,
+            timeline_url: None,
+            database_url: None,
+            task_lease_secs: default_task_lease_secs(),
+            queue_poll_interval_ms: default_queue_poll_interval_ms(),
+            idempotency_ttl_secs: default_idempotency_ttl_secs(),
+            quotas: QuotaConfig::default(),
+            #[cfg(feature = "wasm")]
+            wasm: WasmLimits::default(),
+            #[cfg(feature = "wasm")]
+            module_dir: None,
+            record_executions: false,
+            identity_alias: None,
+            drain_deadline_secs: default_drain_deadline_secs(),
+            admin_token: None,
         }
     }
 }
@@ -53,79 +149,451 @@ impl Default for EngineServiceConfig {
 #[derive(Clone)]
 struct EngineApiState {
     handle: EngineHandle,
+    kinds: Arc<Vec<TaskKind>>,
+    admin_token: Option<Arc<str>>,
 }
 
+type KindRegistration = (String, Arc<dyn TaskHandler>, Option<PayloadSchema>);
+
 /// Builder to bootstrap the engine microservice.
 pub struct EngineApiBuilder<H: TaskHandler> {
     handler: Arc<H>,
+    kinds: Vec<KindRegistration>,
+    #[cfg(feature = "wasm")]
+    wasm: Option<Arc<dyn WasmExecutor>>,
 }
 
 impl<H> EngineApiBuilder<H>
 where
     H: TaskHandler,
 {
+    /// `handler` runs the tasks that carry no kind.
     pub fn new(handler: Arc<H>) -> Self {
-        Self { handler }
+        Self {
+            handler,
+            kinds: Vec::new(),
+            #[cfg(feature = "wasm")]
+            wasm: None,
+        }
+    }
+
+    /// Runs tenant modules uploaded to `/tenants/:tenant/modules` on
+    /// `executor`, as tasks of kind [`crate::wasm::WASM_KIND`], instead of
+    /// on the default [`WasmtimeExecutor`].
+    #[cfg(feature = "wasm")]
+    pub fn with_wasm_executor(mut self, executor: Arc<dyn WasmExecutor>) -> Self {
+        self.wasm = Some(executor);
+        self
+    }
+
+    /// Registers a handler for tasks of `kind`, alongside the built-in kinds.
+    pub fn with_kind<K>(mut self, kind: impl Into<String>, handler: Arc<K>) -> Self
+    where
+        K: TaskHandler,
+    {
+        self.kinds.push((kind.into(), handler, None));
+        self
+    }
+
+    /// Registers a handler for tasks of `kind` whose payload matches `schema`.
+    pub fn with_kind_schema<K>(
+        mut self,
+        kind: impl Into<String>,
+        handler: Arc<K>,
+        schema: PayloadSchema,
+    ) -> Self
+    where
+        K: TaskHandler,
+    {
+        self.kinds.push((kind.into(), handler, Some(schema)));
+        self
     }
 
-    fn build_router(handle: EngineHandle) -> Router {
-        let state = EngineApiState { handle };
+    fn build_router(
+        handle: EngineHandle,
+        kinds: Vec<TaskKind>,
+        admin_token: Option<String>,
+    ) -> Router {
+        let state = EngineApiState {
+            handle,
+            kinds: Arc::new(kinds),
+            admin_token: admin_token.map(Arc::from),
+        };
 
         Router::new()
             .route("/health", get(health))
+            .route("/ready", get(ready))
+            .route("/identity", get(engine_identity))
             .route(
                 "/tenants/:tenant/tasks",
                 get(list_tasks).post(schedule_task),
             )
-            .route("/tenants/:tenant/tasks/:task_id", get(get_task))
+            .route(
+                "/tenants/:tenant/tasks/:task_id",
+                get(get_task).delete(cancel_task),
+            )
+            .route(
+                "/tenants/:tenant/dead-letters",
+                get(list_dead_letters).delete(purge_dead_letters),
+            )
+            .route(
+                "/tenants/:tenant/dead-letters/redrive",
+                post(redrive_dead_letters),
+            )
+            .route(
+                "/tenants/:tenant/dead-letters/:task_id",
+                delete(purge_dead_letter),
+            )
+            .route(
+                "/tenants/:tenant/dead-letters/:task_id/redrive",
+                post(redrive_dead_letter),
+            )
+            .route(
+                "/tenants/:tenant/recurring",
+                get(list_recurring).post(schedule_recurring),
+            )
+            .route(
+                "/tenants/:tenant/recurring/:recurring_id",
+                get(get_recurring).delete(cancel_recurring),
+            )
+            .route(
+                "/tenants/:tenant/workflows",
+                get(list_workflows).post(submit_workflow),
+            )
+            .route("/tenants/:tenant/workflows/:workflow_id", get(get_workflow))
+            .route(
+                "/tenants/:tenant/quota",
+                get(get_quota).put(update_quota).delete(reset_quota),
+            )
+            .route("/quotas/wait-times", get(wait_times))
+            .route("/task-kinds", get(list_task_kinds))
+            .route("/tenants/:tenant/task-events", get(task_events_sse))
+            .route(
+                "/tenants/:tenant/task-events/ws",
+                get(task_events_ws_upgrade),
+            )
             .route("/ws/service", get(service_ws_upgrade))
             .with_state(state)
     }
 
     pub async fn serve(self, config: EngineServiceConfig) -> anyhow::Result<oneshot::Sender<()>> {
-        let mut runtime = ExecutionRuntime::new();
-        runtime.start(self.handler.clone(), config.workers);
+        let quotas = TenantQuotas::new(config.quotas.clone());
+        let mut runtime = match config.database_url.as_deref() {
+            Some(url) => {
+                let queue = PostgresTaskQueue::connect(url)
+                    .await?
+                    .with_poll_interval(Duration::from_millis(config.queue_poll_interval_ms));
+                info!("engine task queue backed by postgres");
+                match queue.subscription_tiers().await {
+                    Ok(tiers) => {
+                        for (tenant, tier) in tiers {
+                            if !config.quotas.tenant_tiers.contains_key(&tenant) {
+                                quotas.set_tier(tenant, tier);
+                            }
+                        }
+                    }
+                    Err(err) => warn!(%err, "failed to load subscription tiers"),
+                }
+                ExecutionRuntime::with_queue(Arc::new(queue))
+            }
+            None => ExecutionRuntime::new(),
+        }
+        .with_lease(Duration::from_secs(config.task_lease_secs.max(1)))
+        .with_quotas(quotas)
+        .with_idempotency_ttl(Duration::from_secs(config.idempotency_ttl_secs));
+        let timeline_url = config
+            .timeline_url
+            .clone()
+            .or_else(|| std::env::var("TIMELINE_URL").ok());
+        let mut spans: Option<Arc<dyn SpanRecorder>> = None;
+        if let Some(url) = timeline_url {
+            match TimelineClient::new(&url) {
+                Ok(client) => {
+                    info!(timeline_url = %client.base_url(), "recording engine spans on the timeline");
+                    let client: Arc<dyn SpanRecorder> = Arc::new(client);
+                    runtime = runtime.with_span_recorder(client.clone());
+                    spans = Some(client);
+                }
+                Err(err) => {
+                    warn!(%url, ?err, "failed to initialise timeline client; engine spans disabled")
+                }
+            }
+        }
+        if config.record_executions {
+            if spans.is_none() {
+                warn!("no timeline configured; execution spans will not be recorded");
+            }
+            runtime = runtime.with_execution_spans(load_identity(&config));
+        }
+        let registry = TaskRegistry::new()
+            .with_fallback(self.handler)
+            .with_builtins(spans, ws_client::create_rules_client(&config));
+        #[cfg(feature = "wasm")]
+        let (registry, modules) = {
+            let modules = match config.module_dir.as_deref() {
+                Some(directory) => WasmModuleStore::open(directory, &config.wasm)?,
+                None => {
+                    warn!("no module directory configured; uploaded modules are lost on restart");
+                    WasmModuleStore::new(&config.wasm)
+                }
+            };
+            let executor = match self.wasm {
+                Some(executor) => executor,
+                None => Arc::new(WasmtimeExecutor::new()?),
+            };
+            (
+                registry.with_wasm(modules.clone(), executor, config.wasm),
+                modules,
+            )
+        };
+        let registry = self
+            .kinds
+            .into_iter()
+            .fold(registry, |registry, (kind, handler, schema)| {
+                registry.insert(kind, handler, schema)
+            });
+        let kinds = registry.kinds();
+        runtime.start(Arc::new(registry), config.workers);
         let handle = runtime.handle();
-        let router = Self::build_router(handle.clone());
+        let admin_token = config
+            .admin_token
+            .clone()
+            .or_else(|| std::env::var("ENGINE_ADMIN_TOKEN").ok());
+        let router = Self::build_router(handle.clone(), kinds, admin_token);
+        #[cfg(feature = "wasm")]
+        let router = router.merge(modules::router(modules, &config.wasm));
         ws_client::start_service_mesh(handle.clone(), &config);
         let listener = tokio::net::TcpListener::bind(&config.bind_address).await?;
         let (tx, rx) = oneshot::channel();
 
         tokio::spawn(async move {
             info!(address = %config.bind_address, "starting engine runtime service");
-            axum::serve(listener, router)
-                .with_graceful_shutdown(async move {
-                    let _ = rx.await;
-                })
-                .await
-                .ok();
-            runtime.shutdown().await;
+            let (stop_tx, stop_rx) = oneshot::channel::<()>();
+            let server = tokio::spawn(async move {
+                axum::serve(listener, router)
+                    .with_graceful_shutdown(async move {
+                        let _ = stop_rx.await;
+                    })
+                    .await
+            });
+            let _ = rx.await;
+            // Keep serving while draining so readiness reports it and
+            // submissions are rejected rather than refused.
+            runtime
+                .drain(Duration::from_secs(config.drain_deadline_secs))
+                .await;
+            let _ = stop_tx.send(());
+            let _ = server.await;
         });
 
         Ok(tx)
     }
 }
 
+/// Identity named by `identity_alias` (or `ENGINE_IDENTITY`), falling back
+/// to a freshly generated one.
+fn load_identity(config: &EngineServiceConfig) -> LogLineKeyPair {
+    let alias = config
+        .identity_alias
+        .clone()
+        .or_else(|| std::env::var("ENGINE_IDENTITY").ok());
+    if let Some(alias) = alias {
+        match LogLineID::load_from_file(&alias) {
+            Ok(identity) => {
+                info!(identity = %identity.id.id, "signing execution spans");
+                return identity;
+            }
+            Err(err) => warn!(%alias, %err, "failed to load engine identity"),
+        }
+    }
+    let identity = LogLineKeyPair::generate("logline-engine", None, None, false);
+    warn!(identity = %identity.id.id, "signing execution spans with an ephemeral identity");
+    identity
+}
+
 async fn health() -> impl IntoResponse {
     Json(serde_json::json!({ "status": "ok" }))
 }
 
+/// Ready until the runtime starts draining.
+async fn ready(State(state): State<EngineApiState>) -> impl IntoResponse {
+    if state.handle.is_draining() {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "status": "draining" })),
+        )
+    } else {
+        (
+            StatusCode::OK,
+            Json(serde_json::json!({ "status": "ready" })),
+        )
+    }
+}
+
+/// Public identity execution spans are signed with.
+async fn engine_identity(
+    State(state): State<EngineApiState>,
+) -> Result<Json<LogLineID>, (StatusCode, Json<ErrorResponse>)> {
+    state
+        .handle
+        .execution_identity()
+        .cloned()
+        .map(Json)
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    code: "not_found".into(),
+                    message: "execution spans are not recorded".into(),
+                }),
+            )
+        })
+}
+
+#[derive(Debug, Deserialize)]
+struct TaskEventsQuery {
+    /// Only report transitions of this task.
+    #[serde(default)]
+    task_id: Option<Uuid>,
+}
+
+impl TaskEventsQuery {
+    fn matches(&self, tenant: &str, event: &TaskEvent) -> bool {
+        event.tenant_id == tenant && self.task_id.is_none_or(|id| id == event.task_id)
+    }
+}
+
+/// Next event of `tenant` matching `query`, or the number of events skipped
+/// because the subscriber fell behind. `None` once the runtime is gone.
+async fn next_task_event(
+    events: &mut broadcast::Receiver<TaskEvent>,
+    tenant: &str,
+    query: &TaskEventsQuery,
+) -> Option<Result<TaskEvent, u64>> {
+    loop {
+        match events.recv().await {
+            Ok(event) if query.matches(tenant, &event) => return Some(Ok(event)),
+            Ok(_) => {}
+            Err(broadcast::error::RecvError::Lagged(skipped)) => return Some(Err(skipped)),
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Server-sent events stream of the tenant's task transitions. Each event is
+/// named `task_status_changed`; `lagged` reports events the client missed.
+async fn task_events_sse(
+    State(state): State<EngineApiState>,
+    Path(tenant): Path<String>,
+    Query(query): Query<TaskEventsQuery>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let events = state.handle.subscribe_events();
+    let stream = futures::stream::unfold(
+        (events, tenant, query),
+        |(mut events, tenant, query)| async move {
+            let event = match next_task_event(&mut events, &tenant, &query).await? {
+                Ok(event) => Event::default()
+                    .event("task_status_changed")
+                    .json_data(&event)
+                    .unwrap_or_else(|_| Event::default().comment("unserializable event")),
+                Err(skipped) => Event::default()
+                    .event("lagged")
+                    .data(serde_json::json!({ "skipped": skipped }).to_string()),
+            };
+            Some((Ok(event), (events, tenant, query)))
+        },
+    );
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// WebSocket stream of the tenant's task transitions, as
+/// `task_status_changed` envelopes.
+async fn task_events_ws_upgrade(
+    ws: WebSocketUpgrade,
+    State(state): State<EngineApiState>,
+    Path(tenant): Path<String>,
+    Query(query): Query<TaskEventsQuery>,
+) -> impl IntoResponse {
+    let events = state.handle.subscribe_events();
+    ws.on_upgrade(move |socket| async move {
+        if let Err(err) = stream_task_events(socket, events, tenant, query).await {
+            debug!(?err, "task event websocket closed with error");
+        }
+    })
+}
+
+async fn stream_task_events(
+    socket: WebSocket,
+    mut events: broadcast::Receiver<TaskEvent>,
+    tenant: String,
+    query: TaskEventsQuery,
+) -> anyhow::Result<()> {
+    let (mut sender, mut receiver) = socket.split();
+    loop {
+        tokio::select! {
+            next = next_task_event(&mut events, &tenant, &query) => {
+                let envelope = match next {
+                    Some(Ok(event)) => task_event_envelope(&event)?,
+                    Some(Err(skipped)) => {
+                        WebSocketEnvelope::new("lagged", serde_json::json!({ "skipped": skipped }))
+                    }
+                    None => break,
+                };
+                let message = envelope
+                    .to_message()
+                    .map_err(|err| anyhow::anyhow!(err.to_string()))?;
+                sender
+                    .send(message)
+                    .await
+                    .map_err(|err| anyhow::anyhow!(err.to_string()))?;
+            }
+            incoming = receiver.next() => match incoming {
+                Some(Ok(Message::Ping(payload))) => {
+                    sender
+                        .send(Message::Pong(payload))
+                        .await
+                        .map_err(|err| anyhow::anyhow!(err.to_string()))?;
+                }
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => {}
+                Some(Err(err)) => return Err(anyhow::anyhow!(err.to_string())),
+            },
+        }
+    }
+    Ok(())
+}
+
+fn task_event_envelope(event: &TaskEvent) -> anyhow::Result<WebSocketEnvelope> {
+    WebSocketEnvelope::from_service_message(&event.to_service_message())
+        .map_err(|err| anyhow::anyhow!(err.to_string()))
+}
+
 async fn service_ws_upgrade(
     ws: WebSocketUpgrade,
-    State(_state): State<EngineApiState>,
+    State(state): State<EngineApiState>,
 ) -> impl IntoResponse {
+    let events = state.handle.subscribe_events();
     ws.on_upgrade(|socket| async move {
-        if let Err(err) = handle_service_socket(socket).await {
+        if let Err(err) = handle_service_socket(socket, events).await {
             warn!(?err, "engine service websocket closed with error");
         }
     })
 }
 
-async fn handle_service_socket(socket: WebSocket) -> anyhow::Result<()> {
+/// Serves a service peer such as the gateway, which also receives every task
+/// transition as a [`ServiceMessage::TaskStatusChanged`].
+async fn handle_service_socket(
+    socket: WebSocket,
+    mut events: broadcast::Receiver<TaskEvent>,
+) -> anyhow::Result<()> {
     let (mut sender, mut receiver) = socket.split();
     let hello = ServiceMessage::ServiceHello {
         sender: "logline-engine".into(),
-        capabilities: vec!["task_scheduler".into(), "rule_dispatch".into()],
+        capabilities: vec![
+            "task_scheduler".into(),
+            "rule_dispatch".into(),
+            "task_events".into(),
+        ],
     };
     let hello_message = WebSocketEnvelope::from_service_message(&hello)
         .and_then(|envelope| envelope.to_message())
@@ -136,7 +604,31 @@ async fn handle_service_socket(socket: WebSocket) -> anyhow::Result<()> {
         .await
         .map_err(|err| anyhow::anyhow!(err.to_string()))?;
 
-    while let Some(message) = receiver.next().await {
+    loop {
+        let message = tokio::select! {
+            event = events.recv() => {
+                match event {
+                    Ok(event) => {
+                        let message = task_event_envelope(&event)?
+                            .to_message()
+                            .map_err(|err| anyhow::anyhow!(err.to_string()))?;
+                        sender
+                            .send(message)
+                            .await
+                            .map_err(|err| anyhow::anyhow!(err.to_string()))?;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(skipped, "service peer fell behind on task events");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+                continue;
+            }
+            message = receiver.next() => match message {
+                Some(message) => message,
+                None => break,
+            },
+        };
         match message {
             Ok(Message::Text(text)) => {
                 handle_service_message(Message::Text(text), &mut sender).await?;
@@ -206,12 +698,22 @@ struct ScheduleTaskRequest {
     scheduled_for: Option<DateTime<Utc>>,
     #[serde(default)]
     metadata: Option<serde_json::Value>,
+    #[serde(default)]
+    retry: Option<RetryPolicy>,
+    #[serde(default)]
+    timeout_ms: Option<u64>,
+    #[serde(default)]
+    kind: Option<String>,
+    /// Alternative to the `Idempotency-Key` header.
+    #[serde(default)]
+    idempotency_key: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 struct TaskResponse {
     id: Uuid,
     tenant_id: String,
+    kind: Option<String>,
     priority: TaskPriority,
     status: TaskStatus,
     created_at: DateTime<Utc>,
@@ -222,6 +724,12 @@ struct TaskResponse {
     payload: serde_json::Value,
     result: Option<serde_json::Value>,
     last_error: Option<String>,
+    retry: RetryPolicy,
+    attempts: u32,
+    error_history: Vec<TaskFailure>,
+    timeout_ms: Option<u64>,
+    cancel_requested: bool,
+    idempotency_key: Option<String>,
 }
 
 impl From<TaskRecord> for TaskResponse {
@@ -229,6 +737,7 @@ impl From<TaskRecord> for TaskResponse {
         Self {
             id: record.task.id,
             tenant_id: record.task.tenant_id,
+            kind: record.task.kind,
             priority: record.task.priority,
             status: record.status,
             created_at: record.task.created_at,
@@ -239,6 +748,12 @@ impl From<TaskRecord> for TaskResponse {
             payload: record.task.payload,
             result: record.result,
             last_error: record.last_error,
+            retry: record.task.retry,
+            attempts: record.attempts,
+            error_history: record.error_history,
+            timeout_ms: record.task.timeout_ms,
+            cancel_requested: record.cancel_requested,
+            idempotency_key: record.task.idempotency_key,
         }
     }
 }
@@ -249,11 +764,33 @@ struct ErrorResponse {
     message: String,
 }
 
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// Set on responses that return a task submitted earlier with the same key.
+const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
 async fn schedule_task(
     State(state): State<EngineApiState>,
     Path(tenant): Path<String>,
+    headers: HeaderMap,
     Json(request): Json<ScheduleTaskRequest>,
-) -> Result<Json<TaskResponse>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let header_key = match headers.get(IDEMPOTENCY_KEY_HEADER) {
+        Some(value) => Some(value.to_str().map(str::to_owned).map_err(|_| {
+            map_error(EngineError::Rejected(
+                "Idempotency-Key header must be visible ASCII".into(),
+            ))
+        })?),
+        None => None,
+    };
+    let idempotency_key = match (header_key, request.idempotency_key) {
+        (Some(header), Some(field)) if header != field => {
+            return Err(map_error(EngineError::Rejected(
+                "Idempotency-Key header and idempotency_key field differ".into(),
+            )));
+        }
+        (header, field) => header.or(field),
+    };
+
     let mut builder = ExecutionTask::builder(&tenant).payload(request.payload);
 
     if let Some(priority) = request.priority {
@@ -265,35 +802,54 @@ async fn schedule_task(
     if let Some(metadata) = request.metadata {
         builder = builder.metadata(metadata);
     }
+    if let Some(retry) = request.retry {
+        builder = builder.retry(retry);
+    }
+    if let Some(timeout_ms) = request.timeout_ms {
+        builder = builder.timeout(std::time::Duration::from_millis(timeout_ms));
+    }
+    if let Some(kind) = request.kind {
+        builder = builder.kind(kind);
+    }
+    if let Some(key) = idempotency_key {
+        builder = builder.idempotency_key(key);
+    }
 
-    let task = builder.build();
-    match state.handle.submit(task.clone()) {
-        Ok(_) => {
-            let record = state.handle.get(&task.id).expect("task must exist");
-            Ok(Json(TaskResponse::from(record)))
-        }
-        Err(err) => Err(map_error(err)),
+    let submission = state
+        .handle
+        .submit_idempotent(builder.build())
+        .await
+        .map_err(map_error)?;
+    let mut response_headers = HeaderMap::new();
+    if submission.replayed {
+        response_headers.insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
     }
+    Ok((
+        response_headers,
+        Json(TaskResponse::from(submission.record)),
+    ))
 }
 
 async fn list_tasks(
     State(state): State<EngineApiState>,
     Path(tenant): Path<String>,
-) -> impl IntoResponse {
+) -> Result<Json<Vec<TaskResponse>>, (StatusCode, Json<ErrorResponse>)> {
     let tasks: Vec<TaskResponse> = state
         .handle
         .list_for_tenant(&tenant)
+        .await
+        .map_err(map_error)?
         .into_iter()
         .map(TaskResponse::from)
         .collect();
-    Json(tasks)
+    Ok(Json(tasks))
 }
 
 async fn get_task(
     State(state): State<EngineApiState>,
     Path((tenant, task_id)): Path<(String, Uuid)>,
 ) -> Result<Json<TaskResponse>, (StatusCode, Json<ErrorResponse>)> {
-    match state.handle.get(&task_id) {
+    match state.handle.get(&task_id).await {
         Ok(record) if record.task.tenant_id == tenant => Ok(Json(TaskResponse::from(record))),
         Ok(_) => Err((
             StatusCode::NOT_FOUND,
@@ -306,6 +862,377 @@ async fn get_task(
     }
 }
 
+async fn cancel_task(
+    State(state): State<EngineApiState>,
+    Path((tenant, task_id)): Path<(String, Uuid)>,
+) -> Result<Json<TaskResponse>, (StatusCode, Json<ErrorResponse>)> {
+    state
+        .handle
+        .cancel(&tenant, &task_id)
+        .await
+        .map(|record| Json(TaskResponse::from(record)))
+        .map_err(map_error)
+}
+
+#[derive(Debug, Serialize)]
+struct DeadLetterAction {
+    task_ids: Vec<Uuid>,
+}
+
+async fn list_dead_letters(
+    State(state): State<EngineApiState>,
+    Path(tenant): Path<String>,
+) -> Result<Json<Vec<TaskResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let tasks = state
+        .handle
+        .dead_letters(&tenant)
+        .await
+        .map_err(map_error)?
+        .into_iter()
+        .map(TaskResponse::from)
+        .collect();
+    Ok(Json(tasks))
+}
+
+async fn redrive_dead_letters(
+    State(state): State<EngineApiState>,
+    Path(tenant): Path<String>,
+) -> Result<Json<DeadLetterAction>, (StatusCode, Json<ErrorResponse>)> {
+    let task_ids = state
+        .handle
+        .redrive(&tenant, None)
+        .await
+        .map_err(map_error)?;
+    Ok(Json(DeadLetterAction { task_ids }))
+}
+
+async fn redrive_dead_letter(
+    State(state): State<EngineApiState>,
+    Path((tenant, task_id)): Path<(String, Uuid)>,
+) -> Result<Json<DeadLetterAction>, (StatusCode, Json<ErrorResponse>)> {
+    let task_ids = state
+        .handle
+        .redrive(&tenant, Some(&task_id))
+        .await
+        .map_err(map_error)?;
+    if task_ids.is_empty() {
+        return Err(map_error(EngineError::TaskNotFound(task_id.to_string())));
+    }
+    Ok(Json(DeadLetterAction { task_ids }))
+}
+
+async fn purge_dead_letters(
+    State(state): State<EngineApiState>,
+    Path(tenant): Path<String>,
+) -> Result<Json<DeadLetterAction>, (StatusCode, Json<ErrorResponse>)> {
+    let task_ids = state
+        .handle
+        .purge_dead_letters(&tenant, None)
+        .await
+        .map_err(map_error)?;
+    Ok(Json(DeadLetterAction { task_ids }))
+}
+
+async fn purge_dead_letter(
+    State(state): State<EngineApiState>,
+    Path((tenant, task_id)): Path<(String, Uuid)>,
+) -> Result<Json<DeadLetterAction>, (StatusCode, Json<ErrorResponse>)> {
+    let task_ids = state
+        .handle
+        .purge_dead_letters(&tenant, Some(&task_id))
+        .await
+        .map_err(map_error)?;
+    if task_ids.is_empty() {
+        return Err(map_error(EngineError::TaskNotFound(task_id.to_string())));
+    }
+    Ok(Json(DeadLetterAction { task_ids }))
+}
+
+#[derive(Debug, Deserialize)]
+struct RecurringTaskRequest {
+    name: String,
+    schedule: RecurrenceSchedule,
+    #[serde(default)]
+    missed_runs: MissedRunPolicy,
+    #[serde(default)]
+    payload: serde_json::Value,
+    #[serde(default)]
+    priority: Option<TaskPriority>,
+    #[serde(default)]
+    metadata: Option<serde_json::Value>,
+    #[serde(default)]
+    start_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    kind: Option<String>,
+}
+
+async fn schedule_recurring(
+    State(state): State<EngineApiState>,
+    Path(tenant): Path<String>,
+    Json(request): Json<RecurringTaskRequest>,
+) -> Result<Json<RecurringTask>, (StatusCode, Json<ErrorResponse>)> {
+    let mut builder = RecurringTask::builder(&tenant, request.name, request.schedule)
+        .missed_runs(request.missed_runs)
+        .payload(request.payload);
+
+    if let Some(priority) = request.priority {
+        builder = builder.priority(priority);
+    }
+    if let Some(metadata) = request.metadata {
+        builder = builder.metadata(metadata);
+    }
+    if let Some(start_at) = request.start_at {
+        builder = builder.start_at(start_at);
+    }
+    if let Some(kind) = request.kind {
+        builder = builder.kind(kind);
+    }
+
+    let task = builder.build().map_err(map_error)?;
+    state
+        .handle
+        .schedule_recurring(task.clone())
+        .map_err(map_error)?;
+    Ok(Json(task))
+}
+
+async fn list_recurring(
+    State(state): State<EngineApiState>,
+    Path(tenant): Path<String>,
+) -> impl IntoResponse {
+    Json(state.handle.list_recurring(&tenant))
+}
+
+/// Looks up a recurring task, hiding those owned by other tenants.
+fn tenant_recurring(
+    state: &EngineApiState,
+    tenant: &str,
+    id: &Uuid,
+) -> Result<RecurringTask, (StatusCode, Json<ErrorResponse>)> {
+    match state.handle.get_recurring(id) {
+        Ok(task) if task.tenant_id == tenant => Ok(task),
+        Ok(_) => Err(map_error(EngineError::TaskNotFound(id.to_string()))),
+        Err(err) => Err(map_error(err)),
+    }
+}
+
+async fn get_recurring(
+    State(state): State<EngineApiState>,
+    Path((tenant, recurring_id)): Path<(String, Uuid)>,
+) -> Result<Json<RecurringTask>, (StatusCode, Json<ErrorResponse>)> {
+    tenant_recurring(&state, &tenant, &recurring_id).map(Json)
+}
+
+async fn cancel_recurring(
+    State(state): State<EngineApiState>,
+    Path((tenant, recurring_id)): Path<(String, Uuid)>,
+) -> Result<Json<RecurringTask>, (StatusCode, Json<ErrorResponse>)> {
+    tenant_recurring(&state, &tenant, &recurring_id)?;
+    state
+        .handle
+        .cancel_recurring(&recurring_id)
+        .map(Json)
+        .map_err(map_error)
+}
+
+#[derive(Debug, Deserialize)]
+struct WorkflowRequest {
+    name: String,
+    steps: Vec<WorkflowStep>,
+    #[serde(default)]
+    on_failure: FailurePolicy,
+}
+
+async fn submit_workflow(
+    State(state): State<EngineApiState>,
+    Path(tenant): Path<String>,
+    Json(request): Json<WorkflowRequest>,
+) -> Result<Json<WorkflowState>, (StatusCode, Json<ErrorResponse>)> {
+    let workflow = request
+        .steps
+        .into_iter()
+        .fold(Workflow::builder(&tenant, request.name), |builder, step| {
+            builder.step(step)
+        })
+        .on_failure(request.on_failure)
+        .build()
+        .map_err(map_error)?;
+    let workflow_id = state
+        .handle
+        .submit_workflow(workflow)
+        .await
+        .map_err(map_error)?;
+    state
+        .handle
+        .get_workflow(&workflow_id)
+        .map(Json)
+        .map_err(map_error)
+}
+
+async fn list_workflows(
+    State(state): State<EngineApiState>,
+    Path(tenant): Path<String>,
+) -> impl IntoResponse {
+    Json(state.handle.list_workflows(&tenant))
+}
+
+async fn get_workflow(
+    State(state): State<EngineApiState>,
+    Path((tenant, workflow_id)): Path<(String, Uuid)>,
+) -> Result<Json<WorkflowState>, (StatusCode, Json<ErrorResponse>)> {
+    match state.handle.get_workflow(&workflow_id) {
+        Ok(workflow) if workflow.workflow.tenant_id == tenant => Ok(Json(workflow)),
+        Ok(_) => Err(map_error(EngineError::TaskNotFound(
+            workflow_id.to_string(),
+        ))),
+        Err(err) => Err(map_error(err)),
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct QuotaResponse {
+    tenant_id: String,
+    tier: SubscriptionTier,
+    quota: TenantQuota,
+    /// Whether `quota` was set for the tenant rather than taken from its tier.
+    overridden: bool,
+    load: TenantLoad,
+    wait_times: WaitTimeStats,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateQuotaRequest {
+    #[serde(default)]
+    tier: Option<SubscriptionTier>,
+    /// Replaces the tier's quota for this tenant.
+    #[serde(default)]
+    quota: Option<TenantQuota>,
+}
+
+async fn quota_response(
+    state: &EngineApiState,
+    tenant: String,
+) -> Result<Json<QuotaResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let quotas = state.handle.quotas();
+    let load = state.handle.tenant_load(&tenant).await.map_err(map_error)?;
+    Ok(Json(QuotaResponse {
+        tier: quotas.tier(&tenant),
+        quota: quotas.quota(&tenant),
+        overridden: quotas.is_overridden(&tenant),
+        load,
+        wait_times: quotas.wait_times(&tenant),
+        tenant_id: tenant,
+    }))
+}
+
+async fn get_quota(
+    State(state): State<EngineApiState>,
+    Path(tenant): Path<String>,
+) -> Result<Json<QuotaResponse>, (StatusCode, Json<ErrorResponse>)> {
+    quota_response(&state, tenant).await
+}
+
+/// Lets the request through only when it carries the admin bearer token.
+/// Admin routes are closed when no token is configured.
+fn require_admin(
+    state: &EngineApiState,
+    headers: &HeaderMap,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let token = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim);
+    let Some(token) = token else {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                code: "unauthorized".into(),
+                message: "admin bearer token required".into(),
+            }),
+        ));
+    };
+    let matches = state.admin_token.as_deref().is_some_and(|expected| {
+        expected.len() == token.len()
+            && expected
+                .bytes()
+                .zip(token.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    });
+    if matches {
+        Ok(())
+    } else {
+        Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                code: "forbidden".into(),
+                message: "quota changes are restricted to administrators".into(),
+            }),
+        ))
+    }
+}
+
+async fn update_quota(
+    State(state): State<EngineApiState>,
+    Path(tenant): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<UpdateQuotaRequest>,
+) -> Result<Json<QuotaResponse>, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&state, &headers)?;
+    let quotas = state.handle.quotas();
+    if let Some(tier) = request.tier {
+        quotas.set_tier(tenant.clone(), tier);
+    }
+    if let Some(quota) = request.quota {
+        if quota.weight == 0 {
+            return Err(map_error(EngineError::Rejected(
+                "quota weight must be at least 1".into(),
+            )));
+        }
+        quotas.set_override(tenant.clone(), quota);
+    }
+    quota_response(&state, tenant).await
+}
+
+async fn reset_quota(
+    State(state): State<EngineApiState>,
+    Path(tenant): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<QuotaResponse>, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&state, &headers)?;
+    state.handle.quotas().clear_override(&tenant);
+    quota_response(&state, tenant).await
+}
+
+#[derive(Debug, Serialize)]
+struct TenantWaitTimes {
+    tenant_id: String,
+    tier: SubscriptionTier,
+    weight: u32,
+    wait_times: WaitTimeStats,
+}
+
+async fn wait_times(State(state): State<EngineApiState>) -> impl IntoResponse {
+    let quotas = state.handle.quotas();
+    let mut report: Vec<TenantWaitTimes> = quotas
+        .all_wait_times()
+        .into_iter()
+        .map(|(tenant, wait_times)| TenantWaitTimes {
+            tier: quotas.tier(&tenant),
+            weight: quotas.quota(&tenant).weight,
+            tenant_id: tenant,
+            wait_times,
+        })
+        .collect();
+    report.sort_by(|a, b| a.tenant_id.cmp(&b.tenant_id));
+    Json(report)
+}
+
+async fn list_task_kinds(State(state): State<EngineApiState>) -> Json<Vec<TaskKind>> {
+    Json(state.kinds.as_ref().clone())
+}
+
 fn map_error(err: EngineError) -> (StatusCode, Json<ErrorResponse>) {
     match err {
         EngineError::TaskNotFound(id) => (
@@ -336,5 +1263,206 @@ fn map_error(err: EngineError) -> (StatusCode, Json<ErrorResponse>) {
                 message,
             }),
         ),
+        EngineError::InvalidSchedule(message) => (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                code: "invalid_schedule".into(),
+                message,
+            }),
+        ),
+        EngineError::InvalidWorkflow(message) => (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                code: "invalid_workflow".into(),
+                message,
+            }),
+        ),
+        EngineError::UnknownTaskKind(kind) => (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                code: "unknown_task_kind".into(),
+                message: format!("no handler is registered for task kind `{}`", kind),
+            }),
+        ),
+        EngineError::QuotaExceeded { tenant, limit } => (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(ErrorResponse {
+                code: "quota_exceeded".into(),
+                message: format!(
+                    "tenant {} has reached its limit of {} queued tasks",
+                    tenant, limit
+                ),
+            }),
+        ),
+        EngineError::IdempotencyConflict(key) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ErrorResponse {
+                code: "idempotency_conflict".into(),
+                message: format!(
+                    "idempotency key `{}` was already used with a different payload",
+                    key
+                ),
+            }),
+        ),
+        EngineError::WorkflowsUnavailable => (
+            StatusCode::NOT_IMPLEMENTED,
+            Json(ErrorResponse {
+                code: "workflows_unavailable".into(),
+                message: "workflows are tracked in memory and need the engine's in-process queue"
+                    .into(),
+            }),
+        ),
+        EngineError::LeaseLost(id) => (
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                code: "lease_lost".into(),
+                message: format!("task {} is held by another worker", id),
+            }),
+        ),
+        EngineError::TaskFinished(id) => (
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                code: "task_finished".into(),
+                message: format!("task {} has already finished", id),
+            }),
+        ),
+        EngineError::Queue(message) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ErrorResponse {
+                code: "queue_unavailable".into(),
+                message,
+            }),
+        ),
+    }
+}
+
+/// Routes managing tenant WebAssembly modules.
+#[cfg(feature = "wasm")]
+mod modules {
+    use axum::body::Bytes;
+    use axum::extract::{DefaultBodyLimit, Path, State};
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use axum::{Json, Router};
+
+    use super::ErrorResponse;
+    use crate::wasm::{WasmError, WasmLimits, WasmModule, WasmModuleStore};
+
+    pub(super) fn router(modules: WasmModuleStore, limits: &WasmLimits) -> Router {
+        Router::new()
+            .route(
+                "/tenants/:tenant/modules",
+                get(list_modules)
+                    .post(upload_module)
+                    .layer(DefaultBodyLimit::max(limits.max_module_bytes)),
+            )
+            .route(
+                "/tenants/:tenant/modules/:hash",
+                get(get_module).delete(delete_module),
+            )
+            .with_state(modules)
+    }
+
+    async fn upload_module(
+        State(modules): State<WasmModuleStore>,
+        Path(tenant): Path<String>,
+        body: Bytes,
+    ) -> Result<Json<WasmModule>, (StatusCode, Json<ErrorResponse>)> {
+        modules
+            .put(&tenant, &body)
+            .map(Json)
+            .map_err(map_wasm_error)
+    }
+
+    async fn list_modules(
+        State(modules): State<WasmModuleStore>,
+        Path(tenant): Path<String>,
+    ) -> impl IntoResponse {
+        Json(modules.list_for_tenant(&tenant))
+    }
+
+    async fn get_module(
+        State(modules): State<WasmModuleStore>,
+        Path((tenant, hash)): Path<(String, String)>,
+    ) -> Result<Json<WasmModule>, (StatusCode, Json<ErrorResponse>)> {
+        modules
+            .get(&tenant, &hash)
+            .map(Json)
+            .ok_or_else(|| map_wasm_error(WasmError::ModuleNotFound(hash)))
+    }
+
+    async fn delete_module(
+        State(modules): State<WasmModuleStore>,
+        Path((tenant, hash)): Path<(String, String)>,
+    ) -> Result<Json<WasmModule>, (StatusCode, Json<ErrorResponse>)> {
+        modules
+            .remove(&tenant, &hash)
+            .map(Json)
+            .ok_or_else(|| map_wasm_error(WasmError::ModuleNotFound(hash)))
+    }
+
+    fn map_wasm_error(err: WasmError) -> (StatusCode, Json<ErrorResponse>) {
+        let (status, code) = match err {
+            WasmError::ModuleNotFound(_) => (StatusCode::NOT_FOUND, "not_found"),
+            WasmError::Storage(_) => (StatusCode::INTERNAL_SERVER_ERROR, "storage_error"),
+            _ => (StatusCode::BAD_REQUEST, "invalid_module"),
+        };
+        (
+            status,
+            Json(ErrorResponse {
+                code: code.into(),
+                message: err.to_string(),
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    fn quota_request(token: Option<&str>) -> Request<Body> {
+        let mut request = Request::put("/tenants/acme/quota")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{ "tier": "enterprise" }"#))
+            .unwrap();
+        if let Some(token) = token {
+            request.headers_mut().insert(
+                axum::http::header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {}", token)).unwrap(),
+            );
+        }
+        request
+    }
+
+    #[tokio::test]
+    async fn restricts_quota_updates_to_admins() {
+        let runtime = ExecutionRuntime::new();
+        let handle = runtime.handle();
+        let router = EngineApiBuilder::<TaskRegistry>::build_router(
+            handle.clone(),
+            Vec::new(),
+            Some("admin-secret".into()),
+        );
+
+        for (token, status) in [
+            (None, StatusCode::UNAUTHORIZED),
+            (Some("tenant-token"), StatusCode::FORBIDDEN),
+        ] {
+            let response = router.clone().oneshot(quota_request(token)).await.unwrap();
+            assert_eq!(response.status(), status);
+        }
+        assert_eq!(handle.quotas().tier("acme"), SubscriptionTier::Free);
+
+        let response = router
+            .oneshot(quota_request(Some("admin-secret")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(handle.quotas().tier("acme"), SubscriptionTier::Enterprise);
     }
 }
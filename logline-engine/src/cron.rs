@@ -0,0 +1,237 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, TimeZone, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::EngineError;
+
+/// How far ahead `next_after` searches before giving up on an expression that
+/// can never match (e.g. `0 0 30 2 *`).
+const SEARCH_HORIZON_DAYS: i64 = 5 * 366;
+
+/// Five-field cron expression (`minute hour day-of-month month day-of-week`),
+/// evaluated in UTC.
+///
+/// Fields accept `*`, single values, ranges (`1-5`), steps (`*/15`, `0-30/10`)
+/// and comma separated lists. Day-of-week runs from 0 (Sunday) to 7 (Sunday
+/// again). The `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly`
+/// shorthands are also recognised. As in classic cron, when both day fields
+/// are restricted a day matching either of them is accepted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CronSchedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    day_of_month_restricted: bool,
+    day_of_week_restricted: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, EngineError> {
+        let trimmed = expression.trim();
+        let expanded = match trimmed {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(EngineError::InvalidSchedule(format!(
+                "cron expression `{}` must have 5 fields, found {}",
+                trimmed,
+                fields.len()
+            )));
+        }
+
+        let mut days_of_week = parse_field(fields[4], 0, 7, "day-of-week")?;
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+
+        Ok(Self {
+            expression: trimmed.to_string(),
+            minutes: parse_field(fields[0], 0, 59, "minute")?,
+            hours: parse_field(fields[1], 0, 23, "hour")?,
+            days_of_month: parse_field(fields[2], 1, 31, "day-of-month")?,
+            months: parse_field(fields[3], 1, 12, "month")?,
+            days_of_week,
+            day_of_month_restricted: fields[2] != "*",
+            day_of_week_restricted: fields[4] != "*",
+        })
+    }
+
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    /// First matching minute strictly after `after`, or `None` when the
+    /// expression has no occurrence within the next few years.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut candidate = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let horizon = after + Duration::days(SEARCH_HORIZON_DAYS);
+
+        while candidate <= horizon {
+            if !has_bit(self.months, candidate.month()) {
+                let (year, month) = if candidate.month() == 12 {
+                    (candidate.year() + 1, 1)
+                } else {
+                    (candidate.year(), candidate.month() + 1)
+                };
+                candidate = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+                continue;
+            }
+            if !self.day_matches(candidate) {
+                candidate = candidate
+                    .date_naive()
+                    .succ_opt()?
+                    .and_hms_opt(0, 0, 0)?
+                    .and_utc();
+                continue;
+            }
+            if !has_bit(self.hours, candidate.hour()) {
+                candidate = candidate.with_minute(0)? + Duration::hours(1);
+                continue;
+            }
+            if !has_bit(self.minutes, candidate.minute()) {
+                candidate += Duration::minutes(1);
+                continue;
+            }
+            return Some(candidate);
+        }
+
+        None
+    }
+
+    fn day_matches(&self, at: DateTime<Utc>) -> bool {
+        let day_of_month = has_bit(self.days_of_month, at.day());
+        let day_of_week = has_bit(self.days_of_week, at.weekday().num_days_from_sunday());
+        match (self.day_of_month_restricted, self.day_of_week_restricted) {
+            (true, true) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        }
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = EngineError;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        Self::parse(expression)
+    }
+}
+
+impl TryFrom<String> for CronSchedule {
+    type Error = EngineError;
+
+    fn try_from(expression: String) -> Result<Self, Self::Error> {
+        Self::parse(&expression)
+    }
+}
+
+impl From<CronSchedule> for String {
+    fn from(schedule: CronSchedule) -> Self {
+        schedule.expression
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+fn has_bit(mask: u64, value: u32) -> bool {
+    mask & (1 << value) != 0
+}
+
+fn parse_field(field: &str, min: u32, max: u32, name: &str) -> Result<u64, EngineError> {
+    let invalid = |reason: &str| {
+        EngineError::InvalidSchedule(format!("invalid {} field `{}`: {}", name, field, reason))
+    };
+    let number = |value: &str| -> Result<u32, EngineError> {
+        let parsed: u32 = value.parse().map_err(|_| invalid("not a number"))?;
+        if parsed < min || parsed > max {
+            return Err(invalid(&format!("values must be within {}-{}", min, max)));
+        }
+        Ok(parsed)
+    };
+
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().map_err(|_| invalid("step is not a number"))?;
+                if step == 0 {
+                    return Err(invalid("step must be positive"));
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (number(start)?, number(end)?)
+        } else {
+            let value = number(range)?;
+            // `5/15` means "from 5 to the end of the range, every 15".
+            (value, if part.contains('/') { max } else { value })
+        };
+        if start > end {
+            return Err(invalid("range start is after its end"));
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> DateTime<Utc> {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn finds_next_occurrences() {
+        let every_quarter = CronSchedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(
+            every_quarter.next_after(at("2024-03-01T10:07:30Z")),
+            Some(at("2024-03-01T10:15:00Z"))
+        );
+
+        let weekdays = CronSchedule::parse("30 9 * * 1-5").unwrap();
+        // 2024-03-02 is a Saturday.
+        assert_eq!(
+            weekdays.next_after(at("2024-03-02T12:00:00Z")),
+            Some(at("2024-03-04T09:30:00Z"))
+        );
+
+        let yearly = CronSchedule::parse("@yearly").unwrap();
+        assert_eq!(
+            yearly.next_after(at("2024-03-01T00:00:00Z")),
+            Some(at("2025-01-01T00:00:00Z"))
+        );
+
+        let never = CronSchedule::parse("0 0 30 2 *").unwrap();
+        assert_eq!(never.next_after(at("2024-01-01T00:00:00Z")), None);
+
+        assert!(CronSchedule::parse("61 * * * *").is_err());
+        assert!(CronSchedule::parse("* * * *").is_err());
+    }
+}
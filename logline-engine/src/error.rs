@@ -11,4 +11,22 @@ pub enum EngineError {
     InvalidTenant,
     #[error("task rejected: {0}")]
     Rejected(String),
+    #[error("unknown task kind: {0}")]
+    UnknownTaskKind(String),
+    #[error("invalid schedule: {0}")]
+    InvalidSchedule(String),
+    #[error("invalid workflow: {0}")]
+    InvalidWorkflow(String),
+    #[error("task {0} has already finished")]
+    TaskFinished(String),
+    #[error("tenant {tenant} has reached its limit of {limit} queued tasks")]
+    QuotaExceeded { tenant: String, limit: usize },
+    #[error("idempotency key `{0}` was already used with a different payload")]
+    IdempotencyConflict(String),
+    #[error("workflows need a task queue local to this process")]
+    WorkflowsUnavailable,
+    #[error("lease on task {0} is held by another worker")]
+    LeaseLost(String),
+    #[error("task queue error: {0}")]
+    Queue(String),
 }
@@ -0,0 +1,88 @@
+use chrono::{DateTime, Utc};
+use logline_core::websocket::ServiceMessage;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::task::{TaskRecord, TaskStatus};
+use crate::workflow::workflow_step;
+
+/// Events buffered for each subscriber; slower subscribers skip the oldest.
+pub const TASK_EVENT_CAPACITY: usize = 1_024;
+
+/// A task moving from one lifecycle status to another.
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskEvent {
+    pub task_id: Uuid,
+    pub tenant_id: String,
+    pub kind: Option<String>,
+    pub status: TaskStatus,
+    /// `None` when the task was just submitted.
+    pub previous_status: Option<TaskStatus>,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub workflow_id: Option<Uuid>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl TaskEvent {
+    pub fn new(record: &TaskRecord, previous_status: Option<TaskStatus>) -> Self {
+        Self {
+            task_id: record.task.id,
+            tenant_id: record.task.tenant_id.clone(),
+            kind: record.task.kind.clone(),
+            status: record.status.clone(),
+            previous_status,
+            attempts: record.attempts,
+            last_error: record.last_error.clone(),
+            workflow_id: workflow_step(&record.task).map(|(id, _)| id),
+            occurred_at: Utc::now(),
+        }
+    }
+
+    /// The event as published on the service mesh.
+    pub fn to_service_message(&self) -> ServiceMessage {
+        ServiceMessage::TaskStatusChanged {
+            task_id: self.task_id.to_string(),
+            tenant_id: self.tenant_id.clone(),
+            status: self.status.as_str().to_string(),
+            previous_status: self
+                .previous_status
+                .as_ref()
+                .map(|status| status.as_str().to_string()),
+            task: serde_json::to_value(self).unwrap_or_default(),
+        }
+    }
+}
+
+/// Fan-out of the task events of this process to any number of subscribers.
+///
+/// Only transitions made by this process are published; with a shared queue,
+/// subscribers see the tasks run by this process's workers and the ones
+/// submitted, cancelled or re-driven through it.
+#[derive(Clone)]
+pub struct TaskEvents {
+    sender: broadcast::Sender<TaskEvent>,
+}
+
+impl Default for TaskEvents {
+    fn default() -> Self {
+        Self::new(TASK_EVENT_CAPACITY)
+    }
+}
+
+impl TaskEvents {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        Self { sender }
+    }
+
+    pub fn publish(&self, event: TaskEvent) {
+        // Sending only fails when nobody is subscribed.
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<TaskEvent> {
+        self.sender.subscribe()
+    }
+}
@@ -0,0 +1,153 @@
+//! Signed spans recording the final outcome of every task.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::Utc;
+use logline_core::identity::{LogLineID, LogLineKeyPair};
+use logline_protocol::timeline::{Span, SpanBuilder, SpanStatus, SpanType};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::task::{TaskRecord, TaskStatus};
+use crate::workflow::workflow_step;
+
+/// Task metadata field naming the span a task originates from, e.g. the span
+/// whose rule evaluation requested it.
+pub const CAUSED_BY_METADATA_KEY: &str = "caused_by";
+
+/// Builds and signs the span recorded for each task outcome with the
+/// engine's node identity.
+pub struct ExecutionSpans {
+    identity: LogLineKeyPair,
+}
+
+impl ExecutionSpans {
+    pub fn new(identity: LogLineKeyPair) -> Self {
+        Self { identity }
+    }
+
+    /// Identity the spans are signed with; its public key verifies them.
+    pub fn identity(&self) -> &LogLineID {
+        &self.identity.id
+    }
+
+    /// Signed span for the outcome just recorded in `record`: Executed when
+    /// the task completed, Reverted when it failed for good or was
+    /// cancelled. `None` while the task is not finished, e.g. when a failed
+    /// attempt will be retried. `caused_by` defaults to the task's
+    /// `caused_by` metadata.
+    pub fn span_for(&self, record: &TaskRecord, caused_by: Option<Uuid>) -> Option<Span> {
+        let task = &record.task;
+        let kind = task.kind.as_deref().unwrap_or("task");
+        let (status, outcome) = match record.status {
+            TaskStatus::Completed => (SpanStatus::Executed, "completed"),
+            TaskStatus::Cancelled => (SpanStatus::Reverted, "cancelled"),
+            TaskStatus::Failed => (SpanStatus::Reverted, "failed"),
+            TaskStatus::Queued | TaskStatus::Running => return None,
+        };
+        let finished_at = record.finished_at.unwrap_or_else(Utc::now);
+
+        let mut span = SpanBuilder::new(
+            self.identity.id.id.to_string(),
+            format!("{} {}", kind, outcome),
+        )
+        .status(status)
+        .tenant_id(task.tenant_id.clone())
+        .span_type(SpanType::System)
+        .payload(json!({
+            "task_id": task.id,
+            "kind": task.kind,
+            "status": outcome,
+            "attempt": record.attempts,
+            "result": record.result,
+            "error": record.last_error,
+        }))
+        .build();
+        span.timestamp = finished_at;
+        span.workflow_id = workflow_step(task).map(|(workflow_id, _)| workflow_id.to_string());
+        span.flow_id = workflow_step(task).map(|(_, step)| step);
+        span.delta_s = record.started_at.map(|started_at| {
+            (finished_at - started_at)
+                .to_std()
+                .unwrap_or_default()
+                .as_secs_f64()
+        });
+        span.caused_by = caused_by.or_else(|| {
+            task.metadata
+                .as_ref()
+                .and_then(|metadata| metadata.get(CAUSED_BY_METADATA_KEY))
+                .and_then(|value| value.as_str())
+                .and_then(|value| Uuid::parse_str(value).ok())
+        });
+        span.add_tag("engine_execution");
+
+        let signature = self
+            .identity
+            .id
+            .sign(&self.identity.signing_key, &signing_payload(&span));
+        // Set directly: `Span::sign` also marks the span verified, which is
+        // for the timeline to decide.
+        span.signature = Some(URL_SAFE_NO_PAD.encode(signature.to_bytes()));
+        Some(span)
+    }
+}
+
+/// Bytes covered by the signature: the span without its signature and
+/// verification status.
+fn signing_payload(span: &Span) -> Vec<u8> {
+    let mut unsigned = span.clone();
+    unsigned.signature = None;
+    unsigned.verification_status = None;
+    serde_json::to_vec(&unsigned).unwrap_or_default()
+}
+
+/// Whether `span` carries a valid signature by `identity`.
+pub fn verify_span(span: &Span, identity: &LogLineID) -> bool {
+    let Some(signature) = span
+        .signature
+        .as_deref()
+        .and_then(|signature| URL_SAFE_NO_PAD.decode(signature).ok())
+    else {
+        return false;
+    };
+    identity
+        .verify_signature(&signing_payload(span), &signature)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::ExecutionTask;
+
+    #[test]
+    fn signs_spans_for_task_outcomes() {
+        let spans = ExecutionSpans::new(LogLineKeyPair::generate("engine", None, None, false));
+        let origin = Uuid::new_v4();
+        let mut record = TaskRecord::new(
+            ExecutionTask::builder("acme")
+                .kind("http.webhook")
+                .metadata(json!({ "caused_by": origin.to_string() }))
+                .build(),
+        );
+        let started_at = Utc::now();
+        record.started_at = Some(started_at);
+        record.finished_at = Some(started_at + chrono::Duration::milliseconds(1_500));
+        record.attempts = 2;
+
+        record.status = TaskStatus::Queued;
+        assert!(spans.span_for(&record, None).is_none());
+
+        record.status = TaskStatus::Failed;
+        let mut span = spans.span_for(&record, None).unwrap();
+        assert_eq!(span.status, SpanStatus::Reverted);
+        assert_eq!(span.verification_status, None);
+        assert_eq!(span.caused_by, Some(origin));
+        assert_eq!(span.delta_s, Some(1.5));
+        assert_eq!(span.tenant_id.as_deref(), Some("acme"));
+        assert!(verify_span(&span, spans.identity()));
+
+        span.status = SpanStatus::Executed;
+        assert!(!verify_span(&span, spans.identity()));
+    }
+}
@@ -0,0 +1,303 @@
+//! Built-in handlers for common task kinds.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use logline_protocol::timeline::{SpanBuilder, SpanStatus, SpanType};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::registry::{FieldType, PayloadSchema, TaskRegistry};
+use crate::rules_client::RulesServiceClient;
+use crate::runtime::TaskHandler;
+use crate::task::ExecutionTask;
+use crate::timeline_client::SpanRecorder;
+use crate::workflow::ENGINE_LOGLINE_ID;
+
+/// Calls an HTTP endpoint; see [`WebhookHandler`].
+pub const WEBHOOK_KIND: &str = "http.webhook";
+/// Records a span on the timeline; see [`EmitSpanHandler`].
+pub const EMIT_SPAN_KIND: &str = "timeline.emit_span";
+/// Evaluates a span against the tenant's rules; see [`EvaluateRulesHandler`].
+pub const EVALUATE_RULES_KIND: &str = "rules.evaluate";
+
+/// Errors the webhook handler reports for requests that will not succeed if
+/// repeated (client errors other than 408 and 429).
+pub(crate) const PERMANENT_ERROR_PREFIX: &str = "permanent: ";
+
+const DEFAULT_WEBHOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+impl TaskRegistry {
+    /// Registers the built-in kinds. Emitting spans and evaluating rules are
+    /// only available when the corresponding service is configured.
+    pub fn with_builtins(
+        self,
+        spans: Option<Arc<dyn SpanRecorder>>,
+        rules: Option<Arc<RulesServiceClient>>,
+    ) -> Self {
+        let mut registry = self.register_with_schema(
+            WEBHOOK_KIND,
+            Arc::new(WebhookHandler::new()),
+            WebhookHandler::schema(),
+        );
+        if let Some(spans) = spans {
+            registry = registry.register_with_schema(
+                EMIT_SPAN_KIND,
+                Arc::new(EmitSpanHandler::new(spans)),
+                EmitSpanHandler::schema(),
+            );
+        }
+        if let Some(rules) = rules {
+            registry = registry.register_with_schema(
+                EVALUATE_RULES_KIND,
+                Arc::new(EvaluateRulesHandler::new(rules)),
+                EvaluateRulesHandler::schema(),
+            );
+        }
+        registry
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct WebhookRequest {
+    url: String,
+    #[serde(default)]
+    method: Option<String>,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    body: Option<Value>,
+    #[serde(default)]
+    timeout_ms: Option<u64>,
+}
+
+/// Sends the payload's `body` as JSON to `url` and returns the response
+/// status and body. Non-2xx responses fail the task; 4xx ones other than 408
+/// and 429 are not retried.
+#[derive(Clone, Default)]
+pub struct WebhookHandler {
+    http: reqwest::Client,
+}
+
+impl WebhookHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn schema() -> PayloadSchema {
+        PayloadSchema::new()
+            .required("url", FieldType::String)
+            .optional("method", FieldType::String)
+            .optional("headers", FieldType::Object)
+            .optional("body", FieldType::Any)
+            .optional("timeout_ms", FieldType::Integer)
+    }
+}
+
+#[async_trait]
+impl TaskHandler for WebhookHandler {
+    async fn handle(&self, task: ExecutionTask) -> Result<Value, String> {
+        self.handle_with_cancellation(task, CancellationToken::new())
+            .await
+    }
+
+    async fn handle_with_cancellation(
+        &self,
+        task: ExecutionTask,
+        cancel: CancellationToken,
+    ) -> Result<Value, String> {
+        let request: WebhookRequest = serde_json::from_value(task.payload)
+            .map_err(|err| format!("{}invalid webhook payload: {}", PERMANENT_ERROR_PREFIX, err))?;
+        let method = request.method.as_deref().unwrap_or("POST").to_uppercase();
+        let method = reqwest::Method::from_bytes(method.as_bytes())
+            .map_err(|_| format!("{}invalid HTTP method {}", PERMANENT_ERROR_PREFIX, method))?;
+
+        let mut builder = self
+            .http
+            .request(method, &request.url)
+            .timeout(
+                request
+                    .timeout_ms
+                    .map(Duration::from_millis)
+                    .unwrap_or(DEFAULT_WEBHOOK_TIMEOUT),
+            )
+            .header("X-Tenant-ID", &task.tenant_id)
+            .header("X-Task-ID", task.id.to_string());
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(body) = &request.body {
+            builder = builder.json(body);
+        }
+
+        let response = tokio::select! {
+            response = builder.send() => response.map_err(|err| format!("webhook request failed: {}", err))?,
+            _ = cancel.cancelled() => return Err("webhook request cancelled".into()),
+        };
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|err| format!("failed to read webhook response: {}", err))?;
+        let body = serde_json::from_str(&text).unwrap_or(Value::String(text));
+
+        if status.is_success() {
+            Ok(json!({ "status": status.as_u16(), "body": body }))
+        } else if status.is_client_error()
+            && status != reqwest::StatusCode::REQUEST_TIMEOUT
+            && status != reqwest::StatusCode::TOO_MANY_REQUESTS
+        {
+            Err(format!(
+                "{}webhook returned {}: {}",
+                PERMANENT_ERROR_PREFIX, status, body
+            ))
+        } else {
+            Err(format!("webhook returned {}: {}", status, body))
+        }
+    }
+
+    fn is_retryable(&self, _task: &ExecutionTask, error: &str) -> bool {
+        !error.starts_with(PERMANENT_ERROR_PREFIX)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct EmitSpanRequest {
+    title: String,
+    #[serde(default)]
+    logline_id: Option<String>,
+    #[serde(default)]
+    status: Option<SpanStatus>,
+    #[serde(default)]
+    data: Option<Value>,
+    #[serde(default)]
+    metadata: Option<Value>,
+    #[serde(default)]
+    workflow_id: Option<String>,
+    #[serde(default)]
+    flow_id: Option<String>,
+    #[serde(default)]
+    caused_by: Option<Uuid>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// Records a span described by the payload on the task tenant's timeline and
+/// returns its id.
+pub struct EmitSpanHandler {
+    recorder: Arc<dyn SpanRecorder>,
+}
+
+impl EmitSpanHandler {
+    pub fn new(recorder: Arc<dyn SpanRecorder>) -> Self {
+        Self { recorder }
+    }
+
+    pub fn schema() -> PayloadSchema {
+        PayloadSchema::new()
+            .required("title", FieldType::String)
+            .optional("logline_id", FieldType::String)
+            .optional("status", FieldType::String)
+            .optional("data", FieldType::Any)
+            .optional("metadata", FieldType::Object)
+            .optional("workflow_id", FieldType::String)
+            .optional("flow_id", FieldType::String)
+            .optional("caused_by", FieldType::String)
+            .optional("tags", FieldType::Array)
+    }
+}
+
+#[async_trait]
+impl TaskHandler for EmitSpanHandler {
+    async fn handle(&self, task: ExecutionTask) -> Result<Value, String> {
+        let request: EmitSpanRequest = serde_json::from_value(task.payload)
+            .map_err(|err| format!("invalid span payload: {}", err))?;
+        let mut builder = SpanBuilder::new(
+            request
+                .logline_id
+                .unwrap_or_else(|| ENGINE_LOGLINE_ID.to_string()),
+            request.title,
+        )
+        .status(request.status.unwrap_or(SpanStatus::Executed))
+        .tenant_id(task.tenant_id.clone())
+        .span_type(SpanType::System);
+        if let Some(data) = request.data {
+            builder = builder.payload(data);
+        }
+        if let Some(metadata) = request.metadata {
+            builder = builder.metadata(metadata);
+        }
+        let mut span = builder.build();
+        span.workflow_id = request.workflow_id;
+        span.flow_id = request.flow_id;
+        span.caused_by = request.caused_by;
+        for tag in request.tags {
+            span.add_tag(tag);
+        }
+
+        let span_id = span.id;
+        self.recorder
+            .record(span)
+            .await
+            .map_err(|err| err.to_string())?;
+        Ok(json!({ "span_id": span_id }))
+    }
+}
+
+/// Evaluates the payload's `span` against the task tenant's rules through the
+/// rules service and returns the decision.
+pub struct EvaluateRulesHandler {
+    rules: Arc<RulesServiceClient>,
+}
+
+impl EvaluateRulesHandler {
+    pub fn new(rules: Arc<RulesServiceClient>) -> Self {
+        Self { rules }
+    }
+
+    pub fn schema() -> PayloadSchema {
+        PayloadSchema::new().required("span", FieldType::Object)
+    }
+}
+
+#[async_trait]
+impl TaskHandler for EvaluateRulesHandler {
+    async fn handle(&self, task: ExecutionTask) -> Result<Value, String> {
+        let span = task
+            .payload
+            .get("span")
+            .cloned()
+            .ok_or_else(|| "payload has no span".to_string())
+            .and_then(|span| {
+                serde_json::from_value(span).map_err(|err| format!("invalid span: {}", err))
+            })?;
+        let outcome = self
+            .rules
+            .evaluate_span(&task.tenant_id, &span)
+            .await
+            .map_err(|err| err.to_string())?;
+
+        Ok(json!({
+            "decision": {
+                "state": outcome.decision.state,
+                "reason": outcome.decision.reason,
+                "note": outcome.decision.note,
+            },
+            "applied_rules": outcome.applied_rules,
+            "notes": outcome.notes,
+            "added_tags": outcome.added_tags,
+            "metadata_updates": outcome.metadata_updates,
+            "requested_tasks": outcome
+                .tasks
+                .iter()
+                .map(|request| &request.rule_id)
+                .collect::<Vec<_>>(),
+            "derived_spans": outcome.derived_spans,
+            "span": outcome.span,
+        }))
+    }
+}
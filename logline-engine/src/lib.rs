@@ -1,16 +1,65 @@
 //! LogLine Engine - task scheduler and execution runtime service.
 
 pub mod api;
+pub mod cron;
 pub mod error;
+pub mod events;
+pub mod execution_spans;
+pub mod handlers;
+pub mod postgres;
+pub mod queue;
+pub mod quota;
+pub mod recurring;
+pub mod registry;
+pub mod retry;
 pub mod rules_client;
 pub mod runtime;
 pub mod scheduler;
 pub mod task;
+pub mod timeline_client;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "wasm")]
+pub mod wasm_executor;
+pub mod workflow;
 pub mod ws_client;
 
 pub use api::{EngineApiBuilder, EngineServiceConfig};
+pub use cron::CronSchedule;
 pub use error::EngineError;
+pub use events::{TaskEvent, TaskEvents, TASK_EVENT_CAPACITY};
+pub use execution_spans::{verify_span, ExecutionSpans, CAUSED_BY_METADATA_KEY};
+pub use handlers::{
+    EmitSpanHandler, EvaluateRulesHandler, WebhookHandler, EMIT_SPAN_KIND, EVALUATE_RULES_KIND,
+    WEBHOOK_KIND,
+};
+pub use postgres::PostgresTaskQueue;
+pub use queue::{MemoryTaskQueue, TaskQueue};
+pub use quota::{
+    QuotaConfig, SubscriptionTier, TenantLoad, TenantQuota, TenantQuotas, WaitTimeStats,
+};
+pub use recurring::{
+    MissedRunPolicy, RecurrenceSchedule, RecurringScheduler, RecurringTask, MAX_CATCH_UP_RUNS,
+};
+pub use registry::{FieldType, PayloadSchema, TaskKind, TaskRegistry};
+pub use retry::{RetryPolicy, TaskFailure};
 pub use rules_client::{RulesClientError, RulesServiceClient};
-pub use runtime::{EngineHandle, ExecutionRuntime, TaskHandler};
+pub use runtime::{
+    DrainReport, EngineHandle, ExecutionRuntime, Submission, TaskHandler, CANCEL_GRACE_PERIOD,
+    DEFAULT_DRAIN_DEADLINE, DEFAULT_IDEMPOTENCY_TTL, DEFAULT_TASK_LEASE, MAX_IDEMPOTENCY_KEY_LEN,
+};
 pub use scheduler::TaskScheduler;
 pub use task::{ExecutionOutcome, ExecutionTask, TaskPriority, TaskRecord, TaskStatus};
+pub use timeline_client::{SpanRecorder, TimelineClient, TimelineClientError};
+pub use tokio_util::sync::CancellationToken;
+#[cfg(feature = "wasm")]
+pub use wasm::{
+    content_hash, WasmError, WasmExecutor, WasmInvocation, WasmLimits, WasmModule, WasmModuleStore,
+    WasmOutput, WasmTaskHandler, WASM_KIND,
+};
+#[cfg(feature = "wasm")]
+pub use wasm_executor::{WasmtimeExecutor, EPOCH_TICK, MAX_LOG_LINES};
+pub use workflow::{
+    FailurePolicy, StepState, StepStatus, Workflow, WorkflowProgress, WorkflowState,
+    WorkflowStatus, WorkflowStep, WorkflowTracker,
+};
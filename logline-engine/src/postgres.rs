@@ -0,0 +1,924 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use logline_core::db::DatabasePool;
+use serde_json::Value;
+use sqlx::types::Json;
+use sqlx::{FromRow, PgExecutor};
+use uuid::Uuid;
+
+use crate::error::EngineError;
+use crate::queue::TaskQueue;
+use crate::quota::{SubscriptionTier, TenantLoad, TenantQuotas};
+use crate::retry::{RetryPolicy, TaskFailure};
+use crate::task::{ExecutionOutcome, ExecutionTask, TaskPriority, TaskRecord, TaskStatus};
+
+const TASK_COLUMNS: &str =
+    "id, tenant_id, payload, priority, scheduled_for, created_at, metadata, \
+     status, started_at, finished_at, last_error, result, lease_owner, lease_expires_at, \
+     attempts, error_history, retry_policy, cancel_requested, timeout_ms, kind, \
+     idempotency_key";
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Durable queue stored in the `engine_tasks` table.
+///
+/// Several engine processes can share the table: claims use
+/// `FOR UPDATE SKIP LOCKED` so each task goes to a single worker, and the
+/// `engine_task_tenants` cursor hands the next task to the tenant that was
+/// served longest ago.
+#[derive(Clone)]
+pub struct PostgresTaskQueue {
+    pool: DatabasePool,
+    poll_interval: Duration,
+}
+
+impl PostgresTaskQueue {
+    /// Connects to the database and applies the engine migrations, which
+    /// create the queue tables.
+    pub async fn connect(database_url: &str) -> Result<Self, EngineError> {
+        let pool = DatabasePool::connect_with_url(database_url)
+            .await
+            .map_err(queue_error)?;
+        Self::from_pool(pool).await
+    }
+
+    /// Builds the queue on an existing pool and applies the engine
+    /// migrations.
+    ///
+    /// The engine keeps its migrations in `logline-engine/migrations`, apart
+    /// from the timeline's, so it can run on a database of its own. When it
+    /// shares the timeline database both sets are recorded in the same
+    /// `_sqlx_migrations` table, and each migrator skips the versions
+    /// applied by the other.
+    pub async fn from_pool(pool: DatabasePool) -> Result<Self, EngineError> {
+        let mut migrator = sqlx::migrate!("./migrations");
+        migrator.set_ignore_missing(true);
+        migrator.run(pool.inner()).await.map_err(queue_error)?;
+        Ok(Self {
+            pool,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        })
+    }
+
+    /// How often idle workers check the table for tasks enqueued elsewhere.
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Subscription tier of every organisation, by tenant id. Empty when the
+    /// database has no `organizations` table.
+    pub async fn subscription_tiers(&self) -> Result<Vec<(String, SubscriptionTier)>, EngineError> {
+        let exists: bool = sqlx::query_scalar("SELECT to_regclass('organizations') IS NOT NULL")
+            .fetch_one(self.pool.inner())
+            .await
+            .map_err(queue_error)?;
+        if !exists {
+            return Ok(Vec::new());
+        }
+
+        let rows: Vec<(String, Option<String>)> =
+            sqlx::query_as("SELECT tenant_id, subscription_tier FROM organizations")
+                .fetch_all(self.pool.inner())
+                .await
+                .map_err(queue_error)?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(tenant, tier)| {
+                let tier = SubscriptionTier::parse(tier.as_deref().unwrap_or("free"))?;
+                Some((tenant, tier))
+            })
+            .collect())
+    }
+
+    /// Tenants whose running tasks reached their in-flight limit.
+    async fn saturated_tenants(&self, quotas: &TenantQuotas) -> Result<Vec<String>, EngineError> {
+        if !quotas.limits_in_flight() {
+            return Ok(Vec::new());
+        }
+        let running: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT tenant_id, COUNT(*) FROM engine_tasks \
+             WHERE status = 'running' GROUP BY tenant_id",
+        )
+        .fetch_all(self.pool.inner())
+        .await
+        .map_err(queue_error)?;
+        Ok(running
+            .into_iter()
+            .filter(|(tenant, count)| !quotas.admits(tenant, *count as usize))
+            .map(|(tenant, _)| tenant)
+            .collect())
+    }
+}
+
+fn queue_error(err: impl std::fmt::Display) -> EngineError {
+    EngineError::Queue(err.to_string())
+}
+
+fn lease_deadline(now: DateTime<Utc>, lease: Duration) -> DateTime<Utc> {
+    now + chrono::Duration::from_std(lease).unwrap_or_else(|_| chrono::Duration::zero())
+}
+
+fn priority_from_rank(rank: i32) -> TaskPriority {
+    match rank {
+        0 => TaskPriority::Critical,
+        10 => TaskPriority::High,
+        100 => TaskPriority::Low,
+        _ => TaskPriority::Normal,
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct TaskRow {
+    id: Uuid,
+    tenant_id: String,
+    payload: Value,
+    priority: i32,
+    scheduled_for: DateTime<Utc>,
+    created_at: DateTime<Utc>,
+    metadata: Option<Value>,
+    status: String,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    result: Option<Value>,
+    lease_owner: Option<String>,
+    lease_expires_at: Option<DateTime<Utc>>,
+    attempts: i32,
+    error_history: Json<Vec<TaskFailure>>,
+    retry_policy: Option<Json<RetryPolicy>>,
+    cancel_requested: bool,
+    timeout_ms: Option<i64>,
+    kind: Option<String>,
+    idempotency_key: Option<String>,
+}
+
+impl TryFrom<TaskRow> for TaskRecord {
+    type Error = EngineError;
+
+    fn try_from(row: TaskRow) -> Result<Self, Self::Error> {
+        let status = TaskStatus::parse(&row.status).ok_or_else(|| {
+            EngineError::Queue(format!(
+                "task {} has unknown status `{}`",
+                row.id, row.status
+            ))
+        })?;
+        Ok(TaskRecord {
+            task: ExecutionTask {
+                id: row.id,
+                tenant_id: row.tenant_id,
+                kind: row.kind,
+                payload: row.payload,
+                priority: priority_from_rank(row.priority),
+                scheduled_for: row.scheduled_for,
+                created_at: row.created_at,
+                metadata: row.metadata,
+                retry: row.retry_policy.map(|policy| policy.0).unwrap_or_default(),
+                timeout_ms: row.timeout_ms.map(|timeout| timeout.max(0) as u64),
+                idempotency_key: row.idempotency_key,
+            },
+            status,
+            started_at: row.started_at,
+            finished_at: row.finished_at,
+            last_error: row.last_error,
+            result: row.result,
+            attempts: row.attempts.max(0) as u32,
+            error_history: row.error_history.0,
+            cancel_requested: row.cancel_requested,
+            lease_owner: row.lease_owner,
+            lease_expires_at: row.lease_expires_at,
+        })
+    }
+}
+
+/// Inserts `record`, returning whether a row was written; `on_conflict` is
+/// appended to the statement.
+async fn insert_task<'e>(
+    executor: impl PgExecutor<'e>,
+    record: &TaskRecord,
+    on_conflict: &str,
+) -> Result<bool, EngineError> {
+    let task = &record.task;
+    let result = sqlx::query(&format!(
+        r#"
+        INSERT INTO engine_tasks (
+            id, tenant_id, payload, priority, scheduled_for, created_at, metadata, status,
+            retry_policy, timeout_ms, kind, idempotency_key
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        {}
+        "#,
+        on_conflict
+    ))
+    .bind(task.id)
+    .bind(&task.tenant_id)
+    .bind(&task.payload)
+    .bind(task.priority as i32)
+    .bind(task.scheduled_for)
+    .bind(task.created_at)
+    .bind(&task.metadata)
+    .bind(record.status.as_str())
+    .bind(Json(&task.retry))
+    .bind(
+        task.timeout_ms
+            .map(|timeout| timeout.min(i64::MAX as u64) as i64),
+    )
+    .bind(&task.kind)
+    .bind(&task.idempotency_key)
+    .execute(executor)
+    .await
+    .map_err(queue_error)?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[async_trait]
+impl TaskQueue for PostgresTaskQueue {
+    async fn enqueue(&self, record: TaskRecord) -> Result<(), EngineError> {
+        insert_task(self.pool.inner(), &record, "").await?;
+        Ok(())
+    }
+
+    async fn enqueue_idempotent(
+        &self,
+        record: TaskRecord,
+        since: DateTime<Utc>,
+    ) -> Result<Option<TaskRecord>, EngineError> {
+        let Some(key) = record.task.idempotency_key.as_deref() else {
+            self.enqueue(record).await?;
+            return Ok(None);
+        };
+        let tenant = record.task.tenant_id.as_str();
+        let mut tx = self.pool.inner().begin().await.map_err(queue_error)?;
+
+        // Release the key from an expired task so that it can be reused.
+        sqlx::query(
+            "UPDATE engine_tasks SET idempotency_key = NULL \
+             WHERE tenant_id = $1 AND idempotency_key = $2 AND created_at < $3",
+        )
+        .bind(tenant)
+        .bind(key)
+        .bind(since)
+        .execute(&mut *tx)
+        .await
+        .map_err(queue_error)?;
+
+        let inserted = insert_task(
+            &mut *tx,
+            &record,
+            "ON CONFLICT (tenant_id, idempotency_key) WHERE idempotency_key IS NOT NULL \
+             DO NOTHING",
+        )
+        .await?;
+        let existing = if inserted {
+            None
+        } else {
+            let row = sqlx::query_as::<_, TaskRow>(&format!(
+                "SELECT {} FROM engine_tasks WHERE tenant_id = $1 AND idempotency_key = $2",
+                TASK_COLUMNS
+            ))
+            .bind(tenant)
+            .bind(key)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(queue_error)?;
+            Some(TaskRecord::try_from(row)?)
+        };
+        tx.commit().await.map_err(queue_error)?;
+        Ok(existing)
+    }
+
+    async fn find_idempotent(
+        &self,
+        tenant: &str,
+        key: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<TaskRecord>, EngineError> {
+        let row = sqlx::query_as::<_, TaskRow>(&format!(
+            "SELECT {} FROM engine_tasks \
+             WHERE tenant_id = $1 AND idempotency_key = $2 AND created_at >= $3",
+            TASK_COLUMNS
+        ))
+        .bind(tenant)
+        .bind(key)
+        .bind(since)
+        .fetch_optional(self.pool.inner())
+        .await
+        .map_err(queue_error)?;
+        row.map(TaskRecord::try_from).transpose()
+    }
+
+    async fn claim(
+        &self,
+        worker: &str,
+        lease: Duration,
+        quotas: &TenantQuotas,
+    ) -> Result<Option<TaskRecord>, EngineError> {
+        let now = Utc::now();
+        // In-flight limits are checked before the claim, so concurrent claims
+        // from several processes may briefly exceed them.
+        let saturated = self.saturated_tenants(quotas).await?;
+        let mut tx = self.pool.inner().begin().await.map_err(queue_error)?;
+
+        let row = sqlx::query_as::<_, TaskRow>(&format!(
+            r#"
+            WITH candidate AS (
+                SELECT t.id AS candidate_id
+                FROM engine_tasks t
+                LEFT JOIN engine_task_tenants c ON c.tenant_id = t.tenant_id
+                WHERE t.status = 'queued' AND t.scheduled_for <= $1
+                  AND NOT (t.tenant_id = ANY($4))
+                ORDER BY c.virtual_time ASC NULLS FIRST, c.last_claimed_at ASC NULLS FIRST,
+                         t.priority ASC, t.scheduled_for ASC, t.created_at ASC
+                LIMIT 1
+                FOR UPDATE OF t SKIP LOCKED
+            )
+            UPDATE engine_tasks
+            SET status = 'running', started_at = $1, last_error = NULL,
+                attempts = engine_tasks.attempts + 1, lease_owner = $2, lease_expires_at = $3, updated_at = now()
+            FROM candidate
+            WHERE engine_tasks.id = candidate.candidate_id
+            RETURNING {}
+            "#,
+            TASK_COLUMNS
+        ))
+        .bind(now)
+        .bind(worker)
+        .bind(lease_deadline(now, lease))
+        .bind(&saturated)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(queue_error)?;
+
+        let Some(row) = row else {
+            tx.rollback().await.map_err(queue_error)?;
+            return Ok(None);
+        };
+
+        // A tenant returning from idle starts at most one unit behind the
+        // leader, which bounds the burst it gets before sharing again.
+        sqlx::query(
+            r#"
+            INSERT INTO engine_task_tenants AS c (tenant_id, last_claimed_at, virtual_time)
+            VALUES (
+                $1, $2,
+                COALESCE((SELECT MAX(virtual_time) FROM engine_task_tenants), 1) - 1 + $3
+            )
+            ON CONFLICT (tenant_id) DO UPDATE
+            SET last_claimed_at = EXCLUDED.last_claimed_at,
+                virtual_time = GREATEST(
+                    c.virtual_time,
+                    (SELECT MAX(virtual_time) FROM engine_task_tenants) - 1
+                ) + $3
+            "#,
+        )
+        .bind(&row.tenant_id)
+        .bind(now)
+        .bind(1.0 / f64::from(quotas.quota(&row.tenant_id).weight.max(1)))
+        .execute(&mut *tx)
+        .await
+        .map_err(queue_error)?;
+        tx.commit().await.map_err(queue_error)?;
+
+        row.try_into().map(Some)
+    }
+
+    async fn renew_lease(
+        &self,
+        task_id: &Uuid,
+        worker: &str,
+        lease: Duration,
+    ) -> Result<bool, EngineError> {
+        sqlx::query_scalar::<_, bool>(
+            r#"
+            UPDATE engine_tasks SET lease_expires_at = $3, updated_at = now()
+            WHERE id = $1 AND status = 'running' AND lease_owner = $2
+            RETURNING cancel_requested
+            "#,
+        )
+        .bind(task_id)
+        .bind(worker)
+        .bind(lease_deadline(Utc::now(), lease))
+        .fetch_optional(self.pool.inner())
+        .await
+        .map_err(queue_error)?
+        .ok_or_else(|| EngineError::LeaseLost(task_id.to_string()))
+    }
+
+    async fn cancel(&self, tenant: &str, task_id: &Uuid) -> Result<TaskRecord, EngineError> {
+        let row = sqlx::query_as::<_, TaskRow>(&format!(
+            r#"
+            UPDATE engine_tasks
+            SET status = CASE WHEN status = 'queued' THEN 'cancelled' ELSE status END,
+                finished_at = CASE WHEN status = 'queued' THEN now() ELSE finished_at END,
+                cancel_requested = cancel_requested OR status = 'running',
+                updated_at = now()
+            WHERE id = $1 AND tenant_id = $2 AND status IN ('queued', 'running')
+            RETURNING {}
+            "#,
+            TASK_COLUMNS
+        ))
+        .bind(task_id)
+        .bind(tenant)
+        .fetch_optional(self.pool.inner())
+        .await
+        .map_err(queue_error)?;
+
+        match row {
+            Some(row) => row.try_into(),
+            None => match self.get(task_id).await {
+                Ok(record) if record.task.tenant_id == tenant => {
+                    Err(EngineError::TaskFinished(task_id.to_string()))
+                }
+                Ok(_) => Err(EngineError::TaskNotFound(task_id.to_string())),
+                Err(err) => Err(err),
+            },
+        }
+    }
+
+    async fn complete(&self, worker: &str, outcome: &ExecutionOutcome) -> Result<(), EngineError> {
+        let updated = sqlx::query(
+            r#"
+            UPDATE engine_tasks
+            SET status = $3, finished_at = $4, last_error = $5, result = $6,
+                lease_owner = NULL, lease_expires_at = NULL, updated_at = now()
+            WHERE id = $1 AND lease_owner = $2
+            "#,
+        )
+        .bind(outcome.task_id)
+        .bind(worker)
+        .bind(outcome.status.as_str())
+        .bind(outcome.finished_at)
+        .bind(&outcome.error)
+        .bind(&outcome.result)
+        .execute(self.pool.inner())
+        .await
+        .map_err(queue_error)?;
+
+        if updated.rows_affected() == 0 {
+            return Err(EngineError::LeaseLost(outcome.task_id.to_string()));
+        }
+        Ok(())
+    }
+
+    async fn fail(
+        &self,
+        worker: &str,
+        task_id: &Uuid,
+        failure: TaskFailure,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<(), EngineError> {
+        let updated = sqlx::query(
+            r#"
+            UPDATE engine_tasks
+            SET status = CASE WHEN $4::timestamptz IS NULL THEN 'failed' ELSE 'queued' END,
+                scheduled_for = COALESCE($4, scheduled_for),
+                started_at = CASE WHEN $4::timestamptz IS NULL THEN started_at END,
+                finished_at = CASE WHEN $4::timestamptz IS NULL THEN now() END,
+                last_error = $3::jsonb ->> 'error',
+                error_history = error_history || jsonb_build_array($3::jsonb),
+                lease_owner = NULL, lease_expires_at = NULL, updated_at = now()
+            WHERE id = $1 AND lease_owner = $2
+            "#,
+        )
+        .bind(task_id)
+        .bind(worker)
+        .bind(Json(&failure))
+        .bind(retry_at)
+        .execute(self.pool.inner())
+        .await
+        .map_err(queue_error)?;
+
+        if updated.rows_affected() == 0 {
+            return Err(EngineError::LeaseLost(task_id.to_string()));
+        }
+        Ok(())
+    }
+
+    async fn dead_letters(&self, tenant: &str) -> Result<Vec<TaskRecord>, EngineError> {
+        sqlx::query_as::<_, TaskRow>(&format!(
+            "SELECT {} FROM engine_tasks WHERE tenant_id = $1 AND status = 'failed' \
+             ORDER BY finished_at DESC",
+            TASK_COLUMNS
+        ))
+        .bind(tenant)
+        .fetch_all(self.pool.inner())
+        .await
+        .map_err(queue_error)?
+        .into_iter()
+        .map(TaskRecord::try_from)
+        .collect()
+    }
+
+    async fn redrive(
+        &self,
+        tenant: &str,
+        task_id: Option<&Uuid>,
+    ) -> Result<Vec<Uuid>, EngineError> {
+        sqlx::query_scalar::<_, Uuid>(
+            r#"
+            UPDATE engine_tasks
+            SET status = 'queued', attempts = 0, scheduled_for = now(), started_at = NULL,
+                finished_at = NULL, last_error = NULL, updated_at = now()
+            WHERE tenant_id = $1 AND status = 'failed' AND ($2::uuid IS NULL OR id = $2)
+            RETURNING id
+            "#,
+        )
+        .bind(tenant)
+        .bind(task_id)
+        .fetch_all(self.pool.inner())
+        .await
+        .map_err(queue_error)
+    }
+
+    async fn purge_dead_letters(
+        &self,
+        tenant: &str,
+        task_id: Option<&Uuid>,
+    ) -> Result<Vec<Uuid>, EngineError> {
+        sqlx::query_scalar::<_, Uuid>(
+            r#"
+            DELETE FROM engine_tasks
+            WHERE tenant_id = $1 AND status = 'failed' AND ($2::uuid IS NULL OR id = $2)
+            RETURNING id
+            "#,
+        )
+        .bind(tenant)
+        .bind(task_id)
+        .fetch_all(self.pool.inner())
+        .await
+        .map_err(queue_error)
+    }
+
+    async fn recover_expired_leases(&self, now: DateTime<Utc>) -> Result<Vec<Uuid>, EngineError> {
+        sqlx::query_scalar::<_, Uuid>(
+            r#"
+            UPDATE engine_tasks
+            SET status = CASE WHEN cancel_requested THEN 'cancelled' ELSE 'queued' END,
+                started_at = CASE WHEN cancel_requested THEN started_at END,
+                finished_at = CASE WHEN cancel_requested THEN now() END,
+                lease_owner = NULL, lease_expires_at = NULL, updated_at = now()
+            WHERE status = 'running' AND lease_expires_at < $1
+            RETURNING id
+            "#,
+        )
+        .bind(now)
+        .fetch_all(self.pool.inner())
+        .await
+        .map_err(queue_error)
+    }
+
+    async fn hand_off(&self, runtime_id: &str) -> Result<Vec<TaskRecord>, EngineError> {
+        sqlx::query_as::<_, TaskRow>(&format!(
+            r#"
+            UPDATE engine_tasks
+            SET status = CASE WHEN cancel_requested THEN 'cancelled' ELSE 'queued' END,
+                started_at = CASE WHEN cancel_requested THEN started_at END,
+                finished_at = CASE WHEN cancel_requested THEN now() END,
+                lease_owner = NULL, lease_expires_at = NULL, updated_at = now()
+            WHERE status = 'running' AND starts_with(lease_owner, $1)
+            RETURNING {}
+            "#,
+            TASK_COLUMNS
+        ))
+        .bind(format!("{}/", runtime_id))
+        .fetch_all(self.pool.inner())
+        .await
+        .map_err(queue_error)?
+        .into_iter()
+        .map(TaskRecord::try_from)
+        .collect()
+    }
+
+    async fn take_queued(&self) -> Result<Vec<TaskRecord>, EngineError> {
+        Ok(Vec::new())
+    }
+
+    async fn get(&self, task_id: &Uuid) -> Result<TaskRecord, EngineError> {
+        sqlx::query_as::<_, TaskRow>(&format!(
+            "SELECT {} FROM engine_tasks WHERE id = $1",
+            TASK_COLUMNS
+        ))
+        .bind(task_id)
+        .fetch_optional(self.pool.inner())
+        .await
+        .map_err(queue_error)?
+        .ok_or_else(|| EngineError::TaskNotFound(task_id.to_string()))?
+        .try_into()
+    }
+
+    async fn list_for_tenant(&self, tenant: &str) -> Result<Vec<TaskRecord>, EngineError> {
+        sqlx::query_as::<_, TaskRow>(&format!(
+            "SELECT {} FROM engine_tasks WHERE tenant_id = $1 ORDER BY created_at",
+            TASK_COLUMNS
+        ))
+        .bind(tenant)
+        .fetch_all(self.pool.inner())
+        .await
+        .map_err(queue_error)?
+        .into_iter()
+        .map(TaskRecord::try_from)
+        .collect()
+    }
+
+    async fn pending(&self) -> Result<usize, EngineError> {
+        let count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM engine_tasks WHERE status = 'queued'")
+                .fetch_one(self.pool.inner())
+                .await
+                .map_err(queue_error)?;
+        Ok(count as usize)
+    }
+
+    async fn tenant_load(&self, tenant: &str) -> Result<TenantLoad, EngineError> {
+        let (queued, running): (i64, i64) = sqlx::query_as(
+            "SELECT COUNT(*) FILTER (WHERE status = 'queued'), \
+                    COUNT(*) FILTER (WHERE status = 'running') \
+             FROM engine_tasks WHERE tenant_id = $1",
+        )
+        .bind(tenant)
+        .fetch_one(self.pool.inner())
+        .await
+        .map_err(queue_error)?;
+        Ok(TenantLoad {
+            queued: queued as usize,
+            running: running as usize,
+        })
+    }
+
+    async fn next_due_at(&self) -> Result<Option<DateTime<Utc>>, EngineError> {
+        sqlx::query_scalar(
+            "SELECT MIN(scheduled_for) FROM engine_tasks \
+             WHERE status = 'queued' AND scheduled_for > $1",
+        )
+        .bind(Utc::now())
+        .fetch_one(self.pool.inner())
+        .await
+        .map_err(queue_error)
+    }
+
+    fn poll_interval(&self) -> Option<Duration> {
+        Some(self.poll_interval)
+    }
+}
+
+/// These tests run against the database named by `DATABASE_URL` and are
+/// skipped when it is unset. Each test gets a schema of its own, so they can
+/// run in parallel on a shared database.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quota::TenantQuota;
+
+    struct TestDatabase {
+        admin: DatabasePool,
+        schema: String,
+        queue: PostgresTaskQueue,
+    }
+
+    impl TestDatabase {
+        async fn connect() -> Option<Self> {
+            let Ok(database_url) = std::env::var("DATABASE_URL") else {
+                eprintln!("DATABASE_URL is not set; skipping Postgres queue test");
+                return None;
+            };
+            let admin = DatabasePool::connect_with_url(&database_url)
+                .await
+                .expect("connects to DATABASE_URL");
+            let schema = format!("engine_test_{}", Uuid::new_v4().simple());
+            sqlx::query(&format!("CREATE SCHEMA {}", schema))
+                .execute(admin.inner())
+                .await
+                .unwrap();
+
+            let mut url = url::Url::parse(&database_url).unwrap();
+            url.query_pairs_mut()
+                .append_pair("options", &format!("-c search_path={}", schema));
+            let queue = PostgresTaskQueue::connect(url.as_str()).await.unwrap();
+            Some(Self {
+                admin,
+                schema,
+                queue,
+            })
+        }
+
+        async fn cleanup(self) {
+            self.queue.pool.inner().close().await;
+            sqlx::query(&format!("DROP SCHEMA {} CASCADE", self.schema))
+                .execute(self.admin.inner())
+                .await
+                .unwrap();
+        }
+    }
+
+    fn failure(attempt: u32) -> TaskFailure {
+        TaskFailure {
+            attempt,
+            error: "upstream timed out".into(),
+            retryable: true,
+            failed_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn claims_skip_locked_rows_and_rotate_tenants() {
+        let Some(db) = TestDatabase::connect().await else {
+            return;
+        };
+        let queue = &db.queue;
+        let quotas = TenantQuotas::default();
+        quotas.set_override("heavy", TenantQuota::with_weight(2));
+        let lease = Duration::from_secs(30);
+        for tenant in [
+            "heavy", "heavy", "heavy", "heavy", "heavy", "light", "light", "light",
+        ] {
+            let task = ExecutionTask::builder(tenant).build();
+            queue.enqueue(TaskRecord::new(task)).await.unwrap();
+        }
+
+        // A row locked by another transaction is passed over, not waited on.
+        let mut tx = queue.pool.inner().begin().await.unwrap();
+        let locked: Uuid = sqlx::query_scalar(
+            "SELECT id FROM engine_tasks WHERE tenant_id = 'light' \
+             ORDER BY created_at LIMIT 1 FOR UPDATE",
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .unwrap();
+
+        let mut claimed = Vec::new();
+        while let Some(record) = queue.claim("worker", lease, &quotas).await.unwrap() {
+            assert_ne!(record.task.id, locked);
+            claimed.push(record.task.tenant_id);
+        }
+        tx.rollback().await.unwrap();
+
+        assert_eq!(
+            claimed,
+            ["heavy", "light", "heavy", "light", "heavy", "heavy", "heavy"]
+        );
+        let last = queue
+            .claim("worker", lease, &quotas)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(last.task.id, locked);
+        db.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn requeues_tasks_whose_lease_expired() {
+        let Some(db) = TestDatabase::connect().await else {
+            return;
+        };
+        let queue = &db.queue;
+        let quotas = TenantQuotas::default();
+        let task = ExecutionTask::builder("tenant").build();
+        queue.enqueue(TaskRecord::new(task.clone())).await.unwrap();
+
+        let claimed = queue
+            .claim("worker-a", Duration::from_millis(10), &quotas)
+            .await
+            .unwrap()
+            .expect("task is due");
+        assert_eq!(claimed.lease_owner.as_deref(), Some("worker-a"));
+        assert!(queue
+            .claim("worker-b", Duration::from_secs(1), &quotas)
+            .await
+            .unwrap()
+            .is_none());
+
+        let later = Utc::now() + chrono::Duration::seconds(1);
+        assert_eq!(
+            queue.recover_expired_leases(later).await.unwrap(),
+            vec![task.id]
+        );
+        let reclaimed = queue
+            .claim("worker-b", Duration::from_secs(30), &quotas)
+            .await
+            .unwrap()
+            .expect("recovered task is claimable");
+        assert_eq!(reclaimed.task.id, task.id);
+        assert_eq!(reclaimed.attempts, 2);
+
+        let outcome = ExecutionOutcome::success(&task, Utc::now(), Value::Null);
+        assert!(matches!(
+            queue.complete("worker-a", &outcome).await,
+            Err(EngineError::LeaseLost(_))
+        ));
+        queue.complete("worker-b", &outcome).await.unwrap();
+        assert_eq!(
+            queue.get(&task.id).await.unwrap().status,
+            TaskStatus::Completed
+        );
+        db.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn returns_the_task_already_holding_an_idempotency_key() {
+        let Some(db) = TestDatabase::connect().await else {
+            return;
+        };
+        let queue = &db.queue;
+        let since = Utc::now() - chrono::Duration::hours(1);
+        let keyed = |tenant: &str| {
+            TaskRecord::new(
+                ExecutionTask::builder(tenant)
+                    .idempotency_key("order-42")
+                    .build(),
+            )
+        };
+
+        let first = keyed("tenant");
+        assert!(queue
+            .enqueue_idempotent(first.clone(), since)
+            .await
+            .unwrap()
+            .is_none());
+        let existing = queue
+            .enqueue_idempotent(keyed("tenant"), since)
+            .await
+            .unwrap()
+            .expect("key is taken");
+        assert_eq!(existing.task.id, first.task.id);
+        assert!(queue
+            .enqueue_idempotent(keyed("other"), since)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(queue.list_for_tenant("tenant").await.unwrap().len(), 1);
+
+        // Once the first task is older than `since` the key is free again.
+        let second = keyed("tenant");
+        assert!(queue
+            .enqueue_idempotent(second.clone(), Utc::now())
+            .await
+            .unwrap()
+            .is_none());
+        let found = queue
+            .find_idempotent("tenant", "order-42", since)
+            .await
+            .unwrap()
+            .expect("key is held");
+        assert_eq!(found.task.id, second.task.id);
+        db.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn retries_then_dead_letters_and_redrives() {
+        let Some(db) = TestDatabase::connect().await else {
+            return;
+        };
+        let queue = &db.queue;
+        let quotas = TenantQuotas::default();
+        let task = ExecutionTask::builder("tenant").build();
+        queue.enqueue(TaskRecord::new(task.clone())).await.unwrap();
+        let lease = Duration::from_secs(30);
+
+        queue
+            .claim("worker", lease, &quotas)
+            .await
+            .unwrap()
+            .unwrap();
+        queue
+            .fail("worker", &task.id, failure(1), Some(Utc::now()))
+            .await
+            .unwrap();
+        let retried = queue
+            .claim("worker", lease, &quotas)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(retried.attempts, 2);
+        queue
+            .fail("worker", &task.id, failure(2), None)
+            .await
+            .unwrap();
+
+        let dead = queue.dead_letters("tenant").await.unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].error_history.len(), 2);
+        assert!(queue.dead_letters("other").await.unwrap().is_empty());
+
+        assert_eq!(queue.redrive("tenant", None).await.unwrap(), vec![task.id]);
+        let redriven = queue
+            .claim("worker", lease, &quotas)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(redriven.attempts, 1);
+        queue
+            .fail("worker", &task.id, failure(1), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            queue
+                .purge_dead_letters("tenant", Some(&task.id))
+                .await
+                .unwrap(),
+            vec![task.id]
+        );
+        assert!(queue.get(&task.id).await.is_err());
+        db.cleanup().await;
+    }
+}
@@ -0,0 +1,614 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use uuid::Uuid;
+
+use crate::error::EngineError;
+use crate::quota::{TenantLoad, TenantQuotas};
+use crate::retry::TaskFailure;
+use crate::scheduler::TaskScheduler;
+use crate::task::{ExecutionOutcome, TaskRecord, TaskStatus};
+
+/// Storage backend holding queued tasks and their records.
+///
+/// Workers `claim` due tasks under a lease that they renew while the handler
+/// runs. Tasks whose lease expires, because the worker or its process died,
+/// are put back in the queue by `recover_expired_leases`.
+#[async_trait]
+pub trait TaskQueue: Send + Sync + 'static {
+    /// Stores a new task; it becomes claimable at its `scheduled_for` time.
+    async fn enqueue(&self, record: TaskRecord) -> Result<(), EngineError>;
+
+    /// Stores a new task unless its tenant already has a task with the same
+    /// idempotency key created at or after `since`; that task is returned
+    /// instead and nothing is stored. Tasks without a key are always stored.
+    async fn enqueue_idempotent(
+        &self,
+        record: TaskRecord,
+        since: DateTime<Utc>,
+    ) -> Result<Option<TaskRecord>, EngineError>;
+
+    /// Task of `tenant` created at or after `since` with idempotency key
+    /// `key`, if any.
+    async fn find_idempotent(
+        &self,
+        tenant: &str,
+        key: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<TaskRecord>, EngineError>;
+
+    /// Hands the next due task to `worker`, rotating between tenants in
+    /// proportion to their weight and passing over tenants at their in-flight
+    /// limit, marks it running until `lease` elapses and counts the attempt.
+    async fn claim(
+        &self,
+        worker: &str,
+        lease: Duration,
+        quotas: &TenantQuotas,
+    ) -> Result<Option<TaskRecord>, EngineError>;
+
+    /// Extends the lease `worker` holds on a running task. Returns whether
+    /// the task has been asked to cancel.
+    async fn renew_lease(
+        &self,
+        task_id: &Uuid,
+        worker: &str,
+        lease: Duration,
+    ) -> Result<bool, EngineError>;
+
+    /// Cancels a task of `tenant`. Queued tasks are marked cancelled and never
+    /// handed out; running ones are flagged for their worker to abort.
+    async fn cancel(&self, tenant: &str, task_id: &Uuid) -> Result<TaskRecord, EngineError>;
+
+    /// Records the outcome of a task leased by `worker`.
+    async fn complete(&self, worker: &str, outcome: &ExecutionOutcome) -> Result<(), EngineError>;
+
+    /// Records a failed attempt of a task leased by `worker`. With a
+    /// `retry_at` the task is queued again for that time; otherwise it is
+    /// marked failed and joins the dead letters.
+    async fn fail(
+        &self,
+        worker: &str,
+        task_id: &Uuid,
+        failure: TaskFailure,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<(), EngineError>;
+
+    /// Failed tasks of `tenant`, most recent first.
+    async fn dead_letters(&self, tenant: &str) -> Result<Vec<TaskRecord>, EngineError>;
+
+    /// Queues failed tasks of `tenant` again with a fresh attempt budget; all
+    /// of them, or only `task_id`. Returns the re-driven ids.
+    async fn redrive(&self, tenant: &str, task_id: Option<&Uuid>)
+        -> Result<Vec<Uuid>, EngineError>;
+
+    /// Deletes failed tasks of `tenant`; all of them, or only `task_id`.
+    /// Returns the purged ids.
+    async fn purge_dead_letters(
+        &self,
+        tenant: &str,
+        task_id: Option<&Uuid>,
+    ) -> Result<Vec<Uuid>, EngineError>;
+
+    /// Requeues running tasks whose lease expired before `now`, or cancels
+    /// them if cancellation was requested, returning their ids.
+    async fn recover_expired_leases(&self, now: DateTime<Utc>) -> Result<Vec<Uuid>, EngineError>;
+
+    async fn get(&self, task_id: &Uuid) -> Result<TaskRecord, EngineError>;
+
+    async fn list_for_tenant(&self, tenant: &str) -> Result<Vec<TaskRecord>, EngineError>;
+
+    /// Queued tasks, including the ones not yet due.
+    async fn pending(&self) -> Result<usize, EngineError>;
+
+    /// Queued and running tasks of `tenant`.
+    async fn tenant_load(&self, tenant: &str) -> Result<TenantLoad, EngineError>;
+
+    /// Releases the tasks still leased by the workers of a draining runtime
+    /// (ids prefixed with `runtime_id/`): they are queued again in place,
+    /// keeping their attempts and metadata, for whichever process claims
+    /// them next, or finished as cancelled when that was requested. Returns
+    /// the released tasks.
+    async fn hand_off(&self, runtime_id: &str) -> Result<Vec<TaskRecord>, EngineError>;
+
+    /// Removes the queued tasks of a draining runtime so that they can be
+    /// submitted to another one. A shared queue returns nothing: the other
+    /// processes claim its tasks.
+    async fn take_queued(&self) -> Result<Vec<TaskRecord>, EngineError>;
+
+    /// Earliest `scheduled_for` among queued tasks that are not yet due.
+    async fn next_due_at(&self) -> Result<Option<DateTime<Utc>>, EngineError>;
+
+    /// How often idle workers look for work enqueued by other processes.
+    /// `None` when every enqueue goes through this process and wakes the
+    /// workers directly.
+    fn poll_interval(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Process-local queue; tasks are lost when the process exits.
+#[derive(Clone, Default)]
+pub struct MemoryTaskQueue {
+    scheduler: TaskScheduler,
+    registry: Arc<RwLock<HashMap<Uuid, TaskRecord>>>,
+}
+
+impl MemoryTaskQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn scheduler(&self) -> &TaskScheduler {
+        &self.scheduler
+    }
+}
+
+fn lease_deadline(lease: Duration) -> DateTime<Utc> {
+    Utc::now() + chrono::Duration::from_std(lease).unwrap_or_else(|_| chrono::Duration::zero())
+}
+
+fn is_dead_letter(record: &TaskRecord, tenant: &str, task_id: Option<&Uuid>) -> bool {
+    record.task.tenant_id == tenant
+        && record.status == TaskStatus::Failed
+        && task_id.is_none_or(|id| *id == record.task.id)
+}
+
+/// Queues a task whose lease ended again, or finishes it as cancelled when
+/// cancellation was requested meanwhile.
+fn release_lease(scheduler: &TaskScheduler, record: &mut TaskRecord, now: DateTime<Utc>) {
+    record.lease_owner = None;
+    record.lease_expires_at = None;
+    if record.cancel_requested {
+        record.status = TaskStatus::Cancelled;
+        record.finished_at = Some(now);
+    } else {
+        record.status = TaskStatus::Queued;
+        record.started_at = None;
+        scheduler.enqueue(record.task.clone());
+    }
+}
+
+/// Latest task of `tenant` created at or after `since` with idempotency key
+/// `key`.
+fn idempotent_match<'a>(
+    registry: &'a HashMap<Uuid, TaskRecord>,
+    tenant: &str,
+    key: &str,
+    since: DateTime<Utc>,
+) -> Option<&'a TaskRecord> {
+    registry
+        .values()
+        .filter(|existing| {
+            existing.task.tenant_id == tenant
+                && existing.task.idempotency_key.as_deref() == Some(key)
+                && existing.task.created_at >= since
+        })
+        .max_by_key(|existing| existing.task.created_at)
+}
+
+#[async_trait]
+impl TaskQueue for MemoryTaskQueue {
+    async fn enqueue(&self, record: TaskRecord) -> Result<(), EngineError> {
+        let task = record.task.clone();
+        self.registry.write().insert(task.id, record);
+        self.scheduler.enqueue(task);
+        Ok(())
+    }
+
+    async fn enqueue_idempotent(
+        &self,
+        record: TaskRecord,
+        since: DateTime<Utc>,
+    ) -> Result<Option<TaskRecord>, EngineError> {
+        let Some(key) = record.task.idempotency_key.clone() else {
+            self.enqueue(record).await?;
+            return Ok(None);
+        };
+        let mut registry = self.registry.write();
+        if let Some(existing) = idempotent_match(&registry, &record.task.tenant_id, &key, since) {
+            return Ok(Some(existing.clone()));
+        }
+        let task = record.task.clone();
+        registry.insert(task.id, record);
+        drop(registry);
+        self.scheduler.enqueue(task);
+        Ok(None)
+    }
+
+    async fn find_idempotent(
+        &self,
+        tenant: &str,
+        key: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<TaskRecord>, EngineError> {
+        Ok(idempotent_match(&self.registry.read(), tenant, key, since).cloned())
+    }
+
+    async fn claim(
+        &self,
+        worker: &str,
+        lease: Duration,
+        quotas: &TenantQuotas,
+    ) -> Result<Option<TaskRecord>, EngineError> {
+        let mut registry = self.registry.write();
+        let mut running: HashMap<String, usize> = HashMap::new();
+        if quotas.limits_in_flight() {
+            for record in registry.values() {
+                if record.status == TaskStatus::Running {
+                    *running.entry(record.task.tenant_id.clone()).or_default() += 1;
+                }
+            }
+        }
+        let admit = |tenant: &str| {
+            let in_flight = running.get(tenant).copied().unwrap_or(0);
+            quotas
+                .admits(tenant, in_flight)
+                .then(|| quotas.quota(tenant).weight)
+        };
+        while let Some(task) = self.scheduler.next_task_weighted(admit) {
+            let Some(record) = registry.get_mut(&task.id) else {
+                continue;
+            };
+            if record.status != TaskStatus::Queued {
+                continue;
+            }
+            record.status = TaskStatus::Running;
+            record.started_at = Some(Utc::now());
+            record.last_error = None;
+            record.attempts += 1;
+            record.lease_owner = Some(worker.to_string());
+            record.lease_expires_at = Some(lease_deadline(lease));
+            return Ok(Some(record.clone()));
+        }
+        Ok(None)
+    }
+
+    async fn renew_lease(
+        &self,
+        task_id: &Uuid,
+        worker: &str,
+        lease: Duration,
+    ) -> Result<bool, EngineError> {
+        let mut registry = self.registry.write();
+        match registry.get_mut(task_id) {
+            Some(record)
+                if record.status == TaskStatus::Running
+                    && record.lease_owner.as_deref() == Some(worker) =>
+            {
+                record.lease_expires_at = Some(lease_deadline(lease));
+                Ok(record.cancel_requested)
+            }
+            Some(_) => Err(EngineError::LeaseLost(task_id.to_string())),
+            None => Err(EngineError::TaskNotFound(task_id.to_string())),
+        }
+    }
+
+    async fn complete(&self, worker: &str, outcome: &ExecutionOutcome) -> Result<(), EngineError> {
+        let mut registry = self.registry.write();
+        let record = registry
+            .get_mut(&outcome.task_id)
+            .ok_or_else(|| EngineError::TaskNotFound(outcome.task_id.to_string()))?;
+        if record.lease_owner.as_deref() != Some(worker) {
+            return Err(EngineError::LeaseLost(outcome.task_id.to_string()));
+        }
+        record.status = outcome.status.clone();
+        record.finished_at = outcome.finished_at;
+        record.last_error = outcome.error.clone();
+        record.result = outcome.result.clone();
+        record.lease_owner = None;
+        record.lease_expires_at = None;
+        Ok(())
+    }
+
+    async fn cancel(&self, tenant: &str, task_id: &Uuid) -> Result<TaskRecord, EngineError> {
+        let mut registry = self.registry.write();
+        let record = registry
+            .get_mut(task_id)
+            .filter(|record| record.task.tenant_id == tenant)
+            .ok_or_else(|| EngineError::TaskNotFound(task_id.to_string()))?;
+        match record.status {
+            TaskStatus::Queued => {
+                self.scheduler.remove(task_id);
+                record.status = TaskStatus::Cancelled;
+                record.finished_at = Some(Utc::now());
+            }
+            TaskStatus::Running => record.cancel_requested = true,
+            _ => return Err(EngineError::TaskFinished(task_id.to_string())),
+        }
+        Ok(record.clone())
+    }
+
+    async fn fail(
+        &self,
+        worker: &str,
+        task_id: &Uuid,
+        failure: TaskFailure,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<(), EngineError> {
+        let mut registry = self.registry.write();
+        let record = registry
+            .get_mut(task_id)
+            .ok_or_else(|| EngineError::TaskNotFound(task_id.to_string()))?;
+        if record.lease_owner.as_deref() != Some(worker) {
+            return Err(EngineError::LeaseLost(task_id.to_string()));
+        }
+        record.last_error = Some(failure.error.clone());
+        record.error_history.push(failure);
+        record.lease_owner = None;
+        record.lease_expires_at = None;
+        match retry_at {
+            Some(retry_at) => {
+                record.status = TaskStatus::Queued;
+                record.started_at = None;
+                record.task.scheduled_for = retry_at;
+                self.scheduler.enqueue(record.task.clone());
+            }
+            None => {
+                record.status = TaskStatus::Failed;
+                record.finished_at = Some(Utc::now());
+            }
+        }
+        Ok(())
+    }
+
+    async fn dead_letters(&self, tenant: &str) -> Result<Vec<TaskRecord>, EngineError> {
+        let mut failed: Vec<TaskRecord> = self
+            .registry
+            .read()
+            .values()
+            .filter(|record| record.task.tenant_id == tenant && record.status == TaskStatus::Failed)
+            .cloned()
+            .collect();
+        failed.sort_by_key(|record| std::cmp::Reverse(record.finished_at));
+        Ok(failed)
+    }
+
+    async fn redrive(
+        &self,
+        tenant: &str,
+        task_id: Option<&Uuid>,
+    ) -> Result<Vec<Uuid>, EngineError> {
+        let now = Utc::now();
+        let mut redriven = Vec::new();
+        let mut registry = self.registry.write();
+        for record in registry.values_mut() {
+            if !is_dead_letter(record, tenant, task_id) {
+                continue;
+            }
+            record.status = TaskStatus::Queued;
+            record.attempts = 0;
+            record.started_at = None;
+            record.finished_at = None;
+            record.last_error = None;
+            record.task.scheduled_for = now;
+            self.scheduler.enqueue(record.task.clone());
+            redriven.push(record.task.id);
+        }
+        Ok(redriven)
+    }
+
+    async fn purge_dead_letters(
+        &self,
+        tenant: &str,
+        task_id: Option<&Uuid>,
+    ) -> Result<Vec<Uuid>, EngineError> {
+        let mut registry = self.registry.write();
+        let purged: Vec<Uuid> = registry
+            .values()
+            .filter(|record| is_dead_letter(record, tenant, task_id))
+            .map(|record| record.task.id)
+            .collect();
+        for id in &purged {
+            registry.remove(id);
+        }
+        Ok(purged)
+    }
+
+    async fn recover_expired_leases(&self, now: DateTime<Utc>) -> Result<Vec<Uuid>, EngineError> {
+        let mut recovered = Vec::new();
+        let mut registry = self.registry.write();
+        for record in registry.values_mut() {
+            let expired = record.status == TaskStatus::Running
+                && record
+                    .lease_expires_at
+                    .is_some_and(|deadline| deadline < now);
+            if expired {
+                release_lease(&self.scheduler, record, now);
+                recovered.push(record.task.id);
+            }
+        }
+        Ok(recovered)
+    }
+
+    async fn get(&self, task_id: &Uuid) -> Result<TaskRecord, EngineError> {
+        self.registry
+            .read()
+            .get(task_id)
+            .cloned()
+            .ok_or_else(|| EngineError::TaskNotFound(task_id.to_string()))
+    }
+
+    async fn list_for_tenant(&self, tenant: &str) -> Result<Vec<TaskRecord>, EngineError> {
+        Ok(self
+            .registry
+            .read()
+            .values()
+            .filter(|record| record.task.tenant_id == tenant)
+            .cloned()
+            .collect())
+    }
+
+    async fn pending(&self) -> Result<usize, EngineError> {
+        Ok(self.scheduler.pending())
+    }
+
+    async fn tenant_load(&self, tenant: &str) -> Result<TenantLoad, EngineError> {
+        let running = self
+            .registry
+            .read()
+            .values()
+            .filter(|record| {
+                record.task.tenant_id == tenant && record.status == TaskStatus::Running
+            })
+            .count();
+        Ok(TenantLoad {
+            queued: self.scheduler.pending_for_tenant(tenant),
+            running,
+        })
+    }
+
+    async fn hand_off(&self, runtime_id: &str) -> Result<Vec<TaskRecord>, EngineError> {
+        let prefix = format!("{}/", runtime_id);
+        let now = Utc::now();
+        let mut released = Vec::new();
+        for record in self.registry.write().values_mut() {
+            let leased = record.status == TaskStatus::Running
+                && record
+                    .lease_owner
+                    .as_deref()
+                    .is_some_and(|owner| owner.starts_with(&prefix));
+            if leased {
+                release_lease(&self.scheduler, record, now);
+                released.push(record.clone());
+            }
+        }
+        Ok(released)
+    }
+
+    async fn take_queued(&self) -> Result<Vec<TaskRecord>, EngineError> {
+        let mut registry = self.registry.write();
+        let queued: Vec<Uuid> = registry
+            .values()
+            .filter(|record| record.status == TaskStatus::Queued)
+            .map(|record| record.task.id)
+            .collect();
+        let mut taken: Vec<TaskRecord> = queued
+            .iter()
+            .filter_map(|id| {
+                self.scheduler.remove(id);
+                registry.remove(id)
+            })
+            .collect();
+        taken.sort_by_key(|record| (record.task.scheduled_for, record.task.created_at));
+        Ok(taken)
+    }
+
+    async fn next_due_at(&self) -> Result<Option<DateTime<Utc>>, EngineError> {
+        Ok(self.scheduler.next_due_at())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::ExecutionTask;
+
+    #[tokio::test]
+    async fn requeues_tasks_whose_lease_expired() {
+        let queue = MemoryTaskQueue::new();
+        let quotas = TenantQuotas::default();
+        let task = ExecutionTask::builder("tenant").build();
+        queue.enqueue(TaskRecord::new(task.clone())).await.unwrap();
+
+        let claimed = queue
+            .claim("worker-a", Duration::from_millis(10), &quotas)
+            .await
+            .unwrap()
+            .expect("task is due");
+        assert_eq!(claimed.lease_owner.as_deref(), Some("worker-a"));
+        assert!(queue
+            .claim("worker-b", Duration::from_secs(1), &quotas)
+            .await
+            .unwrap()
+            .is_none());
+
+        let later = Utc::now() + chrono::Duration::seconds(1);
+        assert_eq!(
+            queue.recover_expired_leases(later).await.unwrap(),
+            vec![task.id]
+        );
+
+        let reclaimed = queue
+            .claim("worker-b", Duration::from_secs(30), &quotas)
+            .await
+            .unwrap()
+            .expect("recovered task is claimable");
+        assert_eq!(reclaimed.task.id, task.id);
+
+        let outcome = ExecutionOutcome::success(&task, Utc::now(), serde_json::Value::Null);
+        assert!(matches!(
+            queue.complete("worker-a", &outcome).await,
+            Err(EngineError::LeaseLost(_))
+        ));
+        queue.complete("worker-b", &outcome).await.unwrap();
+        assert_eq!(
+            queue.get(&task.id).await.unwrap().status,
+            TaskStatus::Completed
+        );
+    }
+
+    #[tokio::test]
+    async fn retries_then_dead_letters_and_redrives() {
+        let queue = MemoryTaskQueue::new();
+        let quotas = TenantQuotas::default();
+        let task = ExecutionTask::builder("tenant").build();
+        queue.enqueue(TaskRecord::new(task.clone())).await.unwrap();
+        let lease = Duration::from_secs(30);
+        let failure = |attempt| TaskFailure {
+            attempt,
+            error: "upstream timed out".into(),
+            retryable: true,
+            failed_at: Utc::now(),
+        };
+
+        queue
+            .claim("worker", lease, &quotas)
+            .await
+            .unwrap()
+            .unwrap();
+        queue
+            .fail("worker", &task.id, failure(1), Some(Utc::now()))
+            .await
+            .unwrap();
+        let retried = queue
+            .claim("worker", lease, &quotas)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(retried.attempts, 2);
+        queue
+            .fail("worker", &task.id, failure(2), None)
+            .await
+            .unwrap();
+
+        let dead = queue.dead_letters("tenant").await.unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].error_history.len(), 2);
+        assert!(queue.dead_letters("other").await.unwrap().is_empty());
+
+        assert_eq!(queue.redrive("tenant", None).await.unwrap(), vec![task.id]);
+        let redriven = queue
+            .claim("worker", lease, &quotas)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(redriven.attempts, 1);
+        queue
+            .fail("worker", &task.id, failure(1), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            queue
+                .purge_dead_letters("tenant", Some(&task.id))
+                .await
+                .unwrap(),
+            vec![task.id]
+        );
+        assert!(queue.get(&task.id).await.is_err());
+    }
+}
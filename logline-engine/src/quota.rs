@@ -0,0 +1,340 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+
+/// Queue wait samples kept per tenant for the percentiles in
+/// [`TenantQuotas::wait_times`].
+pub const WAIT_TIME_SAMPLES: usize = 1_024;
+
+/// Plan an organisation is on, mirroring `organizations.subscription_tier`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionTier {
+    #[default]
+    Free,
+    Pro,
+    Enterprise,
+}
+
+impl SubscriptionTier {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubscriptionTier::Free => "free",
+            SubscriptionTier::Pro => "pro",
+            SubscriptionTier::Enterprise => "enterprise",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "free" => Some(SubscriptionTier::Free),
+            "pro" => Some(SubscriptionTier::Pro),
+            "enterprise" => Some(SubscriptionTier::Enterprise),
+            _ => None,
+        }
+    }
+}
+
+/// Scheduling limits applied to a tenant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TenantQuota {
+    /// Share of worker time relative to other tenants with queued work: a
+    /// tenant with weight 3 is handed three tasks for every one handed to a
+    /// tenant with weight 1.
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    /// Tasks of the tenant allowed to run at once.
+    #[serde(default)]
+    pub max_in_flight: Option<usize>,
+    /// Queued tasks, due or delayed, beyond which submissions are rejected.
+    #[serde(default)]
+    pub max_queued: Option<usize>,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+impl TenantQuota {
+    pub fn with_weight(weight: u32) -> Self {
+        Self {
+            weight,
+            max_in_flight: None,
+            max_queued: None,
+        }
+    }
+}
+
+impl Default for TenantQuota {
+    fn default() -> Self {
+        Self::with_weight(default_weight())
+    }
+}
+
+/// Quotas per subscription tier, and the tier of known tenants.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotaConfig {
+    #[serde(default = "default_free_quota")]
+    pub free: TenantQuota,
+    #[serde(default = "default_pro_quota")]
+    pub pro: TenantQuota,
+    #[serde(default = "default_enterprise_quota")]
+    pub enterprise: TenantQuota,
+    /// Tier of each tenant; tenants not listed are on the free tier unless
+    /// the tier is loaded from `organizations`.
+    #[serde(default)]
+    pub tenant_tiers: HashMap<String, SubscriptionTier>,
+}
+
+fn default_free_quota() -> TenantQuota {
+    TenantQuota::with_weight(1)
+}
+
+fn default_pro_quota() -> TenantQuota {
+    TenantQuota::with_weight(2)
+}
+
+fn default_enterprise_quota() -> TenantQuota {
+    TenantQuota::with_weight(3)
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            free: default_free_quota(),
+            pro: default_pro_quota(),
+            enterprise: default_enterprise_quota(),
+            tenant_tiers: HashMap::new(),
+        }
+    }
+}
+
+impl QuotaConfig {
+    pub fn for_tier(&self, tier: SubscriptionTier) -> TenantQuota {
+        match tier {
+            SubscriptionTier::Free => self.free,
+            SubscriptionTier::Pro => self.pro,
+            SubscriptionTier::Enterprise => self.enterprise,
+        }
+    }
+}
+
+/// Tasks of a tenant currently held by the queue.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TenantLoad {
+    /// Queued tasks, including the ones not yet due.
+    pub queued: usize,
+    pub running: usize,
+}
+
+/// Queue wait statistics of a tenant: the time between a task becoming due
+/// and a worker claiming it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct WaitTimeStats {
+    /// Claims measured since start-up.
+    pub claims: u64,
+    pub mean_ms: f64,
+    pub max_ms: u64,
+    /// Percentiles over the last [`WAIT_TIME_SAMPLES`] claims.
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+}
+
+#[derive(Default)]
+struct WaitSamples {
+    claims: u64,
+    total_ms: u128,
+    max_ms: u64,
+    recent: VecDeque<u64>,
+}
+
+impl WaitSamples {
+    fn record(&mut self, wait_ms: u64) {
+        self.claims += 1;
+        self.total_ms += u128::from(wait_ms);
+        self.max_ms = self.max_ms.max(wait_ms);
+        if self.recent.len() == WAIT_TIME_SAMPLES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(wait_ms);
+    }
+
+    fn stats(&self) -> WaitTimeStats {
+        let mut sorted: Vec<u64> = self.recent.iter().copied().collect();
+        sorted.sort_unstable();
+        let percentile = |p: f64| {
+            let index = ((sorted.len() as f64 - 1.0) * p).round() as usize;
+            sorted.get(index).copied().unwrap_or(0)
+        };
+        WaitTimeStats {
+            claims: self.claims,
+            mean_ms: if self.claims == 0 {
+                0.0
+            } else {
+                self.total_ms as f64 / self.claims as f64
+            },
+            max_ms: self.max_ms,
+            p50_ms: percentile(0.5),
+            p95_ms: percentile(0.95),
+        }
+    }
+}
+
+#[derive(Default)]
+struct QuotaState {
+    config: QuotaConfig,
+    overrides: HashMap<String, TenantQuota>,
+}
+
+/// Per-tenant quotas consulted when submitting and claiming tasks, plus the
+/// queue wait times observed for each tenant.
+#[derive(Clone, Default)]
+pub struct TenantQuotas {
+    state: Arc<RwLock<QuotaState>>,
+    waits: Arc<Mutex<HashMap<String, WaitSamples>>>,
+}
+
+impl TenantQuotas {
+    pub fn new(config: QuotaConfig) -> Self {
+        Self {
+            state: Arc::new(RwLock::new(QuotaState {
+                config,
+                overrides: HashMap::new(),
+            })),
+            waits: Arc::default(),
+        }
+    }
+
+    pub fn tier(&self, tenant: &str) -> SubscriptionTier {
+        self.state
+            .read()
+            .config
+            .tenant_tiers
+            .get(tenant)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_tier(&self, tenant: impl Into<String>, tier: SubscriptionTier) {
+        self.state
+            .write()
+            .config
+            .tenant_tiers
+            .insert(tenant.into(), tier);
+    }
+
+    /// Effective quota of `tenant`: its override if set, otherwise the quota
+    /// of its tier.
+    pub fn quota(&self, tenant: &str) -> TenantQuota {
+        let state = self.state.read();
+        state.overrides.get(tenant).copied().unwrap_or_else(|| {
+            let tier = state
+                .config
+                .tenant_tiers
+                .get(tenant)
+                .copied()
+                .unwrap_or_default();
+            state.config.for_tier(tier)
+        })
+    }
+
+    /// Whether `tenant` has a quota of its own rather than its tier's.
+    pub fn is_overridden(&self, tenant: &str) -> bool {
+        self.state.read().overrides.contains_key(tenant)
+    }
+
+    pub fn set_override(&self, tenant: impl Into<String>, quota: TenantQuota) {
+        self.state.write().overrides.insert(tenant.into(), quota);
+    }
+
+    pub fn clear_override(&self, tenant: &str) -> Option<TenantQuota> {
+        self.state.write().overrides.remove(tenant)
+    }
+
+    /// Whether any tenant or tier caps in-flight tasks, so that claiming
+    /// needs to count running tasks.
+    pub fn limits_in_flight(&self) -> bool {
+        let state = self.state.read();
+        let config = &state.config;
+        [config.free, config.pro, config.enterprise]
+            .iter()
+            .chain(state.overrides.values())
+            .any(|quota| quota.max_in_flight.is_some())
+    }
+
+    /// Whether `tenant` may start another task while `running` of its tasks
+    /// are in flight.
+    pub fn admits(&self, tenant: &str, running: usize) -> bool {
+        self.quota(tenant)
+            .max_in_flight
+            .is_none_or(|limit| running < limit)
+    }
+
+    pub fn record_wait(&self, tenant: &str, wait: Duration) {
+        let wait_ms = u64::try_from(wait.as_millis()).unwrap_or(u64::MAX);
+        self.waits
+            .lock()
+            .entry(tenant.to_string())
+            .or_default()
+            .record(wait_ms);
+    }
+
+    pub fn wait_times(&self, tenant: &str) -> WaitTimeStats {
+        self.waits
+            .lock()
+            .get(tenant)
+            .map(WaitSamples::stats)
+            .unwrap_or_default()
+    }
+
+    /// Wait times of every tenant that had a task claimed, by tenant.
+    pub fn all_wait_times(&self) -> HashMap<String, WaitTimeStats> {
+        self.waits
+            .lock()
+            .iter()
+            .map(|(tenant, samples)| (tenant.clone(), samples.stats()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_quota_from_tier_and_override() {
+        let mut config = QuotaConfig::default();
+        config.enterprise.max_in_flight = Some(8);
+        config
+            .tenant_tiers
+            .insert("acme".into(), SubscriptionTier::Enterprise);
+        let quotas = TenantQuotas::new(config);
+
+        assert_eq!(quotas.quota("acme").weight, 3);
+        assert_eq!(quotas.quota("unknown").weight, 1);
+        assert!(quotas.limits_in_flight());
+        assert!(quotas.admits("acme", 7));
+        assert!(!quotas.admits("acme", 8));
+
+        quotas.set_override(
+            "acme",
+            TenantQuota {
+                max_in_flight: None,
+                ..TenantQuota::with_weight(5)
+            },
+        );
+        assert_eq!(quotas.quota("acme").weight, 5);
+        assert!(quotas.admits("acme", 100));
+
+        for wait in [10, 20, 30, 40] {
+            quotas.record_wait("acme", Duration::from_millis(wait));
+        }
+        let stats = quotas.wait_times("acme");
+        assert_eq!(stats.claims, 4);
+        assert_eq!(stats.mean_ms, 25.0);
+        assert_eq!(stats.max_ms, 40);
+    }
+}
@@ -0,0 +1,347 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+use crate::cron::CronSchedule;
+use crate::error::EngineError;
+use crate::task::{ExecutionTask, TaskPriority};
+
+/// Upper bound on the runs enqueued at once when catching up; older missed
+/// occurrences beyond it are dropped.
+pub const MAX_CATCH_UP_RUNS: usize = 100;
+
+/// When a recurring task fires.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecurrenceSchedule {
+    Cron { expression: CronSchedule },
+    Interval { every_secs: u64 },
+}
+
+impl RecurrenceSchedule {
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            RecurrenceSchedule::Cron { expression } => expression.next_after(after),
+            RecurrenceSchedule::Interval { every_secs } => {
+                Some(after + Duration::seconds(*every_secs as i64))
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<(), EngineError> {
+        match self {
+            RecurrenceSchedule::Interval { every_secs } if *every_secs == 0 => Err(
+                EngineError::InvalidSchedule("interval must be at least one second".into()),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What to do with occurrences that passed while the engine was not able to
+/// fire them (downtime, a start time in the past, a stalled timer).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissedRunPolicy {
+    /// Fire a single run for the whole backlog and continue from the next
+    /// future occurrence.
+    #[default]
+    Skip,
+    /// Fire one run per missed occurrence, up to [`MAX_CATCH_UP_RUNS`].
+    CatchUp,
+}
+
+/// Task definition that produces an [`ExecutionTask`] on every occurrence of
+/// its schedule.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecurringTask {
+    pub id: Uuid,
+    pub tenant_id: String,
+    pub name: String,
+    pub schedule: RecurrenceSchedule,
+    #[serde(default)]
+    pub missed_runs: MissedRunPolicy,
+    #[serde(default)]
+    pub kind: Option<String>,
+    pub payload: Value,
+    pub priority: TaskPriority,
+    pub metadata: Option<Value>,
+    pub created_at: DateTime<Utc>,
+    /// `None` once the schedule has no further occurrences.
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    /// Execution tasks enqueued so far.
+    pub runs: u64,
+}
+
+impl RecurringTask {
+    pub fn builder(
+        tenant_id: impl Into<String>,
+        name: impl Into<String>,
+        schedule: RecurrenceSchedule,
+    ) -> RecurringTaskBuilder {
+        RecurringTaskBuilder {
+            tenant_id: tenant_id.into(),
+            name: name.into(),
+            schedule,
+            missed_runs: MissedRunPolicy::default(),
+            kind: None,
+            payload: Value::Null,
+            priority: TaskPriority::Normal,
+            metadata: None,
+            start_at: None,
+        }
+    }
+
+    /// Builds the execution task for the occurrence due at `occurrence`.
+    pub(crate) fn instantiate(&self, occurrence: DateTime<Utc>) -> ExecutionTask {
+        let recurrence = json!({
+            "id": self.id,
+            "name": self.name,
+            "occurrence": occurrence,
+        });
+        let metadata = match self.metadata.clone() {
+            Some(Value::Object(mut map)) => {
+                map.insert("recurring_task".into(), recurrence);
+                Value::Object(map)
+            }
+            Some(other) => json!({ "recurring_task": recurrence, "user": other }),
+            None => json!({ "recurring_task": recurrence }),
+        };
+
+        let mut builder = ExecutionTask::builder(&self.tenant_id)
+            .payload(self.payload.clone())
+            .priority(self.priority)
+            .scheduled_for(occurrence)
+            .metadata(metadata);
+        if let Some(kind) = &self.kind {
+            builder = builder.kind(kind.clone());
+        }
+        builder.build()
+    }
+
+    /// Occurrences due at `now` according to the missed-run policy, advancing
+    /// `next_run_at` past them.
+    fn take_due(&mut self, now: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+        let mut due = Vec::new();
+        while let Some(at) = self.next_run_at.filter(|at| *at <= now) {
+            if due.len() == MAX_CATCH_UP_RUNS {
+                self.next_run_at = self.schedule.next_after(now);
+                break;
+            }
+            due.push(at);
+            self.next_run_at = self.schedule.next_after(at);
+            if self.missed_runs == MissedRunPolicy::Skip {
+                if self.next_run_at.is_some_and(|next| next <= now) {
+                    self.next_run_at = self.schedule.next_after(now);
+                }
+                break;
+            }
+        }
+
+        if !due.is_empty() {
+            self.last_run_at = Some(now);
+            self.runs += due.len() as u64;
+        }
+        due
+    }
+}
+
+pub struct RecurringTaskBuilder {
+    tenant_id: String,
+    name: String,
+    schedule: RecurrenceSchedule,
+    missed_runs: MissedRunPolicy,
+    kind: Option<String>,
+    payload: Value,
+    priority: TaskPriority,
+    metadata: Option<Value>,
+    start_at: Option<DateTime<Utc>>,
+}
+
+impl RecurringTaskBuilder {
+    pub fn missed_runs(mut self, policy: MissedRunPolicy) -> Self {
+        self.missed_runs = policy;
+        self
+    }
+
+    pub fn kind(mut self, kind: impl Into<String>) -> Self {
+        self.kind = Some(kind.into());
+        self
+    }
+
+    pub fn payload(mut self, payload: Value) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    pub fn priority(mut self, priority: TaskPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn metadata(mut self, metadata: Value) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Time the schedule starts counting from; the first run is the first
+    /// occurrence after it. Defaults to now.
+    pub fn start_at(mut self, start_at: DateTime<Utc>) -> Self {
+        self.start_at = Some(start_at);
+        self
+    }
+
+    pub fn build(self) -> Result<RecurringTask, EngineError> {
+        self.schedule.validate()?;
+        let start_at = self.start_at.unwrap_or_else(Utc::now);
+        let next_run_at = self.schedule.next_after(start_at).ok_or_else(|| {
+            EngineError::InvalidSchedule("schedule has no upcoming occurrence".into())
+        })?;
+
+        Ok(RecurringTask {
+            id: Uuid::new_v4(),
+            tenant_id: self.tenant_id,
+            name: self.name,
+            schedule: self.schedule,
+            missed_runs: self.missed_runs,
+            kind: self.kind,
+            payload: self.payload,
+            priority: self.priority,
+            metadata: self.metadata,
+            created_at: Utc::now(),
+            next_run_at: Some(next_run_at),
+            last_run_at: None,
+            runs: 0,
+        })
+    }
+}
+
+/// Registry of recurring task definitions, polled by the runtime to enqueue
+/// execution tasks as occurrences become due.
+#[derive(Clone, Default)]
+pub struct RecurringScheduler {
+    tasks: Arc<RwLock<HashMap<Uuid, RecurringTask>>>,
+    notify: Arc<Notify>,
+}
+
+impl RecurringScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self, task: RecurringTask) -> Uuid {
+        let id = task.id;
+        self.tasks.write().insert(id, task);
+        self.notify.notify_one();
+        id
+    }
+
+    pub fn remove(&self, id: &Uuid) -> Result<RecurringTask, EngineError> {
+        self.tasks
+            .write()
+            .remove(id)
+            .ok_or_else(|| EngineError::TaskNotFound(id.to_string()))
+    }
+
+    pub fn get(&self, id: &Uuid) -> Result<RecurringTask, EngineError> {
+        self.tasks
+            .read()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| EngineError::TaskNotFound(id.to_string()))
+    }
+
+    pub fn list_for_tenant(&self, tenant: &str) -> Vec<RecurringTask> {
+        let mut tasks: Vec<RecurringTask> = self
+            .tasks
+            .read()
+            .values()
+            .filter(|task| task.tenant_id == tenant)
+            .cloned()
+            .collect();
+        tasks.sort_by_key(|task| task.created_at);
+        tasks
+    }
+
+    /// Earliest upcoming occurrence across all definitions.
+    pub fn next_run_at(&self) -> Option<DateTime<Utc>> {
+        self.tasks
+            .read()
+            .values()
+            .filter_map(|task| task.next_run_at)
+            .min()
+    }
+
+    /// Execution tasks for every occurrence due at `now`.
+    pub fn take_due(&self, now: DateTime<Utc>) -> Vec<ExecutionTask> {
+        let mut tasks = self.tasks.write();
+        tasks
+            .values_mut()
+            .flat_map(|task| {
+                let due = task.take_due(now);
+                due.into_iter()
+                    .map(|occurrence| task.instantiate(occurrence))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    pub(crate) fn notify(&self) -> &Notify {
+        &self.notify
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_missed_run_policy() {
+        let start = Utc::now() - Duration::seconds(35);
+        let every_ten = RecurrenceSchedule::Interval { every_secs: 10 };
+        let scheduler = RecurringScheduler::new();
+
+        let skip = RecurringTask::builder("tenant", "sync", every_ten.clone())
+            .start_at(start)
+            .build()
+            .unwrap();
+        let catch_up = RecurringTask::builder("tenant", "report", every_ten)
+            .start_at(start)
+            .missed_runs(MissedRunPolicy::CatchUp)
+            .build()
+            .unwrap();
+        let skip_id = scheduler.register(skip);
+        let catch_up_id = scheduler.register(catch_up);
+
+        let now = Utc::now();
+        let due = scheduler.take_due(now);
+        let fired = |id: Uuid| {
+            due.iter()
+                .filter(|task| task.metadata.as_ref().unwrap()["recurring_task"]["id"] == json!(id))
+                .count()
+        };
+        assert_eq!(fired(skip_id), 1);
+        assert_eq!(fired(catch_up_id), 3);
+
+        for id in [skip_id, catch_up_id] {
+            let task = scheduler.get(&id).unwrap();
+            assert!(task.next_run_at.unwrap() > now);
+        }
+        assert!(scheduler.take_due(now).is_empty());
+
+        assert!(RecurringTask::builder(
+            "tenant",
+            "bad",
+            RecurrenceSchedule::Interval { every_secs: 0 }
+        )
+        .build()
+        .is_err());
+    }
+}
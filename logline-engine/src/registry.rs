@@ -0,0 +1,296 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio_util::sync::CancellationToken;
+
+use crate::error::EngineError;
+use crate::runtime::TaskHandler;
+use crate::task::ExecutionTask;
+
+/// JSON type a payload field must have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    Object,
+    Array,
+    /// Any JSON value, including `null`.
+    Any,
+}
+
+impl FieldType {
+    fn matches(&self, value: &Value) -> bool {
+        match self {
+            FieldType::String => value.is_string(),
+            FieldType::Number => value.is_number(),
+            FieldType::Integer => value.is_i64() || value.is_u64(),
+            FieldType::Boolean => value.is_boolean(),
+            FieldType::Object => value.is_object(),
+            FieldType::Array => value.is_array(),
+            FieldType::Any => true,
+        }
+    }
+}
+
+/// Shape a task payload must have to be accepted for a kind: an object with
+/// the `required` fields present and the listed `properties` of the given
+/// types. Fields not listed are allowed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PayloadSchema {
+    #[serde(default)]
+    pub required: Vec<String>,
+    #[serde(default)]
+    pub properties: BTreeMap<String, FieldType>,
+}
+
+impl PayloadSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn required(mut self, field: impl Into<String>, field_type: FieldType) -> Self {
+        let field = field.into();
+        self.required.push(field.clone());
+        self.properties.insert(field, field_type);
+        self
+    }
+
+    pub fn optional(mut self, field: impl Into<String>, field_type: FieldType) -> Self {
+        self.properties.insert(field.into(), field_type);
+        self
+    }
+
+    /// Lists every way `payload` violates the schema.
+    pub fn violations(&self, payload: &Value) -> Vec<String> {
+        let Some(object) = payload.as_object() else {
+            return vec!["payload must be a JSON object".into()];
+        };
+        let mut violations: Vec<String> = self
+            .required
+            .iter()
+            .filter(|field| !object.contains_key(field.as_str()))
+            .map(|field| format!("missing required field `{}`", field))
+            .collect();
+        for (field, field_type) in &self.properties {
+            if let Some(value) = object.get(field) {
+                let optional_null = value.is_null() && !self.required.contains(field);
+                if !optional_null && !field_type.matches(value) {
+                    violations.push(format!(
+                        "field `{}` must be of type {:?}",
+                        field, field_type
+                    ));
+                }
+            }
+        }
+        violations
+    }
+}
+
+struct RegisteredHandler {
+    handler: Arc<dyn TaskHandler>,
+    schema: Option<PayloadSchema>,
+}
+
+/// Description of a registered kind, as listed by the API.
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskKind {
+    pub kind: String,
+    pub schema: Option<PayloadSchema>,
+}
+
+/// Dispatches tasks to the handler registered for their `kind`.
+///
+/// Tasks without a kind go to the fallback handler, if any. The registry is
+/// itself a [`TaskHandler`], so it is what the runtime is started with.
+#[derive(Default)]
+pub struct TaskRegistry {
+    handlers: HashMap<String, RegisteredHandler>,
+    fallback: Option<Arc<dyn TaskHandler>>,
+}
+
+impl TaskRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `handler` for tasks of `kind`, replacing any previous one.
+    pub fn register<H>(self, kind: impl Into<String>, handler: Arc<H>) -> Self
+    where
+        H: TaskHandler,
+    {
+        self.insert(kind.into(), handler, None)
+    }
+
+    /// Registers `handler` for tasks of `kind` whose payload matches `schema`.
+    pub fn register_with_schema<H>(
+        self,
+        kind: impl Into<String>,
+        handler: Arc<H>,
+        schema: PayloadSchema,
+    ) -> Self
+    where
+        H: TaskHandler,
+    {
+        self.insert(kind.into(), handler, Some(schema))
+    }
+
+    pub(crate) fn insert(
+        mut self,
+        kind: String,
+        handler: Arc<dyn TaskHandler>,
+        schema: Option<PayloadSchema>,
+    ) -> Self {
+        self.handlers
+            .insert(kind, RegisteredHandler { handler, schema });
+        self
+    }
+
+    /// Handles tasks that carry no kind.
+    pub fn with_fallback<H>(mut self, handler: Arc<H>) -> Self
+    where
+        H: TaskHandler,
+    {
+        self.fallback = Some(handler);
+        self
+    }
+
+    pub fn kinds(&self) -> Vec<TaskKind> {
+        let mut kinds: Vec<TaskKind> = self
+            .handlers
+            .iter()
+            .map(|(kind, entry)| TaskKind {
+                kind: kind.clone(),
+                schema: entry.schema.clone(),
+            })
+            .collect();
+        kinds.sort_by(|a, b| a.kind.cmp(&b.kind));
+        kinds
+    }
+
+    fn handler_for(&self, task: &ExecutionTask) -> Option<&Arc<dyn TaskHandler>> {
+        match task.kind.as_deref() {
+            Some(kind) => self.handlers.get(kind).map(|entry| &entry.handler),
+            None => self.fallback.as_ref(),
+        }
+    }
+}
+
+#[async_trait]
+impl TaskHandler for TaskRegistry {
+    async fn handle(&self, task: ExecutionTask) -> Result<Value, String> {
+        match self.handler_for(&task) {
+            Some(handler) => handler.handle(task).await,
+            None => Err(no_handler(&task)),
+        }
+    }
+
+    async fn handle_with_cancellation(
+        &self,
+        task: ExecutionTask,
+        cancel: CancellationToken,
+    ) -> Result<Value, String> {
+        match self.handler_for(&task) {
+            Some(handler) => handler.handle_with_cancellation(task, cancel).await,
+            None => Err(no_handler(&task)),
+        }
+    }
+
+    fn is_retryable(&self, task: &ExecutionTask, error: &str) -> bool {
+        self.handler_for(task)
+            .is_some_and(|handler| handler.is_retryable(task, error))
+    }
+
+    fn validate(&self, task: &ExecutionTask) -> Result<(), EngineError> {
+        let Some(kind) = task.kind.as_deref() else {
+            return match &self.fallback {
+                Some(fallback) => fallback.validate(task),
+                None => Err(EngineError::Rejected(
+                    "task has no kind and no default handler is registered".into(),
+                )),
+            };
+        };
+        let entry = self
+            .handlers
+            .get(kind)
+            .ok_or_else(|| EngineError::UnknownTaskKind(kind.to_string()))?;
+        if let Some(schema) = &entry.schema {
+            let violations = schema.violations(&task.payload);
+            if !violations.is_empty() {
+                return Err(EngineError::Rejected(format!(
+                    "payload does not match the `{}` schema: {}",
+                    kind,
+                    violations.join("; ")
+                )));
+            }
+        }
+        entry.handler.validate(task)
+    }
+}
+
+fn no_handler(task: &ExecutionTask) -> String {
+    match task.kind.as_deref() {
+        Some(kind) => format!("no handler registered for task kind `{}`", kind),
+        None => "no handler registered for tasks without a kind".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct Echo;
+
+    #[async_trait]
+    impl TaskHandler for Echo {
+        async fn handle(&self, task: ExecutionTask) -> Result<Value, String> {
+            Ok(task.payload)
+        }
+    }
+
+    #[tokio::test]
+    async fn dispatches_by_kind_and_validates_payloads() {
+        let registry = TaskRegistry::new().register_with_schema(
+            "echo",
+            Arc::new(Echo),
+            PayloadSchema::new()
+                .required("message", FieldType::String)
+                .optional("count", FieldType::Integer),
+        );
+
+        let task = ExecutionTask::builder("tenant")
+            .kind("echo")
+            .payload(json!({ "message": "hi", "count": 2 }))
+            .build();
+        assert!(registry.validate(&task).is_ok());
+        assert_eq!(registry.handle(task).await.unwrap()["message"], "hi");
+
+        let bad = ExecutionTask::builder("tenant")
+            .kind("echo")
+            .payload(json!({ "count": "two" }))
+            .build();
+        assert!(matches!(
+            registry.validate(&bad),
+            Err(EngineError::Rejected(message)) if message.contains("`message`") && message.contains("`count`")
+        ));
+
+        let unknown = ExecutionTask::builder("tenant").kind("missing").build();
+        assert!(matches!(
+            registry.validate(&unknown),
+            Err(EngineError::UnknownTaskKind(kind)) if kind == "missing"
+        ));
+        assert!(registry
+            .validate(&ExecutionTask::builder("tenant").build())
+            .is_err());
+        assert!(registry
+            .with_fallback(Arc::new(Echo))
+            .validate(&ExecutionTask::builder("tenant").build())
+            .is_ok());
+    }
+}
@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// How often and how quickly a failed task is tried again.
+///
+/// The delay before attempt `n + 1` is
+/// `initial_backoff_ms * multiplier^(n - 1)`, capped at `max_backoff_ms` and
+/// then spread by up to `jitter` (a fraction of the delay) in either
+/// direction so that tasks failing together do not retry in lockstep.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first one. `1` disables retries.
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    #[serde(default = "default_multiplier")]
+    pub multiplier: f64,
+    #[serde(default = "default_jitter")]
+    pub jitter: f64,
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    1_000
+}
+
+fn default_max_backoff_ms() -> u64 {
+    5 * 60 * 1_000
+}
+
+fn default_multiplier() -> f64 {
+    2.0
+}
+
+fn default_jitter() -> f64 {
+    0.2
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_max_attempts(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+            multiplier: default_multiplier(),
+            jitter: default_jitter(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy that gives up after the first failure.
+    pub fn no_retries() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Whether another attempt is allowed after `attempts` have been made.
+    pub fn allows_retry(&self, attempts: u32) -> bool {
+        attempts < self.max_attempts
+    }
+
+    /// Delay before retrying a task that failed its `attempt`-th attempt.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.backoff_with(attempt, rand::random::<f64>())
+    }
+
+    /// [`Self::backoff`] with the jitter driven by `sample` in `[0, 1)`.
+    fn backoff_with(&self, attempt: u32, sample: f64) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(63) as i32;
+        let base = self.initial_backoff_ms as f64 * self.multiplier.max(1.0).powi(exponent);
+        let capped = base.min(self.max_backoff_ms as f64);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let spread = capped * jitter * (2.0 * sample - 1.0);
+        Duration::from_millis((capped + spread).max(0.0) as u64)
+    }
+}
+
+/// One failed attempt, kept in the task's error history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskFailure {
+    pub attempt: u32,
+    pub error: String,
+    /// `false` when the handler classified the error as fatal.
+    pub retryable: bool,
+    pub failed_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backs_off_exponentially_within_jitter_and_cap() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1_000,
+            multiplier: 2.0,
+            jitter: 0.5,
+        };
+
+        assert_eq!(policy.backoff_with(1, 0.5), Duration::from_millis(100));
+        assert_eq!(policy.backoff_with(3, 0.5), Duration::from_millis(400));
+        assert_eq!(policy.backoff_with(8, 0.5), Duration::from_millis(1_000));
+        assert_eq!(policy.backoff_with(3, 0.0), Duration::from_millis(200));
+        assert!(policy.backoff(3) <= Duration::from_millis(600));
+
+        assert!(policy.allows_retry(4));
+        assert!(!policy.allows_retry(5));
+        assert!(!RetryPolicy::no_retries().allows_retry(1));
+    }
+}
@@ -1,11 +1,14 @@
 use std::fmt;
 
+use chrono::Utc;
 use logline_protocol::timeline::Span;
 use serde::{Deserialize, Serialize};
-use serde_json::{Map, Value};
+use serde_json::{json, Map, Value};
 use thiserror::Error;
 use url::Url;
 
+use crate::task::{ExecutionTask, TaskPriority};
+
 /// Typed HTTP client used by the engine to interact with the external
 /// `logline-rules` microservice.
 #[derive(Clone)]
@@ -100,9 +103,45 @@ struct EvaluationResponse {
     added_tags: Vec<String>,
     #[serde(default)]
     metadata_updates: Map<String, Value>,
+    #[serde(default)]
+    tasks: Vec<RuleTaskRequest>,
+    #[serde(default)]
+    derived_spans: Vec<Span>,
     span: Span,
 }
 
+/// Execution task requested by a matched rule.
+#[derive(Debug, Deserialize, Clone)]
+pub struct RuleTaskRequest {
+    pub rule_id: String,
+    #[serde(default)]
+    pub kind: Option<String>,
+    pub payload: Value,
+    #[serde(default)]
+    pub priority: Option<TaskPriority>,
+    #[serde(default)]
+    pub delay_seconds: Option<u64>,
+}
+
+impl RuleTaskRequest {
+    /// Builds the task to submit for the tenant, recording the originating
+    /// rule in the task metadata.
+    pub fn into_task(self, tenant_id: impl Into<String>) -> ExecutionTask {
+        let mut builder = ExecutionTask::builder(tenant_id)
+            .payload(self.payload)
+            .priority(self.priority.unwrap_or_default())
+            .metadata(json!({ "rule_id": self.rule_id }));
+        if let Some(kind) = self.kind {
+            builder = builder.kind(kind);
+        }
+        if let Some(delay) = self.delay_seconds {
+            let delay = i64::from(u32::try_from(delay).unwrap_or(u32::MAX));
+            builder = builder.scheduled_for(Utc::now() + chrono::Duration::seconds(delay));
+        }
+        builder.build()
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct EvaluationDecision {
     pub state: String,
@@ -131,6 +170,10 @@ pub struct RulesEvaluation {
     pub notes: Vec<String>,
     pub added_tags: Vec<String>,
     pub metadata_updates: Map<String, Value>,
+    /// Tasks the matched rules ask the engine to enqueue.
+    pub tasks: Vec<RuleTaskRequest>,
+    /// Spans the matched rules ask the caller to record.
+    pub derived_spans: Vec<Span>,
     pub span: Span,
 }
 
@@ -142,6 +185,8 @@ impl From<EvaluationResponse> for RulesEvaluation {
             notes: value.notes,
             added_tags: value.added_tags,
             metadata_updates: value.metadata_updates,
+            tasks: value.tasks,
+            derived_spans: value.derived_spans,
             span: value.span,
         }
     }
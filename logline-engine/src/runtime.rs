@@ -1,196 +1,950 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
 };
+use std::time::Duration;
 
 use async_trait::async_trait;
-use parking_lot::RwLock;
+use logline_core::identity::{LogLineID, LogLineKeyPair};
+use logline_protocol::timeline::Span;
+use parking_lot::{Mutex, RwLock};
 use tokio::sync::Notify;
 use tokio::task::JoinHandle;
-use tracing::{error, info};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+use uuid::Uuid;
 
 use crate::error::EngineError;
-use crate::scheduler::TaskScheduler;
+use crate::events::{TaskEvent, TaskEvents};
+use crate::execution_spans::ExecutionSpans;
+use crate::queue::{MemoryTaskQueue, TaskQueue};
+use crate::quota::{TenantLoad, TenantQuotas};
+use crate::recurring::{RecurringScheduler, RecurringTask};
+use crate::retry::TaskFailure;
 use crate::task::{ExecutionOutcome, ExecutionTask, TaskRecord, TaskStatus};
+use crate::timeline_client::SpanRecorder;
+use crate::workflow::{
+    preview_tasks, workflow_step, Workflow, WorkflowProgress, WorkflowState, WorkflowTracker,
+};
+
+/// How long a claimed task stays with its worker without a lease renewal.
+pub const DEFAULT_TASK_LEASE: Duration = Duration::from_secs(30);
+
+/// How long an idempotency key keeps returning the task first submitted with
+/// it.
+pub const DEFAULT_IDEMPOTENCY_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Longest idempotency key accepted.
+pub const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+/// How long a cancelled or timed-out handler gets to observe its token and
+/// return before it is dropped.
+pub const CANCEL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// How long a draining runtime waits for running tasks by default.
+pub const DEFAULT_DRAIN_DEADLINE: Duration = Duration::from_secs(30);
+
+/// Cancellation tokens of the tasks running in this process.
+type RunningTasks = Arc<Mutex<HashMap<Uuid, CancellationToken>>>;
+
+/// Handler the workers were started with, consulted to validate submissions.
+type SharedHandler = Arc<RwLock<Option<Arc<dyn TaskHandler>>>>;
 
 #[async_trait]
 pub trait TaskHandler: Send + Sync + 'static {
     async fn handle(&self, task: ExecutionTask) -> Result<serde_json::Value, String>;
+
+    /// Runs `task` with a token that fires when the task is cancelled or
+    /// exceeds its timeout. Handlers that hold resources should override this
+    /// and stop cooperatively; the default ignores the token and the worker
+    /// drops the future once the grace period has passed.
+    async fn handle_with_cancellation(
+        &self,
+        task: ExecutionTask,
+        _cancel: CancellationToken,
+    ) -> Result<serde_json::Value, String> {
+        self.handle(task).await
+    }
+
+    /// Checks a task before it is queued; an error rejects the submission.
+    fn validate(&self, _task: &ExecutionTask) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    /// Whether `error` may go away on a later attempt. Fatal errors skip the
+    /// remaining retries and send the task straight to the dead letters.
+    fn is_retryable(&self, _task: &ExecutionTask, _error: &str) -> bool {
+        true
+    }
 }
 
 /// Handle returned when the runtime is running, used to submit tasks.
 #[derive(Clone)]
 pub struct EngineHandle {
-    scheduler: TaskScheduler,
-    registry: Arc<RwLock<HashMap<uuid::Uuid, TaskRecord>>>,
+    runtime_id: Uuid,
+    queue: Arc<dyn TaskQueue>,
+    recurring: RecurringScheduler,
     notify: Arc<Notify>,
     shutting_down: Arc<AtomicBool>,
+    interrupt: CancellationToken,
+    lease: Duration,
+    running: RunningTasks,
+    workflows: WorkflowTracker,
+    spans: Option<Arc<dyn SpanRecorder>>,
+    execution_spans: Option<Arc<ExecutionSpans>>,
+    quotas: TenantQuotas,
+    handler: SharedHandler,
+    idempotency_ttl: Duration,
+    events: TaskEvents,
+}
+
+/// Task a submission resolved to.
+#[derive(Debug, Clone)]
+pub struct Submission {
+    pub record: TaskRecord,
+    /// Whether the task had been submitted before with the same idempotency
+    /// key, so nothing new was queued.
+    pub replayed: bool,
+}
+
+/// Submission resolving to `existing`, the task first submitted with the
+/// idempotency key of `task`, unless the two differ in kind or payload.
+fn replay(task: &ExecutionTask, existing: TaskRecord) -> Result<Submission, EngineError> {
+    if existing.task.kind != task.kind || existing.task.payload != task.payload {
+        return Err(EngineError::IdempotencyConflict(
+            task.idempotency_key.clone().unwrap_or_default(),
+        ));
+    }
+    Ok(Submission {
+        record: existing,
+        replayed: true,
+    })
 }
 
 impl EngineHandle {
-    pub fn submit(&self, task: ExecutionTask) -> Result<uuid::Uuid, EngineError> {
+    /// Queues `task` once the handler accepts it, failing with
+    /// [`EngineError::QuotaExceeded`] when its tenant already has as many
+    /// queued tasks as its quota allows.
+    pub async fn submit(&self, task: ExecutionTask) -> Result<Uuid, EngineError> {
+        self.submit_idempotent(task)
+            .await
+            .map(|submission| submission.record.task.id)
+    }
+
+    /// Like [`EngineHandle::submit`], but a task whose idempotency key was
+    /// used by its tenant within the idempotency TTL resolves to the task
+    /// first submitted with it. Reusing a key with a different kind or
+    /// payload fails with [`EngineError::IdempotencyConflict`]. Replays are
+    /// resolved before validation and quotas, so retrying a submission that
+    /// went through succeeds even once the tenant is at its queue limit.
+    pub async fn submit_idempotent(&self, task: ExecutionTask) -> Result<Submission, EngineError> {
         if self.shutting_down.load(Ordering::Relaxed) {
             return Err(EngineError::ShuttingDown);
         }
+        let since = chrono::Utc::now()
+            - chrono::Duration::from_std(self.idempotency_ttl)
+                .unwrap_or_else(|_| chrono::Duration::zero());
+        if let Some(key) = &task.idempotency_key {
+            if key.trim().is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LEN {
+                return Err(EngineError::Rejected(format!(
+                    "idempotency key must be between 1 and {} characters",
+                    MAX_IDEMPOTENCY_KEY_LEN
+                )));
+            }
+            if let Some(existing) = self
+                .queue
+                .find_idempotent(&task.tenant_id, key, since)
+                .await?
+            {
+                return replay(&task, existing);
+            }
+        }
+        let handler = self.handler.read().clone();
+        if let Some(handler) = handler {
+            handler.validate(&task)?;
+        }
+        if let Some(limit) = self.quotas.quota(&task.tenant_id).max_queued {
+            if self.queue.tenant_load(&task.tenant_id).await?.queued >= limit {
+                return Err(EngineError::QuotaExceeded {
+                    tenant: task.tenant_id,
+                    limit,
+                });
+            }
+        }
 
-        let task_id = task.id;
-        {
-            let mut registry = self.registry.write();
-            registry.insert(task_id, TaskRecord::new(task.clone()));
+        // Submissions racing with the same key are settled by the queue.
+        let record = TaskRecord::new(task);
+        match self.queue.enqueue_idempotent(record.clone(), since).await? {
+            Some(existing) => replay(&record.task, existing),
+            None => {
+                self.notify.notify_one();
+                self.publish(&record, None);
+                Ok(Submission {
+                    record,
+                    replayed: false,
+                })
+            }
+        }
+    }
+
+    pub async fn get(&self, task_id: &Uuid) -> Result<TaskRecord, EngineError> {
+        self.queue.get(task_id).await
+    }
+
+    /// Cancels a task of `tenant`. A queued task never runs; a running one has
+    /// its cancellation token fired, here or by its worker at the next lease
+    /// renewal when it runs in another process.
+    pub async fn cancel(&self, tenant: &str, task_id: &Uuid) -> Result<TaskRecord, EngineError> {
+        let record = self.queue.cancel(tenant, task_id).await?;
+        let held_here = record
+            .lease_owner
+            .as_deref()
+            .is_some_and(|owner| owner.starts_with(&format!("{}/", self.runtime_id)));
+        if held_here {
+            // A worker that has not registered its token yet sees the request
+            // at its next lease renewal; one that already finished has
+            // nothing left to cancel.
+            if let Some(token) = self.running.lock().get(task_id) {
+                token.cancel();
+            }
+        }
+        if record.status == TaskStatus::Cancelled {
+            self.publish(&record, Some(TaskStatus::Queued));
+            self.advance_workflow(&record).await;
+        }
+        Ok(record)
+    }
+
+    /// Identity execution spans are signed with, when they are recorded.
+    pub fn execution_identity(&self) -> Option<&LogLineID> {
+        self.execution_spans
+            .as_deref()
+            .map(|execution_spans| execution_spans.identity())
+    }
+
+    /// Whether the runtime is draining or shut down and rejects new work.
+    pub fn is_draining(&self) -> bool {
+        self.shutting_down.load(Ordering::Relaxed)
+    }
+
+    /// Lifecycle transitions of tasks from now on.
+    pub fn subscribe_events(&self) -> tokio::sync::broadcast::Receiver<TaskEvent> {
+        self.events.subscribe()
+    }
+
+    fn publish(&self, record: &TaskRecord, previous_status: Option<TaskStatus>) {
+        self.events.publish(TaskEvent::new(record, previous_status));
+    }
+
+    pub async fn list_for_tenant(&self, tenant: &str) -> Result<Vec<TaskRecord>, EngineError> {
+        self.queue.list_for_tenant(tenant).await
+    }
+
+    pub async fn pending_tasks(&self) -> Result<usize, EngineError> {
+        self.queue.pending().await
+    }
+
+    /// Quotas applied to tenants, shared with the workers.
+    pub fn quotas(&self) -> &TenantQuotas {
+        &self.quotas
+    }
+
+    pub async fn tenant_load(&self, tenant: &str) -> Result<TenantLoad, EngineError> {
+        self.queue.tenant_load(tenant).await
+    }
+
+    /// Tasks of `tenant` that failed for good.
+    pub async fn dead_letters(&self, tenant: &str) -> Result<Vec<TaskRecord>, EngineError> {
+        self.queue.dead_letters(tenant).await
+    }
+
+    /// Queues dead-lettered tasks again with a fresh attempt budget.
+    pub async fn redrive(
+        &self,
+        tenant: &str,
+        task_id: Option<&Uuid>,
+    ) -> Result<Vec<Uuid>, EngineError> {
+        if self.shutting_down.load(Ordering::Relaxed) {
+            return Err(EngineError::ShuttingDown);
+        }
+        let redriven = self.queue.redrive(tenant, task_id).await?;
+        if !redriven.is_empty() {
+            self.notify.notify_one();
+        }
+        for id in &redriven {
+            if let Ok(record) = self.queue.get(id).await {
+                self.publish(&record, Some(TaskStatus::Failed));
+            }
+        }
+        Ok(redriven)
+    }
+
+    pub async fn purge_dead_letters(
+        &self,
+        tenant: &str,
+        task_id: Option<&Uuid>,
+    ) -> Result<Vec<Uuid>, EngineError> {
+        self.queue.purge_dead_letters(tenant, task_id).await
+    }
+
+    /// Submits the root steps of `workflow`; the others are submitted as the
+    /// steps they depend on finish. When a root step is refused, for instance
+    /// by the tenant's queue quota, the roots already queued are cancelled
+    /// and the error is returned.
+    ///
+    /// Workflow progress is tracked in this process, so a runtime on a queue
+    /// shared with other processes, whose workers would finish steps this
+    /// one never hears about, refuses workflows with
+    /// [`EngineError::WorkflowsUnavailable`].
+    pub async fn submit_workflow(&self, workflow: Workflow) -> Result<Uuid, EngineError> {
+        if self.shutting_down.load(Ordering::Relaxed) {
+            return Err(EngineError::ShuttingDown);
+        }
+        if self.queue.poll_interval().is_some() {
+            return Err(EngineError::WorkflowsUnavailable);
+        }
+        let handler = self.handler.read().clone();
+        if let Some(handler) = handler {
+            for task in preview_tasks(&workflow) {
+                handler.validate(&task)?;
+            }
+        }
+        let workflow_id = workflow.id;
+        let tenant = workflow.tenant_id.clone();
+        let progress = self.workflows.start(workflow);
+        let mut submitted = Vec::new();
+        for task in progress.released {
+            match self.submit(task).await {
+                Ok(task_id) => submitted.push(task_id),
+                Err(err) => {
+                    self.workflows.remove(&workflow_id);
+                    for task_id in submitted {
+                        if let Err(err) = self.cancel(&tenant, &task_id).await {
+                            warn!(%task_id, %err, "failed to cancel step of rejected workflow");
+                        }
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        for span in progress.spans {
+            self.record_span(span);
+        }
+        Ok(workflow_id)
+    }
+
+    pub fn get_workflow(&self, id: &Uuid) -> Result<WorkflowState, EngineError> {
+        self.workflows.get(id)
+    }
+
+    pub fn list_workflows(&self, tenant: &str) -> Vec<WorkflowState> {
+        self.workflows.list_for_tenant(tenant)
+    }
+
+    /// Moves the workflow of a finished task forward, if it belongs to one.
+    async fn advance_workflow(&self, record: &TaskRecord) {
+        if workflow_step(&record.task).is_some() {
+            let progress = self.workflows.step_finished(record);
+            self.apply_progress(progress).await;
+        }
+    }
+
+    /// Submits the steps `progress` released and records its spans. A step
+    /// that cannot be submitted fails, and the workflow's failure policy
+    /// decides what happens to the rest.
+    async fn apply_progress(&self, progress: WorkflowProgress) {
+        let mut pending = VecDeque::from([progress]);
+        while let Some(progress) = pending.pop_front() {
+            for task in progress.released {
+                let task_id = task.id;
+                if let Err(err) = self.submit(task.clone()).await {
+                    warn!(%task_id, %err, "failed to submit workflow step");
+                    pending.push_back(self.workflows.step_rejected(&task, &err));
+                }
+            }
+            for span in progress.spans {
+                self.record_span(span);
+            }
         }
+    }
 
-        self.scheduler.enqueue(task);
-        self.notify.notify_one();
-        Ok(task_id)
+    /// Sends `span` to the timeline in the background, if a recorder is set.
+    pub(crate) fn record_span(&self, span: Span) {
+        if let Some(recorder) = &self.spans {
+            let recorder = recorder.clone();
+            tokio::spawn(async move {
+                let span_id = span.id;
+                if let Err(err) = recorder.record(span).await {
+                    warn!(%span_id, %err, "failed to record span on the timeline");
+                }
+            });
+        }
     }
 
-    pub fn get(&self, task_id: &uuid::Uuid) -> Result<TaskRecord, EngineError> {
-        self.registry
-            .read()
-            .get(task_id)
-            .cloned()
-            .ok_or_else(|| EngineError::TaskNotFound(task_id.to_string()))
+    /// Records the outcome of a finished task as a signed execution span,
+    /// caused by its workflow's span for workflow steps. Attempts that will
+    /// be retried are not recorded.
+    fn record_execution(&self, record: &TaskRecord) {
+        let Some(execution_spans) = &self.execution_spans else {
+            return;
+        };
+        let caused_by = workflow_step(&record.task)
+            .and_then(|(workflow_id, _)| self.workflows.get(&workflow_id).ok())
+            .map(|workflow| workflow.span_id);
+        if let Some(span) = execution_spans.span_for(record, caused_by) {
+            self.record_span(span);
+        }
     }
 
-    pub fn list_for_tenant(&self, tenant: &str) -> Vec<TaskRecord> {
-        self.registry
-            .read()
-            .values()
-            .filter(|record| record.task.tenant_id == tenant)
-            .cloned()
-            .collect()
+    /// Registers a recurring task; its runs are submitted as they fall due.
+    pub fn schedule_recurring(&self, task: RecurringTask) -> Result<Uuid, EngineError> {
+        if self.shutting_down.load(Ordering::Relaxed) {
+            return Err(EngineError::ShuttingDown);
+        }
+        if let (Some(handler), Some(next_run_at)) = (self.handler.read().clone(), task.next_run_at)
+        {
+            handler.validate(&task.instantiate(next_run_at))?;
+        }
+        Ok(self.recurring.register(task))
+    }
+
+    pub fn get_recurring(&self, id: &Uuid) -> Result<RecurringTask, EngineError> {
+        self.recurring.get(id)
+    }
+
+    pub fn list_recurring(&self, tenant: &str) -> Vec<RecurringTask> {
+        self.recurring.list_for_tenant(tenant)
     }
 
-    pub fn pending_tasks(&self) -> usize {
-        self.scheduler.pending()
+    /// Removes a recurring task. Runs already submitted are left untouched.
+    pub fn cancel_recurring(&self, id: &Uuid) -> Result<RecurringTask, EngineError> {
+        self.recurring.remove(id)
     }
 }
 
 /// Execution runtime responsible for coordinating workers and task lifecycle.
 pub struct ExecutionRuntime {
-    scheduler: TaskScheduler,
-    registry: Arc<RwLock<HashMap<uuid::Uuid, TaskRecord>>>,
+    id: Uuid,
+    queue: Arc<dyn TaskQueue>,
+    recurring: RecurringScheduler,
     notify: Arc<Notify>,
     shutting_down: Arc<AtomicBool>,
+    /// Fired when a drain interrupts the tasks still running.
+    interrupt: CancellationToken,
+    lease: Duration,
+    running: RunningTasks,
+    workflows: WorkflowTracker,
+    spans: Option<Arc<dyn SpanRecorder>>,
+    execution_spans: Option<Arc<ExecutionSpans>>,
+    quotas: TenantQuotas,
+    handler: SharedHandler,
+    idempotency_ttl: Duration,
+    events: TaskEvents,
     workers: Vec<JoinHandle<()>>,
+    lease_reaper: Option<JoinHandle<()>>,
 }
 
 impl ExecutionRuntime {
+    /// Runtime backed by an in-memory queue.
     pub fn new() -> Self {
+        Self::with_queue(Arc::new(MemoryTaskQueue::new()))
+    }
+
+    /// Runtime whose tasks live in `queue`, e.g. a
+    /// [`crate::postgres::PostgresTaskQueue`] shared with other processes.
+    pub fn with_queue(queue: Arc<dyn TaskQueue>) -> Self {
         Self {
-            scheduler: TaskScheduler::new(),
-            registry: Arc::new(RwLock::new(HashMap::new())),
+            id: Uuid::new_v4(),
+            queue,
+            recurring: RecurringScheduler::new(),
             notify: Arc::new(Notify::new()),
             shutting_down: Arc::new(AtomicBool::new(false)),
+            interrupt: CancellationToken::new(),
+            lease: DEFAULT_TASK_LEASE,
+            running: RunningTasks::default(),
+            workflows: WorkflowTracker::new(),
+            spans: None,
+            execution_spans: None,
+            quotas: TenantQuotas::default(),
+            handler: SharedHandler::default(),
+            idempotency_ttl: DEFAULT_IDEMPOTENCY_TTL,
+            events: TaskEvents::default(),
             workers: Vec::new(),
+            lease_reaper: None,
         }
     }
 
+    /// Sets how long claimed tasks are leased; workers renew the lease while
+    /// the handler runs.
+    pub fn with_lease(mut self, lease: Duration) -> Self {
+        self.lease = lease.max(Duration::from_millis(100));
+        self
+    }
+
+    /// Records the spans the engine emits, such as workflow steps, with
+    /// `recorder`.
+    pub fn with_span_recorder(mut self, recorder: Arc<dyn SpanRecorder>) -> Self {
+        self.spans = Some(recorder);
+        self
+    }
+
+    /// Records every task outcome as a span signed with `identity`, sent to
+    /// the span recorder. Has no effect without one.
+    pub fn with_execution_spans(mut self, identity: LogLineKeyPair) -> Self {
+        self.execution_spans = Some(Arc::new(ExecutionSpans::new(identity)));
+        self
+    }
+
+    /// Applies per-tenant weights, in-flight limits and queue caps.
+    pub fn with_quotas(mut self, quotas: TenantQuotas) -> Self {
+        self.quotas = quotas;
+        self
+    }
+
+    /// Sets how long finished workflows stay available before they are
+    /// forgotten.
+    pub fn with_workflow_retention(mut self, retention: Duration) -> Self {
+        self.workflows = WorkflowTracker::with_retention(retention);
+        self
+    }
+
+    /// Sets how long idempotency keys are remembered.
+    pub fn with_idempotency_ttl(mut self, ttl: Duration) -> Self {
+        self.idempotency_ttl = ttl;
+        self
+    }
+
     pub fn handle(&self) -> EngineHandle {
         EngineHandle {
-            scheduler: self.scheduler.clone(),
-            registry: self.registry.clone(),
+            runtime_id: self.id,
+            queue: self.queue.clone(),
+            recurring: self.recurring.clone(),
             notify: self.notify.clone(),
             shutting_down: self.shutting_down.clone(),
+            interrupt: self.interrupt.clone(),
+            lease: self.lease,
+            running: self.running.clone(),
+            workflows: self.workflows.clone(),
+            spans: self.spans.clone(),
+            execution_spans: self.execution_spans.clone(),
+            quotas: self.quotas.clone(),
+            handler: self.handler.clone(),
+            idempotency_ttl: self.idempotency_ttl,
+            events: self.events.clone(),
         }
     }
 
+    /// Spawns `worker_count` workers running tasks with `handler`, typically
+    /// a [`crate::registry::TaskRegistry`].
     pub fn start<H>(&mut self, handler: Arc<H>, worker_count: usize)
     where
         H: TaskHandler,
     {
+        *self.handler.write() = Some(handler.clone());
+        if self.workers.is_empty() {
+            let handle = self.handle();
+            self.workers
+                .push(tokio::spawn(async move { recurring_loop(handle).await }));
+        }
+        if self.lease_reaper.is_none() {
+            let handle = self.handle();
+            self.lease_reaper = Some(tokio::spawn(async move { lease_reaper(handle).await }));
+        }
+
         let worker_count = worker_count.max(1);
         for worker_index in 0..worker_count {
-            let scheduler = self.scheduler.clone();
-            let registry = self.registry.clone();
-            let notify = self.notify.clone();
-            let shutting_down = self.shutting_down.clone();
+            let worker_id = format!("{}/{}", self.id, worker_index);
+            let handle = self.handle();
             let handler = handler.clone();
 
-            let handle = tokio::spawn(async move {
-                worker_loop(
-                    worker_index,
-                    scheduler,
-                    registry,
-                    notify,
-                    shutting_down,
-                    handler,
-                )
-                .await;
+            let join = tokio::spawn(async move {
+                worker_loop(worker_index, worker_id, handle, handler).await;
             });
 
-            self.workers.push(handle);
+            self.workers.push(join);
         }
     }
 
     pub async fn shutdown(self) {
-        self.shutting_down.store(true, Ordering::Relaxed);
-        self.notify.notify_waiters();
+        self.stop_accepting();
         for handle in self.workers {
             if let Err(err) = handle.await {
                 error!("worker crashed: {:?}", err);
             }
         }
     }
+
+    /// Stops accepting work and waits up to `deadline` for running tasks.
+    /// Tasks still running then are interrupted: their cancellation tokens
+    /// fire, workers get [`CANCEL_GRACE_PERIOD`] to wind down before they
+    /// are aborted, and the tasks are released back to the queue with their
+    /// attempts rather than recorded as cancelled.
+    pub async fn drain(self, deadline: Duration) -> DrainReport {
+        self.stop_accepting();
+        let aborts: Vec<_> = self.workers.iter().map(JoinHandle::abort_handle).collect();
+        let mut workers = std::pin::pin!(futures::future::join_all(self.workers));
+        let results = match tokio::time::timeout(deadline, &mut workers).await {
+            Ok(results) => results,
+            Err(_) => {
+                warn!(
+                    tasks = self.running.lock().len(),
+                    "drain deadline elapsed; interrupting running tasks"
+                );
+                self.interrupt.cancel();
+                match tokio::time::timeout(CANCEL_GRACE_PERIOD, &mut workers).await {
+                    Ok(results) => results,
+                    Err(_) => {
+                        warn!("workers did not stop after interruption; aborting them");
+                        for abort in aborts {
+                            abort.abort();
+                        }
+                        workers.await
+                    }
+                }
+            }
+        };
+        for err in results.into_iter().filter_map(Result::err) {
+            if !err.is_cancelled() {
+                error!("worker crashed: {:?}", err);
+            }
+        }
+
+        let interrupted = match self.queue.hand_off(&self.id.to_string()).await {
+            Ok(interrupted) => interrupted,
+            Err(err) => {
+                warn!(%err, "failed to release interrupted tasks; their leases will expire");
+                Vec::new()
+            }
+        };
+        let remaining = self.queue.take_queued().await.unwrap_or_else(|err| {
+            warn!(%err, "failed to take the queued tasks");
+            Vec::new()
+        });
+        if !remaining.is_empty() {
+            warn!(
+                tasks = remaining.len(),
+                "queued tasks are lost unless they are submitted to another runtime; back the engine with a shared queue to hand them off"
+            );
+        }
+        info!(
+            interrupted = interrupted.len(),
+            remaining = remaining.len(),
+            "engine runtime drained"
+        );
+        DrainReport {
+            interrupted,
+            remaining,
+        }
+    }
+
+    /// Rejects new work and wakes idle workers so they exit.
+    fn stop_accepting(&self) {
+        self.shutting_down.store(true, Ordering::Relaxed);
+        if let Some(reaper) = &self.lease_reaper {
+            reaper.abort();
+        }
+        self.notify.notify_waiters();
+        self.recurring.notify().notify_waiters();
+    }
+}
+
+/// Work a drained runtime left behind.
+#[derive(Debug, Clone, Default)]
+pub struct DrainReport {
+    /// Tasks still running at the deadline, as they were released: queued
+    /// again, or cancelled when that had been requested.
+    pub interrupted: Vec<TaskRecord>,
+    /// Tasks taken out of a process-local queue, including the interrupted
+    /// ones, for the caller to submit to another runtime. Empty for a
+    /// shared queue, whose other processes run them.
+    pub remaining: Vec<TaskRecord>,
+}
+
+/// Sleeps until new work is submitted, the next delayed task is due or, for
+/// shared queues, the poll interval elapses.
+async fn wait_for_work(handle: &EngineHandle) {
+    let notified = handle.notify.notified();
+    tokio::pin!(notified);
+    notified.as_mut().enable();
+
+    if handle.shutting_down.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let next_due_at = handle.queue.next_due_at().await.unwrap_or_else(|err| {
+        warn!(%err, "failed to look up the next delayed task");
+        None
+    });
+    let until_due =
+        next_due_at.map(|due_at| (due_at - chrono::Utc::now()).to_std().unwrap_or_default());
+    let delay = match (until_due, handle.queue.poll_interval()) {
+        (Some(until_due), Some(poll)) => Some(until_due.min(poll)),
+        (until_due, poll) => until_due.or(poll),
+    };
+
+    match delay {
+        Some(delay) => {
+            let _ = tokio::time::timeout(delay, notified).await;
+        }
+        None => notified.await,
+    }
 }
 
 async fn worker_loop<H>(
     worker_index: usize,
-    scheduler: TaskScheduler,
-    registry: Arc<RwLock<HashMap<uuid::Uuid, TaskRecord>>>,
-    notify: Arc<Notify>,
-    shutting_down: Arc<AtomicBool>,
+    worker_id: String,
+    handle: EngineHandle,
     handler: Arc<H>,
 ) where
     H: TaskHandler,
 {
     loop {
-        if shutting_down.load(Ordering::Relaxed) {
+        if handle.shutting_down.load(Ordering::Relaxed) {
             break;
         }
 
-        let task = loop {
-            if let Some(task) = scheduler.next_task() {
-                break task;
+        let record = match handle
+            .queue
+            .claim(&worker_id, handle.lease, &handle.quotas)
+            .await
+        {
+            Ok(Some(record)) => record,
+            Ok(None) => {
+                wait_for_work(&handle).await;
+                continue;
             }
-
-            if shutting_down.load(Ordering::Relaxed) {
-                return;
+            Err(err) => {
+                warn!(worker = worker_index, %err, "failed to claim task");
+                wait_for_work(&handle).await;
+                continue;
             }
+        };
+        // More tasks may be ready (several delayed ones coming due at once);
+        // let another idle worker look.
+        handle.notify.notify_one();
+        handle.publish(&record, Some(TaskStatus::Queued));
 
-            notify.notified().await;
+        let task = record.task.clone();
+        let start = record.started_at.unwrap_or_else(chrono::Utc::now);
+        let waited = (start - task.scheduled_for.max(task.created_at))
+            .to_std()
+            .unwrap_or_default();
+        handle.quotas.record_wait(&task.tenant_id, waited);
+        info!(worker = worker_index, task_id = %task.id, tenant = %task.tenant_id, "executing task");
+
+        let cancel = CancellationToken::new();
+        handle.running.lock().insert(task.id, cancel.clone());
+        let result = run_task(&handle, &worker_id, handler.as_ref(), &task, &cancel).await;
+        handle.running.lock().remove(&task.id);
+
+        let recorded = match result {
+            RunResult::Finished(Ok(result)) => {
+                let outcome = ExecutionOutcome::success(&task, start, result);
+                handle.queue.complete(&worker_id, &outcome).await
+            }
+            RunResult::Finished(Err(err)) => {
+                record_failure(&handle, &worker_id, handler.as_ref(), &record, err).await
+            }
+            RunResult::TimedOut(timeout) => {
+                let err = format!("timed out after {} ms", timeout.as_millis());
+                record_failure(&handle, &worker_id, handler.as_ref(), &record, err).await
+            }
+            RunResult::Cancelled => {
+                info!(task_id = %task.id, "task cancelled");
+                let outcome = ExecutionOutcome::cancelled(&task, start);
+                handle.queue.complete(&worker_id, &outcome).await
+            }
+            // The task belongs to whoever holds the lease now; recording an
+            // outcome would overwrite theirs.
+            RunResult::LeaseLost => {
+                warn!(task_id = %task.id, "task lease lost; abandoning attempt");
+                continue;
+            }
+            // The drain releases the lease once the workers have stopped.
+            RunResult::Interrupted => {
+                info!(task_id = %task.id, "task interrupted by drain");
+                continue;
+            }
         };
 
-        let start = chrono::Utc::now();
-        {
-            let mut registry = registry.write();
-            if let Some(record) = registry.get_mut(&task.id) {
-                record.status = TaskStatus::Running;
-                record.started_at = Some(start);
-                record.last_error = None;
+        if let Err(err) = recorded {
+            warn!(task_id = %task.id, %err, "failed to record task outcome");
+            continue;
+        }
+        match handle.queue.get(&task.id).await {
+            Ok(record) => {
+                handle.publish(&record, Some(TaskStatus::Running));
+                handle.record_execution(&record);
+                if workflow_step(&task).is_some() {
+                    handle.advance_workflow(&record).await;
+                }
             }
+            Err(err) => warn!(task_id = %task.id, %err, "failed to reload task outcome"),
         }
+    }
+}
 
-        info!(worker = worker_index, task_id = %task.id, tenant = %task.tenant_id, "executing task");
+enum RunResult {
+    Finished(Result<serde_json::Value, String>),
+    TimedOut(Duration),
+    Cancelled,
+    /// The lease expired and the task may already run elsewhere.
+    LeaseLost,
+    /// The runtime is draining and stopped the task to hand it off.
+    Interrupted,
+}
 
-        let outcome = match handler.handle(task.clone()).await {
-            Ok(result) => ExecutionOutcome::success(&task, start, result),
-            Err(err) => ExecutionOutcome::failure(&task, start, err),
-        };
+/// Drives the handler for one attempt, renewing the lease meanwhile and
+/// enforcing the task's timeout and cancellation.
+async fn run_task<H>(
+    handle: &EngineHandle,
+    worker_id: &str,
+    handler: &H,
+    task: &ExecutionTask,
+    cancel: &CancellationToken,
+) -> RunResult
+where
+    H: TaskHandler,
+{
+    let renew_every = handle.lease / 3;
+    let timeout = task.timeout_ms.map(Duration::from_millis);
+    let deadline = tokio::time::sleep(timeout.unwrap_or(Duration::MAX / 4));
+    tokio::pin!(deadline);
+    let work = handler.handle_with_cancellation(task.clone(), cancel.clone());
+    tokio::pin!(work);
+
+    let stopped = loop {
+        tokio::select! {
+            result = &mut work => {
+                // A handler that fails because its token fired was cancelled,
+                // not failed, and must not be retried.
+                return match result {
+                    Err(_) if cancel.is_cancelled() => RunResult::Cancelled,
+                    result => RunResult::Finished(result),
+                };
+            }
+            _ = &mut deadline, if timeout.is_some() => {
+                warn!(task_id = %task.id, "task timed out");
+                break RunResult::TimedOut(timeout.unwrap_or_default());
+            }
+            _ = handle.interrupt.cancelled() => break RunResult::Interrupted,
+            _ = cancel.cancelled() => break RunResult::Cancelled,
+            _ = tokio::time::sleep(renew_every) => {
+                match handle.queue.renew_lease(&task.id, worker_id, handle.lease).await {
+                    Ok(true) => cancel.cancel(),
+                    Ok(false) => {}
+                    Err(EngineError::LeaseLost(_)) => break RunResult::LeaseLost,
+                    Err(err) => warn!(task_id = %task.id, %err, "failed to renew task lease"),
+                }
+            }
+        }
+    };
+
+    // Give the handler a chance to wind down before its future is dropped.
+    cancel.cancel();
+    if tokio::time::timeout(CANCEL_GRACE_PERIOD, &mut work)
+        .await
+        .is_err()
+    {
+        warn!(task_id = %task.id, "handler ignored cancellation; dropping it");
+    }
+    stopped
+}
+
+/// Records a failed attempt, scheduling a retry when the policy and the
+/// handler allow it.
+async fn record_failure<H>(
+    handle: &EngineHandle,
+    worker_id: &str,
+    handler: &H,
+    record: &TaskRecord,
+    err: String,
+) -> Result<(), EngineError>
+where
+    H: TaskHandler,
+{
+    let task = &record.task;
+    let failure = TaskFailure {
+        attempt: record.attempts,
+        retryable: handler.is_retryable(task, &err),
+        error: err,
+        failed_at: chrono::Utc::now(),
+    };
+    let retry_at = (failure.retryable && task.retry.allows_retry(record.attempts)).then(|| {
+        failure.failed_at
+            + chrono::Duration::from_std(task.retry.backoff(record.attempts))
+                .unwrap_or_else(|_| chrono::Duration::zero())
+    });
+    match retry_at {
+        Some(retry_at) => {
+            warn!(task_id = %task.id, attempt = record.attempts, error = %failure.error, %retry_at, "task failed; retry scheduled");
+        }
+        None => {
+            error!(task_id = %task.id, attempt = record.attempts, error = %failure.error, "task failed; moved to dead letters");
+        }
+    }
+    handle
+        .queue
+        .fail(worker_id, &task.id, failure, retry_at)
+        .await
+}
 
+/// Puts tasks back in the queue when the worker running them stopped renewing
+/// its lease, including tasks left running by a previous process.
+async fn lease_reaper(handle: EngineHandle) {
+    let interval = handle.lease / 2;
+    loop {
+        match handle
+            .queue
+            .recover_expired_leases(chrono::Utc::now())
+            .await
         {
-            let mut registry = registry.write();
-            if let Some(record) = registry.get_mut(&task.id) {
-                record.status = outcome.status.clone();
-                record.finished_at = outcome.finished_at;
-                record.last_error = outcome.error.clone();
-                record.result = outcome.result.clone();
+            Ok(recovered) => {
+                for task_id in &recovered {
+                    warn!(%task_id, "task lease expired; requeued");
+                    handle.notify.notify_one();
+                    if let Ok(record) = handle.queue.get(task_id).await {
+                        handle.publish(&record, Some(TaskStatus::Running));
+                        if record.status == TaskStatus::Cancelled {
+                            handle.advance_workflow(&record).await;
+                        }
+                    }
+                }
             }
+            Err(err) => warn!(%err, "failed to recover expired task leases"),
         }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Submits the runs of recurring tasks as their occurrences fall due.
+async fn recurring_loop(handle: EngineHandle) {
+    let recurring = handle.recurring.clone();
+    loop {
+        let notified = recurring.notify().notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
 
-        if matches!(outcome.status, TaskStatus::Failed) {
-            error!(task_id = %outcome.task_id, error = ?outcome.error, "task failed");
+        if handle.shutting_down.load(Ordering::Relaxed) {
+            break;
+        }
+
+        for task in recurring.take_due(chrono::Utc::now()) {
+            let tenant = task.tenant_id.clone();
+            if let Err(err) = handle.submit(task).await {
+                warn!(%tenant, %err, "failed to submit recurring task run");
+            }
+        }
+
+        match recurring.next_run_at() {
+            Some(next_run_at) => {
+                let delay = (next_run_at - chrono::Utc::now())
+                    .to_std()
+                    .unwrap_or_default();
+                let _ = tokio::time::timeout(delay, notified).await;
+            }
+            None => notified.await,
         }
     }
 }
@@ -198,7 +952,9 @@ async fn worker_loop<H>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::quota::{QuotaConfig, TenantQuota};
     use crate::task::{ExecutionTask, TaskPriority};
+    use crate::workflow::{StepStatus, WorkflowStatus, WorkflowStep};
 
     struct TestHandler;
 
@@ -224,16 +980,378 @@ mod tests {
             if tenant == "b" {
                 task.priority = TaskPriority::High;
             }
-            handle.submit(task).unwrap();
+            handle.submit(task).await.unwrap();
         }
 
         tokio::time::sleep(std::time::Duration::from_millis(50)).await;
 
-        let tasks_a = handle.list_for_tenant("a");
+        let tasks_a = handle.list_for_tenant("a").await.unwrap();
         assert!(tasks_a
             .iter()
             .all(|record| record.status != TaskStatus::Queued));
 
         runtime.shutdown().await;
     }
+
+    #[tokio::test]
+    async fn publishes_task_lifecycle_events() {
+        let mut runtime = ExecutionRuntime::new();
+        let handle = runtime.handle();
+        let mut events = handle.subscribe_events();
+        runtime.start(Arc::new(TestHandler), 1);
+
+        let task_id = handle
+            .submit(ExecutionTask::builder("a").build())
+            .await
+            .unwrap();
+        let mut transitions = Vec::new();
+        while transitions.len() < 3 {
+            let event = tokio::time::timeout(Duration::from_secs(1), events.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(event.task_id, task_id);
+            transitions.push((event.previous_status, event.status));
+        }
+        assert_eq!(
+            transitions,
+            vec![
+                (None, TaskStatus::Queued),
+                (Some(TaskStatus::Queued), TaskStatus::Running),
+                (Some(TaskStatus::Running), TaskStatus::Completed),
+            ]
+        );
+
+        runtime.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn deduplicates_submissions_by_idempotency_key() {
+        let handle = ExecutionRuntime::new().handle();
+        let task = |payload: serde_json::Value| {
+            ExecutionTask::builder("a")
+                .payload(payload)
+                .idempotency_key("order-1")
+                .build()
+        };
+
+        let first = handle
+            .submit_idempotent(task(serde_json::json!({ "n": 1 })))
+            .await
+            .unwrap();
+        assert!(!first.replayed);
+        let again = handle
+            .submit_idempotent(task(serde_json::json!({ "n": 1 })))
+            .await
+            .unwrap();
+        assert!(again.replayed);
+        assert_eq!(again.record.task.id, first.record.task.id);
+        assert!(matches!(
+            handle.submit(task(serde_json::json!({ "n": 2 }))).await,
+            Err(EngineError::IdempotencyConflict(key)) if key == "order-1"
+        ));
+
+        let at_cap = ExecutionRuntime::new()
+            .with_quotas(TenantQuotas::new(QuotaConfig {
+                free: TenantQuota {
+                    max_queued: Some(1),
+                    ..TenantQuota::default()
+                },
+                ..QuotaConfig::default()
+            }))
+            .handle();
+        let queued = at_cap
+            .submit_idempotent(task(serde_json::json!({ "n": 1 })))
+            .await
+            .unwrap();
+        let replayed = at_cap
+            .submit_idempotent(task(serde_json::json!({ "n": 1 })))
+            .await
+            .unwrap();
+        assert!(replayed.replayed);
+        assert_eq!(replayed.record.task.id, queued.record.task.id);
+        assert!(matches!(
+            at_cap
+                .submit(
+                    ExecutionTask::builder("a")
+                        .idempotency_key("order-2")
+                        .build()
+                )
+                .await,
+            Err(EngineError::QuotaExceeded { .. })
+        ));
+
+        let other_tenant = ExecutionTask::builder("b")
+            .payload(serde_json::json!({ "n": 1 }))
+            .idempotency_key("order-1")
+            .build();
+        assert_ne!(
+            handle.submit(other_tenant).await.unwrap(),
+            first.record.task.id
+        );
+
+        let expired = ExecutionRuntime::new()
+            .with_idempotency_ttl(Duration::ZERO)
+            .handle();
+        let id = expired
+            .submit(task(serde_json::json!({ "n": 1 })))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_ne!(
+            expired
+                .submit(task(serde_json::json!({ "n": 2 })))
+                .await
+                .unwrap(),
+            id
+        );
+    }
+
+    struct SlowHandler;
+
+    #[async_trait]
+    impl TaskHandler for SlowHandler {
+        async fn handle(&self, _task: ExecutionTask) -> Result<serde_json::Value, String> {
+            unreachable!("handle_with_cancellation is overridden")
+        }
+
+        async fn handle_with_cancellation(
+            &self,
+            _task: ExecutionTask,
+            cancel: CancellationToken,
+        ) -> Result<serde_json::Value, String> {
+            cancel.cancelled().await;
+            Err("stopped".into())
+        }
+    }
+
+    #[tokio::test]
+    async fn cancels_and_times_out_running_tasks() {
+        let mut runtime = ExecutionRuntime::new();
+        runtime.start(Arc::new(SlowHandler), 2);
+        let handle = runtime.handle();
+
+        let cancelled = handle
+            .submit(ExecutionTask::builder("a").build())
+            .await
+            .unwrap();
+        let timed_out = handle
+            .submit(
+                ExecutionTask::builder("a")
+                    .retry(crate::retry::RetryPolicy::no_retries())
+                    .timeout(Duration::from_millis(20))
+                    .build(),
+            )
+            .await
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(matches!(
+            handle.cancel("b", &cancelled).await,
+            Err(EngineError::TaskNotFound(_))
+        ));
+        let record = handle.cancel("a", &cancelled).await.unwrap();
+        assert!(record.cancel_requested);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let record = handle.get(&cancelled).await.unwrap();
+        assert_eq!(record.status, TaskStatus::Cancelled);
+        assert!(matches!(
+            handle.cancel("a", &cancelled).await,
+            Err(EngineError::TaskFinished(_))
+        ));
+
+        let record = handle.get(&timed_out).await.unwrap();
+        assert_eq!(record.status, TaskStatus::Failed);
+        assert_eq!(record.last_error.as_deref(), Some("timed out after 20 ms"));
+
+        runtime.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn fails_workflow_steps_refused_by_the_queue_quota() {
+        let handle = ExecutionRuntime::new()
+            .with_quotas(TenantQuotas::new(QuotaConfig {
+                free: TenantQuota {
+                    max_queued: Some(1),
+                    ..TenantQuota::default()
+                },
+                ..QuotaConfig::default()
+            }))
+            .handle();
+
+        // A refused root rejects the whole workflow.
+        let two_roots = Workflow::builder("a", "fan-out")
+            .step(WorkflowStep::new("left"))
+            .step(WorkflowStep::new("right"))
+            .build()
+            .unwrap();
+        assert!(matches!(
+            handle.submit_workflow(two_roots).await,
+            Err(EngineError::QuotaExceeded { .. })
+        ));
+        assert!(handle.list_workflows("a").is_empty());
+        let tasks = handle.list_for_tenant("a").await.unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].status, TaskStatus::Cancelled);
+
+        let chain = Workflow::builder("a", "etl")
+            .step(WorkflowStep::new("extract"))
+            .step(WorkflowStep::new("load").depends_on("extract"))
+            .step(WorkflowStep::new("report").depends_on("load"))
+            .build()
+            .unwrap();
+        let workflow_id = handle.submit_workflow(chain).await.unwrap();
+        let worker = format!("{}/0", handle.runtime_id);
+        let extract = handle
+            .queue
+            .claim(&worker, DEFAULT_TASK_LEASE, &TenantQuotas::default())
+            .await
+            .unwrap()
+            .unwrap();
+
+        // The tenant fills its queue while `extract` runs, so `load` is
+        // refused when it is released.
+        handle
+            .submit(
+                ExecutionTask::builder("a")
+                    .scheduled_for(chrono::Utc::now() + chrono::Duration::hours(1))
+                    .build(),
+            )
+            .await
+            .unwrap();
+        let outcome =
+            ExecutionOutcome::success(&extract.task, chrono::Utc::now(), serde_json::Value::Null);
+        handle.queue.complete(&worker, &outcome).await.unwrap();
+        let extract = handle.get(&extract.task.id).await.unwrap();
+        handle.advance_workflow(&extract).await;
+
+        let state = handle.get_workflow(&workflow_id).unwrap();
+        assert_eq!(state.status, WorkflowStatus::Failed);
+        assert_eq!(state.steps[0].status, StepStatus::Completed);
+        assert_eq!(state.steps[1].status, StepStatus::Failed);
+        assert!(state.steps[1]
+            .error
+            .as_deref()
+            .is_some_and(|error| error.starts_with("not submitted")));
+        assert_eq!(state.steps[2].status, StepStatus::Skipped);
+    }
+
+    #[tokio::test]
+    async fn cancelling_a_task_without_a_worker_token_leaves_none_behind() {
+        let queue = Arc::new(MemoryTaskQueue::new());
+        let runtime = ExecutionRuntime::with_queue(queue.clone());
+        let handle = runtime.handle();
+        let task_id = handle
+            .submit(ExecutionTask::builder("a").build())
+            .await
+            .unwrap();
+
+        // Leased to a worker of this runtime that has not registered, or has
+        // already dropped, its token.
+        let worker = format!("{}/0", handle.runtime_id);
+        queue
+            .claim(&worker, DEFAULT_TASK_LEASE, &TenantQuotas::default())
+            .await
+            .unwrap()
+            .unwrap();
+        let record = handle.cancel("a", &task_id).await.unwrap();
+        assert!(record.cancel_requested);
+        assert!(handle.running.lock().is_empty());
+        assert!(queue
+            .renew_lease(&task_id, &worker, DEFAULT_TASK_LEASE)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn stops_tasks_whose_lease_was_lost() {
+        let queue = Arc::new(MemoryTaskQueue::new());
+        let mut runtime =
+            ExecutionRuntime::with_queue(queue.clone()).with_lease(Duration::from_millis(150));
+        runtime.start(Arc::new(SlowHandler), 1);
+        let handle = runtime.handle();
+
+        let task_id = handle
+            .submit(ExecutionTask::builder("a").build())
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let cancel = handle.running.lock().get(&task_id).cloned().unwrap();
+
+        // Another process takes the task over once its lease looks expired.
+        let far_future = chrono::Utc::now() + chrono::Duration::hours(1);
+        queue.recover_expired_leases(far_future).await.unwrap();
+        let quotas = TenantQuotas::default();
+        let claimed = queue
+            .claim("elsewhere/0", Duration::from_secs(60), &quotas)
+            .await
+            .unwrap();
+        assert_eq!(claimed.map(|record| record.task.id), Some(task_id));
+
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        assert!(cancel.is_cancelled());
+        let record = handle.get(&task_id).await.unwrap();
+        assert_eq!(record.status, TaskStatus::Running);
+        assert_eq!(record.lease_owner.as_deref(), Some("elsewhere/0"));
+        assert!(record.error_history.is_empty());
+
+        runtime.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn drains_and_hands_off_remaining_tasks() {
+        let mut runtime = ExecutionRuntime::new();
+        runtime.start(Arc::new(SlowHandler), 1);
+        let handle = runtime.handle();
+
+        let running = handle
+            .submit(ExecutionTask::builder("a").build())
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let queued = handle
+            .submit(ExecutionTask::builder("a").build())
+            .await
+            .unwrap();
+
+        let report = runtime.drain(Duration::from_millis(50)).await;
+        assert!(handle.is_draining());
+        assert!(matches!(
+            handle.submit(ExecutionTask::builder("a").build()).await,
+            Err(EngineError::ShuttingDown)
+        ));
+
+        // The interrupted attempt is released rather than recorded as
+        // cancelled.
+        assert_eq!(report.interrupted.len(), 1);
+        let interrupted = &report.interrupted[0];
+        assert_eq!(interrupted.task.id, running);
+        assert_eq!(interrupted.status, TaskStatus::Queued);
+        assert_eq!(interrupted.attempts, 1);
+        assert_eq!(interrupted.lease_owner, None);
+
+        // Both tasks leave the drained queue for another runtime to run.
+        let remaining: Vec<Uuid> = report
+            .remaining
+            .iter()
+            .map(|record| record.task.id)
+            .collect();
+        assert_eq!(remaining, vec![running, queued]);
+        assert!(handle.get(&running).await.is_err());
+
+        let mut successor = ExecutionRuntime::new();
+        successor.start(Arc::new(TestHandler), 1);
+        let next = successor.handle();
+        for record in report.remaining {
+            next.submit(record.task).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        for id in [running, queued] {
+            let record = next.get(&id).await.unwrap();
+            assert_eq!(record.status, TaskStatus::Completed);
+            assert_eq!(record.result.unwrap()["task_id"], id.to_string());
+        }
+        successor.shutdown().await;
+    }
 }
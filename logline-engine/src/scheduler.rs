@@ -1,29 +1,81 @@
-use std::collections::{HashMap, VecDeque};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
 
+use chrono::{DateTime, Utc};
 use parking_lot::RwLock;
 
 use crate::task::ExecutionTask;
 
 /// Multi-tenant scheduler that provides fair task distribution.
+///
+/// Tenants take turns; with [`TaskScheduler::next_task_weighted`] a tenant
+/// keeps its turn for as many tasks as its weight. Tasks whose
+/// `scheduled_for` lies in the future wait in a min-heap and only join their
+/// tenant's queue once due.
 #[derive(Default, Clone)]
 pub struct TaskScheduler {
     queues: ArcQueues,
     rotation: ArcRotation,
+    delayed: ArcDelayed,
+    credits: ArcCredits,
 }
 
 type ArcQueues = std::sync::Arc<RwLock<HashMap<String, VecDeque<ExecutionTask>>>>;
 type ArcRotation = std::sync::Arc<RwLock<VecDeque<String>>>;
+type ArcDelayed = std::sync::Arc<RwLock<BinaryHeap<DelayedTask>>>;
+/// Tasks handed to the tenant at the front of the rotation during its turn.
+type ArcCredits = std::sync::Arc<RwLock<HashMap<String, u32>>>;
+
+/// Heap entry ordered so that the earliest `scheduled_for` is on top.
+struct DelayedTask(ExecutionTask);
+
+impl DelayedTask {
+    fn key(&self) -> (DateTime<Utc>, u32) {
+        (self.0.scheduled_for, self.0.priority as u32)
+    }
+}
+
+impl PartialEq for DelayedTask {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for DelayedTask {}
+
+impl PartialOrd for DelayedTask {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DelayedTask {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.key().cmp(&self.key())
+    }
+}
 
 impl TaskScheduler {
     pub fn new() -> Self {
         Self {
             queues: ArcQueues::default(),
             rotation: ArcRotation::default(),
+            delayed: ArcDelayed::default(),
+            credits: ArcCredits::default(),
         }
     }
 
-    /// Enqueue a task, respecting priority ordering.
+    /// Enqueue a task, respecting priority ordering. Tasks scheduled in the
+    /// future are held back until due.
     pub fn enqueue(&self, task: ExecutionTask) {
+        if task.scheduled_for > Utc::now() {
+            self.delayed.write().push(DelayedTask(task));
+            return;
+        }
+        self.enqueue_ready(task);
+    }
+
+    fn enqueue_ready(&self, task: ExecutionTask) {
         let tenant_id = task.tenant_id.clone();
         {
             let mut queues = self.queues.write();
@@ -52,46 +104,142 @@ impl TaskScheduler {
         }
     }
 
-    /// Returns the next task to execute following a round-robin strategy.
+    /// Moves delayed tasks that are due at `now` into their tenant queues.
+    fn release_due(&self, now: DateTime<Utc>) {
+        let due: Vec<ExecutionTask> = {
+            let mut delayed = self.delayed.write();
+            let mut due = Vec::new();
+            while delayed
+                .peek()
+                .is_some_and(|entry| entry.0.scheduled_for <= now)
+            {
+                if let Some(DelayedTask(task)) = delayed.pop() {
+                    due.push(task);
+                }
+            }
+            due
+        };
+        for task in due {
+            self.enqueue_ready(task);
+        }
+    }
+
+    /// Drops a queued task, whether due or delayed. Returns whether it was
+    /// found.
+    pub fn remove(&self, task_id: &uuid::Uuid) -> bool {
+        let mut delayed = self.delayed.write();
+        let before = delayed.len();
+        delayed.retain(|entry| entry.0.id != *task_id);
+        if delayed.len() != before {
+            return true;
+        }
+        drop(delayed);
+
+        let mut rotation = self.rotation.write();
+        let mut queues = self.queues.write();
+        let Some((tenant, position)) = queues.iter().find_map(|(tenant, queue)| {
+            queue
+                .iter()
+                .position(|task| task.id == *task_id)
+                .map(|position| (tenant.clone(), position))
+        }) else {
+            return false;
+        };
+        let queue = queues.get_mut(&tenant).expect("tenant queue exists");
+        queue.remove(position);
+        if queue.is_empty() {
+            queues.remove(&tenant);
+            rotation.retain(|candidate| candidate != &tenant);
+            self.credits.write().remove(&tenant);
+        }
+        true
+    }
+
+    /// When the earliest delayed task becomes due, if any.
+    pub fn next_due_at(&self) -> Option<DateTime<Utc>> {
+        self.delayed
+            .read()
+            .peek()
+            .map(|entry| entry.0.scheduled_for)
+    }
+
+    /// Returns the next due task to execute following a round-robin strategy.
     pub fn next_task(&self) -> Option<ExecutionTask> {
+        self.next_task_weighted(|_| Some(1))
+    }
+
+    /// Weighted round-robin: `admit` returns the weight of a tenant, i.e. how
+    /// many tasks it is handed per turn, or `None` to pass over it for now
+    /// (for example while it is at its in-flight limit).
+    pub fn next_task_weighted(&self, admit: impl Fn(&str) -> Option<u32>) -> Option<ExecutionTask> {
+        self.release_due(Utc::now());
+
         let mut rotation = self.rotation.write();
         let mut queues = self.queues.write();
+        let mut credits = self.credits.write();
 
         let len = rotation.len();
         for _ in 0..len {
-            if let Some(tenant) = rotation.pop_front() {
-                let mut remove_tenant = false;
-                let maybe_task = queues.get_mut(&tenant).and_then(|queue| {
-                    let task = queue.pop_front();
-                    if queue.is_empty() {
-                        remove_tenant = true;
-                    }
-                    task
-                });
-
-                if remove_tenant {
-                    queues.remove(&tenant);
-                } else {
-                    rotation.push_back(tenant.clone());
+            let Some(tenant) = rotation.front().cloned() else {
+                break;
+            };
+            let Some(weight) = admit(&tenant) else {
+                credits.remove(&tenant);
+                rotation.rotate_left(1);
+                continue;
+            };
+
+            let mut remove_tenant = false;
+            let maybe_task = queues.get_mut(&tenant).and_then(|queue| {
+                let task = queue.pop_front();
+                if queue.is_empty() {
+                    remove_tenant = true;
                 }
+                task
+            });
 
-                if let Some(task) = maybe_task {
-                    return Some(task);
+            if remove_tenant || maybe_task.is_none() {
+                queues.remove(&tenant);
+                credits.remove(&tenant);
+                rotation.pop_front();
+            } else {
+                let used = credits.entry(tenant).or_insert(0);
+                *used += 1;
+                if *used >= weight.max(1) {
+                    *used = 0;
+                    rotation.rotate_left(1);
                 }
             }
+
+            if let Some(task) = maybe_task {
+                return Some(task);
+            }
         }
 
         None
     }
 
+    /// Queued tasks, including the ones not yet due.
     pub fn pending(&self) -> usize {
         let queues = self.queues.read();
-        queues.values().map(|queue| queue.len()).sum()
+        queues.values().map(|queue| queue.len()).sum::<usize>() + self.delayed()
     }
 
     pub fn pending_for_tenant(&self, tenant: &str) -> usize {
         let queues = self.queues.read();
-        queues.get(tenant).map(|queue| queue.len()).unwrap_or(0)
+        let ready = queues.get(tenant).map(|queue| queue.len()).unwrap_or(0);
+        let delayed = self
+            .delayed
+            .read()
+            .iter()
+            .filter(|entry| entry.0.tenant_id == tenant)
+            .count();
+        ready + delayed
+    }
+
+    /// Tasks waiting for their `scheduled_for` time.
+    pub fn delayed(&self) -> usize {
+        self.delayed.read().len()
     }
 
     pub fn tenants(&self) -> Vec<String> {
@@ -127,6 +275,39 @@ mod tests {
         assert_eq!(order, vec!["a", "b", "a"]);
     }
 
+    #[test]
+    fn weights_turns_and_passes_over_saturated_tenants() {
+        let scheduler = TaskScheduler::new();
+        for _ in 0..4 {
+            scheduler.enqueue(build_task("enterprise", TaskPriority::Normal));
+            scheduler.enqueue(build_task("free", TaskPriority::Normal));
+            scheduler.enqueue(build_task("busy", TaskPriority::Normal));
+        }
+
+        let weight = |tenant: &str| match tenant {
+            "enterprise" => Some(3),
+            "busy" => None,
+            _ => Some(1),
+        };
+        let order: Vec<String> = (0..6)
+            .filter_map(|_| scheduler.next_task_weighted(weight))
+            .map(|task| task.tenant_id)
+            .collect();
+
+        assert_eq!(
+            order,
+            vec![
+                "enterprise",
+                "enterprise",
+                "enterprise",
+                "free",
+                "enterprise",
+                "free"
+            ]
+        );
+        assert_eq!(scheduler.pending_for_tenant("busy"), 4);
+    }
+
     #[test]
     fn respects_priority_within_tenant() {
         let scheduler = TaskScheduler::new();
@@ -138,4 +319,22 @@ mod tests {
         let first = scheduler.next_task().unwrap();
         assert_eq!(first.id, high.id);
     }
+
+    #[test]
+    fn holds_future_tasks_until_due() {
+        let scheduler = TaskScheduler::new();
+        let later = Utc::now() + chrono::Duration::milliseconds(30);
+        let delayed = ExecutionTask::builder("tenant")
+            .scheduled_for(later)
+            .build();
+        scheduler.enqueue(delayed.clone());
+
+        assert!(scheduler.next_task().is_none());
+        assert_eq!(scheduler.pending(), 1);
+        assert_eq!(scheduler.next_due_at(), Some(later));
+
+        std::thread::sleep(std::time::Duration::from_millis(40));
+        assert_eq!(scheduler.next_task().map(|task| task.id), Some(delayed.id));
+        assert_eq!(scheduler.pending(), 0);
+    }
 }
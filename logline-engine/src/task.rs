@@ -2,6 +2,8 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::retry::{RetryPolicy, TaskFailure};
+
 /// Runtime execution priority. Lower is more urgent.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum TaskPriority {
@@ -22,34 +24,61 @@ impl Default for TaskPriority {
 pub struct ExecutionTask {
     pub id: Uuid,
     pub tenant_id: String,
+    /// Selects the handler in a [`crate::registry::TaskRegistry`]; tasks
+    /// without one go to its fallback handler.
+    #[serde(default)]
+    pub kind: Option<String>,
     pub payload: serde_json::Value,
     pub priority: TaskPriority,
     pub scheduled_for: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
     pub metadata: Option<serde_json::Value>,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// Longest a single attempt may run before it is aborted and recorded as
+    /// a failure.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Client-chosen key; resubmitting a task with the same key returns the
+    /// original task instead of queueing another one.
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
 }
 
 impl ExecutionTask {
     pub fn builder(tenant_id: impl Into<String>) -> ExecutionTaskBuilder {
         ExecutionTaskBuilder {
             tenant_id: tenant_id.into(),
+            kind: None,
             payload: serde_json::Value::Null,
             priority: TaskPriority::Normal,
             scheduled_for: Utc::now(),
             metadata: None,
+            retry: RetryPolicy::default(),
+            timeout: None,
+            idempotency_key: None,
         }
     }
 }
 
 pub struct ExecutionTaskBuilder {
     tenant_id: String,
+    kind: Option<String>,
     payload: serde_json::Value,
     priority: TaskPriority,
     scheduled_for: DateTime<Utc>,
     metadata: Option<serde_json::Value>,
+    retry: RetryPolicy,
+    timeout: Option<std::time::Duration>,
+    idempotency_key: Option<String>,
 }
 
 impl ExecutionTaskBuilder {
+    pub fn kind(mut self, kind: impl Into<String>) -> Self {
+        self.kind = Some(kind.into());
+        self
+    }
+
     pub fn payload(mut self, payload: serde_json::Value) -> Self {
         self.payload = payload;
         self
@@ -70,15 +99,36 @@ impl ExecutionTaskBuilder {
         self
     }
 
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn idempotency_key(mut self, key: impl Into<String>) -> Self {
+        self.idempotency_key = Some(key.into());
+        self
+    }
+
     pub fn build(self) -> ExecutionTask {
         ExecutionTask {
             id: Uuid::new_v4(),
             tenant_id: self.tenant_id,
+            kind: self.kind,
             payload: self.payload,
             priority: self.priority,
             scheduled_for: self.scheduled_for,
             created_at: Utc::now(),
             metadata: self.metadata,
+            retry: self.retry,
+            timeout_ms: self
+                .timeout
+                .map(|timeout| u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX)),
+            idempotency_key: self.idempotency_key,
         }
     }
 }
@@ -89,10 +139,36 @@ pub enum TaskStatus {
     Queued,
     Running,
     Completed,
+    /// Failed for good, either fatally or after the retry policy ran out.
+    /// Such tasks form the tenant's dead-letter list.
     Failed,
     Cancelled,
 }
 
+impl TaskStatus {
+    /// Lowercase name used when persisting the status.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskStatus::Queued => "queued",
+            TaskStatus::Running => "running",
+            TaskStatus::Completed => "completed",
+            TaskStatus::Failed => "failed",
+            TaskStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "queued" => Some(TaskStatus::Queued),
+            "running" => Some(TaskStatus::Running),
+            "completed" => Some(TaskStatus::Completed),
+            "failed" => Some(TaskStatus::Failed),
+            "cancelled" => Some(TaskStatus::Cancelled),
+            _ => None,
+        }
+    }
+}
+
 /// Outcome of a completed execution.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionOutcome {
@@ -122,6 +198,18 @@ impl ExecutionOutcome {
         }
     }
 
+    pub fn cancelled(task: &ExecutionTask, started_at: DateTime<Utc>) -> Self {
+        Self {
+            task_id: task.id,
+            tenant_id: task.tenant_id.clone(),
+            status: TaskStatus::Cancelled,
+            started_at: Some(started_at),
+            finished_at: Some(Utc::now()),
+            result: None,
+            error: None,
+        }
+    }
+
     pub fn failure(
         task: &ExecutionTask,
         started_at: DateTime<Utc>,
@@ -148,6 +236,23 @@ pub struct TaskRecord {
     pub finished_at: Option<DateTime<Utc>>,
     pub last_error: Option<String>,
     pub result: Option<serde_json::Value>,
+    /// Attempts started so far, counting the one in progress.
+    #[serde(default)]
+    pub attempts: u32,
+    /// Every failed attempt, oldest first.
+    #[serde(default)]
+    pub error_history: Vec<TaskFailure>,
+    /// Set when a running task is asked to stop; its worker aborts it at the
+    /// next lease renewal.
+    #[serde(default)]
+    pub cancel_requested: bool,
+    /// Worker currently holding the task while it is running.
+    #[serde(default)]
+    pub lease_owner: Option<String>,
+    /// When the running task is considered abandoned and handed out again,
+    /// unless its worker renews the lease first.
+    #[serde(default)]
+    pub lease_expires_at: Option<DateTime<Utc>>,
 }
 
 impl TaskRecord {
@@ -159,6 +264,11 @@ impl TaskRecord {
             finished_at: None,
             last_error: None,
             result: None,
+            attempts: 0,
+            error_history: Vec::new(),
+            cancel_requested: false,
+            lease_owner: None,
+            lease_expires_at: None,
         }
     }
 }
@@ -0,0 +1,94 @@
+use async_trait::async_trait;
+use logline_protocol::timeline::Span;
+use thiserror::Error;
+use url::Url;
+
+/// Destination for the spans the engine records about its own work.
+#[async_trait]
+pub trait SpanRecorder: Send + Sync + 'static {
+    async fn record(&self, span: Span) -> Result<(), TimelineClientError>;
+}
+
+/// Typed HTTP client used by the engine to append spans to the
+/// `logline-timeline` service.
+#[derive(Clone)]
+pub struct TimelineClient {
+    http: reqwest::Client,
+    base_url: Url,
+}
+
+impl TimelineClient {
+    /// Creates a new client bound to the provided base URL.
+    pub fn new(base_url: &str) -> Result<Self, TimelineClientError> {
+        let mut url = Url::parse(base_url).map_err(|err| TimelineClientError::InvalidUrl {
+            url: base_url.to_string(),
+            source: err,
+        })?;
+
+        if !url.path().ends_with('/') {
+            let mut path = url.path().trim_end_matches('/').to_string();
+            path.push('/');
+            url.set_path(&path);
+        }
+
+        Ok(Self {
+            http: reqwest::Client::new(),
+            base_url: url,
+        })
+    }
+
+    /// Returns the configured base URL as a string reference.
+    pub fn base_url(&self) -> &Url {
+        &self.base_url
+    }
+}
+
+#[async_trait]
+impl SpanRecorder for TimelineClient {
+    async fn record(&self, span: Span) -> Result<(), TimelineClientError> {
+        let tenant_id = span
+            .tenant_id
+            .clone()
+            .ok_or(TimelineClientError::MissingTenant)?;
+        let url =
+            self.base_url
+                .join("v1/spans")
+                .map_err(|err| TimelineClientError::InvalidUrl {
+                    url: format!("{}/v1/spans", self.base_url),
+                    source: err,
+                })?;
+
+        let response = self
+            .http
+            .post(url)
+            .header("X-Tenant-ID", tenant_id)
+            .json(&span)
+            .send()
+            .await
+            .map_err(|err| TimelineClientError::Http(err.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(TimelineClientError::UnexpectedStatus {
+                status: response.status(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Errors produced when recording spans on the timeline.
+#[derive(Debug, Error)]
+pub enum TimelineClientError {
+    #[error("invalid timeline service url {url}: {source}")]
+    InvalidUrl {
+        url: String,
+        #[source]
+        source: url::ParseError,
+    },
+    #[error("span has no tenant")]
+    MissingTenant,
+    #[error("timeline HTTP request failed: {0}")]
+    Http(String),
+    #[error("timeline service returned unexpected status {status}")]
+    UnexpectedStatus { status: reqwest::StatusCode },
+}
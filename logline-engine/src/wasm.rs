@@ -0,0 +1,594 @@
+//! Tenant-supplied WebAssembly task handlers.
+//!
+//! Tenants upload modules to a [`WasmModuleStore`], where each one is keyed by
+//! the SHA-256 of its bytes, and submit tasks of kind [`WASM_KIND`] naming the
+//! module to run. The module is executed by a [`WasmExecutor`] under
+//! [`WasmLimits`] and only sees the host API described on that trait;
+//! [`crate::wasm_executor::WasmtimeExecutor`] is the executor the service uses.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::error::EngineError;
+use crate::handlers::PERMANENT_ERROR_PREFIX;
+use crate::registry::{FieldType, PayloadSchema, TaskRegistry};
+use crate::runtime::TaskHandler;
+use crate::task::ExecutionTask;
+
+/// Runs a tenant module; see [`WasmTaskHandler`].
+pub const WASM_KIND: &str = "wasm.run";
+
+/// Every WebAssembly binary starts with `\0asm` followed by the version.
+const WASM_MAGIC: &[u8] = b"\0asm";
+
+/// Resources a single module invocation may use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WasmLimits {
+    /// Instructions budget; execution traps once it is spent.
+    #[serde(default = "default_fuel")]
+    pub fuel: u64,
+    /// Wall-clock limit of an invocation.
+    #[serde(default = "default_wasm_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Largest linear memory a module may grow to.
+    #[serde(default = "default_max_memory_bytes")]
+    pub max_memory_bytes: usize,
+    /// Largest module accepted by the store.
+    #[serde(default = "default_max_module_bytes")]
+    pub max_module_bytes: usize,
+}
+
+fn default_fuel() -> u64 {
+    100_000_000
+}
+
+fn default_wasm_timeout_ms() -> u64 {
+    5_000
+}
+
+fn default_max_memory_bytes() -> usize {
+    64 * 1024 * 1024
+}
+
+fn default_max_module_bytes() -> usize {
+    8 * 1024 * 1024
+}
+
+impl Default for WasmLimits {
+    fn default() -> Self {
+        Self {
+            fuel: default_fuel(),
+            timeout_ms: default_wasm_timeout_ms(),
+            max_memory_bytes: default_max_memory_bytes(),
+            max_module_bytes: default_max_module_bytes(),
+        }
+    }
+}
+
+/// Description of a stored module.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WasmModule {
+    pub tenant_id: String,
+    /// Lowercase hex SHA-256 of the module bytes.
+    pub hash: String,
+    pub size_bytes: usize,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+struct StoredModule {
+    module: WasmModule,
+    bytes: Arc<[u8]>,
+}
+
+/// Modules uploaded by each tenant, keyed by content hash. Uploading the same
+/// bytes twice keeps the first upload.
+///
+/// A store opened on a directory keeps each module in
+/// `<dir>/<hex tenant>/<hash>.wasm`, so modules survive restarts and are
+/// shared by engines mounting the same directory; memory then only caches
+/// module bytes.
+#[derive(Clone, Default)]
+pub struct WasmModuleStore {
+    modules: Arc<RwLock<HashMap<(String, String), StoredModule>>>,
+    directory: Option<Arc<Path>>,
+    max_module_bytes: usize,
+}
+
+impl WasmModuleStore {
+    /// Store keeping modules in memory only.
+    pub fn new(limits: &WasmLimits) -> Self {
+        Self {
+            modules: Arc::default(),
+            directory: None,
+            max_module_bytes: limits.max_module_bytes,
+        }
+    }
+
+    /// Store persisting modules under `directory`, which is created if
+    /// missing.
+    pub fn open(directory: impl Into<PathBuf>, limits: &WasmLimits) -> io::Result<Self> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+        Ok(Self {
+            directory: Some(Arc::from(directory)),
+            ..Self::new(limits)
+        })
+    }
+
+    pub fn put(&self, tenant: &str, bytes: &[u8]) -> Result<WasmModule, WasmError> {
+        if tenant.trim().is_empty() {
+            return Err(WasmError::InvalidModule("tenant is empty".into()));
+        }
+        if bytes.len() > self.max_module_bytes {
+            return Err(WasmError::InvalidModule(format!(
+                "module is {} bytes, the limit is {}",
+                bytes.len(),
+                self.max_module_bytes
+            )));
+        }
+        if !bytes.starts_with(WASM_MAGIC) {
+            return Err(WasmError::InvalidModule("not a WebAssembly binary".into()));
+        }
+
+        let hash = content_hash(bytes);
+        if let Some(path) = self.module_path(tenant, &hash) {
+            if !path.exists() {
+                write_atomically(&path, bytes)
+                    .map_err(|err| WasmError::Storage(err.to_string()))?;
+            }
+            return self
+                .get(tenant, &hash)
+                .ok_or_else(|| WasmError::Storage(format!("module {} vanished", hash)));
+        }
+        let mut modules = self.modules.write();
+        let entry = modules
+            .entry((tenant.to_string(), hash.clone()))
+            .or_insert_with(|| StoredModule {
+                module: WasmModule {
+                    tenant_id: tenant.to_string(),
+                    hash,
+                    size_bytes: bytes.len(),
+                    uploaded_at: Utc::now(),
+                },
+                bytes: Arc::from(bytes),
+            });
+        Ok(entry.module.clone())
+    }
+
+    pub fn get(&self, tenant: &str, hash: &str) -> Option<WasmModule> {
+        if let Some(path) = self.module_path(tenant, hash) {
+            return stored_module(tenant, hash, &path);
+        }
+        self.modules
+            .read()
+            .get(&(tenant.to_string(), hash.to_string()))
+            .map(|stored| stored.module.clone())
+    }
+
+    pub fn bytes(&self, tenant: &str, hash: &str) -> Option<Arc<[u8]>> {
+        let key = (tenant.to_string(), hash.to_string());
+        let Some(path) = self.module_path(tenant, hash) else {
+            return self
+                .modules
+                .read()
+                .get(&key)
+                .map(|stored| stored.bytes.clone());
+        };
+        // The file is checked even when cached, as another engine sharing
+        // the directory may have removed the module.
+        let module = stored_module(tenant, hash, &path)?;
+        if let Some(stored) = self.modules.read().get(&key) {
+            return Some(stored.bytes.clone());
+        }
+        let bytes: Arc<[u8]> = Arc::from(fs::read(&path).ok()?);
+        if content_hash(&bytes) != hash {
+            warn!(tenant, hash, path = %path.display(), "stored module does not match its hash");
+            return None;
+        }
+        self.modules.write().insert(
+            key,
+            StoredModule {
+                module,
+                bytes: bytes.clone(),
+            },
+        );
+        Some(bytes)
+    }
+
+    pub fn list_for_tenant(&self, tenant: &str) -> Vec<WasmModule> {
+        let mut modules: Vec<WasmModule> = match &self.directory {
+            Some(directory) => fs::read_dir(directory.join(hex::encode(tenant)))
+                .into_iter()
+                .flatten()
+                .filter_map(|entry| {
+                    let path = entry.ok()?.path();
+                    let hash = path.file_name()?.to_str()?.strip_suffix(".wasm")?;
+                    stored_module(tenant, hash, &path)
+                })
+                .collect(),
+            None => self
+                .modules
+                .read()
+                .values()
+                .filter(|stored| stored.module.tenant_id == tenant)
+                .map(|stored| stored.module.clone())
+                .collect(),
+        };
+        modules.sort_by_key(|module| module.uploaded_at);
+        modules
+    }
+
+    pub fn remove(&self, tenant: &str, hash: &str) -> Option<WasmModule> {
+        let cached = self
+            .modules
+            .write()
+            .remove(&(tenant.to_string(), hash.to_string()))
+            .map(|stored| stored.module);
+        let Some(path) = self.module_path(tenant, hash) else {
+            return cached;
+        };
+        let module = stored_module(tenant, hash, &path)?;
+        fs::remove_file(&path).ok()?;
+        Some(module)
+    }
+
+    /// File `hash` of `tenant` is kept in, when the store is persistent.
+    /// Tenants are hex-encoded so they cannot escape the directory, and
+    /// hashes that are not hex name no file at all.
+    fn module_path(&self, tenant: &str, hash: &str) -> Option<PathBuf> {
+        let directory = self.directory.as_ref()?;
+        let hash = if hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            hash
+        } else {
+            "invalid"
+        };
+        Some(
+            directory
+                .join(hex::encode(tenant))
+                .join(format!("{}.wasm", hash)),
+        )
+    }
+}
+
+/// Description of the module file at `path`, if there is one.
+fn stored_module(tenant: &str, hash: &str, path: &Path) -> Option<WasmModule> {
+    let metadata = fs::metadata(path).ok()?;
+    Some(WasmModule {
+        tenant_id: tenant.to_string(),
+        hash: hash.to_string(),
+        size_bytes: metadata.len() as usize,
+        uploaded_at: metadata
+            .modified()
+            .map(DateTime::from)
+            .unwrap_or_else(|_| Utc::now()),
+    })
+}
+
+/// Writes through a temporary file so readers never see a partial module.
+fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let partial = path.with_extension(format!("{}.partial", Uuid::new_v4()));
+    fs::write(&partial, bytes)?;
+    fs::rename(&partial, path).inspect_err(|_| {
+        let _ = fs::remove_file(&partial);
+    })
+}
+
+/// Lowercase hex SHA-256 of `bytes`, the key modules are stored under.
+pub fn content_hash(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// A module invocation handed to a [`WasmExecutor`].
+pub struct WasmInvocation {
+    pub tenant_id: String,
+    pub module_hash: String,
+    pub module: Arc<[u8]>,
+    /// JSON-encoded input the module reads through the host API.
+    pub input: Vec<u8>,
+    pub limits: WasmLimits,
+    /// Fired when the task is cancelled or its time limit passes.
+    pub cancel: CancellationToken,
+}
+
+/// What a module produced.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WasmOutput {
+    /// Bytes passed to `set_result`; parsed as JSON when possible.
+    pub result: Vec<u8>,
+    /// Lines passed to `log`.
+    pub logs: Vec<String>,
+    pub fuel_consumed: u64,
+}
+
+/// Virtual machine running tenant modules.
+///
+/// Implementations must link nothing but the `logline` host module:
+///
+/// * `input_len() -> i32` and `read_input(ptr: i32)` copy the task input into
+///   the module's memory;
+/// * `set_result(ptr: i32, len: i32)` records the output;
+/// * `log(ptr: i32, len: i32)` appends a UTF-8 log line.
+///
+/// The module exports `memory` and a `run()` entry point. Modules importing
+/// anything else, or without those exports, are rejected with
+/// [`WasmError::InvalidModule`]. Executors enforce the fuel and memory limits
+/// and stop when `cancel` fires.
+#[async_trait]
+pub trait WasmExecutor: Send + Sync + 'static {
+    async fn execute(&self, invocation: WasmInvocation) -> Result<WasmOutput, WasmError>;
+}
+
+/// Errors from storing or running modules.
+#[derive(Debug, Error)]
+pub enum WasmError {
+    #[error("invalid module: {0}")]
+    InvalidModule(String),
+    #[error("module {0} not found")]
+    ModuleNotFound(String),
+    #[error("fuel limit of {0} exhausted")]
+    FuelExhausted(u64),
+    #[error("memory limit of {0} bytes exceeded")]
+    MemoryLimit(usize),
+    #[error("module trapped: {0}")]
+    Trap(String),
+    #[error("module did not finish within {0:?}")]
+    TimedOut(Duration),
+    #[error("module execution cancelled")]
+    Cancelled,
+    #[error("module storage failed: {0}")]
+    Storage(String),
+}
+
+impl WasmError {
+    /// Whether running the module again could give a different outcome.
+    fn is_retryable(&self) -> bool {
+        matches!(self, WasmError::TimedOut(_) | WasmError::Cancelled)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct WasmRequest {
+    module: String,
+    #[serde(default)]
+    input: Value,
+}
+
+/// Runs the tenant module named by the payload's `module` hash with the
+/// payload's `input`, and returns its result and log lines.
+pub struct WasmTaskHandler {
+    store: WasmModuleStore,
+    executor: Arc<dyn WasmExecutor>,
+    limits: WasmLimits,
+}
+
+impl WasmTaskHandler {
+    pub fn new(
+        store: WasmModuleStore,
+        executor: Arc<dyn WasmExecutor>,
+        limits: WasmLimits,
+    ) -> Self {
+        Self {
+            store,
+            executor,
+            limits,
+        }
+    }
+
+    pub fn schema() -> PayloadSchema {
+        PayloadSchema::new()
+            .required("module", FieldType::String)
+            .optional("input", FieldType::Any)
+    }
+}
+
+#[async_trait]
+impl TaskHandler for WasmTaskHandler {
+    async fn handle(&self, task: ExecutionTask) -> Result<Value, String> {
+        self.handle_with_cancellation(task, CancellationToken::new())
+            .await
+    }
+
+    async fn handle_with_cancellation(
+        &self,
+        task: ExecutionTask,
+        cancel: CancellationToken,
+    ) -> Result<Value, String> {
+        let request: WasmRequest = serde_json::from_value(task.payload)
+            .map_err(|err| format!("{}invalid wasm payload: {}", PERMANENT_ERROR_PREFIX, err))?;
+        let module = self
+            .store
+            .bytes(&task.tenant_id, &request.module)
+            .ok_or_else(|| {
+                format!(
+                    "{}{}",
+                    PERMANENT_ERROR_PREFIX,
+                    WasmError::ModuleNotFound(request.module.clone())
+                )
+            })?;
+        let input = serde_json::to_vec(&request.input).map_err(|err| err.to_string())?;
+
+        let limit = Duration::from_millis(self.limits.timeout_ms);
+        let invocation = WasmInvocation {
+            tenant_id: task.tenant_id.clone(),
+            module_hash: request.module.clone(),
+            module,
+            input,
+            limits: self.limits,
+            cancel: cancel.child_token(),
+        };
+        let stop = invocation.cancel.clone();
+        let outcome = match tokio::time::timeout(limit, self.executor.execute(invocation)).await {
+            Ok(outcome) => outcome,
+            Err(_) => {
+                stop.cancel();
+                Err(WasmError::TimedOut(limit))
+            }
+        };
+
+        match outcome {
+            Ok(output) => {
+                for line in &output.logs {
+                    info!(tenant = %task.tenant_id, task_id = %task.id, module = %request.module, "{}", line);
+                }
+                let result = serde_json::from_slice(&output.result).unwrap_or_else(|_| {
+                    Value::String(String::from_utf8_lossy(&output.result).into_owned())
+                });
+                Ok(json!({
+                    "result": result,
+                    "logs": output.logs,
+                    "fuel_consumed": output.fuel_consumed,
+                }))
+            }
+            Err(err) if err.is_retryable() => Err(err.to_string()),
+            Err(err) => Err(format!("{}{}", PERMANENT_ERROR_PREFIX, err)),
+        }
+    }
+
+    fn validate(&self, task: &ExecutionTask) -> Result<(), EngineError> {
+        let module = task
+            .payload
+            .get("module")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        match self.store.get(&task.tenant_id, module) {
+            Some(_) => Ok(()),
+            None => Err(EngineError::Rejected(format!(
+                "module {} has not been uploaded",
+                module
+            ))),
+        }
+    }
+
+    fn is_retryable(&self, _task: &ExecutionTask, error: &str) -> bool {
+        !error.starts_with(PERMANENT_ERROR_PREFIX)
+    }
+}
+
+impl TaskRegistry {
+    /// Registers [`WASM_KIND`], running modules from `store` on `executor`.
+    pub fn with_wasm(
+        self,
+        store: WasmModuleStore,
+        executor: Arc<dyn WasmExecutor>,
+        limits: WasmLimits,
+    ) -> Self {
+        self.register_with_schema(
+            WASM_KIND,
+            Arc::new(WasmTaskHandler::new(store, executor, limits)),
+            WasmTaskHandler::schema(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Echoes its input and charges one unit of fuel per input byte.
+    struct EchoExecutor;
+
+    #[async_trait]
+    impl WasmExecutor for EchoExecutor {
+        async fn execute(&self, invocation: WasmInvocation) -> Result<WasmOutput, WasmError> {
+            let fuel = invocation.input.len() as u64;
+            if fuel > invocation.limits.fuel {
+                return Err(WasmError::FuelExhausted(invocation.limits.fuel));
+            }
+            Ok(WasmOutput {
+                result: invocation.input,
+                logs: vec![format!("ran {}", invocation.module_hash)],
+                fuel_consumed: fuel,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn stores_modules_by_hash_and_runs_them_within_limits() {
+        let limits = WasmLimits {
+            fuel: 16,
+            ..WasmLimits::default()
+        };
+        let store = WasmModuleStore::new(&limits);
+        let bytes = b"\0asm\x01\0\0\0";
+        let module = store.put("acme", bytes).unwrap();
+        assert_eq!(module.hash, content_hash(bytes));
+        assert_eq!(store.put("acme", bytes).unwrap(), module);
+        assert!(store.get("other", &module.hash).is_none());
+        assert!(matches!(
+            store.put("acme", b"not wasm"),
+            Err(WasmError::InvalidModule(_))
+        ));
+
+        let registry = TaskRegistry::new().with_wasm(store, Arc::new(EchoExecutor), limits);
+        let task = ExecutionTask::builder("acme")
+            .kind(WASM_KIND)
+            .payload(json!({ "module": module.hash, "input": { "n": 1 } }))
+            .build();
+        assert!(registry.validate(&task).is_ok());
+        let output = registry.handle(task).await.unwrap();
+        assert_eq!(output["result"], json!({ "n": 1 }));
+        assert_eq!(output["fuel_consumed"], 7);
+
+        let foreign = ExecutionTask::builder("other")
+            .kind(WASM_KIND)
+            .payload(json!({ "module": module.hash }))
+            .build();
+        assert!(registry.validate(&foreign).is_err());
+
+        let greedy = ExecutionTask::builder("acme")
+            .kind(WASM_KIND)
+            .payload(json!({ "module": module.hash, "input": "far too much input" }))
+            .build();
+        let error = registry.handle(greedy.clone()).await.unwrap_err();
+        assert!(error.contains("fuel"));
+        assert!(!registry.is_retryable(&greedy, &error));
+    }
+
+    #[test]
+    fn persists_modules_across_stores_sharing_a_directory() {
+        let directory = tempfile::tempdir().unwrap();
+        let limits = WasmLimits::default();
+        let bytes = b"\0asm\x01\0\0\0";
+        let module = WasmModuleStore::open(directory.path(), &limits)
+            .unwrap()
+            .put("../acme", bytes)
+            .unwrap();
+
+        let reopened = WasmModuleStore::open(directory.path(), &limits).unwrap();
+        assert_eq!(reopened.get("../acme", &module.hash), Some(module.clone()));
+        assert_eq!(
+            reopened.bytes("../acme", &module.hash).as_deref(),
+            Some(&bytes[..])
+        );
+        assert_eq!(reopened.list_for_tenant("../acme"), vec![module.clone()]);
+        assert!(reopened.get("acme", &module.hash).is_none());
+        assert!(reopened.get("../acme", "../../etc/passwd").is_none());
+
+        assert_eq!(
+            reopened.remove("../acme", &module.hash),
+            Some(module.clone())
+        );
+        assert!(WasmModuleStore::open(directory.path(), &limits)
+            .unwrap()
+            .bytes("../acme", &module.hash)
+            .is_none());
+    }
+}
@@ -0,0 +1,454 @@
+//! [`WasmExecutor`] running tenant modules on wasmtime.
+//!
+//! Every invocation gets its own store: fuel metering enforces
+//! [`WasmLimits::fuel`], a [`StoreLimits`] caps linear memory, and epoch
+//! interruption stops the module once its wall-clock limit passes or the
+//! task is cancelled. The linker only defines the `logline` host module.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use thiserror::Error;
+use wasmtime::{
+    Caller, Config, Engine, Extern, ExternType, InstancePre, Linker, Memory, ResourceLimiter,
+    Store, StoreLimits, StoreLimitsBuilder, Trap, UpdateDeadline,
+};
+
+use crate::wasm::{content_hash, WasmError, WasmExecutor, WasmInvocation, WasmLimits, WasmOutput};
+
+/// Interval of the epoch ticks that deadlines and cancellation are checked
+/// on.
+pub const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+/// Log lines kept per invocation; further `log` calls are ignored.
+pub const MAX_LOG_LINES: usize = 256;
+
+/// Compiled modules kept before the cache is cleared.
+const MAX_COMPILED_MODULES: usize = 64;
+
+/// Host module the linker defines, the only one modules may import.
+const HOST_MODULE: &str = "logline";
+
+/// State of one invocation, reachable from the host functions.
+struct HostState {
+    input: Vec<u8>,
+    result: Vec<u8>,
+    logs: Vec<String>,
+    limits: StoreLimits,
+    memory_limit_hit: bool,
+}
+
+/// Delegates to [`StoreLimits`], remembering refused growth so the trap
+/// can be reported as [`WasmError::MemoryLimit`].
+impl ResourceLimiter for HostState {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        let allowed = self.limits.memory_growing(current, desired, maximum);
+        if !matches!(allowed, Ok(true)) {
+            self.memory_limit_hit = true;
+        }
+        allowed
+    }
+
+    fn table_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        self.limits.table_growing(current, desired, maximum)
+    }
+
+    fn instances(&self) -> usize {
+        self.limits.instances()
+    }
+
+    fn tables(&self) -> usize {
+        self.limits.tables()
+    }
+
+    fn memories(&self) -> usize {
+        self.limits.memories()
+    }
+}
+
+/// Why the epoch callback stopped a module.
+#[derive(Debug, Error)]
+enum Interruption {
+    #[error("wall-clock limit reached")]
+    TimedOut,
+    #[error("cancelled")]
+    Cancelled,
+}
+
+/// Runs modules on a shared wasmtime engine whose epoch is advanced by a
+/// background thread every [`EPOCH_TICK`].
+pub struct WasmtimeExecutor {
+    engine: Engine,
+    linker: Arc<Linker<HostState>>,
+    compiled: Arc<RwLock<HashMap<String, InstancePre<HostState>>>>,
+    ticking: Arc<AtomicBool>,
+}
+
+impl WasmtimeExecutor {
+    pub fn new() -> anyhow::Result<Self> {
+        let mut config = Config::new();
+        config.consume_fuel(true).epoch_interruption(true);
+        let engine = Engine::new(&config)?;
+        let linker = Arc::new(host_linker(&engine)?);
+
+        let ticking = Arc::new(AtomicBool::new(true));
+        let ticker = engine.clone();
+        let running = ticking.clone();
+        thread::Builder::new()
+            .name("wasm-epoch".into())
+            .spawn(move || {
+                while running.load(Ordering::Relaxed) {
+                    thread::sleep(EPOCH_TICK);
+                    ticker.increment_epoch();
+                }
+            })?;
+
+        Ok(Self {
+            engine,
+            linker,
+            compiled: Arc::default(),
+            ticking,
+        })
+    }
+}
+
+impl Drop for WasmtimeExecutor {
+    fn drop(&mut self) {
+        self.ticking.store(false, Ordering::Relaxed);
+    }
+}
+
+#[async_trait]
+impl WasmExecutor for WasmtimeExecutor {
+    async fn execute(&self, invocation: WasmInvocation) -> Result<WasmOutput, WasmError> {
+        let engine = self.engine.clone();
+        let linker = self.linker.clone();
+        let compiled = self.compiled.clone();
+        tokio::task::spawn_blocking(move || {
+            let module = prepare(&engine, &linker, &compiled, &invocation.module)?;
+            run(&engine, &module, invocation)
+        })
+        .await
+        .map_err(|err| WasmError::Trap(err.to_string()))?
+    }
+}
+
+/// Compiles `bytes`, or reuses an earlier compilation of the same bytes,
+/// and checks its imports and exports against the host API.
+fn prepare(
+    engine: &Engine,
+    linker: &Linker<HostState>,
+    compiled: &RwLock<HashMap<String, InstancePre<HostState>>>,
+    bytes: &[u8],
+) -> Result<InstancePre<HostState>, WasmError> {
+    let hash = content_hash(bytes);
+    if let Some(module) = compiled.read().get(&hash) {
+        return Ok(module.clone());
+    }
+
+    let module = wasmtime::Module::new(engine, bytes)
+        .map_err(|err| WasmError::InvalidModule(format!("{:#}", err)))?;
+    if let Some(import) = module
+        .imports()
+        .find(|import| import.module() != HOST_MODULE)
+    {
+        return Err(WasmError::InvalidModule(format!(
+            "imports {}::{}; only the {} host module is available",
+            import.module(),
+            import.name(),
+            HOST_MODULE
+        )));
+    }
+    if !matches!(module.get_export("memory"), Some(ExternType::Memory(_))) {
+        return Err(WasmError::InvalidModule("does not export memory".into()));
+    }
+    match module.get_export("run") {
+        Some(ExternType::Func(run)) if run.params().len() == 0 && run.results().len() == 0 => {}
+        _ => return Err(WasmError::InvalidModule("does not export run()".into())),
+    }
+    let module = linker
+        .instantiate_pre(&module)
+        .map_err(|err| WasmError::InvalidModule(format!("{:#}", err)))?;
+
+    let mut compiled = compiled.write();
+    if compiled.len() >= MAX_COMPILED_MODULES {
+        compiled.clear();
+    }
+    compiled.insert(hash, module.clone());
+    Ok(module)
+}
+
+/// Instantiates `module` in a fresh store limited by the invocation and
+/// calls its `run` export.
+fn run(
+    engine: &Engine,
+    module: &InstancePre<HostState>,
+    invocation: WasmInvocation,
+) -> Result<WasmOutput, WasmError> {
+    let limits = invocation.limits;
+    if invocation.cancel.is_cancelled() {
+        return Err(WasmError::Cancelled);
+    }
+
+    let mut store = Store::new(
+        engine,
+        HostState {
+            input: invocation.input,
+            result: Vec::new(),
+            logs: Vec::new(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(limits.max_memory_bytes)
+                .instances(1)
+                .trap_on_grow_failure(true)
+                .build(),
+            memory_limit_hit: false,
+        },
+    );
+    store.limiter(|state| state);
+    store
+        .set_fuel(limits.fuel)
+        .map_err(|err| WasmError::Trap(err.to_string()))?;
+
+    let deadline = Instant::now() + Duration::from_millis(limits.timeout_ms);
+    let cancel = invocation.cancel;
+    store.set_epoch_deadline(1);
+    store.epoch_deadline_callback(move |_| {
+        if cancel.is_cancelled() {
+            Err(Interruption::Cancelled.into())
+        } else if Instant::now() >= deadline {
+            Err(Interruption::TimedOut.into())
+        } else {
+            Ok(UpdateDeadline::Continue(1))
+        }
+    });
+
+    let outcome = module.instantiate(&mut store).and_then(|instance| {
+        instance
+            .get_typed_func::<(), ()>(&mut store, "run")?
+            .call(&mut store, ())
+    });
+    let fuel_consumed = limits.fuel.saturating_sub(store.get_fuel().unwrap_or(0));
+    let state = store.into_data();
+    match outcome {
+        Ok(()) => Ok(WasmOutput {
+            result: state.result,
+            logs: state.logs,
+            fuel_consumed,
+        }),
+        Err(err) => Err(classify(err, state.memory_limit_hit, &limits)),
+    }
+}
+
+/// Maps the error a module stopped with to the limit it hit, if any.
+fn classify(err: anyhow::Error, memory_limit_hit: bool, limits: &WasmLimits) -> WasmError {
+    if let Some(interruption) = err.downcast_ref::<Interruption>() {
+        return match interruption {
+            Interruption::TimedOut => WasmError::TimedOut(Duration::from_millis(limits.timeout_ms)),
+            Interruption::Cancelled => WasmError::Cancelled,
+        };
+    }
+    if memory_limit_hit {
+        return WasmError::MemoryLimit(limits.max_memory_bytes);
+    }
+    match err.downcast_ref::<Trap>() {
+        Some(Trap::OutOfFuel) => WasmError::FuelExhausted(limits.fuel),
+        _ => WasmError::Trap(format!("{:#}", err)),
+    }
+}
+
+/// Linker defining the `logline` host module described on [`WasmExecutor`].
+fn host_linker(engine: &Engine) -> anyhow::Result<Linker<HostState>> {
+    let mut linker = Linker::new(engine);
+    linker.func_wrap(
+        HOST_MODULE,
+        "input_len",
+        |caller: Caller<'_, HostState>| -> i32 { caller.data().input.len() as i32 },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "read_input",
+        |mut caller: Caller<'_, HostState>, ptr: i32| -> anyhow::Result<()> {
+            let memory = guest_memory(&mut caller)?;
+            let (data, state) = memory.data_and_store_mut(&mut caller);
+            guest_range(data, ptr, state.input.len() as i32)?.copy_from_slice(&state.input);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "set_result",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> anyhow::Result<()> {
+            let memory = guest_memory(&mut caller)?;
+            let (data, state) = memory.data_and_store_mut(&mut caller);
+            state.result = guest_range(data, ptr, len)?.to_vec();
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "log",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> anyhow::Result<()> {
+            let memory = guest_memory(&mut caller)?;
+            let (data, state) = memory.data_and_store_mut(&mut caller);
+            let line = String::from_utf8_lossy(guest_range(data, ptr, len)?).into_owned();
+            if state.logs.len() < MAX_LOG_LINES {
+                state.logs.push(line);
+            }
+            Ok(())
+        },
+    )?;
+    Ok(linker)
+}
+
+fn guest_memory(caller: &mut Caller<'_, HostState>) -> anyhow::Result<Memory> {
+    caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| anyhow!("module does not export memory"))
+}
+
+/// The `len` bytes of guest memory at `ptr`; pointers and lengths are
+/// unsigned on the guest side.
+fn guest_range(data: &mut [u8], ptr: i32, len: i32) -> anyhow::Result<&mut [u8]> {
+    let start = ptr as u32 as usize;
+    let end = start + len as u32 as usize;
+    data.get_mut(start..end)
+        .ok_or_else(|| anyhow!("{} bytes at {} are out of bounds", len as u32, start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_util::sync::CancellationToken;
+
+    /// Echoes its input after logging a line, then burns `loops` iterations
+    /// and grows memory by `pages`.
+    fn module(loops: &str, pages: u32) -> Vec<u8> {
+        wat::parse_str(format!(
+            r#"(module
+                (import "logline" "input_len" (func $input_len (result i32)))
+                (import "logline" "read_input" (func $read_input (param i32)))
+                (import "logline" "set_result" (func $set_result (param i32 i32)))
+                (import "logline" "log" (func $log (param i32 i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "started")
+                (func (export "run") (local $i i32)
+                    (call $log (i32.const 0) (i32.const 7))
+                    (call $read_input (i32.const 16))
+                    (call $set_result (i32.const 16) (call $input_len))
+                    (if (i32.lt_s (memory.grow (i32.const {pages})) (i32.const 0))
+                        (then unreachable))
+                    (local.set $i (i32.const {loops}))
+                    (loop $again
+                        (local.set $i (i32.sub (local.get $i) (i32.const 1)))
+                        (br_if $again (i32.ne (local.get $i) (i32.const 0))))))"#
+        ))
+        .unwrap()
+    }
+
+    fn invocation(module: Vec<u8>, limits: WasmLimits) -> WasmInvocation {
+        WasmInvocation {
+            tenant_id: "acme".into(),
+            module_hash: content_hash(&module),
+            module: module.into(),
+            input: br#"{"n":1}"#.to_vec(),
+            limits,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_modules_within_their_limits() {
+        let executor = Arc::new(WasmtimeExecutor::new().unwrap());
+        let limits = WasmLimits {
+            fuel: 1_000_000,
+            timeout_ms: 5_000,
+            max_memory_bytes: 4 * 65_536,
+            ..WasmLimits::default()
+        };
+
+        let output = executor
+            .execute(invocation(module("10", 1), limits))
+            .await
+            .unwrap();
+        assert_eq!(output.result, br#"{"n":1}"#);
+        assert_eq!(output.logs, vec!["started".to_string()]);
+        assert!(output.fuel_consumed > 0);
+
+        let error = executor
+            .execute(invocation(module("-1", 0), limits))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error, WasmError::FuelExhausted(1_000_000)),
+            "{}",
+            error
+        );
+
+        let error = executor
+            .execute(invocation(module("10", 8), limits))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, WasmError::MemoryLimit(_)), "{}", error);
+
+        let endless = WasmLimits {
+            fuel: 1 << 62,
+            timeout_ms: 100,
+            ..limits
+        };
+        let error = executor
+            .execute(invocation(module("-1", 0), endless))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, WasmError::TimedOut(_)), "{}", error);
+
+        let cancelled = invocation(
+            module("-1", 0),
+            WasmLimits {
+                timeout_ms: 60_000,
+                ..endless
+            },
+        );
+        let cancel = cancelled.cancel.clone();
+        let running = tokio::spawn({
+            let executor = executor.clone();
+            async move { executor.execute(cancelled).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        cancel.cancel();
+        assert!(matches!(running.await.unwrap(), Err(WasmError::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn rejects_modules_importing_more_than_the_host_module() {
+        let executor = WasmtimeExecutor::new().unwrap();
+        let module = wat::parse_str(
+            r#"(module
+                (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+                (memory (export "memory") 1)
+                (func (export "run")))"#,
+        )
+        .unwrap();
+        let error = executor
+            .execute(invocation(module, WasmLimits::default()))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, WasmError::InvalidModule(_)), "{}", error);
+    }
+}
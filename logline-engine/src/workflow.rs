@@ -0,0 +1,750 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use logline_protocol::timeline::{Span, SpanBuilder, SpanStatus, SpanType};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+
+use crate::error::EngineError;
+use crate::retry::RetryPolicy;
+use crate::task::{ExecutionTask, TaskPriority, TaskRecord, TaskStatus};
+
+/// Identity recorded on the spans the engine writes about its own work.
+pub const ENGINE_LOGLINE_ID: &str = "logline-engine";
+
+/// How long a finished workflow is kept by default.
+pub const DEFAULT_WORKFLOW_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// One node of a workflow, run as an [`ExecutionTask`] once every step it
+/// depends on has finished.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowStep {
+    /// Unique within the workflow; used by `depends_on` and as the key of the
+    /// step's result in downstream payloads.
+    pub name: String,
+    #[serde(default)]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub payload: Value,
+    #[serde(default)]
+    pub priority: TaskPriority,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub metadata: Option<Value>,
+}
+
+impl WorkflowStep {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            kind: None,
+            depends_on: Vec::new(),
+            payload: Value::Null,
+            priority: TaskPriority::default(),
+            retry: RetryPolicy::default(),
+            timeout_ms: None,
+            metadata: None,
+        }
+    }
+
+    pub fn kind(mut self, kind: impl Into<String>) -> Self {
+        self.kind = Some(kind.into());
+        self
+    }
+
+    pub fn depends_on(mut self, step: impl Into<String>) -> Self {
+        self.depends_on.push(step.into());
+        self
+    }
+
+    pub fn payload(mut self, payload: Value) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    pub fn priority(mut self, priority: TaskPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout_ms = Some(u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX));
+        self
+    }
+
+    pub fn metadata(mut self, metadata: Value) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+}
+
+/// What happens to the rest of a workflow when a step fails or is cancelled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailurePolicy {
+    /// Skip every step that has not been released yet; steps already queued
+    /// run to completion.
+    #[default]
+    FailWorkflow,
+    /// Skip only the steps downstream of the failure; independent branches
+    /// keep running.
+    SkipDescendants,
+    /// Run downstream steps anyway, passing the upstream error in place of
+    /// its result.
+    Continue,
+}
+
+/// A DAG of steps submitted together.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Workflow {
+    pub id: Uuid,
+    pub tenant_id: String,
+    pub name: String,
+    pub steps: Vec<WorkflowStep>,
+    #[serde(default)]
+    pub on_failure: FailurePolicy,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Workflow {
+    pub fn builder(tenant_id: impl Into<String>, name: impl Into<String>) -> WorkflowBuilder {
+        WorkflowBuilder {
+            tenant_id: tenant_id.into(),
+            name: name.into(),
+            steps: Vec::new(),
+            on_failure: FailurePolicy::default(),
+        }
+    }
+
+    fn step_index(&self, name: &str) -> Option<usize> {
+        self.steps.iter().position(|step| step.name == name)
+    }
+
+    /// Checks that step names are unique, dependencies exist and the graph
+    /// has no cycle.
+    fn validate(&self) -> Result<(), EngineError> {
+        let invalid = |message: String| Err(EngineError::InvalidWorkflow(message));
+        if self.steps.is_empty() {
+            return invalid("workflow has no steps".into());
+        }
+
+        let mut names = HashSet::new();
+        for step in &self.steps {
+            if step.name.trim().is_empty() {
+                return invalid("step names must not be empty".into());
+            }
+            if !names.insert(step.name.as_str()) {
+                return invalid(format!("duplicate step `{}`", step.name));
+            }
+        }
+        for step in &self.steps {
+            for parent in &step.depends_on {
+                if !names.contains(parent.as_str()) {
+                    return invalid(format!(
+                        "step `{}` depends on unknown step `{}`",
+                        step.name, parent
+                    ));
+                }
+            }
+        }
+
+        // Kahn's algorithm: every step is visited only if the graph is acyclic.
+        let mut indegree: Vec<usize> = self
+            .steps
+            .iter()
+            .map(|step| step.depends_on.len())
+            .collect();
+        let mut ready: VecDeque<usize> = (0..self.steps.len())
+            .filter(|index| indegree[*index] == 0)
+            .collect();
+        let mut visited = 0;
+        while let Some(index) = ready.pop_front() {
+            visited += 1;
+            let name = &self.steps[index].name;
+            for (child, step) in self.steps.iter().enumerate() {
+                for _ in step.depends_on.iter().filter(|parent| *parent == name) {
+                    indegree[child] -= 1;
+                    if indegree[child] == 0 {
+                        ready.push_back(child);
+                    }
+                }
+            }
+        }
+        if visited != self.steps.len() {
+            return invalid("step dependencies form a cycle".into());
+        }
+        Ok(())
+    }
+}
+
+pub struct WorkflowBuilder {
+    tenant_id: String,
+    name: String,
+    steps: Vec<WorkflowStep>,
+    on_failure: FailurePolicy,
+}
+
+impl WorkflowBuilder {
+    pub fn step(mut self, step: WorkflowStep) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    pub fn on_failure(mut self, policy: FailurePolicy) -> Self {
+        self.on_failure = policy;
+        self
+    }
+
+    pub fn build(self) -> Result<Workflow, EngineError> {
+        let workflow = Workflow {
+            id: Uuid::new_v4(),
+            tenant_id: self.tenant_id,
+            name: self.name,
+            steps: self.steps,
+            on_failure: self.on_failure,
+            created_at: Utc::now(),
+        };
+        workflow.validate()?;
+        Ok(workflow)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkflowStatus {
+    Running,
+    /// Every step completed.
+    Completed,
+    /// Every step finished and at least one failed, was cancelled or skipped.
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    /// Waiting for the steps it depends on.
+    Pending,
+    /// Submitted as a task; see the task for its live status.
+    Queued,
+    Completed,
+    Failed,
+    Cancelled,
+    /// Never run because of the workflow's failure policy.
+    Skipped,
+}
+
+impl StepStatus {
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, StepStatus::Pending | StepStatus::Queued)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepState {
+    pub name: String,
+    pub status: StepStatus,
+    pub task_id: Option<Uuid>,
+    pub result: Option<Value>,
+    pub error: Option<String>,
+    /// Span recorded when the step finished.
+    pub span_id: Option<Uuid>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Progress of a submitted workflow.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowState {
+    pub workflow: Workflow,
+    pub status: WorkflowStatus,
+    /// In the order the steps were declared.
+    pub steps: Vec<StepState>,
+    /// Span recorded when the workflow was submitted; root of the step spans.
+    pub span_id: Uuid,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Tasks to submit and spans to record after a workflow made progress.
+#[derive(Debug, Default)]
+pub struct WorkflowProgress {
+    pub released: Vec<ExecutionTask>,
+    pub spans: Vec<Span>,
+}
+
+impl WorkflowState {
+    fn new(workflow: Workflow) -> Self {
+        let steps = workflow
+            .steps
+            .iter()
+            .map(|step| StepState {
+                name: step.name.clone(),
+                status: StepStatus::Pending,
+                task_id: None,
+                result: None,
+                error: None,
+                span_id: None,
+                finished_at: None,
+            })
+            .collect();
+        Self {
+            workflow,
+            status: WorkflowStatus::Running,
+            steps,
+            span_id: Uuid::new_v4(),
+            finished_at: None,
+        }
+    }
+
+    fn span(&self, title: String, status: SpanStatus, data: Value) -> Span {
+        let mut span = SpanBuilder::new(ENGINE_LOGLINE_ID, title)
+            .status(status)
+            .tenant_id(self.workflow.tenant_id.clone())
+            .span_type(SpanType::System)
+            .payload(data)
+            .build();
+        span.workflow_id = Some(self.workflow.id.to_string());
+        span.flow_id = Some(self.workflow.name.clone());
+        span
+    }
+
+    fn submitted_span(&self) -> Span {
+        let mut span = self.span(
+            format!("workflow {} submitted", self.workflow.name),
+            SpanStatus::Executed,
+            json!({
+                "workflow_id": self.workflow.id,
+                "steps": self.workflow.steps.iter().map(|step| &step.name).collect::<Vec<_>>(),
+                "on_failure": self.workflow.on_failure,
+            }),
+        );
+        span.id = self.span_id;
+        span
+    }
+
+    /// Span for a finished step, caused by its first upstream step (or the
+    /// workflow itself for roots) and related to the other upstream steps.
+    fn step_span(&self, index: usize, cause: Option<Uuid>) -> Span {
+        let state = &self.steps[index];
+        let status = match state.status {
+            StepStatus::Completed => SpanStatus::Executed,
+            StepStatus::Skipped => SpanStatus::Ghost,
+            _ => SpanStatus::Reverted,
+        };
+        let mut span = self.span(
+            format!("workflow {} step {}", self.workflow.name, state.name),
+            status,
+            json!({
+                "step": state.name,
+                "status": state.status,
+                "task_id": state.task_id,
+                "result": state.result,
+                "error": state.error,
+            }),
+        );
+        span.id = state.span_id.unwrap_or(span.id);
+
+        let mut parents = self.workflow.steps[index]
+            .depends_on
+            .iter()
+            .filter_map(|parent| self.workflow.step_index(parent))
+            .filter_map(|parent| self.steps[parent].span_id);
+        span.caused_by = cause.or_else(|| parents.next()).or(Some(self.span_id));
+        for parent in parents {
+            span.relate_to(parent.to_string());
+        }
+        span
+    }
+
+    /// Builds the task for a step, passing upstream results in the payload
+    /// under `upstream`.
+    fn instantiate(&self, index: usize) -> ExecutionTask {
+        let step = &self.workflow.steps[index];
+        let mut payload = step.payload.clone();
+        if !step.depends_on.is_empty() {
+            let upstream: Map<String, Value> = step
+                .depends_on
+                .iter()
+                .filter_map(|parent| {
+                    let state = &self.steps[self.workflow.step_index(parent)?];
+                    let value = match state.status {
+                        StepStatus::Completed => state.result.clone().unwrap_or(Value::Null),
+                        _ => json!({ "status": state.status, "error": state.error }),
+                    };
+                    Some((parent.clone(), value))
+                })
+                .collect();
+            payload = match payload {
+                Value::Object(mut map) => {
+                    map.insert("upstream".into(), Value::Object(upstream));
+                    Value::Object(map)
+                }
+                Value::Null => json!({ "upstream": upstream }),
+                other => json!({ "input": other, "upstream": upstream }),
+            };
+        }
+
+        let link = json!({
+            "id": self.workflow.id,
+            "name": self.workflow.name,
+            "step": step.name,
+        });
+        let metadata = match step.metadata.clone() {
+            Some(Value::Object(mut map)) => {
+                map.insert("workflow".into(), link);
+                Value::Object(map)
+            }
+            Some(other) => json!({ "workflow": link, "user": other }),
+            None => json!({ "workflow": link }),
+        };
+
+        let mut builder = ExecutionTask::builder(&self.workflow.tenant_id)
+            .payload(payload)
+            .priority(step.priority)
+            .retry(step.retry.clone())
+            .metadata(metadata);
+        if let Some(kind) = &step.kind {
+            builder = builder.kind(kind.clone());
+        }
+        if let Some(timeout_ms) = step.timeout_ms {
+            builder = builder.timeout(std::time::Duration::from_millis(timeout_ms));
+        }
+        builder.build()
+    }
+
+    /// Releases pending steps whose dependencies allow them to run.
+    fn release_ready(&mut self, progress: &mut WorkflowProgress) {
+        for index in 0..self.steps.len() {
+            if self.steps[index].status != StepStatus::Pending {
+                continue;
+            }
+            let ready = self.workflow.steps[index].depends_on.iter().all(|parent| {
+                self.workflow
+                    .step_index(parent)
+                    .map(|parent| self.steps[parent].status)
+                    .is_some_and(|status| match self.workflow.on_failure {
+                        FailurePolicy::Continue => status.is_finished(),
+                        _ => status == StepStatus::Completed,
+                    })
+            });
+            if ready {
+                let task = self.instantiate(index);
+                self.steps[index].status = StepStatus::Queued;
+                self.steps[index].task_id = Some(task.id);
+                progress.released.push(task);
+            }
+        }
+    }
+
+    /// Indices of every step downstream of `index`.
+    fn descendants(&self, index: usize) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut queue = VecDeque::from([index]);
+        while let Some(current) = queue.pop_front() {
+            let name = &self.workflow.steps[current].name;
+            for (child, step) in self.workflow.steps.iter().enumerate() {
+                if step.depends_on.contains(name) && !found.contains(&child) {
+                    found.push(child);
+                    queue.push_back(child);
+                }
+            }
+        }
+        found
+    }
+
+    fn skip(&mut self, index: usize, cause: Uuid, progress: &mut WorkflowProgress) {
+        let state = &mut self.steps[index];
+        state.status = StepStatus::Skipped;
+        state.span_id = Some(Uuid::new_v4());
+        state.finished_at = Some(Utc::now());
+        progress.spans.push(self.step_span(index, Some(cause)));
+    }
+
+    fn finish_step(&mut self, index: usize, record: &TaskRecord, progress: &mut WorkflowProgress) {
+        let status = match record.status {
+            TaskStatus::Completed => StepStatus::Completed,
+            TaskStatus::Failed => StepStatus::Failed,
+            TaskStatus::Cancelled => StepStatus::Cancelled,
+            TaskStatus::Queued | TaskStatus::Running => return,
+        };
+        let state = &mut self.steps[index];
+        if state.status == status {
+            return;
+        }
+        state.status = status;
+        state.result = record.result.clone();
+        state.error = record.last_error.clone();
+        state.span_id = Some(Uuid::new_v4());
+        state.finished_at = Some(record.finished_at.unwrap_or_else(Utc::now));
+        let span = self.step_span(index, None);
+        let cause = span.id;
+        progress.spans.push(span);
+
+        if status != StepStatus::Completed {
+            let skipped = match self.workflow.on_failure {
+                FailurePolicy::FailWorkflow => (0..self.steps.len()).collect(),
+                FailurePolicy::SkipDescendants => self.descendants(index),
+                FailurePolicy::Continue => Vec::new(),
+            };
+            for index in skipped {
+                if self.steps[index].status == StepStatus::Pending {
+                    self.skip(index, cause, progress);
+                }
+            }
+        }
+
+        self.release_ready(progress);
+        if self.steps.iter().all(|step| step.status.is_finished()) {
+            self.status = if self
+                .steps
+                .iter()
+                .all(|step| step.status == StepStatus::Completed)
+            {
+                WorkflowStatus::Completed
+            } else {
+                WorkflowStatus::Failed
+            };
+            self.finished_at = Some(Utc::now());
+        } else {
+            self.status = WorkflowStatus::Running;
+            self.finished_at = None;
+        }
+    }
+}
+
+/// The task of every step as it would be released, with placeholders for
+/// upstream results; used to validate a workflow before submitting it.
+pub(crate) fn preview_tasks(workflow: &Workflow) -> Vec<ExecutionTask> {
+    let state = WorkflowState::new(workflow.clone());
+    (0..workflow.steps.len())
+        .map(|index| state.instantiate(index))
+        .collect()
+}
+
+/// Workflow id and step name a task was released for, if any.
+pub fn workflow_step(task: &ExecutionTask) -> Option<(Uuid, String)> {
+    let link = task.metadata.as_ref()?.get("workflow")?;
+    let id = link.get("id")?.as_str()?.parse().ok()?;
+    let step = link.get("step")?.as_str()?.to_string();
+    Some((id, step))
+}
+
+/// Coordinates submitted workflows, releasing each step's task once its
+/// dependencies finish.
+///
+/// Workflows live in this process's memory: they are lost on restart, and
+/// only the steps finished by this process's workers move them forward.
+/// Finished workflows are forgotten once they are older than the retention.
+#[derive(Clone)]
+pub struct WorkflowTracker {
+    workflows: Arc<RwLock<HashMap<Uuid, WorkflowState>>>,
+    retention: Duration,
+}
+
+impl Default for WorkflowTracker {
+    fn default() -> Self {
+        Self::with_retention(DEFAULT_WORKFLOW_RETENTION)
+    }
+}
+
+impl WorkflowTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_retention(retention: Duration) -> Self {
+        Self {
+            workflows: Arc::default(),
+            retention,
+        }
+    }
+
+    /// Forgets the workflows that finished before the retention window.
+    fn evict_finished(&self, now: DateTime<Utc>) {
+        let Some(cutoff) = chrono::Duration::from_std(self.retention)
+            .ok()
+            .and_then(|retention| now.checked_sub_signed(retention))
+        else {
+            return;
+        };
+        self.workflows
+            .write()
+            .retain(|_, state| state.finished_at.is_none_or(|finished| finished >= cutoff));
+    }
+
+    /// Registers `workflow`, returning its root tasks and submission span.
+    /// Evicts the finished workflows past their retention on the way.
+    pub fn start(&self, workflow: Workflow) -> WorkflowProgress {
+        self.evict_finished(Utc::now());
+        let mut state = WorkflowState::new(workflow);
+        let mut progress = WorkflowProgress {
+            released: Vec::new(),
+            spans: vec![state.submitted_span()],
+        };
+        state.release_ready(&mut progress);
+        self.workflows.write().insert(state.workflow.id, state);
+        progress
+    }
+
+    /// Records the outcome of a step's task and releases the steps it
+    /// unblocks. Tasks that are not part of a workflow, or have not finished,
+    /// make no progress.
+    pub fn step_finished(&self, record: &TaskRecord) -> WorkflowProgress {
+        let mut progress = WorkflowProgress::default();
+        let Some((workflow_id, step)) = workflow_step(&record.task) else {
+            return progress;
+        };
+        let mut workflows = self.workflows.write();
+        let Some(state) = workflows.get_mut(&workflow_id) else {
+            return progress;
+        };
+        let Some(index) = state.workflow.step_index(&step) else {
+            return progress;
+        };
+        if state.steps[index].task_id == Some(record.task.id) {
+            state.finish_step(index, record, &mut progress);
+        }
+        progress
+    }
+
+    /// Fails the step `task` was released for when it could not be
+    /// submitted, applying the workflow's failure policy.
+    pub fn step_rejected(&self, task: &ExecutionTask, error: &EngineError) -> WorkflowProgress {
+        let mut record = TaskRecord::new(task.clone());
+        record.status = TaskStatus::Failed;
+        record.last_error = Some(format!("not submitted: {}", error));
+        record.finished_at = Some(Utc::now());
+        self.step_finished(&record)
+    }
+
+    /// Forgets a workflow, e.g. one whose submission was rolled back.
+    pub fn remove(&self, id: &Uuid) -> Option<WorkflowState> {
+        self.workflows.write().remove(id)
+    }
+
+    pub fn get(&self, id: &Uuid) -> Result<WorkflowState, EngineError> {
+        self.workflows
+            .read()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| EngineError::TaskNotFound(id.to_string()))
+    }
+
+    pub fn list_for_tenant(&self, tenant: &str) -> Vec<WorkflowState> {
+        let mut workflows: Vec<WorkflowState> = self
+            .workflows
+            .read()
+            .values()
+            .filter(|state| state.workflow.tenant_id == tenant)
+            .cloned()
+            .collect();
+        workflows.sort_by_key(|state| state.workflow.created_at);
+        workflows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finish(task: &ExecutionTask, status: TaskStatus, result: Value) -> TaskRecord {
+        let mut record = TaskRecord::new(task.clone());
+        match status {
+            TaskStatus::Completed => record.result = Some(result),
+            _ => record.last_error = Some("boom".into()),
+        }
+        record.status = status;
+        record.finished_at = Some(Utc::now());
+        record
+    }
+
+    #[test]
+    fn releases_steps_in_dependency_order() {
+        let workflow = Workflow::builder("tenant", "etl")
+            .step(WorkflowStep::new("extract"))
+            .step(WorkflowStep::new("audit"))
+            .step(
+                WorkflowStep::new("load")
+                    .depends_on("extract")
+                    .depends_on("audit")
+                    .payload(json!({ "table": "spans" })),
+            )
+            .step(WorkflowStep::new("report").depends_on("load"))
+            .on_failure(FailurePolicy::SkipDescendants)
+            .build()
+            .unwrap();
+        let workflow_id = workflow.id;
+        let tracker = WorkflowTracker::new();
+
+        let started = tracker.start(workflow);
+        assert_eq!(started.released.len(), 2);
+        assert_eq!(started.spans.len(), 1);
+        let (extract, audit) = (&started.released[0], &started.released[1]);
+
+        let progress = tracker.step_finished(&finish(extract, TaskStatus::Completed, json!(42)));
+        assert!(progress.released.is_empty());
+        let extract_span = progress.spans[0].id;
+        assert_eq!(progress.spans[0].caused_by, Some(started.spans[0].id));
+
+        let progress = tracker.step_finished(&finish(audit, TaskStatus::Completed, json!("ok")));
+        let load = &progress.released[0];
+        assert_eq!(load.payload["table"], "spans");
+        assert_eq!(load.payload["upstream"]["extract"], 42);
+        assert_eq!(load.payload["upstream"]["audit"], "ok");
+
+        let progress = tracker.step_finished(&finish(load, TaskStatus::Failed, Value::Null));
+        assert!(progress.released.is_empty());
+        assert_eq!(progress.spans[0].caused_by, Some(extract_span));
+        assert_eq!(progress.spans[0].status, SpanStatus::Reverted);
+        assert_eq!(progress.spans[1].caused_by, Some(progress.spans[0].id));
+
+        let state = tracker.get(&workflow_id).unwrap();
+        assert_eq!(state.status, WorkflowStatus::Failed);
+        assert_eq!(state.steps[3].status, StepStatus::Skipped);
+
+        assert!(Workflow::builder("tenant", "cycle")
+            .step(WorkflowStep::new("a").depends_on("b"))
+            .step(WorkflowStep::new("b").depends_on("a"))
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn forgets_finished_workflows_after_the_retention() {
+        let tracker = WorkflowTracker::with_retention(Duration::ZERO);
+        let single = |name: &str| {
+            Workflow::builder("tenant", name)
+                .step(WorkflowStep::new("only"))
+                .build()
+                .unwrap()
+        };
+        let finished = single("finished");
+        let finished_id = finished.id;
+        let started = tracker.start(finished);
+        tracker.step_finished(&finish(
+            &started.released[0],
+            TaskStatus::Completed,
+            json!(1),
+        ));
+        let running = single("running");
+        let running_id = running.id;
+        tracker.start(running);
+        assert!(tracker.get(&finished_id).is_err());
+        assert!(tracker.get(&running_id).is_ok());
+
+        tracker.start(single("next"));
+        assert!(tracker.get(&running_id).is_ok());
+    }
+}
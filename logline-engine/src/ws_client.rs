@@ -10,8 +10,10 @@ use logline_protocol::timeline::{Span, SpanStatus};
 use serde_json::{json, Value};
 use tracing::{debug, info, warn};
 use url::Url;
+use uuid::Uuid;
 
-use crate::rules_client::{RulesEvaluation, RulesServiceClient};
+use crate::execution_spans::CAUSED_BY_METADATA_KEY;
+use crate::rules_client::{RuleTaskRequest, RulesEvaluation, RulesServiceClient};
 use crate::runtime::EngineHandle;
 use crate::EngineServiceConfig;
 
@@ -69,7 +71,7 @@ fn peer_from_config(value: Option<&str>, name: &str) -> Option<WebSocketPeer> {
     })
 }
 
-fn create_rules_client(config: &EngineServiceConfig) -> Option<Arc<RulesServiceClient>> {
+pub(crate) fn create_rules_client(config: &EngineServiceConfig) -> Option<Arc<RulesServiceClient>> {
     let candidate = config
         .rules_service_url
         .as_deref()
@@ -96,16 +98,13 @@ fn create_rules_client(config: &EngineServiceConfig) -> Option<Arc<RulesServiceC
 }
 
 struct EngineMeshHandler {
-    _handle: EngineHandle,
+    handle: EngineHandle,
     rules: Option<Arc<RulesServiceClient>>,
 }
 
 impl EngineMeshHandler {
     fn new(handle: EngineHandle, rules: Option<Arc<RulesServiceClient>>) -> Self {
-        Self {
-            _handle: handle,
-            rules,
-        }
+        Self { handle, rules }
     }
 
     async fn dispatch_remote_rules(
@@ -134,6 +133,41 @@ impl EngineMeshHandler {
         }
     }
 
+    /// Queues the tasks and records the derived spans the matched rules asked
+    /// for, both linked to the evaluated span.
+    async fn apply_rule_effects(
+        &self,
+        span_id: &str,
+        tenant_id: &str,
+        tasks: Vec<RuleTaskRequest>,
+        derived_spans: &[Span],
+    ) {
+        for request in tasks {
+            let rule_id = request.rule_id.clone();
+            let mut task = request.into_task(tenant_id);
+            if let Some(metadata) = task.metadata.as_mut().and_then(Value::as_object_mut) {
+                metadata.insert(
+                    CAUSED_BY_METADATA_KEY.to_string(),
+                    Value::String(span_id.to_string()),
+                );
+            }
+            if let Err(err) = self.handle.submit(task).await {
+                warn!(%span_id, %rule_id, ?err, "failed to enqueue rule task");
+            }
+        }
+
+        for derived in derived_spans {
+            let mut derived = derived.clone();
+            if derived.caused_by.is_none() {
+                derived.caused_by = Uuid::parse_str(span_id).ok();
+            }
+            if derived.tenant_id.is_none() {
+                derived.tenant_id = Some(tenant_id.to_string());
+            }
+            self.handle.record_span(derived);
+        }
+    }
+
     async fn handle_rules_outcome(
         &self,
         client: &ServiceMeshClientHandle,
@@ -148,9 +182,16 @@ impl EngineMeshHandler {
             notes,
             added_tags,
             metadata_updates,
+            tasks,
+            derived_spans,
             mut span,
         } = outcome;
 
+        if decision.state != "reject" {
+            self.apply_rule_effects(span_id, tenant_id, tasks, &derived_spans)
+                .await;
+        }
+
         if decision.state == "simulate" {
             span.status = SpanStatus::Simulated;
             if let Some(note) = decision.note.as_ref() {
@@ -189,6 +230,7 @@ impl EngineMeshHandler {
             "notes": notes,
             "added_tags": added_tags,
             "metadata_updates": metadata_value,
+            "derived_spans": derived_spans,
             "span": span,
         });
 
@@ -333,3 +375,55 @@ impl ServiceMessageHandler for EngineMeshHandler {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::ExecutionRuntime;
+    use crate::timeline_client::{SpanRecorder, TimelineClientError};
+    use logline_protocol::timeline::SpanBuilder;
+    use parking_lot::Mutex;
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct RecordedSpans(Mutex<Vec<Span>>);
+
+    #[async_trait]
+    impl SpanRecorder for RecordedSpans {
+        async fn record(&self, span: Span) -> Result<(), TimelineClientError> {
+            self.0.lock().push(span);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn records_derived_spans_and_queues_rule_tasks() {
+        let recorded = Arc::new(RecordedSpans::default());
+        let runtime = ExecutionRuntime::new().with_span_recorder(recorded.clone());
+        let handle = runtime.handle();
+        let mesh = EngineMeshHandler::new(handle.clone(), None);
+
+        let origin = Uuid::new_v4().to_string();
+        let task: RuleTaskRequest =
+            serde_json::from_value(json!({ "rule_id": "notify", "payload": {} })).unwrap();
+        let derived = SpanBuilder::new("logline-id", "follow-up").build();
+        mesh.apply_rule_effects(&origin, "acme", vec![task], &[derived])
+            .await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let recorded = recorded.0.lock().clone();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(
+            recorded[0].caused_by.map(|id| id.to_string()),
+            Some(origin.clone())
+        );
+        assert_eq!(recorded[0].tenant_id.as_deref(), Some("acme"));
+
+        let tasks = handle.list_for_tenant("acme").await.unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(
+            tasks[0].task.metadata.as_ref().unwrap()[CAUSED_BY_METADATA_KEY],
+            json!(origin)
+        );
+    }
+}
@@ -25,16 +25,27 @@ use crate::discovery::ServiceDiscovery;
 use crate::resilience::ResilienceState;
 use crate::security::{AuthContext, SecurityState};
 
+struct RegisteredClient {
+    tenant_id: Option<String>,
+    sender: mpsc::UnboundedSender<String>,
+}
+
 #[derive(Clone, Default)]
 pub struct ClientRegistry {
-    inner: Arc<Mutex<HashMap<Uuid, mpsc::UnboundedSender<String>>>>,
+    inner: Arc<Mutex<HashMap<Uuid, RegisteredClient>>>,
 }
 
 impl ClientRegistry {
-    pub async fn register(&self) -> (Uuid, mpsc::UnboundedReceiver<String>) {
-        let (tx, rx) = mpsc::unbounded_channel();
+    pub async fn register(
+        &self,
+        tenant_id: Option<String>,
+    ) -> (Uuid, mpsc::UnboundedReceiver<String>) {
+        let (sender, rx) = mpsc::unbounded_channel();
         let id = Uuid::new_v4();
-        self.inner.lock().await.insert(id, tx);
+        self.inner
+            .lock()
+            .await
+            .insert(id, RegisteredClient { tenant_id, sender });
         (id, rx)
     }
 
@@ -44,8 +55,18 @@ impl ClientRegistry {
 
     pub async fn broadcast(&self, payload: &str) {
         let clients = self.inner.lock().await;
-        for sender in clients.values() {
-            let _ = sender.send(payload.to_string());
+        for client in clients.values() {
+            let _ = client.sender.send(payload.to_string());
+        }
+    }
+
+    /// Sends `payload` only to the clients authenticated for `tenant_id`.
+    pub async fn broadcast_to_tenant(&self, tenant_id: &str, payload: &str) {
+        let clients = self.inner.lock().await;
+        for client in clients.values() {
+            if client.tenant_id.as_deref() == Some(tenant_id) {
+                let _ = client.sender.send(payload.to_string());
+            }
         }
     }
 }
@@ -65,6 +86,7 @@ impl MessageRouter {
             }
             ServiceMessage::RuleEvaluationRequest { .. } => vec!["logline-rules"],
             ServiceMessage::RuleExecutionResult { .. } => vec!["logline-engine"],
+            ServiceMessage::TaskStatusChanged { .. } => Vec::new(),
             ServiceMessage::ServiceHello { .. } => Vec::new(),
             ServiceMessage::ConnectionLost { .. } => Vec::new(),
             ServiceMessage::HealthCheckPing | ServiceMessage::HealthCheckPong => Vec::new(),
@@ -160,7 +182,7 @@ async fn handle_connection(
     context: AuthContext,
 ) -> Result<(), LogLineError> {
     let (mut sender, mut receiver) = socket.split();
-    let (client_id, mut outbound) = state.clients.register().await;
+    let (client_id, mut outbound) = state.clients.register(context.tenant_id.clone()).await;
     info!(%client_id, user_id = %context.user_id, "cliente WebSocket conectado ao gateway");
 
     let mut mesh_handle = state.mesh_handle.clone();
@@ -288,7 +310,15 @@ impl ServiceMessageHandler for GatewayMeshHandler {
         let envelope = WebSocketEnvelope::from_service_message(&message)?;
         let serialized = serde_json::to_string(&envelope)
             .map_err(|err| LogLineError::SerializationError(err.to_string()))?;
-        self.clients.broadcast(&serialized).await;
+        match &message {
+            // Task transitions are private to the tenant owning the task.
+            ServiceMessage::TaskStatusChanged { tenant_id, .. } => {
+                self.clients
+                    .broadcast_to_tenant(tenant_id, &serialized)
+                    .await;
+            }
+            _ => self.clients.broadcast(&serialized).await,
+        }
 
         Ok(())
     }
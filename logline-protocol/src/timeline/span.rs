@@ -40,7 +40,7 @@ pub enum Visibility {
 }
 
 /// Primary data structure describing a unit of work/event on the timeline.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Span {
     pub id: Uuid,
     pub timestamp: DateTime<Utc>,
@@ -1,6 +1,9 @@
+use logline_protocol::timeline::{SpanType, Visibility};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::condition::FieldPath;
+
 /// Declarative actions that may be triggered when a rule matches a span.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -19,4 +22,64 @@ pub enum RuleAction {
     MarkProcessed,
     /// Append a diagnostic note to the evaluation outcome.
     Note { message: String },
+    /// Replace a `data` or `metadata` field with `replacement` (defaults to
+    /// `"[REDACTED]"`).
+    RedactField {
+        field: FieldPath,
+        #[serde(default)]
+        replacement: Option<Value>,
+    },
+    /// Replace a `data` or `metadata` field with the SHA-256 of its value.
+    HashField { field: FieldPath },
+    /// Move (or rename) a `data` or `metadata` field.
+    MoveField { from: FieldPath, to: FieldPath },
+    /// Override the span's visibility.
+    SetVisibility { visibility: Visibility },
+    /// Override the span's type.
+    SetSpanType { span_type: SpanType },
+    /// Ask the caller to enqueue an execution task. String values of the
+    /// payload may reference span fields with `{{field.path}}`.
+    EnqueueTask {
+        payload: Value,
+        /// Engine task priority (`Critical`, `High`, `Normal` or `Low`).
+        #[serde(default)]
+        priority: Option<String>,
+        #[serde(default)]
+        delay_seconds: Option<u64>,
+    },
+    /// Ask the caller to record a derived span caused by the evaluated one.
+    /// The payload is templated like [`RuleAction::EnqueueTask`].
+    EmitSpan {
+        title: String,
+        #[serde(default)]
+        payload: Option<Value>,
+        #[serde(default)]
+        span_type: Option<SpanType>,
+        #[serde(default)]
+        tags: Vec<String>,
+    },
+}
+
+impl RuleAction {
+    /// Whether the action contributes to the rule's decision.
+    pub fn is_decision(&self) -> bool {
+        matches!(
+            self,
+            RuleAction::Allow | RuleAction::Reject { .. } | RuleAction::Simulate { .. }
+        )
+    }
+
+    /// Whether the action changes the span itself, invalidating any
+    /// serialized view of it.
+    pub fn mutates_span(&self) -> bool {
+        !matches!(
+            self,
+            RuleAction::Allow
+                | RuleAction::Reject { .. }
+                | RuleAction::Simulate { .. }
+                | RuleAction::Note { .. }
+                | RuleAction::EnqueueTask { .. }
+                | RuleAction::EmitSpan { .. }
+        )
+    }
 }
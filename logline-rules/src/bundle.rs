@@ -0,0 +1,207 @@
+//! Signed rule bundles for shipping rule sets between nodes.
+//!
+//! A bundle is a JSON document holding the rules, a manifest describing them
+//! and an Ed25519 signature over the manifest made with a
+//! [`LogLineKeyPair`]. The manifest carries a SHA-256 digest of the rules, so
+//! the signature covers both. Receivers only activate bundles whose signer's
+//! public key is on their [`TrustedSigner`] list.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::VerifyingKey;
+use logline_core::identity::{LogLineID, LogLineKeyPair};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::RuleError;
+use crate::layer::RuleScope;
+use crate::rule::Rule;
+
+/// Format identifier written to every manifest.
+pub const BUNDLE_FORMAT: &str = "logline-rules-bundle/v1";
+
+/// Description of a bundle's contents; this is what gets signed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BundleManifest {
+    pub format: String,
+    pub name: String,
+    /// Monotonic version; importing a version not newer than the last
+    /// imported one of the same bundle is rejected.
+    pub version: u64,
+    /// Layer the rules are installed into on import.
+    pub scope: RuleScope,
+    pub created_at: DateTime<Utc>,
+    pub rule_count: usize,
+    /// `sha256:<hex>` over the JSON encoding of `rules`.
+    pub rules_digest: String,
+    pub signer: LogLineID,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuleBundle {
+    pub manifest: BundleManifest,
+    pub rules: Vec<Rule>,
+    /// Base64url (unpadded) Ed25519 signature over the manifest's JSON.
+    pub signature: String,
+}
+
+/// Public key allowed to sign bundles for this node.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TrustedSigner {
+    /// Base64url (unpadded) Ed25519 public key, as in [`LogLineID`].
+    pub public_key: String,
+    #[serde(default)]
+    pub label: Option<String>,
+    pub added_at: DateTime<Utc>,
+}
+
+/// Record of a bundle that was verified and activated.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BundleImport {
+    pub name: String,
+    pub version: u64,
+    pub scope: RuleScope,
+    pub signer: String,
+    pub rule_ids: Vec<String>,
+    pub imported_at: DateTime<Utc>,
+}
+
+impl RuleBundle {
+    /// Packages and signs `rules` for installation into `scope`.
+    pub fn sign(
+        name: impl Into<String>,
+        version: u64,
+        scope: RuleScope,
+        rules: Vec<Rule>,
+        keypair: &LogLineKeyPair,
+    ) -> Result<Self, RuleError> {
+        let manifest = BundleManifest {
+            format: BUNDLE_FORMAT.to_string(),
+            name: name.into(),
+            version,
+            scope,
+            created_at: Utc::now(),
+            rule_count: rules.len(),
+            rules_digest: rules_digest(&rules)?,
+            signer: keypair.id.clone(),
+        };
+        let signature = keypair
+            .id
+            .sign(&keypair.signing_key, &manifest_bytes(&manifest)?);
+        Ok(Self {
+            manifest,
+            rules,
+            signature: URL_SAFE_NO_PAD.encode(signature.to_bytes()),
+        })
+    }
+
+    /// Checks the format, the rules digest and the signature, and that the
+    /// signer's key is in `trusted`.
+    pub fn verify(&self, trusted: &[TrustedSigner]) -> Result<(), RuleError> {
+        let manifest = &self.manifest;
+        if manifest.format != BUNDLE_FORMAT {
+            return Err(RuleError::InvalidBundle(format!(
+                "unsupported format `{}`",
+                manifest.format
+            )));
+        }
+        if manifest.rule_count != self.rules.len()
+            || manifest.rules_digest != rules_digest(&self.rules)?
+        {
+            return Err(RuleError::InvalidBundle(
+                "rules do not match the manifest digest".into(),
+            ));
+        }
+        if !trusted
+            .iter()
+            .any(|signer| signer.public_key == manifest.signer.public_key)
+        {
+            return Err(RuleError::UntrustedSigner(
+                manifest.signer.public_key.clone(),
+            ));
+        }
+
+        let signature = URL_SAFE_NO_PAD
+            .decode(&self.signature)
+            .map_err(|err| RuleError::InvalidBundle(format!("malformed signature: {}", err)))?;
+        let valid = manifest
+            .signer
+            .verify_signature(&manifest_bytes(manifest)?, &signature)
+            .map_err(RuleError::InvalidBundle)?;
+        if !valid {
+            return Err(RuleError::InvalidBundle("signature does not verify".into()));
+        }
+        Ok(())
+    }
+}
+
+/// Decodes a base64url (unpadded) Ed25519 public key, as carried by
+/// [`LogLineID`] and [`TrustedSigner`].
+pub(crate) fn decode_public_key(public_key: &str) -> Result<VerifyingKey, RuleError> {
+    let bytes: [u8; 32] = URL_SAFE_NO_PAD
+        .decode(public_key)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| RuleError::InvalidSigner(public_key.to_string()))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| RuleError::InvalidSigner(public_key.to_string()))
+}
+
+fn manifest_bytes(manifest: &BundleManifest) -> Result<Vec<u8>, RuleError> {
+    serde_json::to_vec(manifest).map_err(|err| RuleError::InvalidBundle(err.to_string()))
+}
+
+fn rules_digest(rules: &[Rule]) -> Result<String, RuleError> {
+    let encoded =
+        serde_json::to_vec(rules).map_err(|err| RuleError::InvalidBundle(err.to_string()))?;
+    Ok(format!("sha256:{:x}", Sha256::digest(&encoded)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::condition::RuleCondition;
+    use logline_core::identity::LogLineIDBuilder;
+
+    #[test]
+    fn verifies_signature_digest_and_trust() {
+        let keypair = LogLineIDBuilder::new_system("edge-publisher");
+        let rule = Rule {
+            id: "block-ghosts".into(),
+            description: None,
+            priority: 10,
+            enabled: true,
+            labels: vec![],
+            stop_processing: false,
+            condition: RuleCondition::Always,
+            actions: vec![],
+        };
+        let bundle =
+            RuleBundle::sign("baseline", 3, RuleScope::Platform, vec![rule], &keypair).unwrap();
+        let trusted = vec![TrustedSigner {
+            public_key: keypair.id.public_key.clone(),
+            label: None,
+            added_at: Utc::now(),
+        }];
+
+        bundle.verify(&trusted).expect("valid bundle");
+        assert!(matches!(
+            bundle.verify(&[]),
+            Err(RuleError::UntrustedSigner(_))
+        ));
+
+        let mut tampered = bundle.clone();
+        tampered.rules[0].priority = 1;
+        assert!(matches!(
+            tampered.verify(&trusted),
+            Err(RuleError::InvalidBundle(_))
+        ));
+
+        let mut bumped = bundle;
+        bumped.manifest.version = 4;
+        assert!(matches!(
+            bumped.verify(&trusted),
+            Err(RuleError::InvalidBundle(_))
+        ));
+    }
+}
@@ -0,0 +1,271 @@
+//! Pre-processed rule conditions used by [`crate::RuleEngine`].
+//!
+//! Conditions are compiled once when the engine is built: field paths are
+//! split into segments (with array indices parsed) so evaluation does no
+//! string work per span. Rules whose condition requires an exact value for
+//! one of [`INDEXED_FIELDS`] are bucketed by that value, so a span is only
+//! checked against the rules that could possibly match it.
+
+use std::collections::HashMap;
+
+use logline_protocol::timeline::Span;
+use serde_json::Value;
+
+use crate::condition::{values_equal, FieldPath, RuleCondition, PAYLOAD_ALIAS};
+use crate::rule::Rule;
+
+/// Top-level span fields whose equality predicates are indexed.
+pub(crate) const INDEXED_FIELDS: &[&str] = &["title", "span_type", "contract_id"];
+
+#[derive(Debug, Clone)]
+struct Segment {
+    key: String,
+    index: Option<usize>,
+}
+
+/// [`FieldPath`] resolved into its segments.
+#[derive(Debug, Clone)]
+pub(crate) struct CompiledPath {
+    segments: Vec<Segment>,
+}
+
+impl CompiledPath {
+    fn new(path: &FieldPath) -> Self {
+        Self {
+            segments: path
+                .segments()
+                .map(|segment| Segment {
+                    key: segment.to_string(),
+                    index: segment.parse().ok(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Same lookup as [`FieldPath::locate`].
+    fn locate<'a>(&self, root: &'a Value) -> Option<&'a Value> {
+        let mut current = root;
+        for (position, segment) in self.segments.iter().enumerate() {
+            current = match current {
+                Value::Object(map) => match map.get(&segment.key) {
+                    Some(value) => value,
+                    None if position == 0 && segment.key == PAYLOAD_ALIAS => map.get("data")?,
+                    None => return None,
+                },
+                Value::Array(items) => items.get(segment.index?)?,
+                _ => return None,
+            };
+        }
+        Some(current)
+    }
+}
+
+/// [`RuleCondition`] with its field paths resolved.
+#[derive(Debug, Clone)]
+pub(crate) enum CompiledCondition {
+    Always,
+    All(Vec<CompiledCondition>),
+    Any(Vec<CompiledCondition>),
+    Not(Box<CompiledCondition>),
+    Equals(CompiledPath, Value),
+    NotEquals(CompiledPath, Value),
+    Exists(CompiledPath),
+    Missing(CompiledPath),
+    ContainsText(CompiledPath, String),
+    ContainsTag(String),
+    GreaterThan(CompiledPath, f64),
+    LessThan(CompiledPath, f64),
+}
+
+impl CompiledCondition {
+    pub(crate) fn compile(condition: &RuleCondition) -> Self {
+        let all = |conditions: &[RuleCondition]| conditions.iter().map(Self::compile).collect();
+        match condition {
+            RuleCondition::Always => Self::Always,
+            RuleCondition::All { conditions } => Self::All(all(conditions)),
+            RuleCondition::Any { conditions } => Self::Any(all(conditions)),
+            RuleCondition::Not { condition } => Self::Not(Box::new(Self::compile(condition))),
+            RuleCondition::Equals { field, value } => {
+                Self::Equals(CompiledPath::new(field), value.clone())
+            }
+            RuleCondition::NotEquals { field, value } => {
+                Self::NotEquals(CompiledPath::new(field), value.clone())
+            }
+            RuleCondition::Exists { field } => Self::Exists(CompiledPath::new(field)),
+            RuleCondition::Missing { field } => Self::Missing(CompiledPath::new(field)),
+            RuleCondition::ContainsText { field, text } => {
+                Self::ContainsText(CompiledPath::new(field), text.clone())
+            }
+            RuleCondition::ContainsTag { tag } => Self::ContainsTag(tag.clone()),
+            RuleCondition::GreaterThan { field, value } => {
+                Self::GreaterThan(CompiledPath::new(field), *value)
+            }
+            RuleCondition::LessThan { field, value } => {
+                Self::LessThan(CompiledPath::new(field), *value)
+            }
+        }
+    }
+
+    /// Same semantics as [`RuleCondition::evaluate`].
+    pub(crate) fn evaluate(&self, span: &Span, snapshot: &Value) -> bool {
+        match self {
+            Self::Always => true,
+            Self::All(conditions) => conditions.iter().all(|c| c.evaluate(span, snapshot)),
+            Self::Any(conditions) => conditions.iter().any(|c| c.evaluate(span, snapshot)),
+            Self::Not(condition) => !condition.evaluate(span, snapshot),
+            Self::Equals(path, value) => path
+                .locate(snapshot)
+                .map(|actual| values_equal(actual, value))
+                .unwrap_or(false),
+            Self::NotEquals(path, value) => !path
+                .locate(snapshot)
+                .map(|actual| values_equal(actual, value))
+                .unwrap_or(false),
+            Self::Exists(path) => path.locate(snapshot).is_some(),
+            Self::Missing(path) => path.locate(snapshot).is_none(),
+            Self::ContainsText(path, text) => path
+                .locate(snapshot)
+                .and_then(Value::as_str)
+                .map(|candidate| candidate.contains(text.as_str()))
+                .unwrap_or(false),
+            Self::ContainsTag(tag) => span.tags.iter().any(|existing| existing == tag),
+            Self::GreaterThan(path, value) => path
+                .locate(snapshot)
+                .and_then(Value::as_f64)
+                .map(|candidate| candidate > *value)
+                .unwrap_or(false),
+            Self::LessThan(path, value) => path
+                .locate(snapshot)
+                .and_then(Value::as_f64)
+                .map(|candidate| candidate < *value)
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// Equality predicate the whole condition depends on, if it has one on an
+/// indexed field: either the condition itself or a direct child of `all`.
+fn index_key(condition: &RuleCondition) -> Option<(&'static str, &str)> {
+    match condition {
+        RuleCondition::Equals {
+            field,
+            value: Value::String(expected),
+        } => INDEXED_FIELDS
+            .iter()
+            .find(|indexed| **indexed == field.as_str())
+            .map(|indexed| (*indexed, expected.as_str())),
+        RuleCondition::All { conditions } => conditions.iter().find_map(index_key),
+        _ => None,
+    }
+}
+
+/// Positions of enabled rules, bucketed by their indexed equality predicate.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RuleIndex {
+    keyed: HashMap<&'static str, HashMap<String, Vec<usize>>>,
+    /// Rules without an indexable predicate; always candidates.
+    unkeyed: Vec<usize>,
+}
+
+impl RuleIndex {
+    pub(crate) fn build(rules: &[Rule]) -> Self {
+        let mut index = Self::default();
+        for (position, rule) in rules.iter().enumerate() {
+            if !rule.is_enabled() {
+                continue;
+            }
+            match index_key(&rule.condition) {
+                Some((field, expected)) => index
+                    .keyed
+                    .entry(field)
+                    .or_default()
+                    .entry(expected.to_string())
+                    .or_default()
+                    .push(position),
+                None => index.unkeyed.push(position),
+            }
+        }
+        index
+    }
+
+    /// Positions, in evaluation order, of the rules that may match the span.
+    pub(crate) fn candidates(&self, snapshot: &Value) -> Vec<usize> {
+        let mut positions = self.unkeyed.clone();
+        for (field, buckets) in &self.keyed {
+            let bucket = snapshot
+                .get(*field)
+                .and_then(Value::as_str)
+                .and_then(|actual| buckets.get(actual));
+            if let Some(bucket) = bucket {
+                positions.extend_from_slice(bucket);
+            }
+        }
+        positions.sort_unstable();
+        positions.dedup();
+        positions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rule(id: &str, condition: RuleCondition) -> Rule {
+        Rule {
+            id: id.into(),
+            description: None,
+            priority: 10,
+            enabled: true,
+            labels: vec![],
+            stop_processing: false,
+            condition,
+            actions: vec![],
+        }
+    }
+
+    #[test]
+    fn indexes_equality_predicates_on_span_fields() {
+        let rules = vec![
+            rule(
+                "wire",
+                RuleCondition::All {
+                    conditions: vec![
+                        RuleCondition::GreaterThan {
+                            field: "data.amount".into(),
+                            value: 10.0,
+                        },
+                        RuleCondition::Equals {
+                            field: "title".into(),
+                            value: json!("wire"),
+                        },
+                    ],
+                },
+            ),
+            rule("any", RuleCondition::Always),
+            rule(
+                "ghost",
+                RuleCondition::Equals {
+                    field: "span_type".into(),
+                    value: json!("ghost"),
+                },
+            ),
+        ];
+        let index = RuleIndex::build(&rules);
+
+        assert_eq!(
+            index.candidates(&json!({"title": "wire", "span_type": "user"})),
+            vec![0, 1]
+        );
+        assert_eq!(
+            index.candidates(&json!({"title": "other", "span_type": "ghost"})),
+            vec![1, 2]
+        );
+
+        let compiled = CompiledCondition::compile(&rules[0].condition);
+        let span = logline_protocol::timeline::SpanBuilder::new("node", "wire").build();
+        let snapshot = json!({"title": "wire", "data": {"amount": 11}});
+        assert!(compiled.evaluate(&span, &snapshot));
+        assert!(!compiled.evaluate(&span, &json!({"title": "wire", "data": {"amount": 2}})));
+    }
+}
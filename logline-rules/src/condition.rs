@@ -1,6 +1,8 @@
 use logline_protocol::timeline::Span;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
+
+use crate::trace::ConditionTrace;
 
 /// JSON pointer-like field path used to inspect attributes on a [`Span`].
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -16,16 +18,21 @@ impl FieldPath {
         &self.0
     }
 
-    fn segments(&self) -> impl Iterator<Item = &str> {
+    pub(crate) fn segments(&self) -> impl Iterator<Item = &str> {
         self.0.split('.').filter(|segment| !segment.is_empty())
     }
 
-    fn locate<'a>(&self, root: &'a Value) -> Option<&'a Value> {
+    pub(crate) fn locate<'a>(&self, root: &'a Value) -> Option<&'a Value> {
         let mut current = root;
-        for segment in self.segments() {
+        for (index, segment) in self.segments().enumerate() {
             match current {
                 Value::Object(map) => match map.get(segment) {
                     Some(value) => current = value,
+                    // `payload` names the span's `data` field, as in
+                    // `SpanBuilder::payload`.
+                    None if index == 0 && segment == PAYLOAD_ALIAS => {
+                        current = map.get("data")?;
+                    }
                     None => return None,
                 },
                 Value::Array(items) => {
@@ -39,6 +46,9 @@ impl FieldPath {
     }
 }
 
+/// Root segment accepted as an alias of the span's `data` field.
+pub(crate) const PAYLOAD_ALIAS: &str = "payload";
+
 impl From<&str> for FieldPath {
     fn from(value: &str) -> Self {
         FieldPath::new(value)
@@ -124,9 +134,83 @@ impl RuleCondition {
                 .unwrap_or(false),
         }
     }
+
+    /// Evaluates the condition while recording the result of every node and
+    /// the field values it inspected. Unlike [`RuleCondition::evaluate`] every
+    /// branch of `all`/`any` is visited so the trace is complete.
+    pub fn explain(&self, span: &Span, snapshot: &Value) -> ConditionTrace {
+        match self {
+            RuleCondition::Always => ConditionTrace::leaf("always", true, None, None, None),
+            RuleCondition::All { conditions } => {
+                let children: Vec<ConditionTrace> = conditions
+                    .iter()
+                    .map(|condition| condition.explain(span, snapshot))
+                    .collect();
+                let result = children.iter().all(|child| child.result);
+                ConditionTrace::branch("all", result, children)
+            }
+            RuleCondition::Any { conditions } => {
+                let children: Vec<ConditionTrace> = conditions
+                    .iter()
+                    .map(|condition| condition.explain(span, snapshot))
+                    .collect();
+                let result = children.iter().any(|child| child.result);
+                ConditionTrace::branch("any", result, children)
+            }
+            RuleCondition::Not { condition } => {
+                let child = condition.explain(span, snapshot);
+                ConditionTrace::branch("not", !child.result, vec![child])
+            }
+            RuleCondition::Equals { field, value } => {
+                self.explain_field("equals", field, Some(value.clone()), span, snapshot)
+            }
+            RuleCondition::NotEquals { field, value } => {
+                self.explain_field("not_equals", field, Some(value.clone()), span, snapshot)
+            }
+            RuleCondition::Exists { field } => {
+                self.explain_field("exists", field, None, span, snapshot)
+            }
+            RuleCondition::Missing { field } => {
+                self.explain_field("missing", field, None, span, snapshot)
+            }
+            RuleCondition::ContainsText { field, text } => {
+                self.explain_field("contains_text", field, Some(json!(text)), span, snapshot)
+            }
+            RuleCondition::ContainsTag { tag } => ConditionTrace::leaf(
+                "contains_tag",
+                self.evaluate(span, snapshot),
+                None,
+                Some(json!(tag)),
+                Some(json!(span.tags)),
+            ),
+            RuleCondition::GreaterThan { field, value } => {
+                self.explain_field("greater_than", field, Some(json!(value)), span, snapshot)
+            }
+            RuleCondition::LessThan { field, value } => {
+                self.explain_field("less_than", field, Some(json!(value)), span, snapshot)
+            }
+        }
+    }
+
+    fn explain_field(
+        &self,
+        kind: &str,
+        field: &FieldPath,
+        expected: Option<Value>,
+        span: &Span,
+        snapshot: &Value,
+    ) -> ConditionTrace {
+        ConditionTrace::leaf(
+            kind,
+            self.evaluate(span, snapshot),
+            Some(field.as_str()),
+            expected,
+            field.locate(snapshot).cloned(),
+        )
+    }
 }
 
-fn values_equal(left: &Value, right: &Value) -> bool {
+pub(crate) fn values_equal(left: &Value, right: &Value) -> bool {
     match (left, right) {
         (Value::Number(lhs), Value::Number(rhs)) => match (lhs.as_f64(), rhs.as_f64()) {
             (Some(l), Some(r)) => (l - r).abs() < f64::EPSILON,
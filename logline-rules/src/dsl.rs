@@ -0,0 +1,1176 @@
+//! Textual rule DSL.
+//!
+//! Rules can be authored in a compact, line-oriented syntax instead of the
+//! verbose JSON/YAML condition trees:
+//!
+//! ```text
+//! # Ghost spans must be approved before they reach the timeline.
+//! rule block-ghosts priority 10
+//!   when span_type == "ghost" and not has_tag("approved")
+//!   then reject "ghost spans need approval"
+//! ```
+//!
+//! A rule header accepts, in any order, `description "..."`, `priority N`,
+//! `disabled`, `stop` (stop processing lower-priority rules once this one
+//! matches) and `labels ["a", "b"]`. Conditions support `==`, `!=`, `>`,
+//! `<`, `contains`, `has_tag(..)`, `exists(..)`, `missing(..)`, `always`,
+//! `and`, `or`, `not` and parentheses. Actions are separated by commas:
+//! `allow`, `reject "reason"`, `simulate ["note"]`, `tag "name"`,
+//! `set key = value`, `mark_processed`, `note "message"`,
+//! `redact field [= value]`, `hash field`, `move field to field`,
+//! `visibility name`, `span_type name`,
+//! `enqueue value [priority name] [delay seconds]` and
+//! `emit "title" [payload value] [span_type name] [tags ["a"]]`.
+
+use std::fmt;
+
+use serde_json::{Map, Number, Value};
+
+use crate::action::RuleAction;
+use crate::condition::{FieldPath, RuleCondition};
+use crate::rule::Rule;
+
+/// Syntax error raised while parsing rule DSL sources.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DslError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl DslError {
+    fn new(position: Position, message: impl Into<String>) -> Self {
+        Self {
+            line: position.line,
+            column: position.column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for DslError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for DslError {}
+
+/// Parses every rule declared in the provided DSL source.
+pub fn parse_dsl(source: &str) -> Result<Vec<Rule>, DslError> {
+    let tokens = Lexer::new(source).tokenize()?;
+    Parser::new(tokens).parse_rules()
+}
+
+/// Renders a rule using the DSL syntax.
+pub fn format_rule(rule: &Rule) -> String {
+    let mut out = format!("rule {}", format_name(&rule.id));
+    if let Some(description) = &rule.description {
+        out.push_str(&format!(" description {}", quote(description)));
+    }
+    if rule.priority != Rule::default_priority() {
+        out.push_str(&format!(" priority {}", rule.priority));
+    }
+    if !rule.enabled {
+        out.push_str(" disabled");
+    }
+    if rule.stop_processing {
+        out.push_str(" stop");
+    }
+    if !rule.labels.is_empty() {
+        let labels: Vec<String> = rule.labels.iter().map(|label| quote(label)).collect();
+        out.push_str(&format!(" labels [{}]", labels.join(", ")));
+    }
+    if rule.condition != RuleCondition::Always {
+        out.push_str("\n  when ");
+        out.push_str(&format_condition(&rule.condition));
+    }
+    if !rule.actions.is_empty() {
+        let actions: Vec<String> = rule.actions.iter().map(format_action).collect();
+        out.push_str("\n  then ");
+        out.push_str(&actions.join(", "));
+    }
+    out
+}
+
+/// Renders a set of rules using the DSL syntax, separated by blank lines.
+pub fn format_rules(rules: &[Rule]) -> String {
+    let mut out = rules
+        .iter()
+        .map(format_rule)
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    out.push('\n');
+    out
+}
+
+fn format_condition(condition: &RuleCondition) -> String {
+    match condition {
+        RuleCondition::Always => "always".to_string(),
+        RuleCondition::All { conditions } => match conditions.as_slice() {
+            [] => "always".to_string(),
+            [single] => format_condition(single),
+            many => many
+                .iter()
+                .map(|child| match child {
+                    RuleCondition::All { .. } | RuleCondition::Any { .. } => {
+                        format!("({})", format_condition(child))
+                    }
+                    _ => format_condition(child),
+                })
+                .collect::<Vec<_>>()
+                .join(" and "),
+        },
+        RuleCondition::Any { conditions } => match conditions.as_slice() {
+            [] => "not always".to_string(),
+            [single] => format_condition(single),
+            many => many
+                .iter()
+                .map(|child| match child {
+                    RuleCondition::Any { .. } => format!("({})", format_condition(child)),
+                    _ => format_condition(child),
+                })
+                .collect::<Vec<_>>()
+                .join(" or "),
+        },
+        RuleCondition::Not { condition } => match condition.as_ref() {
+            RuleCondition::All { conditions } | RuleCondition::Any { conditions }
+                if conditions.len() > 1 =>
+            {
+                format!("not ({})", format_condition(condition))
+            }
+            _ => format!("not {}", format_condition(condition)),
+        },
+        RuleCondition::Equals { field, value } => {
+            format!("{} == {}", field.as_str(), format_value(value))
+        }
+        RuleCondition::NotEquals { field, value } => {
+            format!("{} != {}", field.as_str(), format_value(value))
+        }
+        RuleCondition::Exists { field } => format!("exists({})", field.as_str()),
+        RuleCondition::Missing { field } => format!("missing({})", field.as_str()),
+        RuleCondition::ContainsText { field, text } => {
+            format!("{} contains {}", field.as_str(), quote(text))
+        }
+        RuleCondition::ContainsTag { tag } => format!("has_tag({})", quote(tag)),
+        RuleCondition::GreaterThan { field, value } => {
+            format!("{} > {}", field.as_str(), format_number(*value))
+        }
+        RuleCondition::LessThan { field, value } => {
+            format!("{} < {}", field.as_str(), format_number(*value))
+        }
+    }
+}
+
+fn format_action(action: &RuleAction) -> String {
+    match action {
+        RuleAction::Allow => "allow".to_string(),
+        RuleAction::Reject { reason } => format!("reject {}", quote(reason)),
+        RuleAction::Simulate { note: None } => "simulate".to_string(),
+        RuleAction::Simulate { note: Some(note) } => format!("simulate {}", quote(note)),
+        RuleAction::AddTag { tag } => format!("tag {}", quote(tag)),
+        RuleAction::SetMetadata { key, value } => {
+            format!("set {} = {}", format_name(key), format_value(value))
+        }
+        RuleAction::MarkProcessed => "mark_processed".to_string(),
+        RuleAction::Note { message } => format!("note {}", quote(message)),
+        RuleAction::RedactField {
+            field,
+            replacement: None,
+        } => format!("redact {}", format_name(field.as_str())),
+        RuleAction::RedactField {
+            field,
+            replacement: Some(value),
+        } => format!(
+            "redact {} = {}",
+            format_name(field.as_str()),
+            format_value(value)
+        ),
+        RuleAction::HashField { field } => format!("hash {}", format_name(field.as_str())),
+        RuleAction::MoveField { from, to } => format!(
+            "move {} to {}",
+            format_name(from.as_str()),
+            format_name(to.as_str())
+        ),
+        RuleAction::SetVisibility { visibility } => {
+            format!("visibility {}", format_enum(visibility))
+        }
+        RuleAction::SetSpanType { span_type } => format!("span_type {}", format_enum(span_type)),
+        RuleAction::EnqueueTask {
+            payload,
+            priority,
+            delay_seconds,
+        } => {
+            let mut text = format!("enqueue {}", format_value(payload));
+            if let Some(priority) = priority {
+                text.push_str(&format!(" priority {}", format_name(priority)));
+            }
+            if let Some(delay) = delay_seconds {
+                text.push_str(&format!(" delay {}", delay));
+            }
+            text
+        }
+        RuleAction::EmitSpan {
+            title,
+            payload,
+            span_type,
+            tags,
+        } => {
+            let mut text = format!("emit {}", quote(title));
+            if let Some(payload) = payload {
+                text.push_str(&format!(" payload {}", format_value(payload)));
+            }
+            if let Some(span_type) = span_type {
+                text.push_str(&format!(" span_type {}", format_enum(span_type)));
+            }
+            if !tags.is_empty() {
+                let tags: Vec<String> = tags.iter().map(|tag| quote(tag)).collect();
+                text.push_str(&format!(" tags [{}]", tags.join(", ")));
+            }
+            text
+        }
+    }
+}
+
+/// Snake-case name of a unit enum variant such as `Visibility::Public`.
+fn format_enum<T: serde::Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(Value::String(name)) => name,
+        _ => "null".to_string(),
+    }
+}
+
+fn format_value(value: &Value) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| "null".to_string())
+}
+
+fn format_number(value: f64) -> String {
+    if value.is_finite() {
+        format!("{}", value)
+    } else {
+        "0".to_string()
+    }
+}
+
+fn quote(text: &str) -> String {
+    serde_json::to_string(text).unwrap_or_else(|_| "\"\"".to_string())
+}
+
+/// Bare identifiers are emitted when they survive a round-trip through the
+/// lexer, otherwise the name is quoted.
+fn format_name(name: &str) -> String {
+    let mut chars = name.chars();
+    let bare = match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => {
+            chars.all(is_ident_char) && !is_keyword(name)
+        }
+        _ => false,
+    };
+    if bare {
+        name.to_string()
+    } else {
+        quote(name)
+    }
+}
+
+fn is_ident_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || matches!(ch, '_' | '-' | '.')
+}
+
+fn is_keyword(word: &str) -> bool {
+    matches!(
+        word,
+        "rule"
+            | "description"
+            | "priority"
+            | "disabled"
+            | "stop"
+            | "labels"
+            | "when"
+            | "then"
+            | "and"
+            | "or"
+            | "not"
+            | "contains"
+            | "always"
+            | "true"
+            | "false"
+            | "null"
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Position {
+    line: usize,
+    column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Ident(String),
+    Str(String),
+    Number(Number),
+    Eq,
+    NotEq,
+    Greater,
+    Less,
+    Assign,
+    Comma,
+    Colon,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    Eof,
+}
+
+impl TokenKind {
+    fn describe(&self) -> String {
+        match self {
+            TokenKind::Ident(word) => format!("`{}`", word),
+            TokenKind::Str(text) => format!("string {}", quote(text)),
+            TokenKind::Number(number) => format!("number {}", number),
+            TokenKind::Eq => "`==`".to_string(),
+            TokenKind::NotEq => "`!=`".to_string(),
+            TokenKind::Greater => "`>`".to_string(),
+            TokenKind::Less => "`<`".to_string(),
+            TokenKind::Assign => "`=`".to_string(),
+            TokenKind::Comma => "`,`".to_string(),
+            TokenKind::Colon => "`:`".to_string(),
+            TokenKind::LParen => "`(`".to_string(),
+            TokenKind::RParen => "`)`".to_string(),
+            TokenKind::LBracket => "`[`".to_string(),
+            TokenKind::RBracket => "`]`".to_string(),
+            TokenKind::LBrace => "`{`".to_string(),
+            TokenKind::RBrace => "`}`".to_string(),
+            TokenKind::Eof => "end of input".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    position: Position,
+}
+
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            chars: source.chars().peekable(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn position(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.chars.next()?;
+        if ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(ch)
+    }
+
+    fn tokenize(mut self) -> Result<Vec<Token>, DslError> {
+        let mut tokens = Vec::new();
+        loop {
+            self.skip_trivia();
+            let position = self.position();
+            let Some(&ch) = self.chars.peek() else {
+                tokens.push(Token {
+                    kind: TokenKind::Eof,
+                    position,
+                });
+                return Ok(tokens);
+            };
+
+            let kind = match ch {
+                '"' => TokenKind::Str(self.string(position)?),
+                '0'..='9' | '-' => TokenKind::Number(self.number(position)?),
+                c if c.is_ascii_alphabetic() || c == '_' => TokenKind::Ident(self.ident()),
+                _ => {
+                    self.bump();
+                    match ch {
+                        '=' if self.chars.peek() == Some(&'=') => {
+                            self.bump();
+                            TokenKind::Eq
+                        }
+                        '!' if self.chars.peek() == Some(&'=') => {
+                            self.bump();
+                            TokenKind::NotEq
+                        }
+                        '=' => TokenKind::Assign,
+                        '>' => TokenKind::Greater,
+                        '<' => TokenKind::Less,
+                        ',' => TokenKind::Comma,
+                        ':' => TokenKind::Colon,
+                        '(' => TokenKind::LParen,
+                        ')' => TokenKind::RParen,
+                        '[' => TokenKind::LBracket,
+                        ']' => TokenKind::RBracket,
+                        '{' => TokenKind::LBrace,
+                        '}' => TokenKind::RBrace,
+                        other => {
+                            return Err(DslError::new(
+                                position,
+                                format!("unexpected character `{}`", other),
+                            ))
+                        }
+                    }
+                }
+            };
+            tokens.push(Token { kind, position });
+        }
+    }
+
+    fn skip_trivia(&mut self) {
+        while let Some(&ch) = self.chars.peek() {
+            if ch.is_whitespace() {
+                self.bump();
+            } else if ch == '#' {
+                while let Some(&ch) = self.chars.peek() {
+                    if ch == '\n' {
+                        break;
+                    }
+                    self.bump();
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn ident(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(&ch) = self.chars.peek() {
+            if !is_ident_char(ch) {
+                break;
+            }
+            word.push(ch);
+            self.bump();
+        }
+        word
+    }
+
+    fn number(&mut self, start: Position) -> Result<Number, DslError> {
+        let mut raw = String::new();
+        if self.chars.peek() == Some(&'-') {
+            raw.push('-');
+            self.bump();
+        }
+        while let Some(&ch) = self.chars.peek() {
+            if !(ch.is_ascii_digit() || matches!(ch, '.' | 'e' | 'E' | '+')) {
+                break;
+            }
+            if matches!(ch, '+') && !raw.ends_with(['e', 'E']) {
+                break;
+            }
+            raw.push(ch);
+            self.bump();
+        }
+
+        if let Ok(value) = raw.parse::<i64>() {
+            return Ok(Number::from(value));
+        }
+        raw.parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .ok_or_else(|| DslError::new(start, format!("invalid number `{}`", raw)))
+    }
+
+    fn string(&mut self, start: Position) -> Result<String, DslError> {
+        self.bump();
+        let mut text = String::new();
+        loop {
+            let position = self.position();
+            match self.bump() {
+                None => return Err(DslError::new(start, "unterminated string literal")),
+                Some('"') => return Ok(text),
+                Some('\\') => {
+                    let escaped = match self.bump() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('u') => self.unicode_escape(position)?,
+                        _ => return Err(DslError::new(position, "invalid escape sequence")),
+                    };
+                    text.push(escaped);
+                }
+                Some(ch) => text.push(ch),
+            }
+        }
+    }
+
+    fn unicode_escape(&mut self, position: Position) -> Result<char, DslError> {
+        let high = self.hex4(position)?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high)
+                .ok_or_else(|| DslError::new(position, "invalid unicode escape"));
+        }
+
+        if self.bump() != Some('\\') || self.bump() != Some('u') {
+            return Err(DslError::new(
+                position,
+                "unpaired surrogate in unicode escape",
+            ));
+        }
+        let low = self.hex4(position)?;
+        let combined = 0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+        char::from_u32(combined).ok_or_else(|| DslError::new(position, "invalid unicode escape"))
+    }
+
+    fn hex4(&mut self, position: Position) -> Result<u32, DslError> {
+        let mut digits = String::new();
+        for _ in 0..4 {
+            match self.bump() {
+                Some(ch) if ch.is_ascii_hexdigit() => digits.push(ch),
+                _ => return Err(DslError::new(position, "invalid unicode escape")),
+            }
+        }
+        u32::from_str_radix(&digits, 16)
+            .map_err(|_| DslError::new(position, "invalid unicode escape"))
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    cursor: usize,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Self {
+        Self { tokens, cursor: 0 }
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.cursor]
+    }
+
+    fn peek_kind(&self) -> &TokenKind {
+        &self.peek().kind
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.cursor].clone();
+        if self.cursor + 1 < self.tokens.len() {
+            self.cursor += 1;
+        }
+        token
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek_kind(), TokenKind::Ident(word) if word == keyword)
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.at_keyword(keyword) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: TokenKind) -> Result<Token, DslError> {
+        if *self.peek_kind() == expected {
+            Ok(self.advance())
+        } else {
+            Err(self.unexpected(&expected.describe()))
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> DslError {
+        let token = self.peek();
+        DslError::new(
+            token.position,
+            format!("expected {}, found {}", expected, token.kind.describe()),
+        )
+    }
+
+    fn parse_rules(mut self) -> Result<Vec<Rule>, DslError> {
+        let mut rules = Vec::new();
+        while *self.peek_kind() != TokenKind::Eof {
+            rules.push(self.parse_rule()?);
+        }
+        Ok(rules)
+    }
+
+    fn parse_rule(&mut self) -> Result<Rule, DslError> {
+        if !self.eat_keyword("rule") {
+            return Err(self.unexpected("`rule`"));
+        }
+        let id = self.parse_name("rule identifier")?;
+        let mut rule = Rule {
+            id,
+            description: None,
+            priority: Rule::default_priority(),
+            enabled: Rule::default_enabled(),
+            labels: Vec::new(),
+            stop_processing: false,
+            condition: RuleCondition::always(),
+            actions: Vec::new(),
+        };
+
+        loop {
+            if self.eat_keyword("description") {
+                rule.description = Some(self.parse_string("description text")?);
+            } else if self.eat_keyword("priority") {
+                rule.priority = self.parse_priority()?;
+            } else if self.eat_keyword("disabled") {
+                rule.enabled = false;
+            } else if self.eat_keyword("stop") {
+                rule.stop_processing = true;
+            } else if self.eat_keyword("labels") {
+                rule.labels = self.parse_labels()?;
+            } else {
+                break;
+            }
+        }
+
+        if self.eat_keyword("when") {
+            rule.condition = self.parse_or()?;
+        }
+        if self.eat_keyword("then") {
+            rule.actions.push(self.parse_action()?);
+            while *self.peek_kind() == TokenKind::Comma {
+                self.advance();
+                rule.actions.push(self.parse_action()?);
+            }
+        }
+
+        if *self.peek_kind() != TokenKind::Eof && !self.at_keyword("rule") {
+            return Err(self.unexpected("`when`, `then` or the next `rule`"));
+        }
+        Ok(rule)
+    }
+
+    fn parse_name(&mut self, expected: &str) -> Result<String, DslError> {
+        match self.peek_kind().clone() {
+            TokenKind::Ident(word) if !is_keyword(&word) => {
+                self.advance();
+                Ok(word)
+            }
+            TokenKind::Str(text) => {
+                self.advance();
+                Ok(text)
+            }
+            _ => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn parse_string(&mut self, expected: &str) -> Result<String, DslError> {
+        match self.peek_kind().clone() {
+            TokenKind::Str(text) => {
+                self.advance();
+                Ok(text)
+            }
+            _ => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn parse_priority(&mut self) -> Result<u32, DslError> {
+        let token = self.advance();
+        match &token.kind {
+            TokenKind::Number(number) => number
+                .as_u64()
+                .and_then(|value| u32::try_from(value).ok())
+                .ok_or_else(|| {
+                    DslError::new(token.position, "priority must be a non-negative integer")
+                }),
+            other => Err(DslError::new(
+                token.position,
+                format!("expected priority number, found {}", other.describe()),
+            )),
+        }
+    }
+
+    fn parse_seconds(&mut self) -> Result<u64, DslError> {
+        let token = self.advance();
+        match &token.kind {
+            TokenKind::Number(number) => number.as_u64().ok_or_else(|| {
+                DslError::new(token.position, "delay must be a non-negative integer")
+            }),
+            other => Err(DslError::new(
+                token.position,
+                format!("expected delay in seconds, found {}", other.describe()),
+            )),
+        }
+    }
+
+    /// Parses a snake-case enum variant name such as `public`.
+    fn parse_enum<T: serde::de::DeserializeOwned>(
+        &mut self,
+        expected: &str,
+    ) -> Result<T, DslError> {
+        let position = self.peek().position;
+        let name = self.parse_name(expected)?;
+        serde_json::from_value(Value::String(name.clone()))
+            .map_err(|_| DslError::new(position, format!("unknown {} `{}`", expected, name)))
+    }
+
+    fn parse_labels(&mut self) -> Result<Vec<String>, DslError> {
+        self.expect(TokenKind::LBracket)?;
+        let mut labels = Vec::new();
+        if *self.peek_kind() != TokenKind::RBracket {
+            labels.push(self.parse_string("label string")?);
+            while *self.peek_kind() == TokenKind::Comma {
+                self.advance();
+                labels.push(self.parse_string("label string")?);
+            }
+        }
+        self.expect(TokenKind::RBracket)?;
+        Ok(labels)
+    }
+
+    fn parse_or(&mut self) -> Result<RuleCondition, DslError> {
+        let mut conditions = vec![self.parse_and()?];
+        while self.eat_keyword("or") {
+            conditions.push(self.parse_and()?);
+        }
+        Ok(if conditions.len() == 1 {
+            conditions.remove(0)
+        } else {
+            RuleCondition::Any { conditions }
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<RuleCondition, DslError> {
+        let mut conditions = vec![self.parse_unary()?];
+        while self.eat_keyword("and") {
+            conditions.push(self.parse_unary()?);
+        }
+        Ok(if conditions.len() == 1 {
+            conditions.remove(0)
+        } else {
+            RuleCondition::All { conditions }
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<RuleCondition, DslError> {
+        if self.eat_keyword("not") {
+            let condition = self.parse_unary()?;
+            return Ok(RuleCondition::Not {
+                condition: Box::new(condition),
+            });
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<RuleCondition, DslError> {
+        if *self.peek_kind() == TokenKind::LParen {
+            self.advance();
+            let condition = self.parse_or()?;
+            self.expect(TokenKind::RParen)?;
+            return Ok(condition);
+        }
+
+        let token = self.peek().clone();
+        let word = match &token.kind {
+            TokenKind::Ident(word) => word.clone(),
+            _ => return Err(self.unexpected("condition")),
+        };
+
+        if word == "always" {
+            self.advance();
+            return Ok(RuleCondition::Always);
+        }
+        if is_keyword(&word) {
+            return Err(self.unexpected("condition"));
+        }
+        self.advance();
+
+        if *self.peek_kind() == TokenKind::LParen {
+            return self.parse_call(&word, token.position);
+        }
+
+        let field = FieldPath::new(word);
+        let operator = self.advance();
+        match operator.kind {
+            TokenKind::Eq => Ok(RuleCondition::Equals {
+                field,
+                value: self.parse_value()?,
+            }),
+            TokenKind::NotEq => Ok(RuleCondition::NotEquals {
+                field,
+                value: self.parse_value()?,
+            }),
+            TokenKind::Greater => Ok(RuleCondition::GreaterThan {
+                field,
+                value: self.parse_f64()?,
+            }),
+            TokenKind::Less => Ok(RuleCondition::LessThan {
+                field,
+                value: self.parse_f64()?,
+            }),
+            TokenKind::Ident(ref keyword) if keyword == "contains" => {
+                Ok(RuleCondition::ContainsText {
+                    field,
+                    text: self.parse_string("text to search for")?,
+                })
+            }
+            other => Err(DslError::new(
+                operator.position,
+                format!(
+                    "expected comparison operator (==, !=, >, <, contains), found {}",
+                    other.describe()
+                ),
+            )),
+        }
+    }
+
+    fn parse_call(&mut self, name: &str, position: Position) -> Result<RuleCondition, DslError> {
+        self.expect(TokenKind::LParen)?;
+        let condition = match name {
+            "has_tag" => RuleCondition::ContainsTag {
+                tag: self.parse_string("tag string")?,
+            },
+            "exists" => RuleCondition::Exists {
+                field: FieldPath::new(self.parse_name("field path")?),
+            },
+            "missing" => RuleCondition::Missing {
+                field: FieldPath::new(self.parse_name("field path")?),
+            },
+            other => {
+                return Err(DslError::new(
+                    position,
+                    format!(
+                        "unknown function `{}` (expected has_tag, exists or missing)",
+                        other
+                    ),
+                ))
+            }
+        };
+        self.expect(TokenKind::RParen)?;
+        Ok(condition)
+    }
+
+    fn parse_f64(&mut self) -> Result<f64, DslError> {
+        let token = self.advance();
+        match &token.kind {
+            TokenKind::Number(number) => number
+                .as_f64()
+                .ok_or_else(|| DslError::new(token.position, "number out of range")),
+            other => Err(DslError::new(
+                token.position,
+                format!("expected number, found {}", other.describe()),
+            )),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, DslError> {
+        let token = self.advance();
+        match token.kind {
+            TokenKind::Str(text) => Ok(Value::String(text)),
+            TokenKind::Number(number) => Ok(Value::Number(number)),
+            TokenKind::Ident(word) if word == "true" => Ok(Value::Bool(true)),
+            TokenKind::Ident(word) if word == "false" => Ok(Value::Bool(false)),
+            TokenKind::Ident(word) if word == "null" => Ok(Value::Null),
+            TokenKind::LBracket => {
+                let mut items = Vec::new();
+                if *self.peek_kind() != TokenKind::RBracket {
+                    items.push(self.parse_value()?);
+                    while *self.peek_kind() == TokenKind::Comma {
+                        self.advance();
+                        items.push(self.parse_value()?);
+                    }
+                }
+                self.expect(TokenKind::RBracket)?;
+                Ok(Value::Array(items))
+            }
+            TokenKind::LBrace => {
+                let mut map = Map::new();
+                if *self.peek_kind() != TokenKind::RBrace {
+                    loop {
+                        let key = self.parse_string("object key string")?;
+                        self.expect(TokenKind::Colon)?;
+                        map.insert(key, self.parse_value()?);
+                        if *self.peek_kind() != TokenKind::Comma {
+                            break;
+                        }
+                        self.advance();
+                    }
+                }
+                self.expect(TokenKind::RBrace)?;
+                Ok(Value::Object(map))
+            }
+            other => Err(DslError::new(
+                token.position,
+                format!("expected value, found {}", other.describe()),
+            )),
+        }
+    }
+
+    fn parse_action(&mut self) -> Result<RuleAction, DslError> {
+        let token = self.advance();
+        let word = match &token.kind {
+            TokenKind::Ident(word) => word.clone(),
+            other => {
+                return Err(DslError::new(
+                    token.position,
+                    format!("expected action, found {}", other.describe()),
+                ))
+            }
+        };
+
+        match word.as_str() {
+            "allow" => Ok(RuleAction::Allow),
+            "reject" => Ok(RuleAction::Reject {
+                reason: self.parse_string("rejection reason")?,
+            }),
+            "simulate" => {
+                let note = match self.peek_kind() {
+                    TokenKind::Str(_) => Some(self.parse_string("simulation note")?),
+                    _ => None,
+                };
+                Ok(RuleAction::Simulate { note })
+            }
+            "tag" => Ok(RuleAction::AddTag {
+                tag: self.parse_string("tag string")?,
+            }),
+            "set" => {
+                let key = self.parse_name("metadata key")?;
+                self.expect(TokenKind::Assign)?;
+                Ok(RuleAction::SetMetadata {
+                    key,
+                    value: self.parse_value()?,
+                })
+            }
+            "mark_processed" => Ok(RuleAction::MarkProcessed),
+            "note" => Ok(RuleAction::Note {
+                message: self.parse_string("note message")?,
+            }),
+            "redact" => {
+                let field = self.parse_name("field path")?;
+                let replacement = if *self.peek_kind() == TokenKind::Assign {
+                    self.advance();
+                    Some(self.parse_value()?)
+                } else {
+                    None
+                };
+                Ok(RuleAction::RedactField {
+                    field: field.into(),
+                    replacement,
+                })
+            }
+            "hash" => Ok(RuleAction::HashField {
+                field: self.parse_name("field path")?.into(),
+            }),
+            "move" => {
+                let from = self.parse_name("field path")?;
+                if !self.eat_keyword("to") {
+                    return Err(self.unexpected("`to`"));
+                }
+                Ok(RuleAction::MoveField {
+                    from: from.into(),
+                    to: self.parse_name("field path")?.into(),
+                })
+            }
+            "visibility" => Ok(RuleAction::SetVisibility {
+                visibility: self.parse_enum("visibility")?,
+            }),
+            "span_type" => Ok(RuleAction::SetSpanType {
+                span_type: self.parse_enum("span type")?,
+            }),
+            "enqueue" => {
+                let payload = self.parse_value()?;
+                let priority = if self.eat_keyword("priority") {
+                    Some(self.parse_name("task priority")?)
+                } else {
+                    None
+                };
+                let delay_seconds = if self.eat_keyword("delay") {
+                    Some(self.parse_seconds()?)
+                } else {
+                    None
+                };
+                Ok(RuleAction::EnqueueTask {
+                    payload,
+                    priority,
+                    delay_seconds,
+                })
+            }
+            "emit" => {
+                let title = self.parse_string("span title")?;
+                let payload = if self.eat_keyword("payload") {
+                    Some(self.parse_value()?)
+                } else {
+                    None
+                };
+                let span_type = if self.eat_keyword("span_type") {
+                    Some(self.parse_enum("span type")?)
+                } else {
+                    None
+                };
+                let tags = if self.eat_keyword("tags") {
+                    self.parse_labels()?
+                } else {
+                    Vec::new()
+                };
+                Ok(RuleAction::EmitSpan {
+                    title,
+                    payload,
+                    span_type,
+                    tags,
+                })
+            }
+            other => Err(DslError::new(
+                token.position,
+                format!("unknown action `{}`", other),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logline_protocol::timeline::{SpanType, Visibility};
+    use serde_json::json;
+
+    #[test]
+    fn parses_rule_with_header_condition_and_actions() {
+        let source = r#"
+            # ghost spans must be approved
+            rule block-ghosts priority 10 labels ["policy"]
+              when span_type == "ghost" and not has_tag("approved")
+              then reject "ghost spans need approval", tag "blocked"
+        "#;
+
+        let rules = parse_dsl(source).expect("parse dsl");
+        assert_eq!(rules.len(), 1);
+        let rule = &rules[0];
+        assert_eq!(rule.id, "block-ghosts");
+        assert_eq!(rule.priority, 10);
+        assert_eq!(rule.labels, vec!["policy".to_string()]);
+        assert_eq!(
+            rule.condition,
+            RuleCondition::All {
+                conditions: vec![
+                    RuleCondition::Equals {
+                        field: "span_type".into(),
+                        value: json!("ghost"),
+                    },
+                    RuleCondition::Not {
+                        condition: Box::new(RuleCondition::ContainsTag {
+                            tag: "approved".into(),
+                        }),
+                    },
+                ],
+            }
+        );
+        assert_eq!(
+            rule.actions,
+            vec![
+                RuleAction::Reject {
+                    reason: "ghost spans need approval".into(),
+                },
+                RuleAction::AddTag {
+                    tag: "blocked".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn formatted_rules_parse_back_to_the_same_rule() {
+        let rule = Rule {
+            id: "audit large payments".into(),
+            description: Some("flag \"large\" payments".into()),
+            priority: 5,
+            enabled: false,
+            labels: vec!["finance".into()],
+            stop_processing: true,
+            condition: RuleCondition::Any {
+                conditions: vec![
+                    RuleCondition::GreaterThan {
+                        field: "data.amount".into(),
+                        value: 1000.5,
+                    },
+                    RuleCondition::All {
+                        conditions: vec![
+                            RuleCondition::Exists {
+                                field: "contract_id".into(),
+                            },
+                            RuleCondition::ContainsText {
+                                field: "title".into(),
+                                text: "wire".into(),
+                            },
+                        ],
+                    },
+                ],
+            },
+            actions: vec![
+                RuleAction::SetMetadata {
+                    key: "review".into(),
+                    value: json!({"queue": "finance", "level": 2}),
+                },
+                RuleAction::Simulate { note: None },
+                RuleAction::MarkProcessed,
+                RuleAction::RedactField {
+                    field: "data.card".into(),
+                    replacement: Some(json!("****")),
+                },
+                RuleAction::HashField {
+                    field: "data.email".into(),
+                },
+                RuleAction::MoveField {
+                    from: "data.old".into(),
+                    to: "metadata.legacy".into(),
+                },
+                RuleAction::SetVisibility {
+                    visibility: Visibility::Organization,
+                },
+                RuleAction::EnqueueTask {
+                    payload: json!({"amount": "{{data.amount}}"}),
+                    priority: Some("High".into()),
+                    delay_seconds: Some(30),
+                },
+                RuleAction::EmitSpan {
+                    title: "payment flagged".into(),
+                    payload: None,
+                    span_type: Some(SpanType::System),
+                    tags: vec!["review".into()],
+                },
+            ],
+        };
+
+        let source = format_rule(&rule);
+        let parsed = parse_dsl(&source).expect("parse formatted rule");
+        assert_eq!(parsed, vec![rule]);
+    }
+
+    #[test]
+    fn reports_line_and_column_of_syntax_errors() {
+        let source = "rule demo\n  when title === \"x\"";
+        let err = parse_dsl(source).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 16));
+        assert!(err.message.contains("expected value"), "{}", err);
+    }
+}
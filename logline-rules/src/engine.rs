@@ -1,27 +1,72 @@
-use logline_protocol::timeline::Span;
-use serde_json::Value;
+use std::sync::Arc;
+use std::time::Instant;
+
+use logline_protocol::timeline::{Span, SpanBuilder, SpanType};
+use serde_json::{json, Value};
 use tracing::debug;
 
 use crate::action::RuleAction;
+use crate::compiled::{CompiledCondition, RuleIndex};
 use crate::error::RuleError;
 use crate::loader::load_rules;
-use crate::outcome::{Decision, EnforcementOutcome};
+use crate::metrics::{RuleMetrics, RuleObservation, TenantMetrics};
+use crate::outcome::{Decision, EnforcementOutcome, TaskRequest};
 use crate::rule::Rule;
+use crate::strategy::DecisionStrategy;
+use crate::template::{render_text, render_value};
+use crate::trace::{EvaluationTrace, RuleTrace, RuleTraceStatus};
+use crate::transform::{hash_value, move_field, replace_field, REDACTED};
 
 #[cfg(test)]
 use crate::condition::RuleCondition;
 
 /// Runtime executor that evaluates spans against a set of rules.
+///
+/// Rules are compiled and indexed once on construction; clones share the
+/// compiled form.
 #[derive(Debug, Default, Clone)]
 pub struct RuleEngine {
     rules: Vec<Rule>,
+    compiled: Arc<[CompiledCondition]>,
+    index: Arc<RuleIndex>,
+    strategy: DecisionStrategy,
+    metrics: Option<TenantMetrics>,
 }
 
 impl RuleEngine {
     /// Construct an engine from the provided rules, sorting them by priority.
     pub fn new(mut rules: Vec<Rule>) -> Self {
         rules.sort_by(|a, b| a.priority.cmp(&b.priority).then(a.id.cmp(&b.id)));
-        Self { rules }
+        Self {
+            compiled: rules
+                .iter()
+                .map(|rule| CompiledCondition::compile(&rule.condition))
+                .collect(),
+            index: Arc::new(RuleIndex::build(&rules)),
+            rules,
+            strategy: DecisionStrategy::default(),
+            metrics: None,
+        }
+    }
+
+    /// Sets how decisions of several matching rules combine.
+    pub fn with_strategy(mut self, strategy: DecisionStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn strategy(&self) -> DecisionStrategy {
+        self.strategy
+    }
+
+    /// Records per-rule counters for `tenant` on every evaluation. Explain
+    /// runs are not counted.
+    pub fn with_metrics(mut self, metrics: RuleMetrics, tenant: impl Into<String>) -> Self {
+        self.metrics = Some(TenantMetrics {
+            metrics,
+            tenant: tenant.into(),
+        });
+        self
     }
 
     /// Loads rules from the given path (file or directory).
@@ -42,15 +87,106 @@ impl RuleEngine {
 
     /// Evaluate a span and mutate it according to any triggered actions.
     pub fn apply(&self, span: &mut Span) -> EnforcementOutcome {
+        self.run(span, None)
+    }
+
+    /// Evaluate a span without mutating it, returning the outcome.
+    pub fn evaluate(&self, span: &Span) -> EnforcementOutcome {
+        let mut clone = span.clone();
+        self.apply(&mut clone)
+    }
+
+    /// Like [`RuleEngine::apply`], additionally recording how every rule's
+    /// condition evaluated and where evaluation stopped.
+    pub fn apply_with_trace(&self, span: &mut Span) -> (EnforcementOutcome, EvaluationTrace) {
+        let mut trace = EvaluationTrace::default();
+        let outcome = self.run(span, Some(&mut trace));
+        (outcome, trace)
+    }
+
+    /// Explain mode for [`RuleEngine::evaluate`]: evaluates a span without
+    /// mutating it and returns the outcome together with its trace.
+    pub fn explain(&self, span: &Span) -> (EnforcementOutcome, EvaluationTrace) {
+        let mut clone = span.clone();
+        self.apply_with_trace(&mut clone)
+    }
+
+    fn run(&self, span: &mut Span, trace: Option<&mut EvaluationTrace>) -> EnforcementOutcome {
+        match &self.metrics {
+            Some(sink) if trace.is_none() => {
+                let mut observations = Vec::new();
+                let outcome = self.evaluate_rules(span, None, Some(&mut observations));
+                sink.metrics.record(&sink.tenant, &observations);
+                outcome
+            }
+            _ => self.evaluate_rules(span, trace, None),
+        }
+    }
+
+    fn evaluate_rules<'a>(
+        &'a self,
+        span: &mut Span,
+        mut trace: Option<&mut EvaluationTrace>,
+        mut observations: Option<&mut Vec<RuleObservation<'a>>>,
+    ) -> EnforcementOutcome {
         let mut outcome = EnforcementOutcome::new();
+        let mut decided: Option<Decision> = None;
+        let mut snapshot = Snapshot::new(span);
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.strategy = self.strategy;
+        }
 
-        for rule in &self.rules {
+        // Traces report every rule, so the index is only used without one.
+        let tracing = trace.is_some();
+        let candidates = |snapshot: &Value, after: Option<usize>| -> Vec<usize> {
+            let positions = if tracing {
+                (0..self.rules.len()).collect()
+            } else {
+                self.index.candidates(snapshot)
+            };
+            match after {
+                Some(after) => positions.into_iter().filter(|p| *p > after).collect(),
+                None => positions,
+            }
+        };
+        let mut pending = candidates(snapshot.get(span), None);
+        let mut next = 0;
+
+        while let Some(&index) = pending.get(next) {
+            next += 1;
+            let rule = &self.rules[index];
             if !rule.is_enabled() {
+                if let Some(trace) = trace.as_deref_mut() {
+                    record_rule(trace, rule, span, RuleTraceStatus::Disabled);
+                }
                 continue;
             }
 
-            let snapshot = serde_json::to_value(&span).unwrap_or(Value::Null);
-            if !rule.condition.evaluate(span, &snapshot) {
+            let started = Instant::now();
+            let current = snapshot.get(span);
+            let matched = self.compiled[index].evaluate(span, current);
+            if let Some(trace) = trace.as_deref_mut() {
+                let status = if matched {
+                    RuleTraceStatus::Matched
+                } else {
+                    RuleTraceStatus::NotMatched
+                };
+                trace.rules.push(RuleTrace {
+                    rule_id: rule.id.clone(),
+                    priority: rule.priority,
+                    status,
+                    condition: rule.condition.explain(span, current),
+                });
+            }
+            if !matched {
+                if let Some(observations) = observations.as_deref_mut() {
+                    observations.push(RuleObservation {
+                        rule_id: &rule.id,
+                        matched: false,
+                        rejected: false,
+                        elapsed: started.elapsed(),
+                    });
+                }
                 continue;
             }
 
@@ -60,32 +196,121 @@ impl RuleEngine {
                 outcome.push_note(description.clone());
             }
 
+            // Each rule decides on its own; the strategy then combines that
+            // decision with the ones made by earlier rules.
+            outcome.decision = Decision::Allow;
+            let mut rule_decided = false;
             for action in &rule.actions {
-                apply_action(span, action, &mut outcome);
-                if outcome.is_reject() {
-                    debug!(rule_id = %rule.id, "rule rejected span");
-                    return outcome;
+                apply_action(span, &rule.id, action, &mut snapshot, &mut outcome);
+                rule_decided |= action.is_decision();
+                if self.strategy == DecisionStrategy::DenyOverrides && outcome.is_reject() {
+                    break;
+                }
+            }
+
+            if let Some(observations) = observations.as_deref_mut() {
+                observations.push(RuleObservation {
+                    rule_id: &rule.id,
+                    matched: true,
+                    rejected: rule_decided && outcome.is_reject(),
+                    elapsed: started.elapsed(),
+                });
+            }
+
+            let stop = rule.stop_processing
+                || (rule_decided && self.strategy.stops_after(&outcome.decision));
+            if rule_decided {
+                let decision = std::mem::take(&mut outcome.decision);
+                decided = Some(self.strategy.combine(decided, decision));
+            }
+            outcome.decision = decided.clone().unwrap_or_default();
+
+            if stop {
+                debug!(rule_id = %rule.id, "rule stopped evaluation");
+                if let Some(trace) = trace {
+                    trace.stopped_by = Some(rule.id.clone());
+                    for skipped in &self.rules[index + 1..] {
+                        let status = if skipped.is_enabled() {
+                            RuleTraceStatus::Skipped
+                        } else {
+                            RuleTraceStatus::Disabled
+                        };
+                        record_rule(trace, skipped, span, status);
+                    }
                 }
+                return outcome;
+            }
+
+            // Mutations may change indexed fields such as `span_type`.
+            if snapshot.is_stale() {
+                pending = candidates(snapshot.get(span), Some(index));
+                next = 0;
             }
         }
 
         outcome
     }
+}
 
-    /// Evaluate a span without mutating it, returning the outcome.
-    pub fn evaluate(&self, span: &Span) -> EnforcementOutcome {
-        let mut clone = span.clone();
-        self.apply(&mut clone)
+/// Serialized view of the span that conditions and templates read. It is
+/// built once per evaluation and refreshed only after a mutating action.
+struct Snapshot {
+    value: Value,
+    stale: bool,
+}
+
+impl Snapshot {
+    fn new(span: &Span) -> Self {
+        Self {
+            value: serialize(span),
+            stale: false,
+        }
     }
+
+    fn get(&mut self, span: &Span) -> &Value {
+        if self.stale {
+            self.value = serialize(span);
+            self.stale = false;
+        }
+        &self.value
+    }
+
+    fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    fn invalidate(&mut self) {
+        self.stale = true;
+    }
+}
+
+fn serialize(span: &Span) -> Value {
+    serde_json::to_value(span).unwrap_or(Value::Null)
 }
 
-fn apply_action(span: &mut Span, action: &RuleAction, outcome: &mut EnforcementOutcome) {
+fn record_rule(trace: &mut EvaluationTrace, rule: &Rule, span: &Span, status: RuleTraceStatus) {
+    let snapshot = serialize(span);
+    trace.rules.push(RuleTrace {
+        rule_id: rule.id.clone(),
+        priority: rule.priority,
+        status,
+        condition: rule.condition.explain(span, &snapshot),
+    });
+}
+
+fn apply_action(
+    span: &mut Span,
+    rule_id: &str,
+    action: &RuleAction,
+    snapshot: &mut Snapshot,
+    outcome: &mut EnforcementOutcome,
+) {
+    let mut errors = Vec::new();
     match action {
         RuleAction::Allow => outcome.update_decision(Decision::Allow),
         RuleAction::Reject { reason } => {
-            outcome.update_decision(Decision::Reject {
-                reason: reason.clone(),
-            });
+            let reason = render_text(reason, snapshot.get(span), &mut errors);
+            outcome.update_decision(Decision::Reject { reason });
         }
         RuleAction::Simulate { note } => {
             outcome.update_decision(Decision::Simulate { note: note.clone() });
@@ -98,22 +323,104 @@ fn apply_action(span: &mut Span, action: &RuleAction, outcome: &mut EnforcementO
             outcome.push_tag(tag.clone());
         }
         RuleAction::SetMetadata { key, value } => {
+            let value = render_value(value, snapshot.get(span), &mut errors);
             span.add_metadata(key.clone(), value.clone());
-            outcome.push_metadata(key.clone(), value.clone());
+            outcome.push_metadata(key.clone(), value);
         }
         RuleAction::MarkProcessed => {
             span.mark_processed();
         }
         RuleAction::Note { message } => {
-            outcome.push_note(message.clone());
+            outcome.push_note(render_text(message, snapshot.get(span), &mut errors));
+        }
+        RuleAction::RedactField { field, replacement } => {
+            let replacement = replacement.clone().unwrap_or_else(|| json!(REDACTED));
+            if replace_field(span, field, |_| replacement) {
+                outcome.push_transformed_field(field.as_str());
+            }
+        }
+        RuleAction::HashField { field } => {
+            if replace_field(span, field, hash_value) {
+                outcome.push_transformed_field(field.as_str());
+            }
+        }
+        RuleAction::MoveField { from, to } => {
+            if move_field(span, from, to) {
+                outcome.push_transformed_field(from.as_str());
+                outcome.push_transformed_field(to.as_str());
+            }
+        }
+        RuleAction::SetVisibility { visibility } => {
+            span.visibility = Some(*visibility);
+        }
+        RuleAction::SetSpanType { span_type } => {
+            span.span_type = Some(*span_type);
+        }
+        RuleAction::EnqueueTask {
+            payload,
+            priority,
+            delay_seconds,
+        } => {
+            outcome.tasks.push(TaskRequest {
+                rule_id: rule_id.to_string(),
+                payload: render_value(payload, snapshot.get(span), &mut errors),
+                priority: priority.clone(),
+                delay_seconds: *delay_seconds,
+            });
+        }
+        RuleAction::EmitSpan {
+            title,
+            payload,
+            span_type,
+            tags,
+        } => {
+            let current = snapshot.get(span);
+            let title = render_text(title, current, &mut errors);
+            let payload = payload
+                .as_ref()
+                .map(|payload| render_value(payload, current, &mut errors));
+            outcome
+                .derived_spans
+                .push(derived_span(span, title, payload, *span_type, tags));
         }
     }
+
+    if action.mutates_span() {
+        snapshot.invalidate();
+    }
+    for err in errors {
+        outcome.push_error(format!("rule `{}`: {}", rule_id, err));
+    }
+}
+
+/// Builds a span caused by `origin` that inherits its tenancy and workflow.
+fn derived_span(
+    origin: &Span,
+    title: String,
+    payload: Option<Value>,
+    span_type: Option<SpanType>,
+    tags: &[String],
+) -> Span {
+    let mut derived = SpanBuilder::new(origin.logline_id.clone(), title).build();
+    derived.caused_by = Some(origin.id);
+    derived.tenant_id = origin.tenant_id.clone();
+    derived.organization_id = origin.organization_id;
+    derived.user_id = origin.user_id;
+    derived.workflow_id = origin.workflow_id.clone();
+    derived.flow_id = origin.flow_id.clone();
+    derived.visibility = origin.visibility;
+    derived.span_type = span_type;
+    derived.data = payload;
+    for tag in tags {
+        derived.add_tag(tag.clone());
+    }
+    derived
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use logline_protocol::timeline::{SpanBuilder, SpanStatus};
+    use logline_protocol::timeline::{SpanBuilder, SpanStatus, Visibility};
     use serde_json::json;
 
     fn build_span() -> Span {
@@ -130,6 +437,7 @@ mod tests {
             priority: 10,
             enabled: true,
             labels: vec![],
+            stop_processing: false,
             condition: RuleCondition::Always,
             actions: vec![RuleAction::MarkProcessed],
         };
@@ -149,6 +457,7 @@ mod tests {
             priority: 1,
             enabled: true,
             labels: vec![],
+            stop_processing: false,
             condition: RuleCondition::Equals {
                 field: "title".into(),
                 value: json!("example span"),
@@ -165,4 +474,279 @@ mod tests {
         assert!(matches!(outcome.decision, Decision::Reject { .. }));
         assert!(outcome.is_reject());
     }
+
+    #[test]
+    fn explain_traces_every_rule_and_where_evaluation_stopped() {
+        let deny = Rule {
+            id: "deny".into(),
+            description: None,
+            priority: 1,
+            enabled: true,
+            labels: vec![],
+            stop_processing: false,
+            condition: RuleCondition::All {
+                conditions: vec![
+                    RuleCondition::Equals {
+                        field: "title".into(),
+                        value: json!("example span"),
+                    },
+                    RuleCondition::Missing {
+                        field: "contract_id".into(),
+                    },
+                ],
+            },
+            actions: vec![RuleAction::Reject {
+                reason: "contract required".into(),
+            }],
+        };
+        let tag = Rule {
+            id: "tag".into(),
+            description: None,
+            priority: 5,
+            enabled: true,
+            labels: vec![],
+            stop_processing: false,
+            condition: RuleCondition::Always,
+            actions: vec![RuleAction::AddTag { tag: "seen".into() }],
+        };
+
+        let engine = RuleEngine::new(vec![tag, deny]);
+        let span = build_span();
+        let (outcome, trace) = engine.explain(&span);
+
+        assert!(outcome.is_reject());
+        assert_eq!(trace.stopped_by.as_deref(), Some("deny"));
+        let statuses: Vec<_> = trace
+            .rules
+            .iter()
+            .map(|rule| (rule.rule_id.as_str(), rule.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("deny", RuleTraceStatus::Matched),
+                ("tag", RuleTraceStatus::Skipped)
+            ]
+        );
+
+        let title = &trace.rules[0].condition.children[0];
+        assert_eq!(title.field.as_deref(), Some("title"));
+        assert_eq!(title.actual, Some(json!("example span")));
+        assert!(title.result);
+        assert!(span.tags.is_empty(), "explain must not mutate the span");
+    }
+
+    #[test]
+    fn returns_requested_tasks_and_derived_spans() {
+        let rule = Rule {
+            id: "route".into(),
+            description: None,
+            priority: 10,
+            enabled: true,
+            labels: vec![],
+            stop_processing: false,
+            condition: RuleCondition::Always,
+            actions: vec![
+                RuleAction::HashField {
+                    field: "data.email".into(),
+                },
+                RuleAction::SetVisibility {
+                    visibility: Visibility::Private,
+                },
+                RuleAction::EnqueueTask {
+                    payload: json!({"amount": "{{data.amount}}", "label": "pay {{title}}"}),
+                    priority: Some("High".into()),
+                    delay_seconds: None,
+                },
+                RuleAction::EmitSpan {
+                    title: "payment routed".into(),
+                    payload: Some(json!({"source": "{{id}}"})),
+                    span_type: None,
+                    tags: vec![],
+                },
+            ],
+        };
+        let engine = RuleEngine::new(vec![rule]);
+        let mut span = build_span();
+        span.tenant_id = Some("tenant-a".into());
+        span.data = Some(json!({"amount": 42, "email": "ada@example.com"}));
+
+        let outcome = engine.apply(&mut span);
+        assert_eq!(outcome.transformed_fields, vec!["data.email".to_string()]);
+        assert_eq!(span.visibility, Some(Visibility::Private));
+        assert_eq!(
+            outcome.tasks[0].payload,
+            json!({"amount": 42, "label": "pay example span"})
+        );
+
+        let derived = &outcome.derived_spans[0];
+        assert_eq!(derived.caused_by, Some(span.id));
+        assert_eq!(derived.tenant_id.as_deref(), Some("tenant-a"));
+        assert_eq!(derived.data, Some(json!({"source": span.id.to_string()})));
+    }
+
+    #[test]
+    fn renders_templates_and_records_missing_fields() {
+        let rule = Rule {
+            id: "limit".into(),
+            description: None,
+            priority: 10,
+            enabled: true,
+            labels: vec![],
+            stop_processing: false,
+            condition: RuleCondition::Always,
+            actions: vec![
+                RuleAction::SetMetadata {
+                    key: "amount".into(),
+                    value: json!("{{payload.amount}}"),
+                },
+                RuleAction::Note {
+                    message: "currency {{data.currency}}".into(),
+                },
+                RuleAction::Reject {
+                    reason: "amount {{payload.amount}} exceeds limit for {{title}}".into(),
+                },
+            ],
+        };
+        let engine = RuleEngine::new(vec![rule]);
+        let mut span = build_span();
+        span.data = Some(json!({"amount": 1500}));
+
+        let outcome = engine.apply(&mut span);
+        assert_eq!(
+            outcome.decision,
+            Decision::Reject {
+                reason: "amount 1500 exceeds limit for example span".into()
+            }
+        );
+        assert_eq!(
+            outcome.metadata_updates,
+            vec![("amount".into(), json!(1500))]
+        );
+        assert_eq!(
+            outcome.notes,
+            vec!["currency {{data.currency}}".to_string()]
+        );
+        assert_eq!(
+            outcome.errors,
+            vec!["rule `limit`: template references missing field `data.currency`".to_string()]
+        );
+    }
+
+    #[test]
+    fn strategies_and_stop_processing_control_evaluation() {
+        let rule = |id: &str, priority: u32, action: RuleAction| Rule {
+            id: id.into(),
+            description: None,
+            priority,
+            enabled: true,
+            labels: vec![],
+            stop_processing: false,
+            condition: RuleCondition::Always,
+            actions: vec![action, RuleAction::AddTag { tag: id.into() }],
+        };
+        let rules = vec![
+            rule(
+                "deny",
+                10,
+                RuleAction::Reject {
+                    reason: "blocked".into(),
+                },
+            ),
+            rule("exception", 20, RuleAction::Allow),
+            rule(
+                "audit",
+                30,
+                RuleAction::Simulate {
+                    note: Some("audit".into()),
+                },
+            ),
+        ];
+        let decide = |strategy: DecisionStrategy| {
+            let mut span = build_span();
+            let outcome = RuleEngine::new(rules.clone())
+                .with_strategy(strategy)
+                .apply(&mut span);
+            (outcome.decision, span.tags)
+        };
+
+        let (decision, tags) = decide(DecisionStrategy::DenyOverrides);
+        assert!(matches!(decision, Decision::Reject { .. }));
+        assert!(tags.is_empty(), "reject stops the rule's remaining actions");
+
+        let (decision, tags) = decide(DecisionStrategy::AllowOverrides);
+        assert_eq!(decision, Decision::Allow);
+        assert_eq!(tags, vec!["audit", "deny", "exception"]);
+
+        let (decision, tags) = decide(DecisionStrategy::FirstMatch);
+        assert!(matches!(decision, Decision::Reject { .. }));
+        assert_eq!(tags, vec!["deny"]);
+
+        let (decision, tags) = decide(DecisionStrategy::PriorityOrder);
+        assert!(matches!(decision, Decision::Reject { .. }));
+        assert_eq!(tags, vec!["audit", "deny", "exception"]);
+
+        let mut stopping = rules.clone();
+        stopping[1].stop_processing = true;
+        let (outcome, trace) = RuleEngine::new(stopping)
+            .with_strategy(DecisionStrategy::AllowOverrides)
+            .explain(&build_span());
+        assert_eq!(outcome.decision, Decision::Allow);
+        assert_eq!(trace.stopped_by.as_deref(), Some("exception"));
+        assert_eq!(trace.rules[2].status, RuleTraceStatus::Skipped);
+    }
+
+    #[test]
+    fn indexed_rules_see_fields_changed_by_earlier_rules() {
+        let rule = |id: &str, priority: u32, condition: RuleCondition, action: RuleAction| Rule {
+            id: id.into(),
+            description: None,
+            priority,
+            enabled: true,
+            labels: vec![],
+            stop_processing: false,
+            condition,
+            actions: vec![action],
+        };
+        let engine = RuleEngine::new(vec![
+            rule(
+                "classify",
+                10,
+                RuleCondition::Equals {
+                    field: "title".into(),
+                    value: json!("example span"),
+                },
+                RuleAction::SetSpanType {
+                    span_type: SpanType::Ghost,
+                },
+            ),
+            rule(
+                "ghosts",
+                20,
+                RuleCondition::Equals {
+                    field: "span_type".into(),
+                    value: json!("ghost"),
+                },
+                RuleAction::AddTag {
+                    tag: "ghost".into(),
+                },
+            ),
+            rule(
+                "other-title",
+                30,
+                RuleCondition::Equals {
+                    field: "title".into(),
+                    value: json!("unrelated"),
+                },
+                RuleAction::AddTag {
+                    tag: "unrelated".into(),
+                },
+            ),
+        ]);
+
+        let mut span = build_span();
+        let outcome = engine.apply(&mut span);
+        assert_eq!(outcome.applied_rules, vec!["classify", "ghosts"]);
+        assert_eq!(span.tags, vec!["ghost"]);
+    }
 }
@@ -19,6 +19,18 @@ pub enum RuleError {
     DuplicateRule { id: String },
     #[error("rule not found: {0}")]
     NotFound(String),
+    #[error("rule set failed validation: {0}")]
+    Invalid(String),
+    #[error("invalid rule override: {0}")]
+    InvalidOverride(String),
+    #[error("invalid rule bundle: {0}")]
+    InvalidBundle(String),
+    #[error("not a valid Ed25519 public key: {0}")]
+    InvalidSigner(String),
+    #[error("bundle signer is not trusted: {0}")]
+    UntrustedSigner(String),
+    #[error("stale rule bundle: {0}")]
+    StaleBundle(String),
 }
 
 impl RuleError {
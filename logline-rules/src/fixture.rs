@@ -0,0 +1,320 @@
+use std::fs;
+use std::path::Path;
+
+use logline_protocol::timeline::{Span, SpanBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::engine::RuleEngine;
+use crate::error::RuleError;
+use crate::outcome::{Decision, EnforcementOutcome};
+
+/// File name suffix (before the extension) that marks a rule test fixture,
+/// e.g. `ghosts.test.yaml`. Such files are skipped by [`crate::RuleEngine::from_path`].
+pub const FIXTURE_SUFFIX: &str = ".test";
+
+/// Regression test case for a rule set: an input span and the expected outcome.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuleFixture {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Partial span document. Missing required span fields are filled with
+    /// placeholders so fixtures only need to spell out what the rules inspect.
+    #[serde(default)]
+    pub span: Value,
+    pub expect: FixtureExpectation,
+}
+
+/// Expected result of evaluating a fixture. Optional fields are only checked
+/// when present.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FixtureExpectation {
+    pub decision: ExpectedDecision,
+    /// Expected reject reason or simulation note.
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Exact set of tags the rules should add.
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    /// Exact set of metadata updates the rules should make.
+    #[serde(default)]
+    pub metadata: Option<Map<String, Value>>,
+    /// Exact, ordered list of rules expected to match.
+    #[serde(default)]
+    pub applied_rules: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpectedDecision {
+    Allow,
+    Reject,
+    Simulate,
+}
+
+impl ExpectedDecision {
+    fn of(decision: &Decision) -> Self {
+        match decision {
+            Decision::Allow => ExpectedDecision::Allow,
+            Decision::Reject { .. } => ExpectedDecision::Reject,
+            Decision::Simulate { .. } => ExpectedDecision::Simulate,
+        }
+    }
+}
+
+/// Difference between an expected and an actual value of a fixture.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FixtureMismatch {
+    pub field: String,
+    pub expected: Value,
+    pub actual: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FixtureResult {
+    pub name: String,
+    pub passed: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mismatches: Vec<FixtureMismatch>,
+    /// Set when the fixture could not be executed at all.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Aggregated result of running a fixture suite.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FixtureReport {
+    pub passed: usize,
+    pub failed: usize,
+    pub results: Vec<FixtureResult>,
+}
+
+impl FixtureReport {
+    pub fn is_success(&self) -> bool {
+        self.failed == 0
+    }
+}
+
+/// Executes every fixture against the engine and reports the differences.
+pub fn run_fixtures(engine: &RuleEngine, fixtures: &[RuleFixture]) -> FixtureReport {
+    let mut report = FixtureReport::default();
+    for fixture in fixtures {
+        let result = run_fixture(engine, fixture);
+        if result.passed {
+            report.passed += 1;
+        } else {
+            report.failed += 1;
+        }
+        report.results.push(result);
+    }
+    report
+}
+
+fn run_fixture(engine: &RuleEngine, fixture: &RuleFixture) -> FixtureResult {
+    let span = match fixture_span(&fixture.span) {
+        Ok(span) => span,
+        Err(message) => {
+            return FixtureResult {
+                name: fixture.name.clone(),
+                passed: false,
+                mismatches: Vec::new(),
+                error: Some(message),
+            }
+        }
+    };
+
+    let outcome = engine.evaluate(&span);
+    let mismatches = compare(&fixture.expect, &outcome);
+    FixtureResult {
+        name: fixture.name.clone(),
+        passed: mismatches.is_empty(),
+        mismatches,
+        error: None,
+    }
+}
+
+fn fixture_span(partial: &Value) -> Result<Span, String> {
+    let base = SpanBuilder::new("fixture", "fixture").build();
+    let mut document = serde_json::to_value(&base).map_err(|err| err.to_string())?;
+    match (partial, &mut document) {
+        (Value::Null, _) => {}
+        (Value::Object(fields), Value::Object(target)) => {
+            for (key, value) in fields {
+                target.insert(key.clone(), value.clone());
+            }
+        }
+        _ => return Err("fixture span must be an object".to_string()),
+    }
+    serde_json::from_value(document).map_err(|err| format!("invalid fixture span: {}", err))
+}
+
+fn compare(expect: &FixtureExpectation, outcome: &EnforcementOutcome) -> Vec<FixtureMismatch> {
+    let mut mismatches = Vec::new();
+    let mut check = |field: &str, expected: Value, actual: Value| {
+        if expected != actual {
+            mismatches.push(FixtureMismatch {
+                field: field.to_string(),
+                expected,
+                actual,
+            });
+        }
+    };
+
+    let decision = ExpectedDecision::of(&outcome.decision);
+    check(
+        "decision",
+        serde_json::to_value(expect.decision).unwrap_or(Value::Null),
+        serde_json::to_value(decision).unwrap_or(Value::Null),
+    );
+
+    if let Some(reason) = &expect.reason {
+        let actual = match &outcome.decision {
+            Decision::Reject { reason } => Some(reason.clone()),
+            Decision::Simulate { note } => note.clone(),
+            Decision::Allow => None,
+        };
+        check("reason", Value::from(reason.clone()), Value::from(actual));
+    }
+
+    if let Some(tags) = &expect.tags {
+        let mut expected = tags.clone();
+        let mut actual = outcome.added_tags.clone();
+        expected.sort();
+        actual.sort();
+        check("tags", Value::from(expected), Value::from(actual));
+    }
+
+    if let Some(metadata) = &expect.metadata {
+        let actual: Map<String, Value> = outcome.metadata_updates.iter().cloned().collect();
+        check(
+            "metadata",
+            Value::Object(metadata.clone()),
+            Value::Object(actual),
+        );
+    }
+
+    if let Some(rules) = &expect.applied_rules {
+        check(
+            "applied_rules",
+            Value::from(rules.clone()),
+            Value::from(outcome.applied_rules.clone()),
+        );
+    }
+
+    mismatches
+}
+
+/// Whether the file name marks a fixture file (`*.test.json|yaml|yml`).
+pub(crate) fn is_fixture_file(path: &Path) -> bool {
+    let has_extension = matches!(
+        path.extension().and_then(|value| value.to_str()),
+        Some("json" | "yaml" | "yml")
+    );
+    has_extension
+        && path
+            .file_stem()
+            .and_then(|value| value.to_str())
+            .map(|stem| stem.ends_with(FIXTURE_SUFFIX))
+            .unwrap_or(false)
+}
+
+/// Loads fixtures from a fixture file or from every fixture file in a rules
+/// directory.
+pub fn load_fixtures(path: impl AsRef<Path>) -> Result<Vec<RuleFixture>, RuleError> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Err(RuleError::MissingPath(path.display().to_string()));
+    }
+
+    if !path.is_dir() {
+        return load_fixture_file(path);
+    }
+
+    let mut entries: Vec<_> = fs::read_dir(path)
+        .map_err(|err| RuleError::from_io(path, err))?
+        .collect::<Result<_, _>>()
+        .map_err(|err| RuleError::from_io(path, err))?;
+    entries.sort_by_key(|entry| entry.path());
+
+    let mut fixtures = Vec::new();
+    for entry in entries {
+        let entry_path = entry.path();
+        if entry_path.is_file() && is_fixture_file(&entry_path) {
+            fixtures.append(&mut load_fixture_file(&entry_path)?);
+        }
+    }
+    Ok(fixtures)
+}
+
+fn load_fixture_file(path: &Path) -> Result<Vec<RuleFixture>, RuleError> {
+    let raw = fs::read_to_string(path).map_err(|err| RuleError::from_io(path, err))?;
+    parse_fixtures(&raw).map_err(|message| RuleError::parse_error(path, message))
+}
+
+/// Parses fixtures from a YAML/JSON document containing either a `fixtures`
+/// list, a bare list or a single fixture.
+pub fn parse_fixtures(raw: &str) -> Result<Vec<RuleFixture>, String> {
+    if let Ok(doc) = serde_yaml::from_str::<FixtureDocument>(raw) {
+        return Ok(doc.fixtures);
+    }
+    if let Ok(list) = serde_yaml::from_str::<Vec<RuleFixture>>(raw) {
+        return Ok(list);
+    }
+    serde_yaml::from_str::<RuleFixture>(raw)
+        .map(|fixture| vec![fixture])
+        .map_err(|err| format!("unable to parse rule fixtures: {}", err))
+}
+
+#[derive(Debug, Deserialize)]
+struct FixtureDocument {
+    fixtures: Vec<RuleFixture>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_dsl;
+
+    #[test]
+    fn runs_fixtures_and_reports_mismatches() {
+        let rules = parse_dsl(
+            r#"
+            rule block-ghosts priority 10
+              when span_type == "ghost" and not has_tag("approved")
+              then reject "ghost spans need approval"
+            rule tag-all priority 20 then tag "seen"
+            "#,
+        )
+        .expect("parse rules");
+        let engine = RuleEngine::new(rules);
+
+        let fixtures = parse_fixtures(
+            r#"
+            fixtures:
+              - name: ghost is rejected
+                span: { span_type: ghost }
+                expect:
+                  decision: reject
+                  reason: ghost spans need approval
+              - name: approved ghost passes
+                span: { span_type: ghost, tags: [approved] }
+                expect:
+                  decision: allow
+                  tags: [seen, extra]
+            "#,
+        )
+        .expect("parse fixtures");
+
+        let report = run_fixtures(&engine, &fixtures);
+        assert_eq!((report.passed, report.failed), (1, 1));
+        assert_eq!(
+            report.results[1].mismatches,
+            vec![FixtureMismatch {
+                field: "tags".into(),
+                expected: serde_json::json!(["extra", "seen"]),
+                actual: serde_json::json!(["seen"]),
+            }]
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::rule::Rule;
+
+/// Layer of the rule hierarchy a rule set belongs to. Rules are inherited
+/// from the platform, then the tenant's organization, then the tenant.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(tag = "layer", content = "id", rename_all = "snake_case")]
+pub enum RuleScope {
+    Platform,
+    Organization(String),
+    Tenant(String),
+}
+
+impl RuleScope {
+    pub fn organization(id: impl Into<String>) -> Self {
+        RuleScope::Organization(id.into())
+    }
+
+    pub fn tenant(id: impl Into<String>) -> Self {
+        RuleScope::Tenant(id.into())
+    }
+}
+
+impl fmt::Display for RuleScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleScope::Platform => write!(f, "platform"),
+            RuleScope::Organization(id) => write!(f, "organization:{}", id),
+            RuleScope::Tenant(id) => write!(f, "tenant:{}", id),
+        }
+    }
+}
+
+/// Explicit change a lower layer makes to a rule it inherits.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum RuleOverride {
+    /// Drops the inherited rule from the effective set.
+    Disable,
+    /// Replaces the inherited rule with this definition.
+    Replace { rule: Rule },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuleOverrideEntry {
+    pub rule_id: String,
+    #[serde(flatten)]
+    pub change: RuleOverride,
+    pub created_at: DateTime<Utc>,
+    pub updated_by: Option<String>,
+}
+
+/// Rule of the merged set along with the layer that contributed it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EffectiveRule {
+    pub scope: RuleScope,
+    /// Stored version; absent for file-based platform rules.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    /// Layer whose rule was replaced by this one through an override.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replaces: Option<RuleScope>,
+    pub rule: Rule,
+}
+
+/// Why a rule defined in some layer is not part of the effective set.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum ExclusionReason {
+    /// The rule itself is disabled.
+    Disabled,
+    /// A lower layer disabled the inherited rule.
+    DisabledBy { scope: RuleScope },
+    /// A lower layer replaced the inherited rule.
+    ReplacedBy { scope: RuleScope },
+    /// The id is already inherited from `scope`; replacing an inherited rule
+    /// requires an explicit override.
+    Conflict { scope: RuleScope },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExcludedRule {
+    pub rule_id: String,
+    pub scope: RuleScope,
+    #[serde(flatten)]
+    pub reason: ExclusionReason,
+}
+
+/// Merged view of every layer that applies to a tenant.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EffectiveRuleSet {
+    pub tenant_id: String,
+    pub organization_id: Option<String>,
+    /// Rules in evaluation order (priority, then id).
+    pub rules: Vec<EffectiveRule>,
+    pub excluded: Vec<ExcludedRule>,
+}
+
+impl EffectiveRuleSet {
+    pub fn into_rules(self) -> Vec<Rule> {
+        self.rules
+            .into_iter()
+            .map(|effective| effective.rule)
+            .collect()
+    }
+}
+
+/// Rules and overrides a single layer contributes to the merge.
+pub(crate) struct LayerInput {
+    pub scope: RuleScope,
+    pub rules: Vec<(Rule, Option<u32>)>,
+    pub overrides: Vec<RuleOverrideEntry>,
+}
+
+/// Merges layers from the most general to the most specific. Each layer first
+/// applies its overrides to what it inherits, then adds its own rules.
+pub(crate) fn merge_layers(
+    tenant_id: &str,
+    organization_id: Option<String>,
+    layers: Vec<LayerInput>,
+) -> EffectiveRuleSet {
+    let mut merged: HashMap<String, EffectiveRule> = HashMap::new();
+    let mut excluded = Vec::new();
+
+    for layer in layers {
+        let mut overrides = layer.overrides;
+        overrides.sort_by(|a, b| a.rule_id.cmp(&b.rule_id));
+        for entry in overrides {
+            let Some(inherited) = merged.remove(&entry.rule_id) else {
+                continue;
+            };
+            match entry.change {
+                RuleOverride::Disable => excluded.push(ExcludedRule {
+                    rule_id: entry.rule_id,
+                    scope: inherited.scope,
+                    reason: ExclusionReason::DisabledBy {
+                        scope: layer.scope.clone(),
+                    },
+                }),
+                RuleOverride::Replace { rule } => {
+                    excluded.push(ExcludedRule {
+                        rule_id: entry.rule_id.clone(),
+                        scope: inherited.scope.clone(),
+                        reason: ExclusionReason::ReplacedBy {
+                            scope: layer.scope.clone(),
+                        },
+                    });
+                    if rule.is_enabled() {
+                        merged.insert(
+                            entry.rule_id,
+                            EffectiveRule {
+                                scope: layer.scope.clone(),
+                                version: None,
+                                replaces: Some(inherited.scope),
+                                rule,
+                            },
+                        );
+                    }
+                }
+            }
+        }
+
+        let mut rules = layer.rules;
+        rules.sort_by(|a, b| a.0.id.cmp(&b.0.id));
+        for (rule, version) in rules {
+            let reason = if !rule.is_enabled() {
+                Some(ExclusionReason::Disabled)
+            } else {
+                merged
+                    .get(&rule.id)
+                    .map(|existing| ExclusionReason::Conflict {
+                        scope: existing.scope.clone(),
+                    })
+            };
+            match reason {
+                Some(reason) => excluded.push(ExcludedRule {
+                    rule_id: rule.id,
+                    scope: layer.scope.clone(),
+                    reason,
+                }),
+                None => {
+                    merged.insert(
+                        rule.id.clone(),
+                        EffectiveRule {
+                            scope: layer.scope.clone(),
+                            version,
+                            replaces: None,
+                            rule,
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    let mut rules: Vec<EffectiveRule> = merged.into_values().collect();
+    rules.sort_by(|a, b| {
+        a.rule
+            .priority
+            .cmp(&b.rule.priority)
+            .then_with(|| a.rule.id.cmp(&b.rule.id))
+    });
+
+    EffectiveRuleSet {
+        tenant_id: tenant_id.to_string(),
+        organization_id,
+        rules,
+        excluded,
+    }
+}
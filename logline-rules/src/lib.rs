@@ -2,28 +2,65 @@
 //!
 //! This crate exposes a declarative rule system used by services such as the
 //! timeline microservice to enforce policies before spans are persisted. Rules
-//! are expressed as YAML/JSON documents that define matching conditions and
-//! actions to perform when a span satisfies those conditions.
+//! are expressed as YAML/JSON documents, or `.rule` files written in the
+//! textual DSL, that define matching conditions and actions to perform when a
+//! span satisfies those conditions.
 
 mod action;
+mod bundle;
+mod compiled;
 mod condition;
+mod dsl;
 mod engine;
 mod error;
+mod fixture;
+mod layer;
 mod loader;
+mod metrics;
 mod outcome;
+mod rollout;
 mod rule;
 mod service;
 mod store;
+mod strategy;
+mod template;
+mod trace;
+mod transform;
+mod validate;
+mod watcher;
 mod ws_client;
 
 pub use action::RuleAction;
+pub use bundle::{BundleImport, BundleManifest, RuleBundle, TrustedSigner, BUNDLE_FORMAT};
 pub use condition::{FieldPath, RuleCondition};
+pub use dsl::{format_rule, format_rules, parse_dsl, DslError};
 pub use engine::RuleEngine;
 pub use error::RuleError;
-pub use outcome::{Decision, EnforcementOutcome};
+pub use fixture::{
+    load_fixtures, parse_fixtures, run_fixtures, ExpectedDecision, FixtureExpectation,
+    FixtureMismatch, FixtureReport, FixtureResult, RuleFixture, FIXTURE_SUFFIX,
+};
+pub use layer::{
+    EffectiveRule, EffectiveRuleSet, ExcludedRule, ExclusionReason, RuleOverride,
+    RuleOverrideEntry, RuleScope,
+};
+pub use metrics::{LatencyHistogram, RuleMetrics, RuleStats, StaleRule, LATENCY_BUCKETS_MICROS};
+pub use outcome::{Decision, EnforcementOutcome, TaskRequest};
+pub use rollout::{
+    rollout_bucket, RolloutEngine, RolloutMode, RolloutOutcome, RolloutStats, RuleCandidate,
+    ShadowDivergence, ShadowEvaluation,
+};
 pub use rule::Rule;
 pub use service::{RuleApiBuilder, RuleServiceConfig};
 pub use store::{RuleHistoryEntry, RuleStore};
+pub use strategy::DecisionStrategy;
+pub use template::{render_template, TemplateError};
+pub use trace::{ConditionTrace, EvaluationTrace, RuleTrace, RuleTraceStatus};
+pub use validate::{
+    validate_rule, validate_rules, validate_rules_with_strategy, IssueKind, Severity,
+    ValidationIssue, ValidationReport,
+};
+pub use watcher::{RuleReloadStatus, RuleWatcher};
 
 #[cfg(test)]
 mod tests {
@@ -39,6 +76,7 @@ mod tests {
             priority: 1,
             enabled: true,
             labels: vec![],
+            stop_processing: false,
             condition: RuleCondition::Equals {
                 field: FieldPath::from("title"),
                 value: json!("demo"),
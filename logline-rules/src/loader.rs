@@ -1,10 +1,12 @@
 use std::collections::HashSet;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use serde::Deserialize;
 
+use crate::dsl::parse_dsl;
 use crate::error::RuleError;
+use crate::fixture::is_fixture_file;
 use crate::rule::Rule;
 
 pub fn load_rules(path: impl AsRef<Path>) -> Result<Vec<Rule>, RuleError> {
@@ -27,28 +29,47 @@ pub fn load_rules(path: impl AsRef<Path>) -> Result<Vec<Rule>, RuleError> {
 
 fn load_from_directory(path: &Path) -> Result<Vec<Rule>, RuleError> {
     let mut rules = Vec::new();
+    for file in rule_files(path)? {
+        let mut file_rules = load_from_file(&file)?;
+        rules.append(&mut file_rules);
+    }
+
+    Ok(rules)
+}
+
+/// Lists the rule files `load_rules` reads for `path`, sorted by path. Test
+/// fixtures and nested directories are skipped.
+pub(crate) fn rule_files(path: &Path) -> Result<Vec<PathBuf>, RuleError> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
     for entry in fs::read_dir(path).map_err(|err| RuleError::from_io(path, err))? {
         let entry = entry.map_err(|err| RuleError::from_io(path, err))?;
         let file_type = entry
             .file_type()
             .map_err(|err| RuleError::from_io(entry.path(), err))?;
-        if file_type.is_dir() {
+        if file_type.is_dir() || is_fixture_file(&entry.path()) {
             continue;
         }
 
         if let Some(ext) = entry.path().extension().and_then(|value| value.to_str()) {
-            if matches!(ext, "json" | "yaml" | "yml") {
-                let mut file_rules = load_from_file(&entry.path())?;
-                rules.append(&mut file_rules);
+            if matches!(ext, "json" | "yaml" | "yml" | "rule") {
+                files.push(entry.path());
             }
         }
     }
 
-    Ok(rules)
+    files.sort();
+    Ok(files)
 }
 
 fn load_from_file(path: &Path) -> Result<Vec<Rule>, RuleError> {
     let raw = fs::read_to_string(path).map_err(|err| RuleError::from_io(path, err))?;
+    if path.extension().and_then(|value| value.to_str()) == Some("rule") {
+        return parse_dsl(&raw).map_err(|err| RuleError::parse_error(path, err.to_string()));
+    }
     parse_rules(&raw, path)
 }
 
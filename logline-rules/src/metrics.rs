@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+use crate::rule::Rule;
+
+/// Upper bounds, in microseconds, of the rule latency histogram buckets.
+pub const LATENCY_BUCKETS_MICROS: &[u64] = &[10, 50, 100, 250, 500, 1_000, 5_000, 25_000];
+
+/// Name, help text and value of each exported counter.
+type Counter = (&'static str, &'static str, fn(&RuleStats) -> u64);
+
+const COUNTERS: [Counter; 3] = [
+    (
+        "logline_rule_evaluations_total",
+        "Rule conditions checked against spans.",
+        |stats| stats.evaluations,
+    ),
+    (
+        "logline_rule_matches_total",
+        "Spans matched by the rule.",
+        |stats| stats.matches,
+    ),
+    (
+        "logline_rule_rejects_total",
+        "Spans rejected by the rule.",
+        |stats| stats.rejects,
+    ),
+];
+
+/// Distribution of the time spent evaluating a rule (condition and actions).
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct LatencyHistogram {
+    /// Observations per bucket: `buckets[i]` counts latencies up to
+    /// [`LATENCY_BUCKETS_MICROS`]`[i]`, the final entry everything slower.
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    pub sum_micros: u64,
+}
+
+impl LatencyHistogram {
+    fn observe(&mut self, elapsed: Duration) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; LATENCY_BUCKETS_MICROS.len() + 1];
+        }
+        let micros = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+        let bucket = LATENCY_BUCKETS_MICROS
+            .iter()
+            .position(|bound| micros <= *bound)
+            .unwrap_or(LATENCY_BUCKETS_MICROS.len());
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.sum_micros = self.sum_micros.saturating_add(micros);
+    }
+}
+
+/// Counters for a single rule of a tenant.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuleStats {
+    pub rule_id: String,
+    /// Times the rule's condition was checked against a span.
+    pub evaluations: u64,
+    pub matches: u64,
+    /// Matches where the rule itself decided to reject.
+    pub rejects: u64,
+    pub first_evaluated_at: DateTime<Utc>,
+    pub last_matched_at: Option<DateTime<Utc>>,
+    pub latency: LatencyHistogram,
+}
+
+/// Rule that has not matched any span for the requested period.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StaleRule {
+    pub rule_id: String,
+    pub last_matched_at: Option<DateTime<Utc>>,
+    /// Start of the idle period: the last match, or when tracking began for
+    /// rules that never matched.
+    pub idle_since: DateTime<Utc>,
+}
+
+/// Result of checking one rule against a span, as reported by the engine.
+pub(crate) struct RuleObservation<'a> {
+    pub rule_id: &'a str,
+    pub matched: bool,
+    pub rejected: bool,
+    pub elapsed: Duration,
+}
+
+struct MetricsState {
+    started_at: DateTime<Utc>,
+    tenants: HashMap<String, HashMap<String, RuleStats>>,
+}
+
+/// Per-tenant, per-rule evaluation counters shared by every engine built
+/// from a [`crate::RuleStore`]. Counters live in memory and restart with the
+/// process.
+#[derive(Clone)]
+pub struct RuleMetrics {
+    inner: Arc<RwLock<MetricsState>>,
+}
+
+impl Default for RuleMetrics {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(MetricsState {
+                started_at: Utc::now(),
+                tenants: HashMap::new(),
+            })),
+        }
+    }
+}
+
+impl std::fmt::Debug for RuleMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RuleMetrics")
+            .field("started_at", &self.inner.read().started_at)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RuleMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn record(&self, tenant: &str, observations: &[RuleObservation<'_>]) {
+        if observations.is_empty() {
+            return;
+        }
+        let now = Utc::now();
+        let mut inner = self.inner.write();
+        let rules = inner.tenants.entry(tenant.to_string()).or_default();
+        for observation in observations {
+            let stats = rules
+                .entry(observation.rule_id.to_string())
+                .or_insert_with(|| RuleStats {
+                    rule_id: observation.rule_id.to_string(),
+                    evaluations: 0,
+                    matches: 0,
+                    rejects: 0,
+                    first_evaluated_at: now,
+                    last_matched_at: None,
+                    latency: LatencyHistogram::default(),
+                });
+            stats.evaluations += 1;
+            if observation.matched {
+                stats.matches += 1;
+                stats.last_matched_at = Some(now);
+            }
+            if observation.rejected {
+                stats.rejects += 1;
+            }
+            stats.latency.observe(observation.elapsed);
+        }
+    }
+
+    /// Statistics of every rule the tenant has evaluated, ordered by rule id.
+    pub fn tenant_stats(&self, tenant: &str) -> Vec<RuleStats> {
+        let inner = self.inner.read();
+        let mut stats: Vec<RuleStats> = inner
+            .tenants
+            .get(tenant)
+            .map(|rules| rules.values().cloned().collect())
+            .unwrap_or_default();
+        stats.sort_by(|a, b| a.rule_id.cmp(&b.rule_id));
+        stats
+    }
+
+    /// Rules of `rules` that have not matched since `now - max_idle`. Rules
+    /// tracked for less than `max_idle` are left out, since there is not
+    /// enough history to call them stale.
+    pub fn stale_rules(
+        &self,
+        tenant: &str,
+        rules: &[Rule],
+        max_idle: chrono::Duration,
+        now: DateTime<Utc>,
+    ) -> Vec<StaleRule> {
+        let cutoff = now - max_idle;
+        let inner = self.inner.read();
+        let tracked = inner.tenants.get(tenant);
+        let mut stale: Vec<StaleRule> = rules
+            .iter()
+            .filter_map(|rule| {
+                let stats = tracked.and_then(|rules| rules.get(&rule.id));
+                let last_matched_at = stats.and_then(|stats| stats.last_matched_at);
+                let idle_since = last_matched_at.unwrap_or(inner.started_at);
+                (idle_since <= cutoff).then(|| StaleRule {
+                    rule_id: rule.id.clone(),
+                    last_matched_at,
+                    idle_since,
+                })
+            })
+            .collect();
+        stale.sort_by(|a, b| {
+            a.idle_since
+                .cmp(&b.idle_since)
+                .then_with(|| a.rule_id.cmp(&b.rule_id))
+        });
+        stale
+    }
+
+    /// Renders every counter in the Prometheus text exposition format.
+    pub fn render_prometheus(&self) -> String {
+        let inner = self.inner.read();
+        let mut series: Vec<(&String, &RuleStats)> = inner
+            .tenants
+            .iter()
+            .flat_map(|(tenant, rules)| rules.values().map(move |stats| (tenant, stats)))
+            .collect();
+        series.sort_by(|a, b| (a.0, &a.1.rule_id).cmp(&(b.0, &b.1.rule_id)));
+
+        let mut out = String::new();
+        for (name, help, value) in COUNTERS {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            for (tenant, stats) in &series {
+                let _ = writeln!(
+                    out,
+                    "{}{{{}}} {}",
+                    name,
+                    labels(tenant, stats),
+                    value(stats)
+                );
+            }
+        }
+
+        let name = "logline_rule_latency_seconds";
+        let _ = writeln!(out, "# HELP {} Time spent evaluating the rule.", name);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (tenant, stats) in &series {
+            let labels = labels(tenant, stats);
+            let mut cumulative = 0;
+            for (index, bound) in LATENCY_BUCKETS_MICROS.iter().enumerate() {
+                cumulative += stats.latency.buckets.get(index).copied().unwrap_or(0);
+                let _ = writeln!(
+                    out,
+                    "{}_bucket{{{},le=\"{}\"}} {}",
+                    name,
+                    labels,
+                    *bound as f64 / 1_000_000.0,
+                    cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{},le=\"+Inf\"}} {}",
+                name, labels, stats.latency.count
+            );
+            let _ = writeln!(
+                out,
+                "{}_sum{{{}}} {}",
+                name,
+                labels,
+                stats.latency.sum_micros as f64 / 1_000_000.0
+            );
+            let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, stats.latency.count);
+        }
+        out
+    }
+}
+
+fn labels(tenant: &str, stats: &RuleStats) -> String {
+    format!(
+        "tenant=\"{}\",rule=\"{}\"",
+        escape_label(tenant),
+        escape_label(&stats.rule_id)
+    )
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Metrics sink bound to the tenant whose rules an engine evaluates.
+#[derive(Debug, Clone)]
+pub(crate) struct TenantMetrics {
+    pub metrics: RuleMetrics,
+    pub tenant: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::condition::RuleCondition;
+
+    #[test]
+    fn counts_rule_hits_and_reports_stale_rules() {
+        let metrics = RuleMetrics::new();
+        let observe = |rule_id, matched, rejected| RuleObservation {
+            rule_id,
+            matched,
+            rejected,
+            elapsed: Duration::from_micros(80),
+        };
+        metrics.record(
+            "tenant-a",
+            &[observe("block", true, true), observe("quiet", false, false)],
+        );
+        metrics.record("tenant-a", &[observe("block", false, false)]);
+
+        let stats = metrics.tenant_stats("tenant-a");
+        assert_eq!(stats.len(), 2);
+        assert_eq!(
+            (stats[0].evaluations, stats[0].matches, stats[0].rejects),
+            (2, 1, 1)
+        );
+        assert_eq!(stats[0].latency.buckets[2], 2);
+
+        let rule = |id: &str| Rule {
+            id: id.into(),
+            description: None,
+            priority: 10,
+            enabled: true,
+            labels: vec![],
+            stop_processing: false,
+            condition: RuleCondition::Always,
+            actions: vec![],
+        };
+        let rules = vec![rule("block"), rule("quiet"), rule("unused")];
+        let later = Utc::now() + chrono::Duration::days(31);
+        let stale = metrics.stale_rules("tenant-a", &rules, chrono::Duration::days(30), later);
+        let ids: Vec<&str> = stale.iter().map(|rule| rule.rule_id.as_str()).collect();
+        assert_eq!(ids, vec!["quiet", "unused", "block"]);
+        assert!(metrics
+            .stale_rules("tenant-a", &rules, chrono::Duration::days(30), Utc::now())
+            .is_empty());
+
+        let text = metrics.render_prometheus();
+        assert!(text.contains("logline_rule_matches_total{tenant=\"tenant-a\",rule=\"block\"} 1"));
+        assert!(text.contains(
+            "logline_rule_latency_seconds_bucket{tenant=\"tenant-a\",rule=\"block\",le=\"+Inf\"} 2"
+        ));
+    }
+}
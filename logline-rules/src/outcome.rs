@@ -1,11 +1,19 @@
+use logline_protocol::timeline::Span;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 /// Final decision from applying all matched rules.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
 pub enum Decision {
+    #[default]
     Allow,
-    Reject { reason: String },
-    Simulate { note: Option<String> },
+    Reject {
+        reason: String,
+    },
+    Simulate {
+        note: Option<String>,
+    },
 }
 
 impl Decision {
@@ -25,13 +33,21 @@ impl Decision {
     }
 }
 
-impl Default for Decision {
-    fn default() -> Self {
-        Decision::Allow
-    }
+/// Execution task a rule asks the caller to enqueue.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskRequest {
+    pub rule_id: String,
+    pub payload: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay_seconds: Option<u64>,
 }
 
 /// Aggregated view of how rules affected the span.
+///
+/// Rules never perform side effects themselves: requested tasks and derived
+/// spans are collected here for the caller to carry out.
 #[derive(Debug, Clone, PartialEq)]
 pub struct EnforcementOutcome {
     pub decision: Decision,
@@ -39,6 +55,14 @@ pub struct EnforcementOutcome {
     pub added_tags: Vec<String>,
     pub metadata_updates: Vec<(String, Value)>,
     pub notes: Vec<String>,
+    /// `data`/`metadata` paths rewritten by redact, hash or move actions.
+    pub transformed_fields: Vec<String>,
+    pub tasks: Vec<TaskRequest>,
+    /// Spans to record, each with `caused_by` set to the evaluated span.
+    pub derived_spans: Vec<Span>,
+    /// Problems met while applying actions, such as template placeholders
+    /// referencing fields missing from the span.
+    pub errors: Vec<String>,
 }
 
 impl EnforcementOutcome {
@@ -49,6 +73,10 @@ impl EnforcementOutcome {
             added_tags: Vec::new(),
             metadata_updates: Vec::new(),
             notes: Vec::new(),
+            transformed_fields: Vec::new(),
+            tasks: Vec::new(),
+            derived_spans: Vec::new(),
+            errors: Vec::new(),
         }
     }
 
@@ -80,6 +108,17 @@ impl EnforcementOutcome {
         self.notes.push(note.into());
     }
 
+    pub fn push_transformed_field(&mut self, field: impl Into<String>) {
+        let field = field.into();
+        if !self.transformed_fields.contains(&field) {
+            self.transformed_fields.push(field);
+        }
+    }
+
+    pub fn push_error(&mut self, error: impl Into<String>) {
+        self.errors.push(error.into());
+    }
+
     pub fn update_decision(&mut self, new_decision: Decision) {
         let current = std::mem::take(&mut self.decision);
         self.decision = current.merge(new_decision);
@@ -0,0 +1,309 @@
+use chrono::{DateTime, Utc};
+use logline_protocol::timeline::Span;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::engine::RuleEngine;
+use crate::metrics::{RuleMetrics, TenantMetrics};
+use crate::outcome::{Decision, EnforcementOutcome};
+use crate::rule::Rule;
+use crate::strategy::DecisionStrategy;
+
+/// Number of divergences retained per candidate for reporting.
+const RECENT_DIVERGENCES: usize = 20;
+
+/// How a candidate rule version participates in live evaluation.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum RolloutMode {
+    /// Evaluated alongside the active version; decisions are recorded only.
+    Shadow,
+    /// Enforced on a deterministic, hash-based `percent` of spans and shadowed
+    /// on the rest.
+    Canary { percent: u8 },
+}
+
+impl RolloutMode {
+    /// Whether the candidate is enforced for the given span.
+    pub fn enforces(&self, rule_id: &str, span: &Span) -> bool {
+        match self {
+            RolloutMode::Shadow => false,
+            RolloutMode::Canary { percent } => rollout_bucket(rule_id, &span.id) < *percent,
+        }
+    }
+}
+
+/// Deterministic bucket in `0..100` for a span and rule, stable across nodes
+/// and process restarts (FNV-1a over the span id and rule id).
+pub fn rollout_bucket(rule_id: &str, span_id: &Uuid) -> u8 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in span_id.as_bytes().iter().chain(rule_id.as_bytes()) {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    (hash % 100) as u8
+}
+
+/// Recorded disagreement between the active and candidate version of a rule.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ShadowDivergence {
+    pub span_id: Uuid,
+    pub active: Decision,
+    pub candidate: Decision,
+    /// Whether the candidate's decision was the one enforced.
+    pub candidate_enforced: bool,
+    pub observed_at: DateTime<Utc>,
+}
+
+/// Running divergence statistics for a candidate rule version.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct RolloutStats {
+    pub evaluations: u64,
+    pub enforced: u64,
+    pub divergences: u64,
+    pub recent_divergences: Vec<ShadowDivergence>,
+}
+
+impl RolloutStats {
+    pub(crate) fn record(&mut self, evaluation: &ShadowEvaluation) {
+        self.evaluations += 1;
+        if evaluation.candidate_enforced {
+            self.enforced += 1;
+        }
+        if evaluation.active != evaluation.candidate {
+            self.divergences += 1;
+            self.recent_divergences.push(ShadowDivergence {
+                span_id: evaluation.span_id,
+                active: evaluation.active.clone(),
+                candidate: evaluation.candidate.clone(),
+                candidate_enforced: evaluation.candidate_enforced,
+                observed_at: Utc::now(),
+            });
+            if self.recent_divergences.len() > RECENT_DIVERGENCES {
+                self.recent_divergences.remove(0);
+            }
+        }
+    }
+}
+
+/// Pending rule version that is shadowed or canaried before promotion.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuleCandidate {
+    /// Version the rule will receive once promoted.
+    pub version: u32,
+    pub rule: Rule,
+    #[serde(flatten)]
+    pub mode: RolloutMode,
+    pub created_at: DateTime<Utc>,
+    pub updated_by: Option<String>,
+    #[serde(default)]
+    pub stats: RolloutStats,
+}
+
+/// Decisions of both versions of a candidate rule for a single span.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowEvaluation {
+    pub rule_id: String,
+    pub span_id: Uuid,
+    pub active: Decision,
+    pub candidate: Decision,
+    pub candidate_enforced: bool,
+}
+
+/// Result of evaluating a span with rollouts in place.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RolloutOutcome {
+    /// Outcome that was enforced on the span.
+    pub outcome: EnforcementOutcome,
+    pub shadow: Vec<ShadowEvaluation>,
+}
+
+/// Evaluates a tenant's active rules while shadowing or canarying candidate
+/// versions.
+#[derive(Debug, Clone, Default)]
+pub struct RolloutEngine {
+    active: Vec<Rule>,
+    candidates: Vec<RuleCandidate>,
+    strategy: DecisionStrategy,
+    metrics: Option<TenantMetrics>,
+}
+
+impl RolloutEngine {
+    pub fn new(active: Vec<Rule>, candidates: Vec<RuleCandidate>) -> Self {
+        Self {
+            active,
+            candidates,
+            strategy: DecisionStrategy::default(),
+            metrics: None,
+        }
+    }
+
+    /// Sets the decision strategy of both the enforced and shadow engines.
+    pub fn with_strategy(mut self, strategy: DecisionStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Records rule metrics for the enforced engine; shadow evaluations are
+    /// not counted.
+    pub fn with_metrics(mut self, metrics: RuleMetrics, tenant: impl Into<String>) -> Self {
+        self.metrics = Some(TenantMetrics {
+            metrics,
+            tenant: tenant.into(),
+        });
+        self
+    }
+
+    /// Whether any candidate versions are being rolled out.
+    pub fn has_candidates(&self) -> bool {
+        !self.candidates.is_empty()
+    }
+
+    /// Engine enforced for the span: active rules with every canary candidate
+    /// that selects this span swapped in.
+    pub fn engine_for_span(&self, span: &Span) -> RuleEngine {
+        let enforced: Vec<&RuleCandidate> = self
+            .candidates
+            .iter()
+            .filter(|candidate| candidate.mode.enforces(&candidate.rule.id, span))
+            .collect();
+        let mut rules = self.active.clone();
+        for candidate in enforced {
+            swap_rule(&mut rules, &candidate.rule.id, Some(&candidate.rule));
+        }
+        let engine = RuleEngine::new(rules).with_strategy(self.strategy);
+        match &self.metrics {
+            Some(sink) => engine.with_metrics(sink.metrics.clone(), sink.tenant.clone()),
+            None => engine,
+        }
+    }
+
+    /// Applies the enforced rule set to the span and evaluates the opposite
+    /// version of every candidate on an untouched copy for comparison.
+    pub fn apply(&self, span: &mut Span) -> RolloutOutcome {
+        let original = span.clone();
+        let enforced = self.engine_for_span(span);
+        let outcome = enforced.apply(span);
+
+        let shadow = self
+            .candidates
+            .iter()
+            .map(|candidate| {
+                let rule_id = &candidate.rule.id;
+                let candidate_enforced = candidate.mode.enforces(rule_id, &original);
+                let mut alternative = enforced.rules().to_vec();
+                if candidate_enforced {
+                    let active = self.active.iter().find(|rule| &rule.id == rule_id);
+                    swap_rule(&mut alternative, rule_id, active);
+                } else {
+                    swap_rule(&mut alternative, rule_id, Some(&candidate.rule));
+                }
+                let other = RuleEngine::new(alternative)
+                    .with_strategy(self.strategy)
+                    .evaluate(&original)
+                    .decision;
+
+                let (active, candidate_decision) = if candidate_enforced {
+                    (other, outcome.decision.clone())
+                } else {
+                    (outcome.decision.clone(), other)
+                };
+                ShadowEvaluation {
+                    rule_id: rule_id.clone(),
+                    span_id: original.id,
+                    active,
+                    candidate: candidate_decision,
+                    candidate_enforced,
+                }
+            })
+            .collect();
+
+        RolloutOutcome { outcome, shadow }
+    }
+}
+
+/// Replaces (or removes, when `replacement` is `None`) the rule with `id`,
+/// appending the replacement if the rule is new.
+fn swap_rule(rules: &mut Vec<Rule>, id: &str, replacement: Option<&Rule>) {
+    rules.retain(|rule| rule.id != id);
+    if let Some(rule) = replacement.filter(|rule| rule.is_enabled()) {
+        rules.push(rule.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RuleAction, RuleCondition};
+    use logline_protocol::timeline::SpanBuilder;
+    use serde_json::json;
+
+    fn rule(actions: Vec<RuleAction>) -> Rule {
+        Rule {
+            id: "limit".into(),
+            description: None,
+            priority: 10,
+            enabled: true,
+            labels: vec![],
+            stop_processing: false,
+            condition: RuleCondition::Equals {
+                field: "title".into(),
+                value: json!("transfer"),
+            },
+            actions,
+        }
+    }
+
+    fn candidate(mode: RolloutMode) -> RuleCandidate {
+        RuleCandidate {
+            version: 2,
+            rule: rule(vec![RuleAction::Reject {
+                reason: "transfers paused".into(),
+            }]),
+            mode,
+            created_at: Utc::now(),
+            updated_by: None,
+            stats: RolloutStats::default(),
+        }
+    }
+
+    #[test]
+    fn shadow_candidates_are_recorded_but_not_enforced() {
+        let engine = RolloutEngine::new(
+            vec![rule(vec![RuleAction::AddTag { tag: "ok".into() }])],
+            vec![candidate(RolloutMode::Shadow)],
+        );
+        let mut span = SpanBuilder::new("node", "transfer").build();
+
+        let result = engine.apply(&mut span);
+        assert_eq!(result.outcome.decision, Decision::Allow);
+        assert!(span.has_tag("ok"));
+        assert_eq!(result.shadow.len(), 1);
+        assert!(!result.shadow[0].candidate_enforced);
+        assert!(matches!(
+            result.shadow[0].candidate,
+            Decision::Reject { .. }
+        ));
+    }
+
+    #[test]
+    fn canary_enforces_a_deterministic_fraction() {
+        let engine = RolloutEngine::new(
+            vec![rule(vec![RuleAction::Allow])],
+            vec![candidate(RolloutMode::Canary { percent: 30 })],
+        );
+
+        let mut enforced = 0;
+        for _ in 0..1000 {
+            let mut span = SpanBuilder::new("node", "transfer").build();
+            let expected = rollout_bucket("limit", &span.id) < 30;
+            let result = engine.apply(&mut span);
+            assert_eq!(result.outcome.is_reject(), expected);
+            assert_eq!(result.shadow[0].candidate_enforced, expected);
+            if expected {
+                enforced += 1;
+            }
+        }
+        assert!((200..400).contains(&enforced), "enforced {}", enforced);
+    }
+}
@@ -25,6 +25,9 @@ pub struct Rule {
     /// Actions executed when the condition matches.
     #[serde(default)]
     pub actions: Vec<RuleAction>,
+    /// Stop evaluating lower-priority rules once this rule matches.
+    #[serde(default)]
+    pub stop_processing: bool,
 }
 
 impl Rule {
@@ -1,10 +1,14 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
-use axum::response::IntoResponse;
-use axum::routing::{get, post};
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post, put};
 use axum::{Json, Router};
 use futures::{SinkExt, StreamExt};
+use logline_core::identity::LogLineKeyPair;
 use logline_core::websocket::{ServiceMessage, WebSocketEnvelope};
 use logline_protocol::timeline::Span;
 use serde::{Deserialize, Serialize};
@@ -12,7 +16,13 @@ use serde_json::{json, Map, Value};
 use tokio::sync::oneshot;
 use tracing::{debug, info, warn};
 
-use crate::{Decision, EnforcementOutcome, Rule, RuleEngine, RuleStore};
+use crate::{
+    parse_dsl, run_fixtures, validate_rules_with_strategy, BundleImport, Decision,
+    DecisionStrategy, EffectiveRuleSet, EnforcementOutcome, EvaluationTrace, RolloutMode, Rule,
+    RuleBundle, RuleCandidate, RuleEngine, RuleError, RuleFixture, RuleOverride, RuleOverrideEntry,
+    RuleReloadStatus, RuleScope, RuleStats, RuleStore, RuleWatcher, StaleRule, TaskRequest,
+    TrustedSigner, ValidationIssue, ValidationReport,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RuleDocument {
@@ -55,7 +65,21 @@ pub struct EvaluationResponse {
     pub tags: Vec<String>,
     #[serde(default)]
     pub metadata_updates: Map<String, Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transformed_fields: Vec<String>,
+    /// Tasks the rules ask the caller to enqueue.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tasks: Vec<TaskRequest>,
+    /// Derived spans the caller should record.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub derived_spans: Vec<Span>,
+    /// Template problems met while applying actions.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
     pub span: Span,
+    /// Per-rule evaluation trace, present when `explain=true` was requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace: Option<EvaluationTrace>,
 }
 
 impl EvaluationResponse {
@@ -66,9 +90,38 @@ impl EvaluationResponse {
             notes: outcome.notes,
             tags: outcome.added_tags,
             metadata_updates: metadata_updates_to_map(&outcome.metadata_updates),
+            transformed_fields: outcome.transformed_fields,
+            tasks: outcome.tasks,
+            derived_spans: outcome.derived_spans,
+            errors: outcome.errors,
             span,
+            trace: None,
         }
     }
+
+    fn with_trace(mut self, trace: EvaluationTrace) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+}
+
+/// Idle period after which a rule counts as stale.
+const DEFAULT_STALE_DAYS: i64 = 30;
+
+#[derive(Debug, Deserialize)]
+struct StaleQuery {
+    #[serde(default = "default_stale_days")]
+    days: i64,
+}
+
+fn default_stale_days() -> i64 {
+    DEFAULT_STALE_DAYS
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct EvaluateQuery {
+    #[serde(default)]
+    explain: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -102,15 +155,57 @@ impl From<&Decision> for DecisionPayload {
     }
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ValidateRulesRequest {
+    /// Rules to validate, in place of the tenant's rules with the same ids in
+    /// its effective rule set. When neither `rules` nor `source` is provided
+    /// the effective rule set is validated as is.
+    #[serde(default)]
+    pub rules: Option<Vec<Rule>>,
+    /// Rules written in the textual DSL.
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleTestRequest {
+    pub fixtures: Vec<RuleFixture>,
+    /// Rules to test. When neither `rules` nor `source` is provided the
+    /// fixtures run against the tenant's active rules.
+    #[serde(default)]
+    pub rules: Option<Vec<Rule>>,
+    /// Rules written in the textual DSL.
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationResponse {
+    pub valid: bool,
+    pub issues: Vec<ValidationIssue>,
+}
+
 #[derive(Debug, Serialize)]
 struct ErrorResponse {
     code: String,
     message: String,
 }
 
+#[derive(Debug, Serialize)]
+struct InvalidRuleResponse {
+    code: String,
+    message: String,
+    issues: Vec<ValidationIssue>,
+}
+
 #[derive(Clone)]
 struct RuleServiceState {
     store: RuleStore,
+    file_rules: Option<RuleWatcher>,
+    /// Key used to sign exported bundles.
+    signing_key: Option<Arc<LogLineKeyPair>>,
+    /// Bearer token required to change the trust list or import bundles.
+    admin_token: Option<Arc<str>>,
 }
 
 /// Configuration for the rule API.
@@ -120,17 +215,43 @@ pub struct RuleServiceConfig {
     pub bind_address: String,
     #[serde(default)]
     pub engine_ws_url: Option<String>,
+    /// Rules file or directory applied to every tenant and reloaded when it
+    /// changes.
+    #[serde(default)]
+    pub rules_path: Option<String>,
+    #[serde(default = "default_rules_reload_interval_secs")]
+    pub rules_reload_interval_secs: u64,
+    /// JSON-encoded `LogLineKeyPair` used to sign exported rule bundles.
+    #[serde(default)]
+    pub bundle_signing_key_path: Option<String>,
+    /// Public keys trusted to sign imported bundles at startup.
+    #[serde(default)]
+    pub trusted_bundle_signers: Vec<String>,
+    /// Bearer token required to change the bundle trust list and import
+    /// bundles; those routes are refused when unset. Falls back to
+    /// `RULES_ADMIN_TOKEN`.
+    #[serde(default)]
+    pub admin_token: Option<String>,
 }
 
 fn default_bind_address() -> String {
     "0.0.0.0:8081".to_string()
 }
 
+fn default_rules_reload_interval_secs() -> u64 {
+    5
+}
+
 impl Default for RuleServiceConfig {
     fn default() -> Self {
         Self {
             bind_address: default_bind_address(),
             engine_ws_url: None,
+            rules_path: None,
+            rules_reload_interval_secs: default_rules_reload_interval_secs(),
+            bundle_signing_key_path: None,
+            trusted_bundle_signers: Vec::new(),
+            admin_token: None,
         }
     }
 }
@@ -144,19 +265,101 @@ pub struct RuleApiBuilder {
 impl RuleApiBuilder {
     pub fn new(store: RuleStore) -> Self {
         Self {
-            state: RuleServiceState { store },
+            state: RuleServiceState {
+                store,
+                file_rules: None,
+                signing_key: None,
+                admin_token: None,
+            },
         }
     }
 
+    /// Adds the watcher's file-based rules to the platform layer, so they
+    /// apply to every tenant.
+    pub fn with_file_rules(mut self, watcher: RuleWatcher) -> Self {
+        self.state.store.attach_file_rules(watcher.clone());
+        self.state.file_rules = Some(watcher);
+        self
+    }
+
+    /// Enables bundle export, signing with the given key.
+    pub fn with_signing_key(mut self, keypair: LogLineKeyPair) -> Self {
+        self.state.signing_key = Some(Arc::new(keypair));
+        self
+    }
+
+    /// Requires `token` as bearer token on the bundle trust list and import
+    /// routes.
+    pub fn with_admin_token(mut self, token: impl Into<String>) -> Self {
+        self.state.admin_token = Some(Arc::from(token.into()));
+        self
+    }
+
     pub fn into_router(self) -> Router {
         Router::new()
             .route("/health", get(health))
+            .route("/rules/status", get(file_rules_status))
+            .route(
+                "/platform/rules",
+                get(list_platform_rules).post(upsert_platform_rule),
+            )
+            .route("/platform/rules/:rule_id", put(disable_platform_rule))
+            .route(
+                "/organizations/:organization/rules",
+                get(list_organization_rules).post(upsert_organization_rule),
+            )
+            .route(
+                "/organizations/:organization/rules/:rule_id",
+                put(disable_organization_rule),
+            )
+            .route(
+                "/organizations/:organization/overrides",
+                get(list_organization_overrides),
+            )
+            .route(
+                "/organizations/:organization/overrides/:rule_id",
+                put(put_organization_override).delete(remove_organization_override),
+            )
+            .route("/metrics", get(prometheus_metrics))
+            .route("/bundles", get(list_bundle_imports))
+            .route("/bundles/import", post(import_bundle))
+            .route("/bundles/export", post(export_bundle))
+            .route("/bundles/trust", get(list_trusted_signers))
+            .route(
+                "/bundles/trust/:public_key",
+                put(trust_signer).delete(revoke_signer),
+            )
             .route("/tenants", get(list_tenants))
             .route("/tenants/:tenant/rules", get(list_rules).post(upsert_rule))
+            // `rules:<verb>` collection actions. The router captures the verb
+            // (including its leading colon) as the `action` parameter.
+            .route("/tenants/:tenant/rules:action", post(rules_action))
+            .route("/tenants/:tenant/rules/stats", get(rule_stats))
+            .route("/tenants/:tenant/rules/stale", get(stale_rules))
             .route(
                 "/tenants/:tenant/rules/:rule_id",
                 get(get_rule).put(disable_rule),
             )
+            .route(
+                "/tenants/:tenant/rules/:rule_id/candidate",
+                post(stage_candidate).delete(discard_candidate),
+            )
+            .route(
+                "/tenants/:tenant/rules/:rule_id/promote",
+                post(promote_candidate),
+            )
+            .route("/tenants/:tenant/rollouts", get(list_rollouts))
+            .route("/tenants/:tenant/organization", put(assign_organization))
+            .route(
+                "/tenants/:tenant/strategy",
+                get(get_strategy).put(set_strategy),
+            )
+            .route("/tenants/:tenant/overrides", get(list_tenant_overrides))
+            .route(
+                "/tenants/:tenant/overrides/:rule_id",
+                put(put_tenant_override).delete(remove_tenant_override),
+            )
+            .route("/tenants/:tenant/effective-rules", get(effective_rules))
             .route("/tenants/:tenant/evaluate", post(evaluate_span))
             .route("/ws/service", get(service_ws_upgrade))
             .with_state(self.state)
@@ -166,7 +369,36 @@ impl RuleApiBuilder {
     pub async fn serve(self, config: RuleServiceConfig) -> anyhow::Result<oneshot::Sender<()>> {
         let (tx, rx) = oneshot::channel();
         let listener = tokio::net::TcpListener::bind(&config.bind_address).await?;
-        let state = self.state.clone();
+        let mut state = self.state.clone();
+        if state.file_rules.is_none() {
+            if let Some(path) = config.rules_path.as_deref() {
+                let watcher = RuleWatcher::new(path)?;
+                state.store.attach_file_rules(watcher.clone());
+                state.file_rules = Some(watcher);
+            }
+        }
+        if state.signing_key.is_none() {
+            if let Some(path) = config.bundle_signing_key_path.as_deref() {
+                let contents = std::fs::read_to_string(path)?;
+                let keypair: LogLineKeyPair = serde_json::from_str(&contents)?;
+                state.signing_key = Some(Arc::new(keypair));
+            }
+        }
+        for public_key in &config.trusted_bundle_signers {
+            state.store.trust_signer(public_key, None)?;
+        }
+        if state.admin_token.is_none() {
+            state.admin_token = config
+                .admin_token
+                .clone()
+                .or_else(|| std::env::var("RULES_ADMIN_TOKEN").ok())
+                .map(Arc::from);
+        }
+        let reloader = state.file_rules.as_ref().map(|watcher| {
+            watcher.spawn(Duration::from_secs(
+                config.rules_reload_interval_secs.max(1),
+            ))
+        });
 
         crate::ws_client::start_service_mesh(&config);
 
@@ -179,6 +411,9 @@ impl RuleApiBuilder {
                 })
                 .await
                 .ok();
+            if let Some(reloader) = reloader {
+                reloader.abort();
+            }
         });
 
         Ok(tx)
@@ -189,6 +424,24 @@ async fn health() -> impl IntoResponse {
     Json(serde_json::json!({ "status": "ok" }))
 }
 
+async fn file_rules_status(
+    State(state): State<RuleServiceState>,
+) -> Result<Json<RuleReloadStatus>, (StatusCode, Json<ErrorResponse>)> {
+    state
+        .file_rules
+        .as_ref()
+        .map(|watcher| Json(watcher.status()))
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    code: "not_found".into(),
+                    message: "file-based rules are not configured".into(),
+                }),
+            )
+        })
+}
+
 async fn list_tenants(State(state): State<RuleServiceState>) -> impl IntoResponse {
     Json(state.store.tenants())
 }
@@ -242,14 +495,17 @@ async fn upsert_rule(
     State(state): State<RuleServiceState>,
     Path(tenant): Path<String>,
     Json(payload): Json<RuleDocument>,
-) -> Result<Json<RuleResponse>, (StatusCode, String)> {
+) -> Result<Json<RuleResponse>, Response> {
     if !payload.tenant_id.is_empty() && payload.tenant_id != tenant {
         return Err((
             StatusCode::BAD_REQUEST,
             "tenant identifier mismatch".to_string(),
-        ));
+        )
+            .into_response());
     }
 
+    check_rule_for_tenant(&state, &tenant, &payload.rule).map_err(IntoResponse::into_response)?;
+
     let entry = state
         .store
         .put_rule(&tenant, payload.rule, payload.updated_by)
@@ -257,15 +513,622 @@ async fn upsert_rule(
     Ok(Json::<RuleResponse>(entry))
 }
 
+/// Validates the rule against the tenant's rule set as it would look with the
+/// rule in place, so shadowing is judged in context.
+fn check_rule_for_tenant(
+    state: &RuleServiceState,
+    tenant: &str,
+    rule: &Rule,
+) -> Result<(), (StatusCode, Json<InvalidRuleResponse>)> {
+    check_rule_in_scope(state, &RuleScope::tenant(tenant), rule)
+}
+
+/// Validates `rules` in the context of the rules they will be evaluated
+/// with: the tenant's merged platform, organization and tenant set under its
+/// decision strategy, or the layer itself for shared layers. Rules replace
+/// those with the same id; an empty `rules` validates the context as is.
+fn validate_in_scope(
+    state: &RuleServiceState,
+    scope: &RuleScope,
+    rules: &[Rule],
+) -> ValidationReport {
+    let (candidate, strategy) = state.store.rules_in_scope(scope, rules);
+    let mut report = validate_rules_with_strategy(&candidate, strategy);
+    if !rules.is_empty() {
+        report
+            .issues
+            .retain(|issue| rules.iter().any(|rule| rule.id == issue.rule_id));
+    }
+    report
+}
+
+/// Validates the rule in the context of the rules it will be evaluated with.
+fn check_rule_in_scope(
+    state: &RuleServiceState,
+    scope: &RuleScope,
+    rule: &Rule,
+) -> Result<(), (StatusCode, Json<InvalidRuleResponse>)> {
+    let report = validate_in_scope(state, scope, std::slice::from_ref(rule));
+    let errors: Vec<ValidationIssue> = report.errors_for(&rule.id).into_iter().cloned().collect();
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    Err((
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(InvalidRuleResponse {
+            code: "invalid_rule".into(),
+            message: format!("rule {} failed validation", rule.id),
+            issues: errors,
+        }),
+    ))
+}
+
+/// Rule submitted to the platform or an organization layer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerRuleDocument {
+    pub rule: Rule,
+    #[serde(default)]
+    pub updated_by: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OverrideDocument {
+    #[serde(flatten)]
+    change: RuleOverride,
+    #[serde(default)]
+    updated_by: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OrganizationAssignment {
+    #[serde(default)]
+    organization_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StrategyDocument {
+    strategy: DecisionStrategy,
+}
+
+fn scope_rules(state: &RuleServiceState, scope: &RuleScope) -> Json<Vec<RuleResponse>> {
+    Json(
+        state
+            .store
+            .list_scope_rules(scope)
+            .into_iter()
+            .map(RuleResponse::from)
+            .collect(),
+    )
+}
+
+fn upsert_scope_rule(
+    state: &RuleServiceState,
+    scope: &RuleScope,
+    payload: LayerRuleDocument,
+) -> Result<Json<RuleResponse>, (StatusCode, Json<InvalidRuleResponse>)> {
+    check_rule_in_scope(state, scope, &payload.rule)?;
+    let entry = state
+        .store
+        .put_scope_rule(scope, payload.rule, payload.updated_by);
+    Ok(Json(entry.into()))
+}
+
+fn disable_scope_rule(
+    state: &RuleServiceState,
+    scope: &RuleScope,
+    rule_id: &str,
+    payload: DisableRequest,
+) -> Result<Json<RuleResponse>, (StatusCode, Json<ErrorResponse>)> {
+    state
+        .store
+        .disable_scope_rule(scope, rule_id, payload.updated_by)
+        .map(RuleResponse::from)
+        .map(Json)
+        .map_err(|_| rule_not_found(rule_id))
+}
+
+fn put_override(
+    state: &RuleServiceState,
+    scope: &RuleScope,
+    rule_id: &str,
+    payload: OverrideDocument,
+) -> Response {
+    if let RuleOverride::Replace { rule } = &payload.change {
+        let mut rule = rule.clone();
+        rule.id = rule_id.to_string();
+        if let Err(invalid) = check_rule_in_scope(state, scope, &rule) {
+            return invalid.into_response();
+        }
+    }
+    match state
+        .store
+        .set_override(scope, rule_id, payload.change, payload.updated_by)
+    {
+        Ok(entry) => Json(entry).into_response(),
+        Err(err) => bad_request(err.to_string()),
+    }
+}
+
+async fn list_platform_rules(State(state): State<RuleServiceState>) -> impl IntoResponse {
+    scope_rules(&state, &RuleScope::Platform)
+}
+
+async fn upsert_platform_rule(
+    State(state): State<RuleServiceState>,
+    Json(payload): Json<LayerRuleDocument>,
+) -> Result<Json<RuleResponse>, (StatusCode, Json<InvalidRuleResponse>)> {
+    upsert_scope_rule(&state, &RuleScope::Platform, payload)
+}
+
+async fn disable_platform_rule(
+    State(state): State<RuleServiceState>,
+    Path(rule_id): Path<String>,
+    Json(payload): Json<DisableRequest>,
+) -> Result<Json<RuleResponse>, (StatusCode, Json<ErrorResponse>)> {
+    disable_scope_rule(&state, &RuleScope::Platform, &rule_id, payload)
+}
+
+async fn list_organization_rules(
+    State(state): State<RuleServiceState>,
+    Path(organization): Path<String>,
+) -> impl IntoResponse {
+    scope_rules(&state, &RuleScope::organization(organization))
+}
+
+async fn upsert_organization_rule(
+    State(state): State<RuleServiceState>,
+    Path(organization): Path<String>,
+    Json(payload): Json<LayerRuleDocument>,
+) -> Result<Json<RuleResponse>, (StatusCode, Json<InvalidRuleResponse>)> {
+    upsert_scope_rule(&state, &RuleScope::organization(organization), payload)
+}
+
+async fn disable_organization_rule(
+    State(state): State<RuleServiceState>,
+    Path((organization, rule_id)): Path<(String, String)>,
+    Json(payload): Json<DisableRequest>,
+) -> Result<Json<RuleResponse>, (StatusCode, Json<ErrorResponse>)> {
+    disable_scope_rule(
+        &state,
+        &RuleScope::organization(organization),
+        &rule_id,
+        payload,
+    )
+}
+
+async fn list_organization_overrides(
+    State(state): State<RuleServiceState>,
+    Path(organization): Path<String>,
+) -> impl IntoResponse {
+    Json(
+        state
+            .store
+            .overrides(&RuleScope::organization(organization)),
+    )
+}
+
+async fn put_organization_override(
+    State(state): State<RuleServiceState>,
+    Path((organization, rule_id)): Path<(String, String)>,
+    Json(payload): Json<OverrideDocument>,
+) -> Response {
+    put_override(
+        &state,
+        &RuleScope::organization(organization),
+        &rule_id,
+        payload,
+    )
+}
+
+async fn remove_organization_override(
+    State(state): State<RuleServiceState>,
+    Path((organization, rule_id)): Path<(String, String)>,
+) -> Result<Json<RuleOverrideEntry>, (StatusCode, Json<ErrorResponse>)> {
+    state
+        .store
+        .remove_override(&RuleScope::organization(organization), &rule_id)
+        .map(Json)
+        .map_err(|_| rule_not_found(&rule_id))
+}
+
+async fn list_tenant_overrides(
+    State(state): State<RuleServiceState>,
+    Path(tenant): Path<String>,
+) -> impl IntoResponse {
+    Json(state.store.overrides(&RuleScope::tenant(tenant)))
+}
+
+async fn put_tenant_override(
+    State(state): State<RuleServiceState>,
+    Path((tenant, rule_id)): Path<(String, String)>,
+    Json(payload): Json<OverrideDocument>,
+) -> Response {
+    put_override(&state, &RuleScope::tenant(tenant), &rule_id, payload)
+}
+
+async fn remove_tenant_override(
+    State(state): State<RuleServiceState>,
+    Path((tenant, rule_id)): Path<(String, String)>,
+) -> Result<Json<RuleOverrideEntry>, (StatusCode, Json<ErrorResponse>)> {
+    state
+        .store
+        .remove_override(&RuleScope::tenant(tenant), &rule_id)
+        .map(Json)
+        .map_err(|_| rule_not_found(&rule_id))
+}
+
+async fn assign_organization(
+    State(state): State<RuleServiceState>,
+    Path(tenant): Path<String>,
+    Json(payload): Json<OrganizationAssignment>,
+) -> Json<EffectiveRuleSet> {
+    state
+        .store
+        .set_organization(&tenant, payload.organization_id);
+    Json(state.store.effective_rules(&tenant))
+}
+
+async fn get_strategy(
+    State(state): State<RuleServiceState>,
+    Path(tenant): Path<String>,
+) -> Json<StrategyDocument> {
+    Json(StrategyDocument {
+        strategy: state.store.strategy_for(&tenant),
+    })
+}
+
+async fn set_strategy(
+    State(state): State<RuleServiceState>,
+    Path(tenant): Path<String>,
+    Json(payload): Json<StrategyDocument>,
+) -> Json<StrategyDocument> {
+    state.store.set_strategy(&tenant, payload.strategy);
+    Json(payload)
+}
+
+async fn effective_rules(
+    State(state): State<RuleServiceState>,
+    Path(tenant): Path<String>,
+) -> Json<EffectiveRuleSet> {
+    Json(state.store.effective_rules(&tenant))
+}
+
+#[derive(Debug, Deserialize)]
+struct CandidateDocument {
+    rule: Rule,
+    #[serde(flatten)]
+    mode: RolloutMode,
+    #[serde(default)]
+    updated_by: Option<String>,
+}
+
+async fn list_rollouts(
+    State(state): State<RuleServiceState>,
+    Path(tenant): Path<String>,
+) -> impl IntoResponse {
+    Json(state.store.candidates(&tenant))
+}
+
+async fn stage_candidate(
+    State(state): State<RuleServiceState>,
+    Path((tenant, rule_id)): Path<(String, String)>,
+    Json(payload): Json<CandidateDocument>,
+) -> Result<Json<RuleCandidate>, Response> {
+    let mut rule = payload.rule;
+    if rule.id.trim().is_empty() {
+        rule.id = rule_id.clone();
+    }
+    if rule.id != rule_id {
+        return Err(bad_request("rule identifier mismatch".to_string()));
+    }
+    if let RolloutMode::Canary { percent } = payload.mode {
+        if percent > 100 {
+            return Err(bad_request(
+                "canary percent must be between 0 and 100".to_string(),
+            ));
+        }
+    }
+
+    check_rule_for_tenant(&state, &tenant, &rule).map_err(IntoResponse::into_response)?;
+    Ok(Json(state.store.put_candidate(
+        &tenant,
+        rule,
+        payload.mode,
+        payload.updated_by,
+    )))
+}
+
+async fn discard_candidate(
+    State(state): State<RuleServiceState>,
+    Path((tenant, rule_id)): Path<(String, String)>,
+) -> Result<Json<RuleCandidate>, (StatusCode, Json<ErrorResponse>)> {
+    state
+        .store
+        .discard_candidate(&tenant, &rule_id)
+        .map(Json)
+        .map_err(|_| rule_not_found(&rule_id))
+}
+
+async fn promote_candidate(
+    State(state): State<RuleServiceState>,
+    Path((tenant, rule_id)): Path<(String, String)>,
+    Json(payload): Json<DisableRequest>,
+) -> Result<Json<RuleResponse>, (StatusCode, Json<ErrorResponse>)> {
+    state
+        .store
+        .promote_candidate(&tenant, &rule_id, payload.updated_by)
+        .map(RuleResponse::from)
+        .map(Json)
+        .map_err(|_| rule_not_found(&rule_id))
+}
+
+async fn rules_action(
+    State(state): State<RuleServiceState>,
+    Path((tenant, action)): Path<(String, String)>,
+    Json(payload): Json<Value>,
+) -> Response {
+    match action.trim_start_matches(':') {
+        "validate" => match serde_json::from_value(payload) {
+            Ok(request) => validate_tenant_rules(&state, &tenant, request),
+            Err(err) => bad_request(err.to_string()),
+        },
+        "test" => match serde_json::from_value(payload) {
+            Ok(request) => test_tenant_rules(&state, &tenant, request),
+            Err(err) => bad_request(err.to_string()),
+        },
+        other => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                code: "not_found".into(),
+                message: format!("unknown rules action {}", other),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+fn validate_tenant_rules(
+    state: &RuleServiceState,
+    tenant: &str,
+    request: ValidateRulesRequest,
+) -> Response {
+    let mut rules = request.rules.unwrap_or_default();
+    if let Some(source) = request.source.as_deref() {
+        match parse_dsl(source) {
+            Ok(mut parsed) => rules.append(&mut parsed),
+            Err(err) => return bad_request(err.to_string()),
+        }
+    }
+    let report = validate_in_scope(state, &RuleScope::tenant(tenant), &rules);
+    Json(ValidationResponse {
+        valid: report.is_valid(),
+        issues: report.issues,
+    })
+    .into_response()
+}
+
+fn test_tenant_rules(state: &RuleServiceState, tenant: &str, request: RuleTestRequest) -> Response {
+    let mut rules = request.rules.unwrap_or_default();
+    if let Some(source) = request.source.as_deref() {
+        match parse_dsl(source) {
+            Ok(mut parsed) => rules.append(&mut parsed),
+            Err(err) => return bad_request(err.to_string()),
+        }
+    }
+
+    // Supplied rules are tried as they would run once published: on top of
+    // the platform and organization layers, under the tenant's strategy.
+    let engine = if rules.is_empty() {
+        state.store.engine_for(tenant)
+    } else {
+        let (rules, strategy) = state
+            .store
+            .rules_in_scope(&RuleScope::tenant(tenant), &rules);
+        RuleEngine::new(rules).with_strategy(strategy)
+    };
+    Json(run_fixtures(&engine, &request.fixtures)).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportRequest {
+    scope: RuleScope,
+    name: String,
+    version: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TrustRequest {
+    #[serde(default)]
+    label: Option<String>,
+}
+
+async fn list_bundle_imports(State(state): State<RuleServiceState>) -> Json<Vec<BundleImport>> {
+    Json(state.store.bundle_imports())
+}
+
+async fn import_bundle(
+    State(state): State<RuleServiceState>,
+    headers: HeaderMap,
+    Json(bundle): Json<RuleBundle>,
+) -> Result<Json<BundleImport>, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&state, &headers)?;
+    state.store.import_bundle(bundle).map(Json).map_err(|err| {
+        let (status, code) = match &err {
+            RuleError::UntrustedSigner(_) => (StatusCode::FORBIDDEN, "untrusted_signer"),
+            RuleError::StaleBundle(_) => (StatusCode::CONFLICT, "stale_bundle"),
+            RuleError::Invalid(_) => (StatusCode::UNPROCESSABLE_ENTITY, "invalid_rules"),
+            _ => (StatusCode::BAD_REQUEST, "invalid_bundle"),
+        };
+        warn!(%err, "rejected rule bundle");
+        (
+            status,
+            Json(ErrorResponse {
+                code: code.into(),
+                message: err.to_string(),
+            }),
+        )
+    })
+}
+
+async fn export_bundle(
+    State(state): State<RuleServiceState>,
+    Json(request): Json<ExportRequest>,
+) -> Result<Json<RuleBundle>, (StatusCode, Json<ErrorResponse>)> {
+    let Some(keypair) = state.signing_key.as_deref() else {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ErrorResponse {
+                code: "signing_key_missing".into(),
+                message: "no bundle signing key is configured".into(),
+            }),
+        ));
+    };
+    state
+        .store
+        .export_bundle(&request.scope, &request.name, request.version, keypair)
+        .map(Json)
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    code: "export_failed".into(),
+                    message: err.to_string(),
+                }),
+            )
+        })
+}
+
+/// Lets the request through only when it carries the admin bearer token.
+/// Admin routes are closed when no token is configured.
+fn require_admin(
+    state: &RuleServiceState,
+    headers: &HeaderMap,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let token = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim);
+    let Some(token) = token else {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                code: "unauthorized".into(),
+                message: "admin bearer token required".into(),
+            }),
+        ));
+    };
+    let matches = state.admin_token.as_deref().is_some_and(|expected| {
+        expected.len() == token.len()
+            && expected
+                .bytes()
+                .zip(token.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    });
+    if matches {
+        Ok(())
+    } else {
+        Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                code: "forbidden".into(),
+                message: "bundle trust and imports are restricted to administrators".into(),
+            }),
+        ))
+    }
+}
+
+async fn list_trusted_signers(State(state): State<RuleServiceState>) -> Json<Vec<TrustedSigner>> {
+    Json(state.store.trusted_signers())
+}
+
+async fn trust_signer(
+    State(state): State<RuleServiceState>,
+    headers: HeaderMap,
+    Path(public_key): Path<String>,
+    Json(payload): Json<TrustRequest>,
+) -> Result<Json<TrustedSigner>, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&state, &headers)?;
+    state
+        .store
+        .trust_signer(&public_key, payload.label)
+        .map(Json)
+        .map_err(|err| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    code: "invalid_public_key".into(),
+                    message: err.to_string(),
+                }),
+            )
+        })
+}
+
+async fn revoke_signer(
+    State(state): State<RuleServiceState>,
+    headers: HeaderMap,
+    Path(public_key): Path<String>,
+) -> Result<Json<TrustedSigner>, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&state, &headers)?;
+    state
+        .store
+        .revoke_signer(&public_key)
+        .map(Json)
+        .map_err(|_| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    code: "not_found".into(),
+                    message: format!("signer {} is not trusted", public_key),
+                }),
+            )
+        })
+}
+
+async fn rule_stats(
+    State(state): State<RuleServiceState>,
+    Path(tenant): Path<String>,
+) -> Json<Vec<RuleStats>> {
+    Json(state.store.metrics().tenant_stats(&tenant))
+}
+
+async fn stale_rules(
+    State(state): State<RuleServiceState>,
+    Path(tenant): Path<String>,
+    Query(query): Query<StaleQuery>,
+) -> Json<Vec<StaleRule>> {
+    let max_idle = chrono::Duration::days(query.days.max(0));
+    Json(state.store.stale_rules(&tenant, max_idle))
+}
+
+async fn prometheus_metrics(State(state): State<RuleServiceState>) -> impl IntoResponse {
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4",
+        )],
+        state.store.metrics().render_prometheus(),
+    )
+}
+
 async fn evaluate_span(
     State(state): State<RuleServiceState>,
     Path(tenant): Path<String>,
+    Query(query): Query<EvaluateQuery>,
     Json(payload): Json<EvaluationRequest>,
 ) -> impl IntoResponse {
-    let engine: RuleEngine = state.store.engine_for(&tenant);
+    let rollout = state.store.rollout_for(&tenant);
     let mut span = payload.span;
-    let outcome = engine.apply(&mut span);
-    Json(EvaluationResponse::from_outcome(outcome, span))
+    if query.explain {
+        let engine: RuleEngine = rollout.engine_for_span(&span);
+        let (outcome, trace) = engine.apply_with_trace(&mut span);
+        return Json(EvaluationResponse::from_outcome(outcome, span).with_trace(trace));
+    }
+    let result = rollout.apply(&mut span);
+    state.store.record_shadow(&tenant, &result.shadow);
+    Json(EvaluationResponse::from_outcome(result.outcome, span))
 }
 
 async fn service_ws_upgrade(
@@ -338,8 +1201,9 @@ async fn process_service_message(
         } => {
             let mut span: Span =
                 serde_json::from_value(span).map_err(|err| anyhow::anyhow!(err.to_string()))?;
-            let engine = state.store.engine_for(&tenant_id);
-            let outcome = engine.apply(&mut span);
+            let result = state.store.rollout_for(&tenant_id).apply(&mut span);
+            state.store.record_shadow(&tenant_id, &result.shadow);
+            let outcome = result.outcome;
             let response = ServiceMessage::RuleExecutionResult {
                 result_id: request_id.clone(),
                 success: !outcome.is_reject(),
@@ -393,6 +1257,10 @@ fn outcome_to_value(outcome: &EnforcementOutcome, span: &Span) -> Value {
         "notes": outcome.notes.clone(),
         "tags": outcome.added_tags.clone(),
         "metadata_updates": metadata,
+        "transformed_fields": outcome.transformed_fields.clone(),
+        "tasks": outcome.tasks.clone(),
+        "derived_spans": outcome.derived_spans.clone(),
+        "errors": outcome.errors.clone(),
         "span": span,
     })
 }
@@ -413,6 +1281,17 @@ fn decision_to_value(decision: &Decision) -> Value {
     }
 }
 
+fn bad_request(message: String) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse {
+            code: "bad_request".into(),
+            message,
+        }),
+    )
+        .into_response()
+}
+
 fn rule_not_found(id: &str) -> (StatusCode, Json<ErrorResponse>) {
     (
         StatusCode::NOT_FOUND,
@@ -422,3 +1301,153 @@ fn rule_not_found(id: &str) -> (StatusCode, Json<ErrorResponse>) {
         }),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn post(router: Router, uri: &str, body: Value) -> (StatusCode, Value) {
+        let response = router
+            .oneshot(
+                Request::post(uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn validates_rules_against_the_effective_rule_set() {
+        let store = RuleStore::new();
+        store.put_scope_rule(
+            &RuleScope::Platform,
+            parse_dsl(r#"rule deny-all priority 1 then reject "closed""#)
+                .unwrap()
+                .remove(0),
+            None,
+        );
+        let request = json!({ "source": r#"rule after priority 10 then tag "seen""# });
+
+        let router = RuleApiBuilder::new(store.clone()).into_router();
+        let (status, body) = post(router, "/tenants/acme/rules:validate", request.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["valid"], json!(true));
+        assert_eq!(
+            body["issues"],
+            json!([{
+                "rule_id": "after",
+                "severity": "warning",
+                "kind": "unreachable_rule",
+                "message": "rule can never run: rule 'deny-all' rejects every span first",
+            }])
+        );
+
+        store.set_strategy("acme", DecisionStrategy::AllowOverrides);
+        let router = RuleApiBuilder::new(store).into_router();
+        let (_, body) = post(router, "/tenants/acme/rules:validate", request).await;
+        assert_eq!(body["issues"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn runs_fixtures_through_rules_test() {
+        let router = RuleApiBuilder::new(RuleStore::new()).into_router();
+        let (status, body) = post(
+            router.clone(),
+            "/tenants/acme/rules:test",
+            json!({
+                "source": r#"rule block-ghosts priority 10 when span_type == "ghost" then reject "no ghosts""#,
+                "fixtures": [
+                    { "name": "ghost", "span": { "span_type": "ghost" }, "expect": { "decision": "reject" } },
+                    { "name": "user", "span": { "span_type": "user" }, "expect": { "decision": "reject" } },
+                ],
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            (body["passed"].clone(), body["failed"].clone()),
+            (json!(1), json!(1))
+        );
+
+        let (status, _) = post(router, "/tenants/acme/rules:publish", json!({})).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn tests_supplied_rules_on_top_of_the_platform_layer() {
+        let store = RuleStore::new();
+        store.put_scope_rule(
+            &RuleScope::Platform,
+            parse_dsl(r#"rule no-users priority 1 when span_type == "user" then reject "closed""#)
+                .unwrap()
+                .remove(0),
+            None,
+        );
+        let router = RuleApiBuilder::new(store).into_router();
+        let (status, body) = post(
+            router,
+            "/tenants/acme/rules:test",
+            json!({
+                "source": r#"rule allow-users priority 10 when span_type == "user" then tag "seen""#,
+                "fixtures": [
+                    { "name": "user", "span": { "span_type": "user" }, "expect": { "decision": "reject" } },
+                ],
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            (body["passed"].clone(), body["failed"].clone()),
+            (json!(1), json!(0))
+        );
+    }
+
+    #[tokio::test]
+    async fn restricts_bundle_trust_to_admins() {
+        let keypair = LogLineKeyPair::generate("publisher", None, None, false);
+        let router = RuleApiBuilder::new(RuleStore::new())
+            .with_admin_token("secret")
+            .into_router();
+        let trust = |key: &str, token: Option<&str>| {
+            let mut request = Request::put(format!("/bundles/trust/{}", key))
+                .header("content-type", "application/json");
+            if let Some(token) = token {
+                request = request.header("authorization", format!("Bearer {}", token));
+            }
+            router
+                .clone()
+                .oneshot(request.body(Body::from("{}")).unwrap())
+        };
+
+        let key = keypair.id.public_key.as_str();
+        assert_eq!(
+            trust(key, None).await.unwrap().status(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            trust(key, Some("guess")).await.unwrap().status(),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            trust("not-a-key", Some("secret")).await.unwrap().status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            trust(key, Some("secret")).await.unwrap().status(),
+            StatusCode::OK
+        );
+
+        let bundle =
+            RuleBundle::sign("baseline", 1, RuleScope::Platform, vec![], &keypair).unwrap();
+        let (status, _) = post(router, "/bundles/import", json!(bundle)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
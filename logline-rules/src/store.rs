@@ -2,10 +2,20 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use chrono::{DateTime, Utc};
+use logline_core::identity::LogLineKeyPair;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::bundle::{decode_public_key, BundleImport, RuleBundle, TrustedSigner};
+use crate::layer::{
+    merge_layers, EffectiveRuleSet, LayerInput, RuleOverride, RuleOverrideEntry, RuleScope,
+};
+use crate::metrics::{RuleMetrics, StaleRule};
+use crate::rollout::{RolloutEngine, RolloutMode, RolloutStats, RuleCandidate, ShadowEvaluation};
+use crate::strategy::DecisionStrategy;
+use crate::validate::validate_rules_with_strategy;
+use crate::watcher::RuleWatcher;
 use crate::{Rule, RuleEngine, RuleError};
 
 /// Versioned history entry for a stored rule.
@@ -29,14 +39,44 @@ impl RuleHistoryEntry {
 }
 
 #[derive(Default)]
-struct TenantRules {
+struct ScopedRules {
     rules: HashMap<String, Vec<RuleHistoryEntry>>,
+    candidates: HashMap<String, RuleCandidate>,
+    overrides: HashMap<String, RuleOverrideEntry>,
+}
+
+impl ScopedRules {
+    fn latest(&self) -> Vec<RuleHistoryEntry> {
+        self.rules
+            .values()
+            .filter_map(|versions| versions.last().cloned())
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct StoreState {
+    scopes: HashMap<RuleScope, ScopedRules>,
+    /// Organization each tenant inherits rules from.
+    organizations: HashMap<String, String>,
+    /// Decision strategy per tenant; tenants without one use the default.
+    strategies: HashMap<String, DecisionStrategy>,
+    /// Public keys allowed to sign imported bundles, keyed by key.
+    trusted_signers: HashMap<String, TrustedSigner>,
+    /// Most recent import of each bundle, keyed by bundle name.
+    bundles: HashMap<String, BundleImport>,
 }
 
 /// In-memory multi-tenant rule store with version tracking.
+///
+/// Rules are kept per [`RuleScope`]; a tenant's effective rules are the
+/// platform layer, its organization's layer and its own rules merged in that
+/// order.
 #[derive(Default, Clone)]
 pub struct RuleStore {
-    inner: Arc<RwLock<HashMap<String, TenantRules>>>,
+    inner: Arc<RwLock<StoreState>>,
+    file_rules: Arc<RwLock<Option<RuleWatcher>>>,
+    metrics: RuleMetrics,
 }
 
 impl RuleStore {
@@ -48,21 +88,28 @@ impl RuleStore {
     /// Returns the list of tenants currently tracked.
     pub fn tenants(&self) -> Vec<String> {
         let inner = self.inner.read();
-        inner.keys().cloned().collect()
+        inner
+            .scopes
+            .keys()
+            .filter_map(|scope| match scope {
+                RuleScope::Tenant(tenant) => Some(tenant.clone()),
+                _ => None,
+            })
+            .collect()
     }
 
     /// Returns the latest rule versions for the provided tenant.
     pub fn list_rules(&self, tenant: &str) -> Vec<RuleHistoryEntry> {
+        self.list_scope_rules(&RuleScope::tenant(tenant))
+    }
+
+    /// Returns the latest rule versions defined directly in the scope.
+    pub fn list_scope_rules(&self, scope: &RuleScope) -> Vec<RuleHistoryEntry> {
         let inner = self.inner.read();
         inner
-            .get(tenant)
-            .map(|rules| {
-                rules
-                    .rules
-                    .values()
-                    .filter_map(|versions| versions.last().cloned())
-                    .collect()
-            })
+            .scopes
+            .get(scope)
+            .map(ScopedRules::latest)
             .unwrap_or_default()
     }
 
@@ -70,16 +117,23 @@ impl RuleStore {
     pub fn rule_history(&self, tenant: &str, rule_id: &str) -> Vec<RuleHistoryEntry> {
         let inner = self.inner.read();
         inner
-            .get(tenant)
+            .scopes
+            .get(&RuleScope::tenant(tenant))
             .and_then(|rules| rules.rules.get(rule_id).cloned())
             .unwrap_or_default()
     }
 
     /// Returns the latest version of a rule, if available.
     pub fn latest_rule(&self, tenant: &str, rule_id: &str) -> Option<RuleHistoryEntry> {
+        self.latest_scope_rule(&RuleScope::tenant(tenant), rule_id)
+    }
+
+    /// Returns the latest version of a rule defined directly in the scope.
+    pub fn latest_scope_rule(&self, scope: &RuleScope, rule_id: &str) -> Option<RuleHistoryEntry> {
         let inner = self.inner.read();
         inner
-            .get(tenant)
+            .scopes
+            .get(scope)
             .and_then(|rules| rules.rules.get(rule_id))
             .and_then(|versions| versions.last().cloned())
     }
@@ -88,18 +142,28 @@ impl RuleStore {
     pub fn put_rule(
         &self,
         tenant: &str,
+        rule: Rule,
+        updated_by: Option<String>,
+    ) -> RuleHistoryEntry {
+        self.put_scope_rule(&RuleScope::tenant(tenant), rule, updated_by)
+    }
+
+    /// Inserts or updates a rule in the given layer.
+    pub fn put_scope_rule(
+        &self,
+        scope: &RuleScope,
         mut rule: Rule,
         updated_by: Option<String>,
     ) -> RuleHistoryEntry {
         let mut inner = self.inner.write();
-        let tenant_rules = inner.entry(tenant.to_string()).or_default();
+        let scope_rules = inner.scopes.entry(scope.clone()).or_default();
 
         // Ensure the rule id is set. If blank, generate a random id.
         if rule.id.trim().is_empty() {
             rule.id = format!("rule-{}", Uuid::new_v4());
         }
 
-        let entry = tenant_rules.rules.entry(rule.id.clone()).or_default();
+        let entry = scope_rules.rules.entry(rule.id.clone()).or_default();
 
         let version = entry.last().map(|last| last.version + 1).unwrap_or(1);
         let history_entry = RuleHistoryEntry::new(version, rule, updated_by);
@@ -113,17 +177,28 @@ impl RuleStore {
         tenant: &str,
         rule_id: &str,
         updated_by: Option<String>,
+    ) -> Result<RuleHistoryEntry, RuleError> {
+        self.disable_scope_rule(&RuleScope::tenant(tenant), rule_id, updated_by)
+    }
+
+    /// Disables a rule defined directly in the scope. Inherited rules are
+    /// disabled with [`RuleStore::set_override`] instead.
+    pub fn disable_scope_rule(
+        &self,
+        scope: &RuleScope,
+        rule_id: &str,
+        updated_by: Option<String>,
     ) -> Result<RuleHistoryEntry, RuleError> {
         let mut inner = self.inner.write();
-        let tenant_rules = inner
-            .get_mut(tenant)
+        let scope_rules = inner
+            .scopes
+            .get_mut(scope)
             .ok_or_else(|| RuleError::NotFound(rule_id.to_string()))?;
 
-        let history = tenant_rules
+        let history = scope_rules
             .rules
             .get_mut(rule_id)
             .ok_or_else(|| RuleError::NotFound(rule_id.to_string()))?;
-
         let latest = history
             .last()
             .cloned()
@@ -141,22 +216,441 @@ impl RuleStore {
         Ok(entry)
     }
 
-    /// Builds a rule engine using the latest active rules for a tenant.
+    /// Stages a new version of a rule as a shadow or canary candidate. The
+    /// active version keeps being enforced until the candidate is promoted.
+    /// Staging replaces any previous candidate for the rule and resets its
+    /// statistics.
+    pub fn put_candidate(
+        &self,
+        tenant: &str,
+        mut rule: Rule,
+        mode: RolloutMode,
+        updated_by: Option<String>,
+    ) -> RuleCandidate {
+        let mut inner = self.inner.write();
+        let tenant_rules = inner.scopes.entry(RuleScope::tenant(tenant)).or_default();
+
+        if rule.id.trim().is_empty() {
+            rule.id = format!("rule-{}", Uuid::new_v4());
+        }
+
+        let version = tenant_rules
+            .rules
+            .get(&rule.id)
+            .and_then(|history| history.last())
+            .map(|last| last.version + 1)
+            .unwrap_or(1);
+        let candidate = RuleCandidate {
+            version,
+            rule,
+            mode,
+            created_at: Utc::now(),
+            updated_by,
+            stats: RolloutStats::default(),
+        };
+        tenant_rules
+            .candidates
+            .insert(candidate.rule.id.clone(), candidate.clone());
+        candidate
+    }
+
+    /// Returns the candidates currently rolled out for the tenant, including
+    /// their divergence statistics.
+    pub fn candidates(&self, tenant: &str) -> Vec<RuleCandidate> {
+        let inner = self.inner.read();
+        let mut candidates: Vec<RuleCandidate> = inner
+            .scopes
+            .get(&RuleScope::tenant(tenant))
+            .map(|rules| rules.candidates.values().cloned().collect())
+            .unwrap_or_default();
+        candidates.sort_by(|a, b| a.rule.id.cmp(&b.rule.id));
+        candidates
+    }
+
+    /// Makes the candidate the active version of its rule.
+    pub fn promote_candidate(
+        &self,
+        tenant: &str,
+        rule_id: &str,
+        updated_by: Option<String>,
+    ) -> Result<RuleHistoryEntry, RuleError> {
+        let candidate = self.discard_candidate(tenant, rule_id)?;
+        let updated_by = updated_by.or(candidate.updated_by);
+        Ok(self.put_rule(tenant, candidate.rule, updated_by))
+    }
+
+    /// Drops the candidate, leaving the active version untouched.
+    pub fn discard_candidate(
+        &self,
+        tenant: &str,
+        rule_id: &str,
+    ) -> Result<RuleCandidate, RuleError> {
+        let mut inner = self.inner.write();
+        inner
+            .scopes
+            .get_mut(&RuleScope::tenant(tenant))
+            .and_then(|rules| rules.candidates.remove(rule_id))
+            .ok_or_else(|| RuleError::NotFound(rule_id.to_string()))
+    }
+
+    /// Builds an engine that enforces the active rules and shadows or canaries
+    /// the tenant's candidates.
+    pub fn rollout_for(&self, tenant: &str) -> RolloutEngine {
+        let candidates = self.candidates(tenant);
+        let engine = self.engine_for(tenant);
+        RolloutEngine::new(engine.rules().to_vec(), candidates)
+            .with_strategy(engine.strategy())
+            .with_metrics(self.metrics.clone(), tenant)
+    }
+
+    /// Accumulates shadow evaluation results into the candidates' statistics.
+    pub fn record_shadow(&self, tenant: &str, evaluations: &[ShadowEvaluation]) {
+        if evaluations.is_empty() {
+            return;
+        }
+        let mut inner = self.inner.write();
+        if let Some(rules) = inner.scopes.get_mut(&RuleScope::tenant(tenant)) {
+            for evaluation in evaluations {
+                if let Some(candidate) = rules.candidates.get_mut(&evaluation.rule_id) {
+                    candidate.stats.record(evaluation);
+                }
+            }
+        }
+    }
+
+    /// Builds a rule engine from the tenant's effective (merged) rules.
     pub fn engine_for(&self, tenant: &str) -> RuleEngine {
+        RuleEngine::new(self.effective_rules(tenant).into_rules())
+            .with_strategy(self.strategy_for(tenant))
+    }
+
+    /// Rules of `scope` as they are evaluated, with `rules` replacing those
+    /// with the same id, and the decision strategy they are evaluated under:
+    /// a tenant's merged layers under its strategy, or the layer itself for
+    /// shared layers.
+    pub fn rules_in_scope(
+        &self,
+        scope: &RuleScope,
+        rules: &[Rule],
+    ) -> (Vec<Rule>, DecisionStrategy) {
+        let (context, strategy) = match scope {
+            RuleScope::Tenant(tenant) => (
+                self.effective_rules(tenant).into_rules(),
+                self.strategy_for(tenant),
+            ),
+            _ => (
+                self.list_scope_rules(scope)
+                    .into_iter()
+                    .map(|entry| entry.rule)
+                    .collect(),
+                DecisionStrategy::default(),
+            ),
+        };
+        let mut candidate: Vec<Rule> = context
+            .into_iter()
+            .filter(|existing| rules.iter().all(|rule| rule.id != existing.id))
+            .collect();
+        candidate.extend(rules.iter().cloned());
+        (candidate, strategy)
+    }
+
+    /// Adds (or relabels) a public key allowed to sign rule bundles. The key
+    /// must be a base64url-encoded Ed25519 public key.
+    pub fn trust_signer(
+        &self,
+        public_key: &str,
+        label: Option<String>,
+    ) -> Result<TrustedSigner, RuleError> {
+        decode_public_key(public_key)?;
+        let signer = TrustedSigner {
+            public_key: public_key.to_string(),
+            label,
+            added_at: Utc::now(),
+        };
+        self.inner
+            .write()
+            .trusted_signers
+            .insert(public_key.to_string(), signer.clone());
+        Ok(signer)
+    }
+
+    pub fn revoke_signer(&self, public_key: &str) -> Result<TrustedSigner, RuleError> {
+        self.inner
+            .write()
+            .trusted_signers
+            .remove(public_key)
+            .ok_or_else(|| RuleError::NotFound(public_key.to_string()))
+    }
+
+    pub fn trusted_signers(&self) -> Vec<TrustedSigner> {
+        let mut signers: Vec<TrustedSigner> = self
+            .inner
+            .read()
+            .trusted_signers
+            .values()
+            .cloned()
+            .collect();
+        signers.sort_by(|a, b| a.public_key.cmp(&b.public_key));
+        signers
+    }
+
+    /// Imports recorded so far, one per bundle name.
+    pub fn bundle_imports(&self) -> Vec<BundleImport> {
+        let mut imports: Vec<BundleImport> = self.inner.read().bundles.values().cloned().collect();
+        imports.sort_by(|a, b| a.name.cmp(&b.name));
+        imports
+    }
+
+    /// Signs the latest rules defined directly in `scope` into a bundle.
+    pub fn export_bundle(
+        &self,
+        scope: &RuleScope,
+        name: &str,
+        version: u64,
+        keypair: &LogLineKeyPair,
+    ) -> Result<RuleBundle, RuleError> {
         let rules = self
-            .list_rules(tenant)
+            .list_scope_rules(scope)
             .into_iter()
-            .filter(|entry| entry.rule.enabled)
             .map(|entry| entry.rule)
             .collect();
-        RuleEngine::new(rules)
+        RuleBundle::sign(name, version, scope.clone(), rules, keypair)
+    }
+
+    /// Verifies a bundle against the trust list and validates its rules in
+    /// the context they will be evaluated in before installing them into the
+    /// bundle's scope. Rules shipped by the previous version of the bundle
+    /// but not by this one are disabled. Nothing is activated unless every
+    /// check passes.
+    pub fn import_bundle(&self, bundle: RuleBundle) -> Result<BundleImport, RuleError> {
+        bundle.verify(&self.trusted_signers())?;
+        let manifest = &bundle.manifest;
+        let shipped = |rule_id: &String| bundle.rules.iter().any(|rule| &rule.id == rule_id);
+        let retired: Vec<String> = match self.inner.read().bundles.get(&manifest.name) {
+            Some(previous) if previous.scope == manifest.scope => previous
+                .rule_ids
+                .iter()
+                .filter(|rule_id| !shipped(rule_id))
+                .cloned()
+                .collect(),
+            _ => Vec::new(),
+        };
+        let (mut candidate, strategy) = self.rules_in_scope(&manifest.scope, &bundle.rules);
+        candidate.retain(|rule| !retired.contains(&rule.id));
+        let mut report = validate_rules_with_strategy(&candidate, strategy);
+        report.issues.retain(|issue| shipped(&issue.rule_id));
+        report.into_result()?;
+
+        // The version check and the install share one lock, so concurrent
+        // imports of the same bundle cannot both pass the check.
+        let mut inner = self.inner.write();
+        if let Some(previous) = inner.bundles.get(&manifest.name) {
+            if previous.version >= manifest.version {
+                return Err(RuleError::StaleBundle(format!(
+                    "`{}` version {} is not newer than imported version {}",
+                    manifest.name, manifest.version, previous.version
+                )));
+            }
+        }
+        let updated_by = format!("bundle:{}@{}", manifest.name, manifest.version);
+        let scope_rules = inner.scopes.entry(manifest.scope.clone()).or_default();
+        for rule_id in &retired {
+            let Some(history) = scope_rules.rules.get_mut(rule_id) else {
+                continue;
+            };
+            if let Some(latest) = history.last().filter(|latest| latest.rule.enabled) {
+                let mut rule = latest.rule.clone();
+                rule.enabled = false;
+                let entry =
+                    RuleHistoryEntry::new(latest.version + 1, rule, Some(updated_by.clone()));
+                history.push(entry);
+            }
+        }
+        for rule in bundle.rules.iter().cloned() {
+            let history = scope_rules.rules.entry(rule.id.clone()).or_default();
+            let version = history.last().map(|last| last.version + 1).unwrap_or(1);
+            history.push(RuleHistoryEntry::new(
+                version,
+                rule,
+                Some(updated_by.clone()),
+            ));
+        }
+        let import = BundleImport {
+            name: manifest.name.clone(),
+            version: manifest.version,
+            scope: manifest.scope.clone(),
+            signer: manifest.signer.public_key.clone(),
+            rule_ids: bundle.rules.iter().map(|rule| rule.id.clone()).collect(),
+            imported_at: Utc::now(),
+        };
+        inner.bundles.insert(import.name.clone(), import.clone());
+        Ok(import)
+    }
+
+    /// Per-rule counters recorded by live evaluations through
+    /// [`RuleStore::rollout_for`].
+    pub fn metrics(&self) -> &RuleMetrics {
+        &self.metrics
+    }
+
+    /// Effective rules of the tenant that have not matched for `max_idle`.
+    pub fn stale_rules(&self, tenant: &str, max_idle: chrono::Duration) -> Vec<StaleRule> {
+        let rules = self.effective_rules(tenant).into_rules();
+        self.metrics
+            .stale_rules(tenant, &rules, max_idle, Utc::now())
+    }
+
+    /// Decision strategy the tenant's rules are evaluated with.
+    pub fn strategy_for(&self, tenant: &str) -> DecisionStrategy {
+        self.inner
+            .read()
+            .strategies
+            .get(tenant)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_strategy(&self, tenant: &str, strategy: DecisionStrategy) {
+        self.inner
+            .write()
+            .strategies
+            .insert(tenant.to_string(), strategy);
+    }
+
+    /// Assigns the tenant to an organization whose rules it inherits, or
+    /// detaches it when `organization` is `None`.
+    pub fn set_organization(&self, tenant: &str, organization: Option<String>) {
+        let mut inner = self.inner.write();
+        match organization {
+            Some(organization) => {
+                inner.organizations.insert(tenant.to_string(), organization);
+            }
+            None => {
+                inner.organizations.remove(tenant);
+            }
+        }
+    }
+
+    pub fn organization_of(&self, tenant: &str) -> Option<String> {
+        self.inner.read().organizations.get(tenant).cloned()
+    }
+
+    /// Records an explicit override of an inherited rule in the scope. The
+    /// platform layer inherits nothing and cannot hold overrides.
+    pub fn set_override(
+        &self,
+        scope: &RuleScope,
+        rule_id: &str,
+        mut change: RuleOverride,
+        updated_by: Option<String>,
+    ) -> Result<RuleOverrideEntry, RuleError> {
+        if *scope == RuleScope::Platform {
+            return Err(RuleError::InvalidOverride(
+                "the platform layer has no inherited rules to override".into(),
+            ));
+        }
+        if let RuleOverride::Replace { rule } = &mut change {
+            rule.id = rule_id.to_string();
+        }
+
+        let entry = RuleOverrideEntry {
+            rule_id: rule_id.to_string(),
+            change,
+            created_at: Utc::now(),
+            updated_by,
+        };
+        let mut inner = self.inner.write();
+        inner
+            .scopes
+            .entry(scope.clone())
+            .or_default()
+            .overrides
+            .insert(rule_id.to_string(), entry.clone());
+        Ok(entry)
+    }
+
+    pub fn remove_override(
+        &self,
+        scope: &RuleScope,
+        rule_id: &str,
+    ) -> Result<RuleOverrideEntry, RuleError> {
+        let mut inner = self.inner.write();
+        inner
+            .scopes
+            .get_mut(scope)
+            .and_then(|rules| rules.overrides.remove(rule_id))
+            .ok_or_else(|| RuleError::NotFound(rule_id.to_string()))
+    }
+
+    pub fn overrides(&self, scope: &RuleScope) -> Vec<RuleOverrideEntry> {
+        let inner = self.inner.read();
+        let mut overrides: Vec<RuleOverrideEntry> = inner
+            .scopes
+            .get(scope)
+            .map(|rules| rules.overrides.values().cloned().collect())
+            .unwrap_or_default();
+        overrides.sort_by(|a, b| a.rule_id.cmp(&b.rule_id));
+        overrides
+    }
+
+    /// Makes the watcher's file-based rules part of the platform layer.
+    pub fn attach_file_rules(&self, watcher: RuleWatcher) {
+        *self.file_rules.write() = Some(watcher);
+    }
+
+    /// Merges the platform, organization and tenant layers for the tenant.
+    pub fn effective_rules(&self, tenant: &str) -> EffectiveRuleSet {
+        let file_rules = self
+            .file_rules
+            .read()
+            .as_ref()
+            .map(|watcher| watcher.engine());
+
+        let inner = self.inner.read();
+        let organization = inner.organizations.get(tenant).cloned();
+        let mut scopes = vec![RuleScope::Platform];
+        if let Some(organization) = &organization {
+            scopes.push(RuleScope::organization(organization.clone()));
+        }
+        scopes.push(RuleScope::tenant(tenant));
+
+        let layers = scopes
+            .into_iter()
+            .map(|scope| {
+                let stored = inner.scopes.get(&scope);
+                let mut rules: Vec<(Rule, Option<u32>)> = Vec::new();
+                if scope == RuleScope::Platform {
+                    if let Some(engine) = &file_rules {
+                        rules.extend(engine.rules().iter().cloned().map(|rule| (rule, None)));
+                    }
+                }
+                if let Some(stored) = stored {
+                    rules.extend(
+                        stored
+                            .latest()
+                            .into_iter()
+                            .map(|entry| (entry.rule, Some(entry.version))),
+                    );
+                }
+                LayerInput {
+                    rules,
+                    overrides: stored
+                        .map(|stored| stored.overrides.values().cloned().collect())
+                        .unwrap_or_default(),
+                    scope,
+                }
+            })
+            .collect();
+
+        merge_layers(tenant, organization, layers)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::RuleCondition;
+    use crate::{ExclusionReason, RuleCondition};
+    use logline_core::identity::LogLineIDBuilder;
 
     fn sample_rule(id: &str) -> Rule {
         Rule {
@@ -165,6 +659,7 @@ mod tests {
             priority: 10,
             enabled: true,
             labels: vec!["demo".into()],
+            stop_processing: false,
             condition: RuleCondition::Always,
             actions: vec![],
         }
@@ -203,4 +698,129 @@ mod tests {
         let engine = store.engine_for("tenant-a");
         assert!(engine.is_empty(), "disabled rules should be skipped");
     }
+
+    #[test]
+    fn promoting_a_candidate_activates_it() {
+        let store = RuleStore::new();
+        store.put_rule("tenant-a", sample_rule("deny"), None);
+
+        let mut next = sample_rule("deny");
+        next.description = Some("candidate".into());
+        let candidate = store.put_candidate("tenant-a", next, RolloutMode::Shadow, None);
+        assert_eq!(candidate.version, 2);
+        assert_eq!(
+            store
+                .latest_rule("tenant-a", "deny")
+                .unwrap()
+                .rule
+                .description,
+            Some("demo".into()),
+            "candidate must not replace the active version"
+        );
+
+        let promoted = store
+            .promote_candidate("tenant-a", "deny", Some("bob".into()))
+            .expect("promote");
+        assert_eq!(promoted.version, 2);
+        assert_eq!(promoted.rule.description, Some("candidate".into()));
+        assert!(store.candidates("tenant-a").is_empty());
+    }
+
+    #[test]
+    fn layers_merge_with_explicit_overrides() {
+        let store = RuleStore::new();
+        store.put_scope_rule(&RuleScope::Platform, sample_rule("require-id"), None);
+        store.put_scope_rule(&RuleScope::Platform, sample_rule("audit"), None);
+        store.put_scope_rule(
+            &RuleScope::organization("acme"),
+            sample_rule("org-only"),
+            None,
+        );
+        store.set_organization("tenant-a", Some("acme".into()));
+
+        let mut replacement = sample_rule("ignored-id");
+        replacement.priority = 1;
+        store
+            .set_override(
+                &RuleScope::organization("acme"),
+                "audit",
+                RuleOverride::Replace { rule: replacement },
+                None,
+            )
+            .expect("replace");
+        store
+            .set_override(
+                &RuleScope::tenant("tenant-a"),
+                "org-only",
+                RuleOverride::Disable,
+                None,
+            )
+            .expect("disable");
+        // Redefining an inherited id without an override is a conflict.
+        store.put_rule("tenant-a", sample_rule("require-id"), None);
+
+        let effective = store.effective_rules("tenant-a");
+        let ids: Vec<&str> = effective
+            .rules
+            .iter()
+            .map(|rule| rule.rule.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["audit", "require-id"]);
+        assert_eq!(effective.rules[0].scope, RuleScope::organization("acme"));
+        assert_eq!(effective.rules[1].scope, RuleScope::Platform);
+        assert_eq!(effective.excluded.len(), 3);
+        assert!(effective
+            .excluded
+            .iter()
+            .any(|excluded| excluded.rule_id == "require-id"
+                && excluded.reason
+                    == ExclusionReason::Conflict {
+                        scope: RuleScope::Platform
+                    }));
+
+        assert!(store
+            .set_override(&RuleScope::Platform, "audit", RuleOverride::Disable, None)
+            .is_err());
+        assert_eq!(store.engine_for("tenant-b").rules().len(), 2);
+    }
+
+    #[test]
+    fn imports_newer_bundles_and_retires_dropped_rules() {
+        let store = RuleStore::new();
+        let keypair = LogLineIDBuilder::new_system("edge-publisher");
+        assert!(matches!(
+            store.trust_signer("not-a-key", None),
+            Err(RuleError::InvalidSigner(_))
+        ));
+        store.trust_signer(&keypair.id.public_key, None).unwrap();
+        let bundle = |version, ids: &[&str]| {
+            let rules = ids.iter().map(|id| sample_rule(id)).collect();
+            RuleBundle::sign("baseline", version, RuleScope::Platform, rules, &keypair).unwrap()
+        };
+
+        store.import_bundle(bundle(1, &["a", "b"])).unwrap();
+        store.import_bundle(bundle(2, &["a"])).unwrap();
+        let retired = store.latest_scope_rule(&RuleScope::Platform, "b").unwrap();
+        assert!(!retired.rule.enabled);
+        assert_eq!(retired.updated_by.as_deref(), Some("bundle:baseline@2"));
+        assert!(
+            store
+                .latest_scope_rule(&RuleScope::Platform, "a")
+                .unwrap()
+                .rule
+                .enabled
+        );
+
+        assert!(matches!(
+            store.import_bundle(bundle(2, &["a", "b"])),
+            Err(RuleError::StaleBundle(_))
+        ));
+        assert!(
+            !store
+                .latest_scope_rule(&RuleScope::Platform, "b")
+                .unwrap()
+                .rule
+                .enabled
+        );
+    }
 }
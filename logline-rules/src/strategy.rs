@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+
+use crate::outcome::Decision;
+
+/// How the decisions of several matching rules combine into the outcome.
+///
+/// Rules are always evaluated in priority order. A rule decides when it
+/// carries an `allow`, `reject` or `simulate` action; rules without one only
+/// contribute tags, metadata, transforms and tasks. Regardless of the
+/// strategy, evaluation stops after a matching rule with `stop_processing`.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DecisionStrategy {
+    /// The first rule that decides wins and evaluation stops after it.
+    FirstMatch,
+    /// Reject beats simulate beats allow, and the first reject stops
+    /// evaluation.
+    #[default]
+    DenyOverrides,
+    /// An explicit allow beats reject, which beats simulate. Every matching
+    /// rule is evaluated, so an allow can grant an exception to a deny.
+    AllowOverrides,
+    /// The first rule that decides wins, but later matching rules still apply
+    /// their other actions until a rule with `stop_processing`.
+    PriorityOrder,
+}
+
+impl DecisionStrategy {
+    /// Combines the decision made so far (`None` while no rule has decided)
+    /// with the decision of the rule that just matched.
+    pub(crate) fn combine(self, current: Option<Decision>, next: Decision) -> Decision {
+        let Some(current) = current else {
+            return next;
+        };
+        match self {
+            DecisionStrategy::DenyOverrides => current.merge(next),
+            DecisionStrategy::AllowOverrides => {
+                match allow_rank(&next).cmp(&allow_rank(&current)) {
+                    std::cmp::Ordering::Greater => next,
+                    std::cmp::Ordering::Equal => current.merge(next),
+                    std::cmp::Ordering::Less => current,
+                }
+            }
+            DecisionStrategy::FirstMatch | DecisionStrategy::PriorityOrder => current,
+        }
+    }
+
+    /// Whether evaluation ends after a rule that produced `decision`.
+    pub(crate) fn stops_after(self, decision: &Decision) -> bool {
+        match self {
+            DecisionStrategy::FirstMatch => true,
+            DecisionStrategy::DenyOverrides => matches!(decision, Decision::Reject { .. }),
+            DecisionStrategy::AllowOverrides | DecisionStrategy::PriorityOrder => false,
+        }
+    }
+}
+
+fn allow_rank(decision: &Decision) -> u8 {
+    match decision {
+        Decision::Simulate { .. } => 0,
+        Decision::Reject { .. } => 1,
+        Decision::Allow => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strategies_pick_different_winners() {
+        let deny = Decision::Reject {
+            reason: "blocked".into(),
+        };
+        let allow = Decision::Allow;
+
+        assert_eq!(
+            DecisionStrategy::DenyOverrides.combine(Some(allow.clone()), deny.clone()),
+            deny
+        );
+        assert_eq!(
+            DecisionStrategy::AllowOverrides.combine(Some(deny.clone()), allow.clone()),
+            allow
+        );
+        assert_eq!(
+            DecisionStrategy::FirstMatch.combine(Some(allow.clone()), deny.clone()),
+            allow
+        );
+        assert_eq!(
+            DecisionStrategy::PriorityOrder.combine(None, deny.clone()),
+            deny
+        );
+    }
+}
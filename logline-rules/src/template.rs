@@ -0,0 +1,183 @@
+//! `{{field.path}}` templates in rule action strings.
+//!
+//! Placeholders are resolved with the same [`FieldPath`] lookup conditions
+//! use, against the span as it looks when the action runs. Interpolated values
+//! are inserted as plain text and never re-expanded; `\{{` produces a literal
+//! `{{`.
+
+use logline_protocol::timeline::Span;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::condition::FieldPath;
+
+/// Problem found while parsing or rendering a template.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TemplateError {
+    #[error("template references missing field `{0}`")]
+    MissingField(String),
+    #[error("unclosed placeholder starting at byte {0}")]
+    Unclosed(usize),
+    #[error("empty placeholder at byte {0}")]
+    EmptyPlaceholder(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece<'a> {
+    Literal(&'a str),
+    Field(FieldPath),
+}
+
+fn parse(text: &str) -> Result<Vec<Piece<'_>>, TemplateError> {
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    let mut offset = 0;
+    while let Some(start) = rest.find("{{") {
+        if rest[..start].ends_with('\\') {
+            pieces.push(Piece::Literal(&rest[..start - 1]));
+            pieces.push(Piece::Literal("{{"));
+            rest = &rest[start + 2..];
+            offset += start + 2;
+            continue;
+        }
+
+        pieces.push(Piece::Literal(&rest[..start]));
+        let end = rest[start..]
+            .find("}}")
+            .ok_or(TemplateError::Unclosed(offset + start))?;
+        let path = rest[start + 2..start + end].trim();
+        if path.is_empty() {
+            return Err(TemplateError::EmptyPlaceholder(offset + start));
+        }
+        pieces.push(Piece::Field(FieldPath::new(path)));
+        rest = &rest[start + end + 2..];
+        offset += start + end + 2;
+    }
+    pieces.push(Piece::Literal(rest));
+    pieces.retain(|piece| !matches!(piece, Piece::Literal("")));
+    Ok(pieces)
+}
+
+/// Field paths referenced by the template, for static validation.
+pub(crate) fn template_fields(text: &str) -> Result<Vec<FieldPath>, TemplateError> {
+    Ok(parse(text)?
+        .into_iter()
+        .filter_map(|piece| match piece {
+            Piece::Field(path) => Some(path),
+            Piece::Literal(_) => None,
+        })
+        .collect())
+}
+
+/// Renders the template against the span, failing on the first missing field.
+pub fn render_template(template: &str, span: &Span) -> Result<String, TemplateError> {
+    let snapshot = serde_json::to_value(span).unwrap_or(Value::Null);
+    let mut errors = Vec::new();
+    let rendered = render_text(template, &snapshot, &mut errors);
+    match errors.into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok(rendered),
+    }
+}
+
+/// Best-effort rendering used while applying actions: problems are collected
+/// in `errors` and unresolvable placeholders are kept verbatim.
+pub(crate) fn render_text(text: &str, snapshot: &Value, errors: &mut Vec<TemplateError>) -> String {
+    let pieces = match parse(text) {
+        Ok(pieces) => pieces,
+        Err(err) => {
+            errors.push(err);
+            return text.to_string();
+        }
+    };
+
+    let mut rendered = String::with_capacity(text.len());
+    for piece in pieces {
+        match piece {
+            Piece::Literal(literal) => rendered.push_str(literal),
+            Piece::Field(path) => match path.locate(snapshot) {
+                Some(found) => rendered.push_str(&value_text(found)),
+                None => {
+                    errors.push(TemplateError::MissingField(path.as_str().to_string()));
+                    rendered.push_str(&format!("{{{{{}}}}}", path.as_str()));
+                }
+            },
+        }
+    }
+    rendered
+}
+
+/// Renders every string inside `value`. A string that is exactly one
+/// placeholder is replaced by the referenced JSON value, keeping its type.
+pub(crate) fn render_value(
+    value: &Value,
+    snapshot: &Value,
+    errors: &mut Vec<TemplateError>,
+) -> Value {
+    match value {
+        Value::String(text) => {
+            if let Ok(pieces) = parse(text) {
+                if let [Piece::Field(path)] = pieces.as_slice() {
+                    if let Some(found) = path.locate(snapshot) {
+                        return found.clone();
+                    }
+                }
+            }
+            Value::String(render_text(text, snapshot, errors))
+        }
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| render_value(item, snapshot, errors))
+                .collect(),
+        ),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, item)| (key.clone(), render_value(item, snapshot, errors)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Text form of a JSON value: strings without quotes, everything else as JSON.
+pub(crate) fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logline_protocol::timeline::SpanBuilder;
+    use serde_json::json;
+
+    #[test]
+    fn interpolates_fields_and_reports_missing_ones() {
+        let span = SpanBuilder::new("node-7", "wire")
+            .payload(json!({"amount": 1500}))
+            .build();
+
+        assert_eq!(
+            render_template(
+                "rejected {{title}} from {{logline_id}}: amount {{payload.amount}} exceeds limit",
+                &span
+            ),
+            Ok("rejected wire from node-7: amount 1500 exceeds limit".to_string())
+        );
+        assert_eq!(
+            render_template(r"literal \{{title}} stays", &span),
+            Ok("literal {{title}} stays".to_string())
+        );
+        assert_eq!(
+            render_template("{{data.currency}}", &span),
+            Err(TemplateError::MissingField("data.currency".into()))
+        );
+        assert_eq!(
+            render_template("broken {{title", &span),
+            Err(TemplateError::Unclosed(7))
+        );
+    }
+}
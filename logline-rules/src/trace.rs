@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::strategy::DecisionStrategy;
+
+/// Evaluation tree for a single condition node.
+///
+/// Field-based conditions carry the `field` they inspected and the `actual`
+/// value found on the span; `actual` is omitted when the field is missing.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConditionTrace {
+    pub kind: String,
+    pub result: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actual: Option<Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ConditionTrace>,
+}
+
+impl ConditionTrace {
+    pub(crate) fn leaf(
+        kind: &str,
+        result: bool,
+        field: Option<&str>,
+        expected: Option<Value>,
+        actual: Option<Value>,
+    ) -> Self {
+        Self {
+            kind: kind.to_string(),
+            result,
+            field: field.map(str::to_string),
+            expected,
+            actual,
+            children: Vec::new(),
+        }
+    }
+
+    pub(crate) fn branch(kind: &str, result: bool, children: Vec<ConditionTrace>) -> Self {
+        Self {
+            kind: kind.to_string(),
+            result,
+            field: None,
+            expected: None,
+            actual: None,
+            children,
+        }
+    }
+}
+
+/// How the engine treated a rule while evaluating a span.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleTraceStatus {
+    /// The condition matched and the rule's actions ran.
+    Matched,
+    /// The condition was evaluated and did not match.
+    NotMatched,
+    /// The rule is disabled and was not evaluated.
+    Disabled,
+    /// Evaluation stopped before reaching the rule.
+    Skipped,
+}
+
+/// Per-rule entry of an [`EvaluationTrace`].
+///
+/// Disabled and skipped rules still carry the condition tree they would have
+/// produced against the span, so authors can see what a rule would have seen.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuleTrace {
+    pub rule_id: String,
+    pub priority: u32,
+    pub status: RuleTraceStatus,
+    pub condition: ConditionTrace,
+}
+
+/// Full explanation of how a rule set evaluated a span.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EvaluationTrace {
+    /// Strategy used to combine the decisions of matching rules.
+    #[serde(default)]
+    pub strategy: DecisionStrategy,
+    pub rules: Vec<RuleTrace>,
+    /// Rule after which evaluation stopped, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stopped_by: Option<String>,
+}
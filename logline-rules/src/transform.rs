@@ -0,0 +1,159 @@
+use logline_protocol::timeline::Span;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::condition::FieldPath;
+use crate::template::value_text;
+
+/// Span fields whose contents rule actions may rewrite.
+pub(crate) const TRANSFORMABLE_ROOTS: &[&str] = &["data", "payload", "metadata"];
+
+/// Replacement used by [`crate::RuleAction::RedactField`] by default.
+pub(crate) const REDACTED: &str = "[REDACTED]";
+
+/// Whether the path points into a field rule actions may rewrite.
+pub(crate) fn is_transformable(path: &FieldPath) -> bool {
+    path.segments()
+        .next()
+        .map(|root| TRANSFORMABLE_ROOTS.contains(&root))
+        .unwrap_or(false)
+}
+
+/// `sha256:<hex>` digest of a value; strings are hashed as raw text.
+pub(crate) fn hash_value(value: &Value) -> Value {
+    let digest = Sha256::digest(value_text(value).as_bytes());
+    Value::String(format!("sha256:{:x}", digest))
+}
+
+/// Replaces the value at `path` with `f(value)`. Returns whether the field
+/// existed.
+pub(crate) fn replace_field(
+    span: &mut Span,
+    path: &FieldPath,
+    f: impl FnOnce(&Value) -> Value,
+) -> bool {
+    match locate_mut(span, path) {
+        Some(slot) => {
+            *slot = f(slot);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Moves the value at `from` to `to`, creating intermediate objects. Returns
+/// whether the source field existed.
+pub(crate) fn move_field(span: &mut Span, from: &FieldPath, to: &FieldPath) -> bool {
+    let Some(value) = take_field(span, from) else {
+        return false;
+    };
+    insert_field(span, to, value);
+    true
+}
+
+fn root_mut<'a>(span: &'a mut Span, root: &str) -> Option<&'a mut Option<Value>> {
+    match root {
+        "data" | "payload" => Some(&mut span.data),
+        "metadata" => Some(&mut span.metadata),
+        _ => None,
+    }
+}
+
+fn locate_mut<'a>(span: &'a mut Span, path: &FieldPath) -> Option<&'a mut Value> {
+    let mut segments = path.segments();
+    let mut current = root_mut(span, segments.next()?)?.as_mut()?;
+    for segment in segments {
+        current = match current {
+            Value::Object(map) => map.get_mut(segment)?,
+            Value::Array(items) => items.get_mut(segment.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+fn take_field(span: &mut Span, path: &FieldPath) -> Option<Value> {
+    let segments: Vec<&str> = path.segments().collect();
+    let (last, parents) = segments.split_last()?;
+    if parents.is_empty() {
+        return root_mut(span, last)?.take();
+    }
+
+    let parent = FieldPath::new(parents.join("."));
+    match locate_mut(span, &parent)? {
+        Value::Object(map) => map.remove(*last),
+        Value::Array(items) => {
+            let index = last.parse::<usize>().ok()?;
+            (index < items.len()).then(|| items.remove(index))
+        }
+        _ => None,
+    }
+}
+
+fn insert_field(span: &mut Span, path: &FieldPath, value: Value) {
+    let segments: Vec<&str> = path.segments().collect();
+    let Some((root, nested)) = segments.split_first() else {
+        return;
+    };
+    let Some(slot) = root_mut(span, root) else {
+        return;
+    };
+    let Some((last, parents)) = nested.split_last() else {
+        *slot = Some(value);
+        return;
+    };
+
+    let mut current = slot.get_or_insert_with(|| Value::Object(Map::new()));
+    for segment in parents {
+        current = object_mut(current)
+            .entry(segment.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+    object_mut(current).insert(last.to_string(), value);
+}
+
+/// Borrows the value as an object, replacing non-object values.
+fn object_mut(value: &mut Value) -> &mut Map<String, Value> {
+    if !value.is_object() {
+        *value = Value::Object(Map::new());
+    }
+    match value {
+        Value::Object(map) => map,
+        _ => unreachable!("value was just replaced with an object"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logline_protocol::timeline::SpanBuilder;
+    use serde_json::json;
+
+    #[test]
+    fn moves_redacts_and_hashes_payload_fields() {
+        let mut span = SpanBuilder::new("node", "payment")
+            .payload(json!({"card": "4111", "email": "ada@example.com", "old": 1}))
+            .build();
+
+        assert!(replace_field(&mut span, &"data.card".into(), |_| json!(
+            REDACTED
+        )));
+        assert!(replace_field(&mut span, &"data.email".into(), hash_value));
+        assert!(move_field(
+            &mut span,
+            &"data.old".into(),
+            &"metadata.legacy.value".into()
+        ));
+        assert!(!move_field(
+            &mut span,
+            &"data.absent".into(),
+            &"data.other".into()
+        ));
+
+        let data = span.data.clone().unwrap();
+        assert_eq!(data["card"], json!(REDACTED));
+        assert!(data["email"].as_str().unwrap().starts_with("sha256:"));
+        assert!(data.get("old").is_none());
+        assert_eq!(span.metadata.unwrap()["legacy"]["value"], json!(1));
+    }
+}
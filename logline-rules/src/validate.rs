@@ -0,0 +1,697 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::action::RuleAction;
+use crate::condition::{FieldPath, RuleCondition};
+use crate::error::RuleError;
+use crate::rule::Rule;
+use crate::strategy::DecisionStrategy;
+use crate::template::template_fields;
+use crate::transform::{is_transformable, TRANSFORMABLE_ROOTS};
+
+/// How severe a validation finding is. Errors block activation, warnings are
+/// reported but do not prevent a rule from being stored.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Category of a validation finding.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// The condition references a field path that can never exist on a span.
+    UnknownField,
+    /// The comparison can never succeed for the type of the referenced field.
+    TypeMismatch,
+    /// An earlier rule matching every span ends evaluation before the rule
+    /// is reached.
+    UnreachableRule,
+    /// Conditions combined with `all` can never hold at the same time.
+    ContradictoryConditions,
+    /// The rule matches spans but performs no action.
+    NoActions,
+    /// An action is misconfigured, e.g. it rewrites a field it may not touch.
+    InvalidAction,
+}
+
+/// Single problem found while validating a rule set.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ValidationIssue {
+    pub rule_id: String,
+    pub severity: Severity,
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+/// Result of validating a rule set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Whether the report contains no errors. Warnings do not invalidate a set.
+    pub fn is_valid(&self) -> bool {
+        !self
+            .issues
+            .iter()
+            .any(|issue| issue.severity == Severity::Error)
+    }
+
+    /// Returns the errors reported for the given rule.
+    pub fn errors_for(&self, rule_id: &str) -> Vec<&ValidationIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.rule_id == rule_id && issue.severity == Severity::Error)
+            .collect()
+    }
+
+    /// Turns the report into [`RuleError::Invalid`] listing every error, or
+    /// `Ok` when the set is valid.
+    pub fn into_result(self) -> Result<(), RuleError> {
+        if self.is_valid() {
+            return Ok(());
+        }
+        let errors: Vec<String> = self
+            .issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .map(|issue| format!("{}: {}", issue.rule_id, issue.message))
+            .collect();
+        Err(RuleError::Invalid(errors.join("; ")))
+    }
+
+    fn push(&mut self, rule: &Rule, severity: Severity, kind: IssueKind, message: String) {
+        self.issues.push(ValidationIssue {
+            rule_id: rule.id.clone(),
+            severity,
+            kind,
+            message,
+        });
+    }
+}
+
+/// Statically checks a rule set evaluated with the default decision
+/// strategy. See [`validate_rules_with_strategy`].
+pub fn validate_rules(rules: &[Rule]) -> ValidationReport {
+    validate_rules_with_strategy(rules, DecisionStrategy::default())
+}
+
+/// Statically checks a rule set for problems that would make rules misbehave
+/// at runtime when evaluated with `strategy`. Rules are inspected in
+/// evaluation order (priority, then id).
+pub fn validate_rules_with_strategy(
+    rules: &[Rule],
+    strategy: DecisionStrategy,
+) -> ValidationReport {
+    let mut ordered: Vec<&Rule> = rules.iter().collect();
+    ordered.sort_by(|a, b| a.priority.cmp(&b.priority).then(a.id.cmp(&b.id)));
+
+    let mut report = ValidationReport::default();
+    let mut shadowed_by: Option<(&str, &str)> = None;
+
+    for rule in ordered {
+        check_rule(rule, &mut report);
+
+        if !rule.is_enabled() {
+            continue;
+        }
+
+        if let Some((blocker, reason)) = shadowed_by {
+            report.push(
+                rule,
+                Severity::Warning,
+                IssueKind::UnreachableRule,
+                format!("rule can never run: rule '{}' {}", blocker, reason),
+            );
+        } else if is_unconditional(&rule.condition) {
+            shadowed_by = stop_reason(rule, strategy).map(|reason| (rule.id.as_str(), reason));
+        }
+    }
+
+    report
+}
+
+/// Why evaluation always ends after `rule` once it matches, if it does.
+fn stop_reason(rule: &Rule, strategy: DecisionStrategy) -> Option<&'static str> {
+    if rule.stop_processing {
+        return Some("matches every span and stops processing");
+    }
+    let rejects = rule
+        .actions
+        .iter()
+        .any(|action| matches!(action, RuleAction::Reject { .. }));
+    let decides = rule.actions.iter().any(RuleAction::is_decision);
+    match strategy {
+        DecisionStrategy::DenyOverrides if rejects => Some("rejects every span first"),
+        DecisionStrategy::FirstMatch if decides => Some("decides every span first"),
+        _ => None,
+    }
+}
+
+/// Validates a single rule in isolation.
+pub fn validate_rule(rule: &Rule) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    check_rule(rule, &mut report);
+    report
+}
+
+fn check_rule(rule: &Rule, report: &mut ValidationReport) {
+    check_condition(rule, &rule.condition, report);
+
+    if rule.actions.is_empty() {
+        report.push(
+            rule,
+            Severity::Warning,
+            IssueKind::NoActions,
+            "rule has no actions".to_string(),
+        );
+    }
+
+    for action in &rule.actions {
+        check_action(rule, action, report);
+    }
+}
+
+const TASK_PRIORITIES: &[&str] = &["Critical", "High", "Normal", "Low"];
+
+fn check_action(rule: &Rule, action: &RuleAction, report: &mut ValidationReport) {
+    let mut invalid = |message: String| {
+        report.push(rule, Severity::Error, IssueKind::InvalidAction, message);
+    };
+    let fields: Vec<&FieldPath> = match action {
+        RuleAction::RedactField { field, .. } | RuleAction::HashField { field } => vec![field],
+        RuleAction::MoveField { from, to } => vec![from, to],
+        RuleAction::EnqueueTask {
+            priority: Some(priority),
+            ..
+        } if !TASK_PRIORITIES.contains(&priority.as_str()) => {
+            invalid(format!(
+                "task priority {:?} is not one of {}",
+                priority,
+                TASK_PRIORITIES.join(", ")
+            ));
+            Vec::new()
+        }
+        RuleAction::EmitSpan { title, .. } if title.trim().is_empty() => {
+            invalid("derived span title is empty".to_string());
+            Vec::new()
+        }
+        _ => Vec::new(),
+    };
+    for field in fields {
+        if !is_transformable(field) {
+            invalid(format!(
+                "`{}` cannot be rewritten; only {} fields can",
+                field.as_str(),
+                TRANSFORMABLE_ROOTS.join(", ")
+            ));
+        }
+    }
+
+    for template in action_templates(action) {
+        match template_fields(template) {
+            Ok(fields) => {
+                for field in fields {
+                    resolve_field(rule, &field, report);
+                }
+            }
+            Err(err) => report.push(
+                rule,
+                Severity::Error,
+                IssueKind::InvalidAction,
+                format!("template {:?}: {}", template, err),
+            ),
+        }
+    }
+}
+
+/// Strings of an action that are rendered as templates when it runs.
+fn action_templates(action: &RuleAction) -> Vec<&str> {
+    fn collect<'a>(value: &'a Value, out: &mut Vec<&'a str>) {
+        match value {
+            Value::String(text) => out.push(text),
+            Value::Array(items) => items.iter().for_each(|item| collect(item, out)),
+            Value::Object(map) => map.values().for_each(|item| collect(item, out)),
+            _ => {}
+        }
+    }
+
+    let mut templates = Vec::new();
+    match action {
+        RuleAction::Reject { reason } => templates.push(reason.as_str()),
+        RuleAction::Note { message } => templates.push(message.as_str()),
+        RuleAction::SetMetadata { value, .. } | RuleAction::EnqueueTask { payload: value, .. } => {
+            collect(value, &mut templates)
+        }
+        RuleAction::EmitSpan { title, payload, .. } => {
+            templates.push(title.as_str());
+            if let Some(payload) = payload {
+                collect(payload, &mut templates);
+            }
+        }
+        _ => {}
+    }
+    templates
+}
+
+fn check_condition(rule: &Rule, condition: &RuleCondition, report: &mut ValidationReport) {
+    match condition {
+        RuleCondition::Always | RuleCondition::ContainsTag { .. } => {}
+        RuleCondition::All { conditions } => {
+            for child in conditions {
+                check_condition(rule, child, report);
+            }
+            check_contradictions(rule, condition, report);
+        }
+        RuleCondition::Any { conditions } => {
+            for child in conditions {
+                check_condition(rule, child, report);
+            }
+        }
+        RuleCondition::Not { condition } => check_condition(rule, condition, report),
+        RuleCondition::Exists { field } | RuleCondition::Missing { field } => {
+            resolve_field(rule, field, report);
+        }
+        RuleCondition::Equals { field, value } | RuleCondition::NotEquals { field, value } => {
+            if let Some(kind) = resolve_field(rule, field, report) {
+                if let Some(problem) = kind.rejects_value(value) {
+                    report.push(
+                        rule,
+                        Severity::Error,
+                        IssueKind::TypeMismatch,
+                        format!("`{}` {}", field.as_str(), problem),
+                    );
+                }
+            }
+        }
+        RuleCondition::ContainsText { field, .. } => {
+            if let Some(kind) = resolve_field(rule, field, report) {
+                if !kind.is_textual() {
+                    report.push(
+                        rule,
+                        Severity::Error,
+                        IssueKind::TypeMismatch,
+                        format!(
+                            "contains_text on `{}` never matches: field is {}",
+                            field.as_str(),
+                            kind.describe()
+                        ),
+                    );
+                }
+            }
+        }
+        RuleCondition::GreaterThan { field, .. } | RuleCondition::LessThan { field, .. } => {
+            if let Some(kind) = resolve_field(rule, field, report) {
+                if !matches!(kind, FieldKind::Number | FieldKind::Any) {
+                    report.push(
+                        rule,
+                        Severity::Error,
+                        IssueKind::TypeMismatch,
+                        format!(
+                            "numeric comparison on `{}` never matches: field is {}",
+                            field.as_str(),
+                            kind.describe()
+                        ),
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn resolve_field(
+    rule: &Rule,
+    field: &FieldPath,
+    report: &mut ValidationReport,
+) -> Option<FieldKind> {
+    match FieldKind::resolve(field) {
+        Ok(kind) => Some(kind),
+        Err(message) => {
+            report.push(rule, Severity::Error, IssueKind::UnknownField, message);
+            None
+        }
+    }
+}
+
+/// Whether the condition matches every span regardless of its contents.
+fn is_unconditional(condition: &RuleCondition) -> bool {
+    match condition {
+        RuleCondition::Always => true,
+        RuleCondition::All { conditions } => conditions.iter().all(is_unconditional),
+        RuleCondition::Any { conditions } => conditions.iter().any(is_unconditional),
+        _ => false,
+    }
+}
+
+fn check_contradictions(rule: &Rule, condition: &RuleCondition, report: &mut ValidationReport) {
+    let mut terms = Vec::new();
+    flatten_all(condition, &mut terms);
+
+    for (index, left) in terms.iter().enumerate() {
+        for right in &terms[index + 1..] {
+            if let Some(reason) = contradiction(left, right).or_else(|| contradiction(right, left))
+            {
+                report.push(
+                    rule,
+                    Severity::Error,
+                    IssueKind::ContradictoryConditions,
+                    format!("`all` condition can never match: {}", reason),
+                );
+            }
+        }
+    }
+}
+
+fn flatten_all<'a>(condition: &'a RuleCondition, terms: &mut Vec<&'a RuleCondition>) {
+    match condition {
+        RuleCondition::All { conditions } => {
+            for child in conditions {
+                flatten_all(child, terms);
+            }
+        }
+        other => terms.push(other),
+    }
+}
+
+fn contradiction(left: &RuleCondition, right: &RuleCondition) -> Option<String> {
+    use RuleCondition::*;
+
+    match (left, right) {
+        (Not { condition }, other) if condition.as_ref() == other => {
+            Some("a condition is combined with its own negation".to_string())
+        }
+        (Equals { field: a, value: x }, Equals { field: b, value: y }) if a == b && x != y => Some(
+            format!("`{}` cannot equal both {} and {}", a.as_str(), x, y),
+        ),
+        (Equals { field: a, value: x }, NotEquals { field: b, value: y }) if a == b && x == y => {
+            Some(format!(
+                "`{}` cannot both equal and not equal {}",
+                a.as_str(),
+                x
+            ))
+        }
+        (Exists { field: a }, Missing { field: b }) if a == b => {
+            Some(format!("`{}` cannot both exist and be missing", a.as_str()))
+        }
+        (Equals { field: a, .. }, Missing { field: b })
+        | (ContainsText { field: a, .. }, Missing { field: b })
+        | (GreaterThan { field: a, .. }, Missing { field: b })
+        | (LessThan { field: a, .. }, Missing { field: b })
+            if a == b =>
+        {
+            Some(format!(
+                "`{}` is required to be missing but is also compared",
+                a.as_str()
+            ))
+        }
+        (
+            GreaterThan {
+                field: a,
+                value: low,
+            },
+            LessThan {
+                field: b,
+                value: high,
+            },
+        ) if a == b && high <= low => Some(format!(
+            "`{}` cannot be greater than {} and less than {}",
+            a.as_str(),
+            low,
+            high
+        )),
+        _ => None,
+    }
+}
+
+/// Shape of the value found at a field path on a serialized span.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldKind {
+    Text,
+    Enum(&'static [&'static str]),
+    Number,
+    Bool,
+    TextList,
+    Any,
+}
+
+const SPAN_STATUS: &[&str] = &["executed", "simulated", "reverted", "ghost"];
+const SPAN_TYPE: &[&str] = &["user", "system", "organization", "ghost"];
+const VISIBILITY: &[&str] = &["private", "organization", "public"];
+
+impl FieldKind {
+    /// Top-level fields of the serialized `Span`.
+    fn for_span_field(name: &str) -> Option<Self> {
+        Some(match name {
+            "id"
+            | "timestamp"
+            | "logline_id"
+            | "title"
+            | "contract_id"
+            | "workflow_id"
+            | "flow_id"
+            | "caused_by"
+            | "signature"
+            | "verification_status"
+            | "replay_from"
+            | "tenant_id"
+            | "organization_id"
+            | "user_id" => FieldKind::Text,
+            "status" => FieldKind::Enum(SPAN_STATUS),
+            "span_type" => FieldKind::Enum(SPAN_TYPE),
+            "visibility" => FieldKind::Enum(VISIBILITY),
+            "delta_s" | "replay_count" => FieldKind::Number,
+            "processed" => FieldKind::Bool,
+            "tags" | "related_spans" => FieldKind::TextList,
+            "data" | "payload" | "metadata" => FieldKind::Any,
+            _ => return None,
+        })
+    }
+
+    fn resolve(path: &FieldPath) -> Result<Self, String> {
+        let mut segments = path.segments();
+        let Some(root) = segments.next() else {
+            return Err("field path is empty".to_string());
+        };
+        let kind = FieldKind::for_span_field(root)
+            .ok_or_else(|| format!("`{}` is not a span field", root))?;
+
+        let rest: Vec<&str> = segments.collect();
+        match (kind, rest.as_slice()) {
+            (_, []) => Ok(kind),
+            (FieldKind::Any, _) => Ok(FieldKind::Any),
+            (FieldKind::TextList, [index]) if index.parse::<usize>().is_ok() => Ok(FieldKind::Text),
+            (FieldKind::TextList, _) => Err(format!(
+                "`{}` can only be indexed by position, e.g. `{}.0`",
+                root, root
+            )),
+            _ => Err(format!(
+                "`{}` has no nested fields ({} is {})",
+                path.as_str(),
+                root,
+                kind.describe()
+            )),
+        }
+    }
+
+    fn is_textual(self) -> bool {
+        matches!(self, FieldKind::Text | FieldKind::Enum(_) | FieldKind::Any)
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            FieldKind::Text => "a string",
+            FieldKind::Enum(_) => "an enumeration",
+            FieldKind::Number => "a number",
+            FieldKind::Bool => "a boolean",
+            FieldKind::TextList => "a list of strings",
+            FieldKind::Any => "free-form JSON",
+        }
+    }
+
+    /// Returns why an equality comparison against `value` can never match.
+    fn rejects_value(self, value: &Value) -> Option<String> {
+        if value.is_null() && self != FieldKind::Any {
+            return Some("is omitted when unset, so it never equals null; use missing()".into());
+        }
+
+        match (self, value) {
+            (FieldKind::Any, _)
+            | (FieldKind::Text, Value::String(_))
+            | (FieldKind::Number, Value::Number(_))
+            | (FieldKind::Bool, Value::Bool(_))
+            | (FieldKind::TextList, Value::Array(_)) => None,
+            (FieldKind::Enum(variants), Value::String(text)) => {
+                if variants.contains(&text.as_str()) {
+                    None
+                } else {
+                    Some(format!(
+                        "is never {:?}; expected one of {}",
+                        text,
+                        variants.join(", ")
+                    ))
+                }
+            }
+            (kind, other) => Some(format!(
+                "is {} and is never equal to {}",
+                kind.describe(),
+                other
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rule(id: &str, priority: u32, condition: RuleCondition, actions: Vec<RuleAction>) -> Rule {
+        Rule {
+            id: id.into(),
+            description: None,
+            priority,
+            enabled: true,
+            labels: vec![],
+            stop_processing: false,
+            condition,
+            actions,
+        }
+    }
+
+    fn kinds(report: &ValidationReport) -> Vec<(String, IssueKind)> {
+        report
+            .issues
+            .iter()
+            .map(|issue| (issue.rule_id.clone(), issue.kind))
+            .collect()
+    }
+
+    #[test]
+    fn flags_unknown_fields_and_type_mismatches() {
+        let rules = vec![rule(
+            "bad",
+            10,
+            RuleCondition::All {
+                conditions: vec![
+                    RuleCondition::Exists {
+                        field: "title.length".into(),
+                    },
+                    RuleCondition::GreaterThan {
+                        field: "title".into(),
+                        value: 3.0,
+                    },
+                    RuleCondition::Equals {
+                        field: "span_type".into(),
+                        value: json!("ghosts"),
+                    },
+                    RuleCondition::Equals {
+                        field: "data.amount".into(),
+                        value: json!(10),
+                    },
+                ],
+            },
+            vec![RuleAction::Allow],
+        )];
+
+        let report = validate_rules(&rules);
+        assert!(!report.is_valid());
+        assert_eq!(
+            kinds(&report),
+            vec![
+                ("bad".to_string(), IssueKind::UnknownField),
+                ("bad".to_string(), IssueKind::TypeMismatch),
+                ("bad".to_string(), IssueKind::TypeMismatch),
+            ]
+        );
+    }
+
+    #[test]
+    fn flags_contradictions_shadowed_rules_and_missing_actions() {
+        let rules = vec![
+            rule(
+                "contradiction",
+                1,
+                RuleCondition::All {
+                    conditions: vec![
+                        RuleCondition::GreaterThan {
+                            field: "delta_s".into(),
+                            value: 10.0,
+                        },
+                        RuleCondition::LessThan {
+                            field: "delta_s".into(),
+                            value: 5.0,
+                        },
+                    ],
+                },
+                vec![RuleAction::Allow],
+            ),
+            rule(
+                "deny-all",
+                5,
+                RuleCondition::Always,
+                vec![RuleAction::Reject {
+                    reason: "closed".into(),
+                }],
+            ),
+            rule("after", 10, RuleCondition::Always, vec![]),
+        ];
+
+        let report = validate_rules(&rules);
+        assert_eq!(
+            kinds(&report),
+            vec![
+                (
+                    "contradiction".to_string(),
+                    IssueKind::ContradictoryConditions
+                ),
+                ("after".to_string(), IssueKind::NoActions),
+                ("after".to_string(), IssueKind::UnreachableRule),
+            ]
+        );
+        assert_eq!(report.errors_for("after").len(), 0);
+    }
+
+    #[test]
+    fn judges_shadowing_by_decision_strategy() {
+        let rules = vec![
+            rule(
+                "deny-all",
+                5,
+                RuleCondition::Always,
+                vec![RuleAction::Reject {
+                    reason: "closed".into(),
+                }],
+            ),
+            rule("after", 10, RuleCondition::Always, vec![RuleAction::Allow]),
+        ];
+        let unreachable = |strategy| {
+            validate_rules_with_strategy(&rules, strategy)
+                .issues
+                .iter()
+                .any(|issue| issue.kind == IssueKind::UnreachableRule)
+        };
+
+        assert!(unreachable(DecisionStrategy::DenyOverrides));
+        assert!(unreachable(DecisionStrategy::FirstMatch));
+        assert!(!unreachable(DecisionStrategy::AllowOverrides));
+        assert!(!unreachable(DecisionStrategy::PriorityOrder));
+
+        let mut tagging = rules.clone();
+        tagging[0].actions = vec![RuleAction::AddTag { tag: "seen".into() }];
+        assert!(
+            validate_rules_with_strategy(&tagging, DecisionStrategy::FirstMatch)
+                .issues
+                .is_empty()
+        );
+        tagging[0].stop_processing = true;
+        let report = validate_rules_with_strategy(&tagging, DecisionStrategy::AllowOverrides);
+        assert_eq!(
+            kinds(&report),
+            vec![("after".to_string(), IssueKind::UnreachableRule)]
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use arc_swap::ArcSwap;
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::engine::RuleEngine;
+use crate::error::RuleError;
+use crate::loader::{load_rules, rule_files};
+use crate::validate::validate_rules;
+
+/// Reload state of a watched rules directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct RuleReloadStatus {
+    pub path: String,
+    /// SHA-256 over the file names and contents of the active rule set.
+    pub revision: Option<String>,
+    pub rule_count: usize,
+    pub loaded_at: Option<DateTime<Utc>>,
+    pub last_checked_at: Option<DateTime<Utc>>,
+    /// Error of the most recent failed reload. Cleared by the next successful
+    /// reload; the previous rule set stays active meanwhile.
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+    /// Revision of the files that failed to load, so the same broken set is
+    /// not re-parsed on every poll.
+    pub failed_revision: Option<String>,
+}
+
+/// Keeps a [`RuleEngine`] in sync with a rules directory.
+///
+/// Changes are detected by polling the revision hash of the rule files. A
+/// changed set is re-parsed and validated and the engine is swapped atomically
+/// only when every file loads and validation reports no errors.
+#[derive(Clone)]
+pub struct RuleWatcher {
+    path: PathBuf,
+    engine: Arc<ArcSwap<RuleEngine>>,
+    status: Arc<RwLock<RuleReloadStatus>>,
+}
+
+impl RuleWatcher {
+    /// Loads the rules at `path`, failing if the initial set does not load
+    /// cleanly.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, RuleError> {
+        let path = path.as_ref().to_path_buf();
+        let watcher = Self {
+            engine: Arc::new(ArcSwap::from_pointee(RuleEngine::default())),
+            status: Arc::new(RwLock::new(RuleReloadStatus {
+                path: path.display().to_string(),
+                ..RuleReloadStatus::default()
+            })),
+            path,
+        };
+        watcher.reload()?;
+        Ok(watcher)
+    }
+
+    /// Currently active engine. Callers keep the returned snapshot for the
+    /// duration of an evaluation.
+    pub fn engine(&self) -> Arc<RuleEngine> {
+        self.engine.load_full()
+    }
+
+    pub fn status(&self) -> RuleReloadStatus {
+        self.status.read().clone()
+    }
+
+    /// Reloads the rule set if its files changed. Returns whether the active
+    /// engine was swapped.
+    pub fn reload(&self) -> Result<bool, RuleError> {
+        let now = Utc::now();
+        let revision = match revision(&self.path) {
+            Ok(revision) => revision,
+            Err(err) => return Err(self.record_failure(err, None, now)),
+        };
+
+        {
+            let mut status = self.status.write();
+            status.last_checked_at = Some(now);
+            if status.revision.as_deref() == Some(revision.as_str())
+                || status.failed_revision.as_deref() == Some(revision.as_str())
+            {
+                return Ok(false);
+            }
+        }
+
+        let engine = match load_validated(&self.path) {
+            Ok(engine) => engine,
+            Err(err) => return Err(self.record_failure(err, Some(revision), now)),
+        };
+
+        let mut status = self.status.write();
+        status.rule_count = engine.rules().len();
+        status.revision = Some(revision);
+        status.loaded_at = Some(now);
+        status.last_error = None;
+        status.last_error_at = None;
+        status.failed_revision = None;
+        self.engine.store(Arc::new(engine));
+        info!(path = %status.path, rules = status.rule_count, "reloaded file rules");
+        Ok(true)
+    }
+
+    fn record_failure(
+        &self,
+        err: RuleError,
+        revision: Option<String>,
+        now: DateTime<Utc>,
+    ) -> RuleError {
+        let mut status = self.status.write();
+        status.last_checked_at = Some(now);
+        status.last_error = Some(err.to_string());
+        status.last_error_at = Some(now);
+        status.failed_revision = revision;
+        warn!(path = %status.path, %err, "keeping previous file rules");
+        err
+    }
+
+    /// Polls the rules directory every `interval` until the task is aborted.
+    pub fn spawn(&self, interval: Duration) -> JoinHandle<()> {
+        let watcher = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let current = watcher.clone();
+                // Errors are recorded on the status by `reload`.
+                let _ = tokio::task::spawn_blocking(move || current.reload()).await;
+            }
+        })
+    }
+}
+
+fn load_validated(path: &Path) -> Result<RuleEngine, RuleError> {
+    let rules = load_rules(path)?;
+    validate_rules(&rules).into_result()?;
+    Ok(RuleEngine::new(rules))
+}
+
+fn revision(path: &Path) -> Result<String, RuleError> {
+    if !path.exists() {
+        return Err(RuleError::MissingPath(path.display().to_string()));
+    }
+
+    let mut hasher = Sha256::new();
+    for file in rule_files(path)? {
+        let contents = fs::read(&file).map_err(|err| RuleError::from_io(file.clone(), err))?;
+        let name = file
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+        hasher.update(name.unwrap_or_default().as_bytes());
+        hasher.update([0]);
+        hasher.update(&contents);
+        hasher.update([0]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swaps_only_when_the_whole_set_loads() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let file = dir.path().join("base.rule");
+        fs::write(&file, "rule tag-all then tag \"seen\"\n").unwrap();
+
+        let watcher = RuleWatcher::new(dir.path()).expect("initial load");
+        let first = watcher.status().revision;
+        assert_eq!(watcher.engine().rules().len(), 1);
+        assert!(!watcher.reload().unwrap());
+
+        fs::write(
+            &file,
+            "rule tag-all then tag \"seen\"\nrule block when title == \"x\" then reject \"no\"\n",
+        )
+        .unwrap();
+        assert!(watcher.reload().unwrap());
+        assert_eq!(watcher.engine().rules().len(), 2);
+        assert_ne!(watcher.status().revision, first);
+
+        fs::write(dir.path().join("broken.rule"), "rule oops when ==").unwrap();
+        assert!(watcher.reload().is_err());
+        let status = watcher.status();
+        assert_eq!(watcher.engine().rules().len(), 2);
+        assert_eq!(status.rule_count, 2);
+        assert!(status.last_error.is_some());
+        // The same broken set is not reported again until the files change.
+        assert!(!watcher.reload().unwrap());
+    }
+}
@@ -24,7 +24,11 @@ impl TimelineRepository {
 
     /// Builds the repository from an existing database pool.
     pub async fn from_pool(pool: DatabasePool) -> Result<Self> {
-        sqlx::migrate!("../migrations")
+        // The engine records its own migrations in the same table when it
+        // shares this database.
+        let mut migrator = sqlx::migrate!("../migrations");
+        migrator.set_ignore_missing(true);
+        migrator
             .run(pool.inner())
             .await
             .map_err(|err| LogLineError::TimelineError(err.to_string()))?;
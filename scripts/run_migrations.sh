@@ -119,6 +119,25 @@ if [ -d "$TIMELINE_MIGRATION_DIR" ]; then
     done
 fi
 
+# Run engine task queue migrations
+ENGINE_MIGRATION_DIR="logline-engine/migrations"
+if [ -d "$ENGINE_MIGRATION_DIR" ]; then
+    echo -e "${BLUE}📂 Running engine migrations from: $ENGINE_MIGRATION_DIR${NC}"
+
+    for migration_file in "$ENGINE_MIGRATION_DIR"/*.sql; do
+        if [ -f "$migration_file" ]; then
+            migration_name=$(basename "$migration_file" .sql)
+
+            if is_migration_applied "$migration_name"; then
+                echo -e "${YELLOW}⏭️  Skipping already applied migration: ${migration_name}${NC}"
+            else
+                run_migration "$migration_file"
+                mark_migration_applied "$migration_name"
+            fi
+        fi
+    done
+fi
+
 echo -e "${GREEN}🎉 All migrations completed successfully!${NC}"
 
 # Show applied migrations
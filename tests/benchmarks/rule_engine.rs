@@ -0,0 +1,108 @@
+// Benchmark of rule evaluation over large per-tenant rule sets.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use logline_protocol::timeline::{Span, SpanBuilder};
+use logline_rules::{Rule, RuleAction, RuleCondition, RuleEngine};
+use serde_json::{json, Value};
+
+const RULES: usize = 2_000;
+
+fn rule(id: String, condition: RuleCondition) -> Rule {
+    Rule {
+        id: id.clone(),
+        description: None,
+        priority: 100,
+        enabled: true,
+        labels: vec![],
+        stop_processing: false,
+        condition,
+        actions: vec![RuleAction::AddTag { tag: id }],
+    }
+}
+
+/// Rules keyed on the span title, as generated per contract or workflow.
+fn keyed_rules() -> Vec<Rule> {
+    (0..RULES)
+        .map(|i| {
+            rule(
+                format!("title-{}", i),
+                RuleCondition::All {
+                    conditions: vec![
+                        RuleCondition::Equals {
+                            field: "title".into(),
+                            value: json!(format!("workflow-{}", i)),
+                        },
+                        RuleCondition::GreaterThan {
+                            field: "data.amount".into(),
+                            value: 100.0,
+                        },
+                    ],
+                },
+            )
+        })
+        .collect()
+}
+
+/// Rules without an indexable predicate, evaluated for every span.
+fn unkeyed_rules() -> Vec<Rule> {
+    (0..RULES)
+        .map(|i| {
+            rule(
+                format!("amount-{}", i),
+                RuleCondition::GreaterThan {
+                    field: "data.amount".into(),
+                    value: (i * 10) as f64,
+                },
+            )
+        })
+        .collect()
+}
+
+fn sample_span() -> Span {
+    SpanBuilder::new("bench-node", "workflow-1500")
+        .payload(json!({"amount": 250, "currency": "EUR"}))
+        .build()
+}
+
+/// Evaluation as done before rules were compiled: one snapshot per rule and a
+/// scan over the whole set.
+fn naive_scan(rules: &[Rule], span: &Span) -> usize {
+    rules
+        .iter()
+        .filter(|rule| {
+            let snapshot = serde_json::to_value(span).unwrap_or(Value::Null);
+            rule.condition.evaluate(span, &snapshot)
+        })
+        .count()
+}
+
+fn rule_engine_benchmarks(c: &mut Criterion) {
+    let keyed = keyed_rules();
+    let keyed_engine = RuleEngine::new(keyed.clone());
+    let unkeyed_engine = RuleEngine::new(unkeyed_rules());
+    let span = sample_span();
+
+    c.bench_function("rules_compile_2000", |b| {
+        b.iter(|| RuleEngine::new(black_box(keyed.clone())));
+    });
+
+    c.bench_function("rules_apply_indexed_2000", |b| {
+        b.iter(|| {
+            let mut span = span.clone();
+            black_box(keyed_engine.apply(&mut span));
+        });
+    });
+
+    c.bench_function("rules_apply_unindexed_2000", |b| {
+        b.iter(|| {
+            let mut span = span.clone();
+            black_box(unkeyed_engine.apply(&mut span));
+        });
+    });
+
+    c.bench_function("rules_naive_scan_2000", |b| {
+        b.iter(|| black_box(naive_scan(&keyed, black_box(&span))));
+    });
+}
+
+criterion_group!(benches, rule_engine_benchmarks);
+criterion_main!(benches);
@@ -55,15 +55,17 @@ async fn runtime_dispatches_tasks_to_handler() {
 
     handle
         .submit(ExecutionTask::builder("tenant-a").build())
+        .await
         .expect("submitted task");
     handle
         .submit(ExecutionTask::builder("tenant-b").build())
+        .await
         .expect("submitted task");
 
     tokio::time::sleep(std::time::Duration::from_millis(50)).await;
 
     for tenant in ["tenant-a", "tenant-b"] {
-        let tasks = handle.list_for_tenant(tenant);
+        let tasks = handle.list_for_tenant(tenant).await.expect("task records");
         assert!(tasks
             .iter()
             .all(|record| record.status == TaskStatus::Completed));